@@ -0,0 +1,24 @@
+use payments_engine::profiling::record_allocation;
+use std::alloc::{GlobalAlloc, Layout, System};
+
+/// A global allocator that forwards to the system allocator while recording
+/// each allocation (see [`record_allocation`]), so `--profile` runs can
+/// attribute memory usage to processing phases. Only installed with the
+/// `profiling` feature, as the counting costs every allocation an atomic update.
+pub struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        record_allocation(layout.size());
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        record_allocation(new_size);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
@@ -1,10 +1,996 @@
-use clap::Parser;
+use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum};
+use payments_engine::models::amount::Amount;
+use payments_engine::models::csv_models::transaction::{ColumnMap, Delimiter};
+use payments_engine::models::identifiers::{ClientId, TxId};
+use payments_engine::models::policy::{
+    EngineConfig, ErrorPolicy, LockedDepositPolicy, RedisputePolicy, UnfundedDisputePolicy,
+};
+use payments_engine::services::account_writer::OutputFormat;
+use payments_engine::services::alerts::AlertRule;
+use payments_engine::services::generator::Phase;
+use payments_engine::services::input_format::InputFormat;
+use payments_engine::services::ledger_export::LedgerSyntax;
+use payments_engine::services::locale::Locale;
+use payments_engine::services::state_store::StoreSpec;
 
 /// Represents the command-line interface (CLI) for the application.
 /// Parses input arguments provided by the user.
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub struct Cli {
-    /// The path to the input CSV file containing transactions.
-    pub input: String,
+    /// The subcommand to run; without one, the input files are processed as by
+    /// `process`.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// The `process` arguments, also accepted without the subcommand name.
+    #[command(flatten)]
+    pub process: ProcessArgs,
+}
+
+/// The input files of a run and how to read them, shared by `process`,
+/// `validate` and `replay`.
+#[derive(Args, Debug)]
+pub struct InputArgs {
+    /// The paths to the input CSV files containing transactions (optionally
+    /// gzip- or, with the `zstd` feature, zstd-compressed), or `-` to read them
+    /// from stdin. Files are applied to the same engine in order, each with its
+    /// own header, and produce one consolidated account report.
+    #[arg(required_unless_present = "dir", value_name = "INPUT")]
+    pub input: Vec<String>,
+
+    /// Process every `*.csv`, `*.csv.gz` or `*.csv.zst` file under this directory
+    /// (recursively) instead of listing inputs, in lexicographic path order, and
+    /// log per-file row counts.
+    #[arg(long, value_name = "DIR", conflicts_with = "input")]
+    pub dir: Option<String>,
+
+    /// The format of the input files: `csv`, `avro` for Avro container files
+    /// whose records have the CSV row's fields (`type`, `client`, `tx`, `amount`),
+    /// `fixed-width` for fixed-width records (see `--fixed-width-layout`), `nacha`
+    /// for NACHA ACH files (credit entries deposit, debit entries withdraw), with the `proto` feature, `proto` for length-delimited protobuf messages
+    /// (see `proto/transaction.proto`), with the `arrow` feature, `arrow` for
+    /// Arrow IPC streams and files with those columns, or, with the `xlsx`
+    /// feature, `xlsx` for the first sheet of a workbook with those headers.
+    #[arg(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        default_value_t = InputFormat::Csv,
+        conflicts_with = "dir"
+    )]
+    pub input_format: InputFormat,
+
+    /// The JSON column layout of `--input-format fixed-width` files: the byte
+    /// `start` and `width` of the `type`, `client`, `tx` and `amount` fields, plus
+    /// optional `skip_lines` and type `codes`.
+    #[arg(
+        long,
+        value_name = "PATH",
+        required_if_eq("input_format", "fixed-width")
+    )]
+    pub fixed_width_layout: Option<String>,
+
+    /// The field delimiter of CSV input files and the CSV account report: a
+    /// single ASCII character, e.g. `;`, or `tab` for tab-separated files.
+    #[arg(long, value_name = "CHAR", default_value = ",")]
+    pub delimiter: Delimiter,
+
+    /// Read CSV input files without a header row: every line is a record, with
+    /// the fields in the order `type, client, tx, amount`.
+    #[arg(long)]
+    pub no_headers: bool,
+
+    /// Read CSV input whose header names differ from the engine's, given as
+    /// `<column>=<input name>` pairs, e.g. `type=txn_type,client=customer_id`.
+    /// Unmapped columns keep their names; column order does not matter.
+    #[arg(long, value_name = "MAPPING", conflicts_with = "no_headers")]
+    pub map: Option<ColumnMap>,
+
+    /// The ISO 4217 currency the input amounts are in, e.g. `JPY`. Deposits and
+    /// withdrawals with more decimal places than the currency's minor units are
+    /// rejected as `invalid_precision_for_currency` (`E1005`).
+    #[arg(long, value_name = "CODE")]
+    pub currency: Option<String>,
+
+    /// Override the bundled ISO 4217 minor-units table with a JSON object of
+    /// currency codes to decimal places, e.g. `{"ISK": 2}`.
+    #[arg(long, value_name = "PATH", requires = "currency")]
+    pub currency_table: Option<String>,
+}
+
+/// The policies an engine follows, shared by every subcommand that applies
+/// transactions so they all build the same engine.
+#[derive(Args, Debug)]
+pub struct EngineArgs {
+    /// What to do with deposits for locked accounts: `ignore` them, or `hold`
+    /// them, crediting `held` until a resolve releases or a chargeback reverses
+    /// them. Held deposits are logged.
+    #[arg(long, value_enum, value_name = "POLICY", default_value_t = LockedDepositPolicy::Ignore)]
+    pub locked_deposits: LockedDepositPolicy,
+
+    /// Allow disputes of withdrawals: a disputed withdrawal holds its amount, a
+    /// resolve releases the hold, and a chargeback returns the amount to
+    /// `available` and locks the account. By default they are ignored as
+    /// `not_disputable`.
+    #[arg(long)]
+    pub allow_withdrawal_disputes: bool,
+
+    /// Fail deposits and withdrawals that reuse a recorded tx id with
+    /// `duplicate tx id` (`E2002`), handled per `--on-error`, instead of
+    /// ignoring them.
+    #[arg(long)]
+    pub strict_duplicates: bool,
+
+    /// What to do with disputes of deposits already spent, which the account's
+    /// available funds do not cover: `ignore` them as `insufficient_funds`,
+    /// apply them as `negative`, holding the full amount and taking `available`
+    /// below zero by the shortfall, as card networks do, or `queue` them and
+    /// apply each once a later deposit of the client covers it (a resolve
+    /// cancels a queued dispute; those still queued at the end are dropped).
+    #[arg(long, value_enum, value_name = "POLICY", default_value_t = UnfundedDisputePolicy::Ignore)]
+    pub unfunded_disputes: UnfundedDisputePolicy,
+
+    /// Whether a transaction whose dispute was resolved can be disputed again:
+    /// `allow` (the default) any number of times, or `forbid`, ignoring such
+    /// disputes as `already_resolved`.
+    #[arg(long, value_enum, value_name = "POLICY", default_value_t = RedisputePolicy::Allow)]
+    pub redisputes: RedisputePolicy,
+
+    /// Resolve a dispute automatically once this many further commands have
+    /// been processed without it being resolved or charged back, so its funds
+    /// are not held forever. Input rows carry no timestamps, so the window is
+    /// counted in commands; disputes opened by an earlier run are not expired.
+    #[arg(long, value_name = "COMMANDS")]
+    pub dispute_window: Option<u64>,
+
+    /// Hold back up to N dispute, resolve and chargeback rows whose tx id has
+    /// not been seen yet, and apply them once the deposit or withdrawal
+    /// arrives, for feeds that are not strictly ordered. Held rows are
+    /// reported as `buffered`; rows over the cap are ignored as `unknown_tx`,
+    /// and rows still held at the end of the run are dropped with a warning.
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub orphan_buffer: usize,
+}
+
+impl EngineArgs {
+    /// Returns the engine config these arguments select.
+    ///
+    /// # Arguments
+    /// * `error_policy` - The error policy, chosen by each subcommand's
+    ///   `--on-error`.
+    pub fn config(&self, error_policy: ErrorPolicy) -> EngineConfig {
+        EngineConfig {
+            error_policy,
+            locked_deposits: self.locked_deposits,
+            withdrawal_disputes: self.allow_withdrawal_disputes,
+            strict_duplicates: self.strict_duplicates,
+            unfunded_disputes: self.unfunded_disputes,
+            redisputes: self.redisputes,
+            orphan_buffer: self.orphan_buffer,
+            dispute_window: self.dispute_window,
+        }
+    }
+}
+
+/// Processes input files into an account report: the `process` subcommand,
+/// and what runs without one.
+#[derive(Args, Debug)]
+#[command(group(ArgGroup::new("exposure").multiple(true)))]
+#[command(group(ArgGroup::new("alerting").multiple(true)))]
+pub struct ProcessArgs {
+    /// The input files.
+    #[command(flatten)]
+    pub source: InputArgs,
+
+    /// The account report format: `csv`, a `json` array, `ndjson` (one object
+    /// per line), an aligned `table` for reading at a terminal (balances per
+    /// `--locale`), or, with the `parquet` feature, `parquet` (requires
+    /// `--output`). The JSON formats write balances as exact numbers; Parquet as
+    /// `DECIMAL(18,4)`.
+    #[arg(long, value_enum, value_name = "FORMAT", default_value_t = OutputFormat::Csv)]
+    pub output_format: OutputFormat,
+
+    /// The engine's policies.
+    #[command(flatten)]
+    pub engine: EngineArgs,
+
+    /// Read the inputs twice: apply every deposit and withdrawal first, then
+    /// every dispute, resolve and chargeback, so a file that is not ordered by
+    /// time gives the same result as an ordered one without buffering rows.
+    /// Needs inputs that can be re-read, so stdin is not accepted.
+    #[arg(long, conflicts_with = "sorted_by")]
+    pub two_pass: bool,
+
+    /// Cap the book-wide held total: commands that would raise it above this
+    /// amount are quarantined instead of applied, and raise an alert.
+    #[arg(long, value_name = "AMOUNT", groups = ["exposure", "alerting"])]
+    pub max_book_held: Option<Amount>,
+
+    /// Cap the book-wide available total: commands that would raise it above
+    /// this amount are quarantined instead of applied, and raise an alert.
+    #[arg(long, value_name = "AMOUNT", groups = ["exposure", "alerting"])]
+    pub max_book_available: Option<Amount>,
+
+    /// Write the rows quarantined by `--max-book-held` / `--max-book-available`
+    /// to this path as input CSV, for replay after review.
+    #[arg(long, value_name = "PATH", requires = "exposure")]
+    pub quarantine: Option<String>,
+
+    /// Run as one instance of a horizontally split deployment: apply only the
+    /// rows of clients this shard map (written by `shard-map`) assigns to the
+    /// `--shard` instance, and skip the rest.
+    #[arg(long, value_name = "PATH", requires = "shard")]
+    pub shard_map: Option<String>,
+
+    /// This instance's name in the `--shard-map`.
+    #[arg(long, value_name = "NAME", requires = "shard_map")]
+    pub shard: Option<String>,
+
+    /// Format amounts in human-facing reports (tables, statements) for this
+    /// locale, e.g. `en-US` (`1,234.5000`), `de-DE` (`1.234,5000`) or `fr-FR`.
+    /// Machine formats (CSV, JSON, Parquet) are never localized.
+    #[arg(long, value_name = "TAG")]
+    pub locale: Option<Locale>,
+
+    /// Emit accounts ordered by client id, so the reports of two runs can be
+    /// diffed; by default they come in hash order. Not available with
+    /// `--sorted-by`, which emits accounts as their clients complete.
+    #[arg(long, conflicts_with = "sorted_by")]
+    pub sort_output: bool,
+
+    /// Write amounts in the CSV and JSON account reports without trailing
+    /// fractional zeros, e.g. `1.5` instead of `1.5000`, for consumers that
+    /// compare amounts as strings.
+    #[arg(long)]
+    pub trim_zeros: bool,
+
+    /// Append each client's activity during the run to the account report:
+    /// `deposited` and `withdrawn` sums and `disputes` and `chargebacks`
+    /// counts, for reconciliation. CSV, JSON and NDJSON only; not available with
+    /// `--sorted-by`, which drops clients as they complete.
+    #[arg(long, conflicts_with = "sorted_by")]
+    pub extended: bool,
+
+    /// Emit only these clients' accounts, e.g. `--client 5,42,100`; all input
+    /// is still processed and saved state still holds every account.
+    #[arg(long, value_name = "IDS", value_delimiter = ',')]
+    pub client: Vec<ClientId>,
+
+    /// Write the account report to this path instead of stdout. The report is
+    /// written to a temporary file in the same directory and renamed into place
+    /// once complete, so a failed run never leaves a truncated report.
+    #[arg(long, value_name = "PATH")]
+    pub output: Option<String>,
+
+    /// Write a flamegraph-ready folded profile (timings and allocations per
+    /// processing phase) to this path at the end of the run. Allocations are
+    /// only counted in builds with the `profiling` feature.
+    #[arg(long, value_name = "PATH")]
+    pub profile: Option<String>,
+
+    /// How to handle row and command errors: `skip` logs and continues, `warn`
+    /// logs them as warnings and continues, `fail-fast` stops at the first
+    /// engine error, `collect` reports all errors at the end, `strict` stops at
+    /// the first row that cannot be processed.
+    #[arg(long, value_enum, default_value_t = ErrorPolicy::Skip)]
+    pub on_error: ErrorPolicy,
+
+    /// Abort with a nonzero exit code and the offending line number at the
+    /// first malformed record, missing or invalid amount, or engine error,
+    /// instead of logging and continuing. Shorthand for `--on-error strict`.
+    #[arg(long, conflicts_with = "on_error")]
+    pub strict: bool,
+
+    /// Write the row errors gathered under `--on-error collect` to this path as
+    /// a JSON array of `{code, category, message, line, client, tx}` objects.
+    #[arg(long, value_name = "PATH")]
+    pub error_report: Option<String>,
+
+    /// Write every row the run skipped or ignored to this path as CSV, with its
+    /// line number, a machine-readable reason (e.g. `malformed_record`,
+    /// `invalid_amount`, `insufficient_funds`, `quarantined`), the error code and message
+    /// if an error dropped it, and the row's fields if it was parsed.
+    #[arg(long, value_name = "PATH")]
+    pub rejects: Option<String>,
+
+    /// Exit with code 3 (after writing every output as usual) if any row was
+    /// skipped or quarantined or any command failed or was ignored, so batch
+    /// schedulers can flag suspect runs.
+    #[arg(long)]
+    pub fail_on_rejects: bool,
+
+    /// Run the engine's internal consistency audit after processing and fail
+    /// the run if any invariant is violated.
+    #[arg(long, conflicts_with = "sorted_by")]
+    pub verify: bool,
+
+    /// Declare the input as sorted by the given key. With `client`, each account
+    /// is emitted as soon as the reader moves past that client, and its account
+    /// and tx records are dropped; only its tx ids are kept, as one bit per id
+    /// for sequential ids, to detect duplicates.
+    #[arg(
+        long,
+        value_enum,
+        value_name = "KEY",
+        conflicts_with_all = ["dir", "input_format"]
+    )]
+    pub sorted_by: Option<SortKey>,
+
+    /// Load the initial engine state from this backend (`snapshot:<path>`,
+    /// `json:<path>` or `paged:<path>`) instead of starting empty. A `paged:`
+    /// state is opened lazily: each client's page loads when a command first
+    /// touches it, so startup does not scale with the size of the book.
+    #[arg(
+        long,
+        value_name = "BACKEND",
+        conflicts_with = "sorted_by",
+        group = "opening"
+    )]
+    pub load_state: Option<StoreSpec>,
+
+    /// With a `paged:` `--load-state` backend, read the pages of this many
+    /// hottest clients (those with the most tx records) ahead on a background
+    /// thread while processing starts.
+    #[arg(long, value_name = "N", default_value_t = 0, requires = "load_state")]
+    pub prefetch_hot: usize,
+
+    /// Seed the engine from an opening balances CSV (`client,available,held,locked`,
+    /// e.g. a previous run's account report) before processing.
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with = "load_state",
+        group = "opening"
+    )]
+    pub opening_balances: Option<String>,
+
+    /// Check the state loaded by `--load-state` or `--opening-balances` against
+    /// the closing totals (and, for `--load-state`, the state hash) in the
+    /// previous run's manifest, and abort before processing on mismatch.
+    #[arg(long, value_name = "PATH", requires = "opening")]
+    pub carry_forward: Option<String>,
+
+    /// Save the final engine state to this backend (`snapshot:<path>`,
+    /// `json:<path>` or `paged:<path>`) after processing.
+    #[arg(long, value_name = "BACKEND", conflicts_with = "sorted_by")]
+    pub save_state: Option<StoreSpec>,
+
+    /// Write a run manifest (closing totals and state hash) to this path at the
+    /// end of the run, for the next run's `--carry-forward` check.
+    #[arg(long, value_name = "PATH", conflicts_with = "sorted_by")]
+    pub manifest: Option<String>,
+
+    /// Record how long the engine takes to apply each command in HDR histograms
+    /// and add per-command-type percentiles (p50 to p99.9, in nanoseconds) to the
+    /// `--manifest`, to compare soak and benchmark runs across storage backends.
+    #[arg(long, requires = "manifest")]
+    pub latency: bool,
+
+    /// With `--latency`, also embed each raw histogram in the manifest (V2
+    /// deflate serialized, base64 encoded), for merging or re-analysis with
+    /// HdrHistogram tooling.
+    #[arg(long, requires = "latency")]
+    pub latency_raw: bool,
+
+    /// Write the final balances and tx history as an ISO 20022 camt.053
+    /// bank-to-customer statement to this path, one statement per client, for
+    /// bank reconciliation tooling.
+    #[arg(long, value_name = "PATH", conflicts_with = "sorted_by")]
+    pub camt053: Option<String>,
+
+    /// The ISO 4217 currency code the `--camt053` amounts are stated in.
+    #[arg(long, value_name = "CODE", default_value = "XXX", requires = "camt053")]
+    pub statement_currency: String,
+
+    /// Raise an alert when an account starts breaching a balance threshold,
+    /// e.g. `held>1000` or `available<0` (fields: available, held, total).
+    /// Repeatable. Alerts are logged as they happen.
+    #[arg(
+        long,
+        value_name = "RULE",
+        conflicts_with = "sorted_by",
+        group = "alerting"
+    )]
+    pub alert: Vec<AlertRule>,
+
+    /// Write the alerts raised by `--alert` rules and book exposure caps to this
+    /// path as a JSON array of `{rule, client, tx, line, value}` objects.
+    #[arg(long, value_name = "PATH", requires = "alerting")]
+    pub alerts_report: Option<String>,
+
+    /// Track how many rows each dispute stays open and report disputes resolved
+    /// or charged back (or still open) more than this many rows after opening,
+    /// along with the SLA compliance percentage.
+    #[arg(long, value_name = "ROWS", conflicts_with = "sorted_by")]
+    pub dispute_sla: Option<u64>,
+
+    /// Write the dispute SLA summary and breaches to this path as JSON.
+    #[arg(long, value_name = "PATH", requires = "dispute_sla")]
+    pub sla_report: Option<String>,
+
+    /// Track the money created or destroyed by rounding input amounts with more
+    /// than four decimal places, and report the net residual at the end.
+    #[arg(long, conflicts_with = "sorted_by")]
+    pub track_rounding: bool,
+
+    /// Stream every applied row to a follower (see the `follow` subcommand)
+    /// listening at this address, e.g. `replica-host:7000`. The follower starts
+    /// from an empty engine under this run's engine policies, so a run that
+    /// loads an opening state or seen tx ids cannot be replicated.
+    #[arg(
+        long,
+        value_name = "ADDR",
+        conflicts_with_all = ["sorted_by", "load_state", "opening_balances", "seen_ids"]
+    )]
+    pub replicate_to: Option<String>,
+
+    /// Write a change data capture stream to this path: one JSON line per
+    /// account change, with the account's before and after images and the
+    /// causing tx id. Changes are diffed against the loaded state, so
+    /// carried-over accounts appear as updates.
+    #[arg(long, value_name = "PATH", conflicts_with = "sorted_by")]
+    pub cdc: Option<String>,
+
+    /// Write an end-of-run summary for batch monitoring: rows read and skipped,
+    /// commands applied, ignored and rejected per type, and the number of
+    /// accounts, locked accounts and funds held. Written as text to stderr, or
+    /// as JSON to PATH if given.
+    #[arg(
+        long,
+        value_name = "PATH",
+        num_args = 0..=1,
+        default_missing_value = "-",
+        conflicts_with = "sorted_by"
+    )]
+    pub summary: Option<String>,
+
+    /// Write the final transaction log to this path as CSV, one
+    /// `tx,client,type,amount,state` row per transaction sorted by tx id, so
+    /// auditors can see which transactions ended up disputed or charged back.
+    #[arg(long, value_name = "PATH", conflicts_with = "sorted_by")]
+    pub dump_txs: Option<String>,
+
+    /// Check that the run conserved funds: the book's closing funds must equal
+    /// its opening funds plus deposits, less withdrawals and chargebacks.
+    /// Writes the breakdown as text to stderr, or as JSON to PATH if given, and
+    /// warns of any discrepancy.
+    #[arg(
+        long,
+        value_name = "PATH",
+        num_args = 0..=1,
+        default_missing_value = "-",
+        conflicts_with = "sorted_by"
+    )]
+    pub conservation: Option<String>,
+
+    /// Print a SHA-256 fingerprint of the final account balances to stderr, so
+    /// two runs of the same input can be verified identical without comparing
+    /// their full outputs.
+    #[arg(long, conflicts_with = "sorted_by")]
+    pub fingerprint: bool,
+
+    /// Write a machine-readable JSON run report to this path at the end of the
+    /// run: the rows read, the commands applied, ignored and rejected per type,
+    /// the rejected rows by reason, the duration and the throughput.
+    #[arg(long, value_name = "PATH")]
+    pub report: Option<String>,
+
+    /// Append this run's KPIs (date, inputs, rows, disputes, dispute rate,
+    /// rejects, duration, peak RSS) as a CSV row to this history file, so engine
+    /// health can be trended across batches. Rejects are counted under
+    /// `--on-error collect`.
+    #[arg(long, value_name = "PATH", conflicts_with = "sorted_by")]
+    pub kpi_history: Option<String>,
+
+    /// Write per-input-file statistics (applied rows by type, clients touched)
+    /// and audit findings (row errors under `--on-error collect`, alerts, accounts
+    /// locked) to this path as a JSON array, in input order.
+    #[arg(long, value_name = "PATH", conflicts_with = "sorted_by")]
+    pub file_report: Option<String>,
+
+    /// Persistent set of seen tx ids. Loaded before processing (if it exists) so
+    /// transactions replayed from earlier runs are deduplicated, and rewritten
+    /// with all ids seen at the end of the run.
+    #[arg(long, value_name = "PATH")]
+    pub seen_ids: Option<String>,
+
+    /// Fraction (0.0–1.0) of deposit/withdrawal rows whose tx ids were already
+    /// seen by earlier runs above which `--on-overlap` applies. Requires `--seen-ids`.
+    #[arg(
+        long,
+        value_name = "FRACTION",
+        default_value_t = 0.5,
+        requires = "seen_ids"
+    )]
+    pub overlap_threshold: f64,
+
+    /// What to do when the input overlaps previously seen tx ids beyond
+    /// `--overlap-threshold`.
+    #[arg(long, value_enum, default_value_t = OverlapAction::Warn, requires = "seen_ids")]
+    pub on_overlap: OverlapAction,
+}
+
+impl ProcessArgs {
+    /// Returns the error policy selected by `--on-error`, or by `--strict`.
+    pub fn error_policy(&self) -> ErrorPolicy {
+        if self.strict {
+            ErrorPolicy::Strict
+        } else {
+            self.on_error
+        }
+    }
+}
+
+/// The action taken when an input overlaps previously seen tx ids.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverlapAction {
+    /// Log a warning and process the file anyway.
+    Warn,
+    /// Abort before applying any row.
+    Abort,
+}
+
+/// The keys an input file can be declared as sorted by.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortKey {
+    /// Rows are grouped by client id; each client's rows are contiguous.
+    Client,
+}
+
+/// Subcommands.
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Process input files into an account report (the default without a
+    /// subcommand).
+    Process(Box<ProcessArgs>),
+
+    /// Parse and type-check every input row (amount format, known tx type,
+    /// client and tx ranges, `--currency` precision) and report the problems,
+    /// without loading or changing any state or writing accounts. Exits nonzero
+    /// if any row is invalid. CSV input only.
+    Validate {
+        /// The input files.
+        #[command(flatten)]
+        source: InputArgs,
+
+        /// Write the problems found to this path as a JSON array of
+        /// `{code, category, message, line, client, tx}` objects.
+        #[arg(long, value_name = "PATH")]
+        error_report: Option<String>,
+    },
+
+    /// Replay input files onto a state backend and save the result back in
+    /// place, e.g. to apply a reviewed `--quarantine` file or a late batch to a
+    /// saved closing state, then emit the accounts.
+    Replay {
+        /// The backend holding the engine state, as `<kind>:<path>`. It is
+        /// rewritten in place.
+        #[arg(long, value_name = "BACKEND")]
+        state: StoreSpec,
+
+        /// The input files.
+        #[command(flatten)]
+        source: InputArgs,
+
+        /// How to handle row and command errors (see `process --on-error`).
+        #[arg(long, value_enum, default_value_t = ErrorPolicy::Skip)]
+        on_error: ErrorPolicy,
+
+        /// The engine's policies (see `process`).
+        #[command(flatten)]
+        engine: EngineArgs,
+
+        /// Write the account report to this path instead of stdout.
+        #[arg(long, value_name = "PATH")]
+        output: Option<String>,
+    },
+
+    /// Serve an engine over TCP: each connection streams transaction CSV (with a
+    /// header row) that is applied to the same engine, and is answered with an
+    /// `ok <applied> <row errors>` or `error <message>` line. Connections are
+    /// served one at a time, in arrival order.
+    Serve {
+        /// The address to listen on, e.g. `0.0.0.0:7100`.
+        #[arg(long, value_name = "ADDR")]
+        listen: String,
+
+        /// Start from this backend's state, and save the engine to it after
+        /// every connection.
+        #[arg(long, value_name = "BACKEND")]
+        state: Option<StoreSpec>,
+
+        /// How to handle row and command errors (see `process --on-error`);
+        /// `fail-fast` and `strict` stop only the offending connection's batch.
+        #[arg(long, value_enum, default_value_t = ErrorPolicy::Skip)]
+        on_error: ErrorPolicy,
+
+        /// The engine's policies (see `process`).
+        #[command(flatten)]
+        engine: EngineArgs,
+
+        /// Stop after serving this many connections and emit the accounts to
+        /// stdout; by default the engine is served until the process is killed.
+        #[arg(long, value_name = "N")]
+        connections: Option<usize>,
+    },
+
+    /// Copy accounts and tx records between storage backends, verifying the
+    /// copy by state hash.
+    Migrate {
+        /// The source backend, as `<kind>:<path>` (`snapshot:` or `json:`).
+        #[arg(long, value_name = "BACKEND")]
+        from: StoreSpec,
+
+        /// The target backend, as `<kind>:<path>` (`snapshot:` or `json:`).
+        #[arg(long, value_name = "BACKEND")]
+        to: StoreSpec,
+    },
+
+    /// Back up a state backend into a backup directory as a timestamped
+    /// snapshot, then prune old backups by the retention policy.
+    Backup {
+        /// The backend to back up, as `<kind>:<path>` (`snapshot:` or `json:`).
+        #[arg(long, value_name = "BACKEND")]
+        state: StoreSpec,
+
+        /// The directory holding the backups.
+        #[arg(long, value_name = "DIR")]
+        dir: String,
+
+        /// Keep the newest backup of each of the last N days.
+        #[arg(long, value_name = "N", default_value_t = 7)]
+        keep_daily: usize,
+
+        /// Keep the newest backup of each of the last M weeks.
+        #[arg(long, value_name = "M", default_value_t = 4)]
+        keep_weekly: usize,
+    },
+
+    /// Restore a backup from a backup directory into a state backend.
+    Restore {
+        /// The directory holding the backups.
+        #[arg(long, value_name = "DIR")]
+        dir: String,
+
+        /// The id of the backup to restore (`YYYYMMDDTHHMMSSZ`).
+        #[arg(long, value_name = "ID")]
+        backup: String,
+
+        /// The target backend, as `<kind>:<path>` (`snapshot:` or `json:`).
+        #[arg(long, value_name = "BACKEND")]
+        to: StoreSpec,
+    },
+
+    /// Run a read-only replica: accept a leader started with `--replicate-to`,
+    /// apply its stream, and emit the replica's accounts when the leader finishes.
+    Follow {
+        /// The address to listen on for the leader, e.g. `0.0.0.0:7000`.
+        #[arg(long, value_name = "ADDR")]
+        listen: String,
+
+        /// Save the replica's state to this backend when the stream ends.
+        #[arg(long, value_name = "BACKEND")]
+        save_state: Option<StoreSpec>,
+    },
+
+    /// Export everything the engine stores about one client as JSON, for
+    /// data-subject access requests.
+    ExportClient {
+        /// The backend holding the engine state, as `<kind>:<path>`
+        /// (`snapshot:` or `json:`).
+        #[arg(long, value_name = "BACKEND")]
+        state: StoreSpec,
+
+        /// The client to export.
+        #[arg(long, value_name = "ID")]
+        client: ClientId,
+
+        /// Write the export to this path instead of stdout.
+        #[arg(long, value_name = "PATH")]
+        out: Option<String>,
+    },
+
+    /// Erase a closed client (no funds left) from a state backend, keeping its
+    /// tx ids for duplicate detection and appending an aggregate-only tombstone
+    /// to the erasure audit log.
+    EraseClient {
+        /// The backend holding the engine state, as `<kind>:<path>`
+        /// (`snapshot:` or `json:`). It is rewritten in place.
+        #[arg(long, value_name = "BACKEND")]
+        state: StoreSpec,
+
+        /// The client to erase.
+        #[arg(long, value_name = "ID")]
+        client: ClientId,
+
+        /// The erasure audit log to append the tombstone to (JSON lines).
+        #[arg(long, value_name = "PATH")]
+        audit_log: String,
+    },
+
+    /// Attach a note to an account or transaction record in a state backend,
+    /// stamped with its author and the current time, so investigation context
+    /// is kept, exported and erased with the data.
+    Annotate {
+        /// The backend holding the engine state, as `<kind>:<path>`
+        /// (`snapshot:` or `json:`). It is rewritten in place.
+        #[arg(long, value_name = "BACKEND")]
+        state: StoreSpec,
+
+        /// The client whose account to annotate.
+        #[arg(
+            long,
+            value_name = "ID",
+            required_unless_present = "tx",
+            conflicts_with = "tx"
+        )]
+        client: Option<ClientId>,
+
+        /// The transaction to annotate.
+        #[arg(long, value_name = "ID")]
+        tx: Option<TxId>,
+
+        /// Who is writing the note.
+        #[arg(long, value_name = "NAME")]
+        author: String,
+
+        /// The note.
+        #[arg(long, value_name = "TEXT")]
+        note: String,
+    },
+
+    /// Close an accounting day of a state backend: freeze its closing snapshot
+    /// and write the closing balances, manifest and opening/closing totals to
+    /// `<DIR>/<DATE>`, so the next day's run opens from them.
+    Eod {
+        /// The backend holding the engine state, as `<kind>:<path>`. It is left
+        /// unchanged, as the next day's opening state.
+        #[arg(long, value_name = "BACKEND")]
+        state: StoreSpec,
+
+        /// The directory of closed days.
+        #[arg(long, value_name = "DIR")]
+        dir: String,
+
+        /// The business day to close, `YYYY-MM-DD`; defaults to today (UTC).
+        #[arg(long, value_name = "DATE")]
+        date: Option<String>,
+    },
+
+    /// Move settled tx history to compressed cold files, or search it.
+    Archive {
+        /// The archive action to run.
+        #[command(subcommand)]
+        action: ArchiveAction,
+    },
+
+    /// Analyze an existing snapshot without processing any input: ledger totals,
+    /// open disputes, locked accounts, invariant violations, and accounts
+    /// breaching `--alert` rules, written as JSON.
+    Analyze {
+        /// The binary snapshot to analyze (as written by `--save-state snapshot:<PATH>`).
+        #[arg(long, value_name = "PATH")]
+        snapshot: String,
+
+        /// A balance rule to check every account against, e.g. `held>1000`.
+        /// Repeatable.
+        #[arg(long, value_name = "RULE")]
+        alert: Vec<AlertRule>,
+
+        /// Write the analysis to this path instead of stdout.
+        #[arg(long, value_name = "PATH")]
+        out: Option<String>,
+    },
+
+    /// Write reports over a state backend in external formats.
+    Report {
+        /// The report to write.
+        #[command(subcommand)]
+        report: ReportKind,
+    },
+
+    /// Generate synthetic transaction input for performance testing, shaped by
+    /// named workload presets run as a schedule of phases.
+    Generate {
+        /// The phases to generate, in order, as comma-separated `<preset>:<rows>`
+        /// pairs, e.g. `uniform:50000,dispute-storm:5000,hot-client:20000`.
+        /// Presets: `uniform`, `zipfian` (skewed clients), `dispute-storm` (mostly
+        /// disputes, resolves and chargebacks), `hot-client` (one client sends
+        /// nine in ten rows).
+        #[arg(long, value_name = "PHASES", value_delimiter = ',', required = true)]
+        schedule: Vec<Phase>,
+
+        /// The number of clients.
+        #[arg(long, value_name = "N", default_value_t = 1000)]
+        clients: ClientId,
+
+        /// The random seed; the same seed yields the same rows.
+        #[arg(long, value_name = "SEED", default_value_t = 0)]
+        seed: u64,
+
+        /// Write the rows to this path instead of stdout.
+        #[arg(long, value_name = "PATH")]
+        out: Option<String>,
+    },
+
+    /// Write a consistent-hashing shard map assigning clients to engine
+    /// instances, for splitting a dataset across processes (see `--shard-map`).
+    ShardMap {
+        /// The instance names, comma-separated, e.g. `engine-0,engine-1`.
+        #[arg(long, value_name = "NAMES", value_delimiter = ',', required = true)]
+        instances: Vec<String>,
+
+        /// The number of ring points per instance; more points spread clients
+        /// more evenly.
+        #[arg(long, value_name = "N", default_value_t = 128)]
+        vnodes: u32,
+
+        /// Write the map to this path instead of stdout.
+        #[arg(long, value_name = "PATH")]
+        out: Option<String>,
+    },
+
+    /// Compare two account reports (CSV) client by client, e.g. of two runs of
+    /// a batch, and print every client whose available or held funds or locked
+    /// flag differ, with the deltas. Exits with an error if any client differs.
+    Reconcile {
+        /// The first account report.
+        #[arg(value_name = "A")]
+        a: String,
+
+        /// The second account report.
+        #[arg(value_name = "B")]
+        b: String,
+
+        /// Write the mismatches to this path instead of stdout.
+        #[arg(long, value_name = "PATH")]
+        out: Option<String>,
+    },
+
+    /// Check the integrity of every backup in a backup directory.
+    VerifyBackups {
+        /// The directory holding the backups.
+        #[arg(long, value_name = "DIR")]
+        dir: String,
+    },
+}
+
+/// Archive subcommands.
+#[derive(Subcommand, Debug)]
+pub enum ArchiveAction {
+    /// Archive every tx record that can no longer change state (charged-back
+    /// transactions, and withdrawals unless `--allow-withdrawal-disputes` is
+    /// set) from a state backend into a new cold file, and prune them from the
+    /// backend. Their tx ids are kept for duplicate detection.
+    Prune {
+        /// The backend holding the engine state, as `<kind>:<path>`
+        /// (`snapshot:` or `json:`). It is rewritten in place.
+        #[arg(long, value_name = "BACKEND")]
+        state: StoreSpec,
+
+        /// The directory holding the archive files.
+        #[arg(long, value_name = "DIR")]
+        dir: String,
+
+        /// The policies of the engine the state is used with (see `process`),
+        /// which decide which records can still change state.
+        #[command(flatten)]
+        engine: EngineArgs,
+    },
+
+    /// Print archived tx records matching the filters as CSV.
+    Query {
+        /// The directory holding the archive files.
+        #[arg(long, value_name = "DIR")]
+        dir: String,
+
+        /// Only the record with this tx id.
+        #[arg(long, value_name = "ID")]
+        tx: Option<TxId>,
+
+        /// Only records of this client.
+        #[arg(long, value_name = "ID")]
+        client: Option<ClientId>,
+    },
+}
+
+/// Report subcommands.
+#[derive(Subcommand, Debug)]
+pub enum ReportKind {
+    /// Write one SWIFT MT940 statement per client, with opening balance,
+    /// movements and closing balance derived from the tx log.
+    Mt940 {
+        /// The backend holding the engine state, as `<kind>:<path>`
+        /// (`snapshot:` or `json:`).
+        #[arg(long, value_name = "BACKEND")]
+        state: StoreSpec,
+
+        /// The ISO 4217 currency the balances are in, e.g. `EUR`.
+        #[arg(long, value_name = "CODE")]
+        currency: String,
+
+        /// Override the bundled ISO 4217 minor-units table with a JSON object of
+        /// currency codes to decimal places, e.g. `{"ISK": 2}`.
+        #[arg(long, value_name = "PATH")]
+        currency_table: Option<String>,
+
+        /// Write the statements to this path instead of stdout.
+        #[arg(long, value_name = "PATH")]
+        out: Option<String>,
+    },
+
+    /// Write an OFX 2.2 statement download of account balances and tx history,
+    /// for import into personal-finance tools.
+    Ofx {
+        /// The backend holding the engine state, as `<kind>:<path>`
+        /// (`snapshot:` or `json:`).
+        #[arg(long, value_name = "BACKEND")]
+        state: StoreSpec,
+
+        /// The ISO 4217 currency the balances are in, e.g. `EUR`.
+        #[arg(long, value_name = "CODE")]
+        currency: String,
+
+        /// Override the bundled ISO 4217 minor-units table with a JSON object of
+        /// currency codes to decimal places, e.g. `{"ISK": 2}`.
+        #[arg(long, value_name = "PATH")]
+        currency_table: Option<String>,
+
+        /// Only this client's statement.
+        #[arg(long, value_name = "ID")]
+        client: Option<ClientId>,
+
+        /// Write the statement to this path instead of stdout.
+        #[arg(long, value_name = "PATH")]
+        out: Option<String>,
+    },
+
+    /// Write one client's statement: every entry of its tx history with the
+    /// running available and held balance, and the operator notes about it.
+    Statement {
+        /// The backend holding the engine state, as `<kind>:<path>`
+        /// (`snapshot:` or `json:`).
+        #[arg(long, value_name = "BACKEND")]
+        state: StoreSpec,
+
+        /// The client whose statement to write.
+        #[arg(long, value_name = "ID")]
+        client: ClientId,
+
+        /// Format amounts for this locale, e.g. `en-US` or `de-DE`.
+        #[arg(long, value_name = "TAG")]
+        locale: Option<Locale>,
+
+        /// Write the statement to this path instead of stdout.
+        #[arg(long, value_name = "PATH")]
+        out: Option<String>,
+    },
+
+    /// Write the tx history as double-entry beancount or ledger-cli text, for
+    /// plain-text accounting.
+    Ledger {
+        /// The backend holding the engine state, as `<kind>:<path>`
+        /// (`snapshot:` or `json:`).
+        #[arg(long, value_name = "BACKEND")]
+        state: StoreSpec,
+
+        /// The accounting syntax to write.
+        #[arg(long, value_enum, default_value_t = LedgerSyntax::Beancount)]
+        syntax: LedgerSyntax,
+
+        /// The ISO 4217 currency the amounts are in, e.g. `EUR`.
+        #[arg(long, value_name = "CODE")]
+        currency: String,
+
+        /// Override the bundled ISO 4217 minor-units table with a JSON object of
+        /// currency codes to decimal places, e.g. `{"ISK": 2}`.
+        #[arg(long, value_name = "PATH")]
+        currency_table: Option<String>,
+
+        /// Write the export to this path instead of stdout.
+        #[arg(long, value_name = "PATH")]
+        out: Option<String>,
+    },
 }
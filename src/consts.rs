@@ -1,3 +1,11 @@
 /// Represents the scaling factor used for precise calculations.
 /// The scale is set to 10,000 to allow for fixed-point arithmetic with four decimal places.
 pub const SCALE: i64 = 10_000;
+
+/// The input path that reads transactions from stdin instead of a file.
+pub const STDIN_PATH: &str = "-";
+
+/// The exit code of a run that finished, but skipped rows or ignored commands
+/// under `--fail-on-rejects`; distinct from the failure code `1` and from the
+/// usage error code `2`.
+pub const REJECTS_EXIT_CODE: u8 = 3;
@@ -1,3 +1,17 @@
+//! Error taxonomy.
+//!
+//! All fallible operations return [`AppResult`]. Each [`AppErrors`] variant maps
+//! to a stable code and an [`ErrorCategory`]:
+//! - `E1xxx` — **input**: malformed rows, amounts, or files; the row is skipped.
+//! - `E2xxx` — **engine**: a command could not be applied (e.g., overflow).
+//! - `E3xxx` — **io**: reading or writing a file or stream failed.
+//! - `E9xxx` — **internal**: unexpected failures, including failed consistency audits.
+//!
+//! Codes are part of the public contract and are never reused for a different meaning.
+
+use crate::models::identifiers::TxId;
+use serde::Serialize;
+
 /// Represents the various errors that can occur in the application.
 /// Each variant corresponds to a specific type of error.
 #[derive(thiserror::Error, Debug)]
@@ -10,19 +24,165 @@ pub enum AppErrors {
     #[error("Overflow error")]
     Overflow,
 
+    /// An error indicating that a deposit or withdrawal reused a recorded tx id
+    /// while the engine rejects duplicates (see
+    /// [`EngineConfig::strict_duplicates`](crate::models::policy::EngineConfig::strict_duplicates)).
+    #[error("duplicate tx id {0}")]
+    DuplicateTx(TxId),
+
     /// An error indicating invalid input with a specific message.
     #[error("invalid input: {0}")]
     InvalidInput(&'static str),
 
+    /// An error indicating that a deposit or withdrawal row has no amount.
+    /// Carries the transaction type.
+    #[error("invalid input: {0} missing amount")]
+    MissingAmount(&'static str),
+
     /// An error related to input/output operations with a specific message.
     #[error("io: {0}")]
     Io(String),
 
+    /// An error raised by the CSV reader for a malformed record.
+    #[error("csv: {0}")]
+    Csv(String),
+
+    /// An error raised while decoding a non-CSV input format (e.g., Avro).
+    #[error("decode: {0}")]
+    Decode(String),
+
+    /// An error indicating that an amount has more decimal places than its
+    /// currency's minor units allow (e.g., `100.5` in JPY).
+    #[error("invalid_precision_for_currency: {currency} allows {minor_units} decimal place(s)")]
+    InvalidPrecisionForCurrency {
+        /// The currency code.
+        currency: String,
+        /// The number of decimal places the currency allows.
+        minor_units: u8,
+    },
+
+    /// An error raised while processing the row at the given input line.
+    #[error("line {line}: {source}")]
+    AtLine {
+        /// The line number of the offending row.
+        line: u64,
+        /// The underlying error.
+        source: Box<AppErrors>,
+    },
+
+    /// An error indicating that an input overlaps previously processed tx ids
+    /// beyond the configured threshold.
+    #[error("input overlaps previously seen tx ids: {overlapping} of {total} rows")]
+    OverlapDetected {
+        /// The number of rows whose tx id was already seen.
+        overlapping: usize,
+        /// The number of deposit/withdrawal rows in the input.
+        total: usize,
+    },
+
+    /// An error indicating that the opening state does not match the closing
+    /// state recorded by the previous run's manifest.
+    #[error(
+        "carry-forward mismatch: {what} is {found}, but the previous run closed with {expected}"
+    )]
+    CarryForwardMismatch {
+        /// The total or hash that differs.
+        what: &'static str,
+        /// The value the previous run closed with.
+        expected: String,
+        /// The value the opening state has.
+        found: String,
+    },
+
+    /// An error indicating that a run finished, but skipped rows or ignored
+    /// commands while `--fail-on-rejects` was set.
+    #[error("{rejected} row(s) were skipped or ignored")]
+    RowsRejected {
+        /// The number of rows skipped or ignored.
+        rejected: usize,
+    },
+
+    /// An error indicating that `validate` found invalid rows in its inputs.
+    #[error("{invalid} of {rows} row(s) are invalid")]
+    Validation {
+        /// The number of invalid rows.
+        invalid: usize,
+        /// The number of rows checked.
+        rows: u64,
+    },
+
+    /// An error indicating that a check found differences, e.g. between two
+    /// account reports or in backup checksums. Carries what was found.
+    #[error("mismatch: {0}")]
+    Mismatch(String),
+
     /// An error that wraps an `AmountParseError` and propagates it.
     #[error(transparent)]
     AmountParseError(#[from] AmountParseError),
 }
 
+/// The broad category of an [`AppErrors`] value, for routing failures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCategory {
+    /// The input row or file is malformed or invalid.
+    Input,
+    /// The engine rejected a command (e.g., arithmetic overflow).
+    Engine,
+    /// Reading or writing files or streams failed.
+    Io,
+    /// An unexpected internal failure.
+    Internal,
+}
+
+impl AppErrors {
+    /// Returns the stable machine-readable code for this error.
+    ///
+    /// Codes are grouped by category: `E1xxx` input, `E2xxx` engine,
+    /// `E3xxx` io, `E9xxx` internal. `AtLine` reports its source's code.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppErrors::AmountParseError(_) => "E1001",
+            AppErrors::MissingAmount(_) => "E1002",
+            AppErrors::Csv(_) => "E1003",
+            AppErrors::Decode(_) => "E1004",
+            AppErrors::InvalidPrecisionForCurrency { .. } => "E1005",
+            AppErrors::InvalidInput(_) => "E1000",
+            AppErrors::OverlapDetected { .. } => "E1100",
+            AppErrors::CarryForwardMismatch { .. } => "E1101",
+            AppErrors::RowsRejected { .. } => "E1102",
+            AppErrors::Mismatch(_) => "E1103",
+            AppErrors::Validation { .. } => "E1104",
+            AppErrors::Overflow => "E2001",
+            AppErrors::DuplicateTx(_) => "E2002",
+            AppErrors::Io(_) => "E3001",
+            AppErrors::Internal(_) => "E9001",
+            AppErrors::AtLine { source, .. } => source.code(),
+        }
+    }
+
+    /// Returns the category this error belongs to.
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            AppErrors::AmountParseError(_)
+            | AppErrors::MissingAmount(_)
+            | AppErrors::Csv(_)
+            | AppErrors::Decode(_)
+            | AppErrors::InvalidPrecisionForCurrency { .. }
+            | AppErrors::InvalidInput(_)
+            | AppErrors::OverlapDetected { .. }
+            | AppErrors::CarryForwardMismatch { .. }
+            | AppErrors::RowsRejected { .. }
+            | AppErrors::Mismatch(_)
+            | AppErrors::Validation { .. } => ErrorCategory::Input,
+            AppErrors::Overflow | AppErrors::DuplicateTx(_) => ErrorCategory::Engine,
+            AppErrors::Io(_) => ErrorCategory::Io,
+            AppErrors::Internal(_) => ErrorCategory::Internal,
+            AppErrors::AtLine { source, .. } => source.category(),
+        }
+    }
+}
+
 /// Represents errors that can occur while parsing an amount.
 /// Each variant corresponds to a specific parsing issue.
 #[derive(Debug, thiserror::Error)]
@@ -47,3 +207,44 @@ pub enum AmountParseError {
 /// A type alias for results returned by the application.
 /// Encapsulates a value of type `T` or an `AppErrors` error.
 pub type AppResult<T> = Result<T, AppErrors>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_and_categories_are_stable() {
+        assert_eq!(AppErrors::Overflow.code(), "E2001");
+        assert_eq!(AppErrors::Overflow.category(), ErrorCategory::Engine);
+        assert_eq!(AppErrors::DuplicateTx(7).code(), "E2002");
+        assert_eq!(AppErrors::DuplicateTx(7).category(), ErrorCategory::Engine);
+        assert_eq!(
+            AppErrors::AmountParseError(AmountParseError::Empty).code(),
+            "E1001"
+        );
+        assert_eq!(AppErrors::MissingAmount("deposit").code(), "E1002");
+        let rejected = AppErrors::RowsRejected { rejected: 2 };
+        assert_eq!(rejected.code(), "E1102");
+        assert_eq!(rejected.category(), ErrorCategory::Input);
+        let mismatch = AppErrors::Mismatch("2 client(s) differ".to_string());
+        assert_eq!(mismatch.code(), "E1103");
+        assert_eq!(mismatch.to_string(), "mismatch: 2 client(s) differ");
+        let invalid = AppErrors::Validation {
+            invalid: 1,
+            rows: 3,
+        };
+        assert_eq!(invalid.code(), "E1104");
+        assert_eq!(invalid.category(), ErrorCategory::Input);
+    }
+
+    #[test]
+    fn at_line_reports_source_code_and_category() {
+        let e = AppErrors::AtLine {
+            line: 7,
+            source: Box::new(AppErrors::Overflow),
+        };
+        assert_eq!(e.code(), "E2001");
+        assert_eq!(e.category(), ErrorCategory::Engine);
+        assert_eq!(e.to_string(), "line 7: Overflow error");
+    }
+}
@@ -0,0 +1,60 @@
+//! A streaming payments engine: applies deposits, withdrawals, disputes,
+//! resolves and chargebacks to client accounts.
+//!
+//! The `payments-engine` binary is a thin CLI over this crate; services can
+//! embed the same engine directly. The types most embedders need are
+//! re-exported at the crate root (and, together with the error taxonomy and
+//! identifiers, in [`prelude`]):
+//!
+//! - [`AppState`] / [`Engine`] hold the accounts and transaction records;
+//!   [`Engine::builder`] sets the policies an engine follows (see
+//!   [`EngineBuilder`]).
+//! - [`Transaction`] wraps the [`DepositCommand`], [`WithdrawalCommand`],
+//!   [`DisputeCommand`], [`ResolveCommand`] and [`ChargebackCommand`] types and
+//!   is applied with [`Engine::apply`], which reports an [`Outcome`] (with the
+//!   [`IgnoreReason`] of a no-op). Each
+//!   command can also be executed on its own through [`TxCommandTrait::execute`],
+//!   or evaluated without side effects with [`Engine::probe`].
+//! - [`Amount`] is the fixed-point money type (four decimal places).
+//! - [`EngineHandle`] applies commands from concurrent callers.
+//!
+//! ```
+//! use payments_engine::prelude::*;
+//!
+//! let mut engine = Engine::default();
+//! let deposit = DepositCommand { client: 1, tx: 1, amount: "2.5".parse()? };
+//! assert_eq!(engine.apply(deposit.into())?, Outcome::Applied);
+//!
+//! let overdraft = WithdrawalCommand { client: 1, tx: 2, amount: "9.0".parse()? };
+//! assert_eq!(
+//!     engine.apply(overdraft.into())?,
+//!     Outcome::Ignored(IgnoreReason::InsufficientFunds)
+//! );
+//!
+//! let account = engine.acct(1).unwrap();
+//! assert_eq!(account.available.to_string(), "2.5000");
+//! # Ok::<(), AppErrors>(())
+//! ```
+//!
+//! CSV ingestion, persistence and reporting live under [`services`].
+
+#![forbid(unsafe_code)]
+
+pub mod consts;
+pub mod errors;
+pub mod models;
+pub mod prelude;
+pub mod profiling;
+pub mod services;
+pub mod state;
+
+pub use models::amount::Amount;
+pub use models::tx_command::{
+    ChargebackCommand, DepositCommand, DisputeCommand, ResolveCommand, Transaction,
+    WithdrawalCommand,
+};
+pub use services::commands::traits::tx_command_trait::TxCommandTrait;
+pub use services::engine_handle::EngineHandle;
+pub use state::{
+    AppState, DisputeEvent, Engine, EngineBuilder, IgnoreReason, Outcome, Probe, Released,
+};
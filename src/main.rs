@@ -1,19 +1,83 @@
-use crate::cli::Cli;
-use crate::errors::{AppErrors, AppResult};
-use crate::models::csv_models::transaction::OutputRow;
-use crate::services::csv_service::run_from_csv_path;
-use crate::state::AppState;
-use clap::Parser;
+#![deny(unsafe_code)]
+
+use clap::{Parser, ValueEnum};
+use cli::{
+    ArchiveAction, Cli, Command, InputArgs, OverlapAction, ProcessArgs, ReportKind, SortKey,
+};
 use csv::WriterBuilder;
-use log::info;
-use std::io;
+use log::{error, info, warn};
+use payments_engine::consts::{REJECTS_EXIT_CODE, STDIN_PATH};
+use payments_engine::errors::{AppErrors, AppResult};
+use payments_engine::models::amount::{Amount, AmountStyle};
+use payments_engine::models::csv_models::transaction::{CsvDialect, Delimiter, InputRow};
+use payments_engine::models::domain_state::{Annotation, NoteTarget};
+use payments_engine::models::policy::{EngineConfig, ErrorPolicy, RowError};
+use payments_engine::profiling::Profiler;
+use payments_engine::services::account_writer::{AccountWriter, OutputFormat};
+use payments_engine::services::alerts::{Alert, AlertMonitor};
+use payments_engine::services::analysis::analyze;
+use payments_engine::services::archive::{archive_final, dump_txs, query_archive};
+use payments_engine::services::backup::{
+    RetentionPolicy, create_backup, restore_backup, utc_date, verify_backups,
+};
+use payments_engine::services::camt053::write_camt053_file;
+use payments_engine::services::cdc::CdcStream;
+use payments_engine::services::client_export::export_client;
+use payments_engine::services::conservation::{ConservationReport, book_funds};
+use payments_engine::services::csv_service::{
+    Pass, discover_csv_files, run_from_csv_path_sorted_by_client,
+};
+use payments_engine::services::currency::{Currency, CurrencyTable};
+use payments_engine::services::dedup_store::{check_overlap, load_seen_ids, save_seen_ids};
+use payments_engine::services::dispute_sla::{DisputeSlaTracker, SlaSummary};
+use payments_engine::services::end_of_day::close_day;
+use payments_engine::services::erasure::{append_erasure_log, erase_client};
+use payments_engine::services::exposure::{ExposureGuard, ExposureLimits};
+use payments_engine::services::file_report::{FileReport, write_file_reports};
+use payments_engine::services::fixed_width_input::FixedWidthLayout;
+use payments_engine::services::generator::Generator;
+use payments_engine::services::input_format::{InputFormat, run_from_path_observed};
+use payments_engine::services::latency::LatencyRecorder;
+use payments_engine::services::ledger_export::write_ledger;
+use payments_engine::services::mt940::write_mt940;
+use payments_engine::services::ofx::write_ofx;
+use payments_engine::services::opening_balances::load_opening_balances;
+use payments_engine::services::reconcile::{read_account_report, reconcile};
+use payments_engine::services::rejects::RejectLog;
+use payments_engine::services::replication::{ReplicationSink, follow};
+use payments_engine::services::report_output::{ReportOutput, write_json_file};
+use payments_engine::services::rounding::RoundingTracker;
+use payments_engine::services::run_history::{RunKpis, append_run_kpis, peak_rss_kb};
+use payments_engine::services::run_manifest::{
+    Provenance, RunManifest, provenance_mismatch, verify_carry_forward,
+};
+use payments_engine::services::run_report::RunReport;
+use payments_engine::services::run_summary::SummaryRecorder;
+use payments_engine::services::server::serve_connection;
+use payments_engine::services::shard_map::{ShardMap, ShardRouter};
+use payments_engine::services::state_store::{
+    StoreSpec, account_fingerprint, migrate, state_digest,
+};
+use payments_engine::services::statement::write_statement;
+use payments_engine::services::validation::validate_csv_path;
+use payments_engine::state::{AppState, Outcome};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::net::TcpListener;
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
+// The counting allocator is the only unsafe code in the project.
+#[cfg(feature = "profiling")]
+#[allow(unsafe_code)]
+mod alloc_counter;
 mod cli;
-mod consts;
-mod errors;
-mod models;
-mod services;
-mod state;
+
+/// Counts allocations so `--profile` runs can report them per processing phase.
+#[cfg(feature = "profiling")]
+#[global_allocator]
+static GLOBAL: alloc_counter::CountingAllocator = alloc_counter::CountingAllocator;
 
 /// Application entry point.
 ///
@@ -21,84 +85,1714 @@ mod state;
 /// - Initialize the logger (`env_logger` with default level `info`).
 /// - Parse CLI arguments using `clap`.
 /// - Run the main application logic via [`run_app`].
+/// - Map the outcome to the exit code: `0` on success, [`REJECTS_EXIT_CODE`]
+///   when `--fail-on-rejects` finds rejected rows, `1` on any other error.
 ///
 /// Logs "Application started" and "Application ended" at INFO level.
 ///
 /// # Returns
-/// * `AppResult<()>` - Returns `Ok(())` if the application runs successfully,
-///   or an `AppErrors` variant if an error occurs.
-fn main() -> AppResult<()> {
+/// * `ExitCode` - The process exit code.
+fn main() -> ExitCode {
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
     info!("Application started");
 
     let args = Cli::parse();
-    run_app(&args)?;
+    match run_app(&args) {
+        Ok(()) => {
+            info!("Application ended");
+            ExitCode::SUCCESS
+        }
+        Err(e @ AppErrors::RowsRejected { .. }) => {
+            error!("Application ended with rejects: {e}");
+            ExitCode::from(REJECTS_EXIT_CODE)
+        }
+        Err(e) => {
+            eprintln!("Error [{}]: {e}", e.code());
+            ExitCode::FAILURE
+        }
+    }
+}
 
-    info!("Application ended");
-    Ok(())
+/// Run the core application logic: the subcommand given (via [`run_command`]),
+/// or processing the input files without one (via [`run_process`]).
+///
+/// # Arguments
+/// * `args` - A reference to the parsed CLI arguments.
+///
+/// # Returns
+/// * `AppResult<()>` - Returns `Ok(())` if the application logic runs successfully,
+///   or an `AppErrors` variant if an error occurs.
+pub fn run_app(args: &Cli) -> AppResult<()> {
+    match &args.command {
+        Some(command) => run_command(command),
+        None => run_process(&args.process),
+    }
 }
 
-/// Run the core application logic.
+/// Process the input files into an account report (`process`).
 ///
 /// Responsibilities:
-/// - Create a fresh [`AppState`] which holds the engine (accounts + transactions).
-/// - Process transactions from the input CSV file (via [`run_from_csv_path`]).
-/// - Emit the final account states to stdout (via [`emit_accounts_to_stdout`]).
+/// - Open the state: fresh, loaded or seeded, with the engine policies the
+///   flags select (via [`open_process_state`]).
+/// - Process each input into it, feeding executed rows to the
+///   [`RowObservers`] (via [`run_from_path_observed`]).
+/// - Emit the final account states (via [`open_process_report`] /
+///   [`emit_accounts`]), or stream them as clients finish under
+///   `--sorted-by client` (via [`stream_accounts`]).
+/// - Write the optional outputs that read the final state (via
+///   [`write_run_reports`]).
+///
+/// The order of these steps and how the flags interact are described in the
+/// README (CLI section).
 ///
 /// Logs when processing starts and ends.
 ///
 /// # Arguments
-/// * `args` - A reference to the parsed CLI arguments.
+/// * `args` - The `process` arguments.
 ///
 /// # Returns
-/// * `AppResult<()>` - Returns `Ok(())` if the application logic runs successfully,
-///   or an `AppErrors` variant if an error occurs.
-pub fn run_app(args: &Cli) -> AppResult<()> {
-    info!("Starting to process input file: {}", args.input);
+/// * `AppResult<()>` - Returns `Ok(())` if the run succeeds, or an `AppErrors`
+///   variant if an error occurs (`AppErrors::RowsRejected` under
+///   `--fail-on-rejects`, once every output is written).
+pub fn run_process(args: &ProcessArgs) -> AppResult<()> {
+    let inputs = input_paths(&args.source)?;
+    if args.sorted_by.is_some() && inputs.len() > 1 {
+        return Err(AppErrors::InvalidInput(
+            "--sorted-by supports a single input file",
+        ));
+    }
+    if args.two_pass && inputs.iter().any(|input| input == STDIN_PATH) {
+        return Err(AppErrors::InvalidInput("--two-pass cannot re-read stdin"));
+    }
 
-    let mut app_state = AppState::default();
-    run_from_csv_path(&args.input, &mut app_state)?;
+    // Opened up front so a bad --output fails before any input is processed.
+    let report = open_process_report(args)?;
+    let started = Instant::now();
+    let started_at = unix_now()?;
+    let mut kpis = None;
+    let mut profiler = Profiler::default();
+    let mut app_state = open_process_state(args, &inputs)?;
+    let opening_funds = match args.conservation {
+        Some(_) => {
+            let (available, held) = book_funds(&app_state.engine)?;
+            Some(available.checked_add(held).ok_or(AppErrors::Overflow)?)
+        }
+        None => None,
+    };
+    match args.sorted_by {
+        Some(SortKey::Client) => {
+            let input = &inputs[0];
+            info!("Starting to process input file: {input}");
+            let errors =
+                profiler.phase("ingest", || stream_accounts(input, &mut app_state, report))?;
+            report_row_errors(&errors, args.error_report.as_deref())?;
+            info!("Finished processing input file: {input}");
+        }
+        None => {
+            let observed = args.replicate_to.is_some()
+                || !args.alert.is_empty()
+                || args.dispute_sla.is_some()
+                || args.track_rounding
+                || args.source.dir.is_some()
+                || args.kpi_history.is_some()
+                || args.file_report.is_some()
+                || args.cdc.is_some();
+            let mut observers = RowObservers::new(args, &app_state)?;
+            // Rows read by earlier files, so SLA row counts span file boundaries.
+            let mut rows_before = 0;
+            let layout = fixed_width_layout(&args.source)?;
+            let mut file_reports: Vec<_> =
+                inputs.iter().map(|input| FileReport::new(input)).collect();
+            let mut errors = Vec::new();
+            let passes = match args.two_pass {
+                true => vec![Some(Pass::FundMovements), Some(Pass::Disputes)],
+                false => vec![None],
+            };
+            for pass in passes {
+                app_state.pass = pass;
+                for (input, file) in inputs.iter().zip(&mut file_reports) {
+                    info!("Starting to process input file: {input}");
+                    let mut last_line = 0;
+                    let alerts_before = observers.monitor.alerts().len();
+                    let file_errors = profiler.phase("ingest", || {
+                        if !observed {
+                            return run_from_path_observed(
+                                input,
+                                args.source.input_format,
+                                layout.as_ref(),
+                                &mut app_state,
+                                |_, _, _, _| Ok(()),
+                            );
+                        }
+                        run_from_path_observed(
+                            input,
+                            args.source.input_format,
+                            layout.as_ref(),
+                            &mut app_state,
+                            |line, row, outcome, state| {
+                                last_line = line;
+                                if outcome == Outcome::Applied {
+                                    file.observe(row, state.engine.acct(row.client));
+                                }
+                                observers.observe(line, rows_before + line, row, outcome, state)
+                            },
+                        )
+                    })?;
+                    rows_before += last_line;
+                    file.row_errors += file_errors.len();
+                    file.alerts += observers.monitor.alerts().len() - alerts_before;
+                    if !file_errors.is_empty() {
+                        warn!("{} row error(s) in {input}", file_errors.len());
+                    }
+                    if args.source.dir.is_some() {
+                        info!("{input}: {} row(s) applied", file.applied);
+                    }
+                    errors.extend(file_errors);
+                    info!("Finished processing input file: {input}");
+                }
+            }
+            app_state.pass = None;
+            // Pages no input touched are loaded for emission and saving.
+            app_state.engine.page_in_all()?;
+            observers.finish(args, &app_state)?;
+            report_row_errors(&errors, args.error_report.as_deref())?;
+            if let Some(path) = &args.file_report {
+                write_file_reports(path, &file_reports)?;
+                info!("Per-file report written to {path}");
+            }
+            let sum = |count: fn(&FileReport) -> u64| file_reports.iter().map(count).sum::<u64>();
+            let (rows, deposits, disputes) =
+                (sum(|f| f.applied), sum(|f| f.deposits), sum(|f| f.disputes));
+            kpis = args.kpi_history.is_some().then(|| RunKpis {
+                date: utc_date(started_at),
+                started_at,
+                inputs: inputs.len(),
+                rows,
+                disputes,
+                dispute_rate: if deposits == 0 {
+                    0.0
+                } else {
+                    disputes as f64 / deposits as f64
+                },
+                rejects: errors.len() as u64,
+                duration_ms: 0,
+                peak_rss_kb: None,
+            });
 
-    info!("Finished processing input file: {}", args.input);
-    info!("Emitting results to stdout...");
-    emit_accounts_to_stdout(&app_state)?;
+            if args.verify {
+                app_state.engine.verify()?;
+                info!("Engine consistency verified");
+            }
+            info!("Emitting results...");
+            profiler.phase("emit", || {
+                emit_accounts(&app_state, report, args.sort_output, args.extended)
+            })?;
+        }
+    }
 
     info!("Results successfully emitted");
+    report_dropped_commands(&app_state);
+    if let Some(spec) = &args.save_state {
+        spec.save(&app_state.engine)?;
+        info!(
+            "Saved engine state to {spec} (hash {:016x})",
+            state_digest(&app_state.engine)
+        );
+    }
+    write_run_reports(args, &app_state, opening_funds)?;
+    if let Some(path) = &args.seen_ids {
+        let saved = save_seen_ids(path, &app_state.engine)?;
+        info!("Saved {saved} seen tx id(s) to {path}");
+    }
+    if let (Some(path), Some(mut kpis)) = (&args.kpi_history, kpis) {
+        kpis.duration_ms = started.elapsed().as_millis() as u64;
+        kpis.peak_rss_kb = peak_rss_kb();
+        append_run_kpis(path, &kpis)?;
+        info!("Run KPIs appended to {path}");
+    }
+    if let Some(path) = &args.profile {
+        if !cfg!(feature = "profiling") {
+            warn!("allocations are only counted in builds with the `profiling` feature");
+        }
+        profiler.log_summary();
+        profiler.write_folded(path)?;
+        info!("Profile written to {path}");
+    }
+    if let (Some(path), Some(recorder), Some(rejects)) =
+        (&args.report, &app_state.summary, &app_state.rejects)
+    {
+        RunReport::new(started_at, started.elapsed(), recorder, rejects).write(path)?;
+        info!("Run report written to {path}");
+    }
+    if args.fail_on_rejects
+        && let Some(rejects) = &app_state.rejects
+        && !rejects.is_empty()
+    {
+        return Err(AppErrors::RowsRejected {
+            rejected: rejects.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Build the application state of a `process` run: the engine, loaded from
+/// `--load-state` or seeded from `--opening-balances` and configured by the
+/// engine policy flags, checked against `--carry-forward`, with `--seen-ids`
+/// loaded, and with the recorders, shard router and exposure guard the flags
+/// ask for.
+///
+/// # Arguments
+/// * `args` - The `process` arguments.
+/// * `inputs` - The input paths, checked for overlap with the seen tx ids.
+///
+/// # Returns
+/// * `AppResult<AppState>` - The state, or an `AppErrors` variant if any of it
+///   cannot be loaded or a check fails.
+fn open_process_state(args: &ProcessArgs, inputs: &[String]) -> AppResult<AppState> {
+    let mut app_state = AppState {
+        dialect: input_dialect(&args.source),
+        ..AppState::default()
+    };
+    app_state.currency = input_currency(&args.source)?;
+    if let Some(spec) = &args.load_state {
+        app_state.engine = spec.open(args.prefetch_hot)?;
+        // These read the whole opening book, so a lazily opened state is loaded
+        // in full.
+        if args.carry_forward.is_some()
+            || args.seen_ids.is_some()
+            || args.cdc.is_some()
+            || args.conservation.is_some()
+            || args.max_book_held.is_some()
+            || args.max_book_available.is_some()
+        {
+            app_state.engine.page_in_all()?;
+        }
+        match app_state.engine.pending_pages() {
+            0 => info!("Loaded engine state from {spec}"),
+            pending => info!(
+                "Opened engine state from {spec}; {pending} client page(s) load on first touch"
+            ),
+        }
+    }
+    if let Some(path) = &args.opening_balances {
+        let loaded = load_opening_balances(path, &mut app_state.engine)?;
+        info!("Loaded {loaded} opening balance(s) from {path}");
+    }
+    app_state
+        .engine
+        .set_config(args.engine.config(args.error_policy()));
+    if let Some(path) = &args.carry_forward {
+        let previous = RunManifest::read(path)?;
+        verify_carry_forward(&previous, &app_state.engine, args.load_state.is_some())?;
+        info!("Opening state matches the closing state recorded in {path}");
+        if let Some(reason) = provenance_mismatch(&previous, &run_provenance(args)) {
+            warn!("The run recorded in {path} may not be reproducible by this one: {reason}");
+        }
+    }
+    if let Some(path) = &args.seen_ids {
+        let loaded = load_seen_ids(path, &mut app_state.engine)?;
+        info!("Loaded {loaded} seen tx id(s) from {path}");
+
+        if loaded > 0 {
+            for input in inputs {
+                check_input_overlap(input, &app_state, args)?;
+            }
+        }
+    }
+    if args.latency {
+        app_state.latency = Some(LatencyRecorder::new());
+    }
+    if args.summary.is_some() || args.report.is_some() {
+        app_state.summary = Some(SummaryRecorder::default());
+    }
+    if args.rejects.is_some() || args.fail_on_rejects || args.report.is_some() {
+        app_state.rejects = Some(RejectLog::default());
+    }
+    if let (Some(path), Some(instance)) = (&args.shard_map, &args.shard) {
+        app_state.shard = Some(ShardRouter::new(ShardMap::read(path)?, instance)?);
+        info!("Processing the clients {path} assigns to shard {instance}");
+    }
+    if args.max_book_held.is_some() || args.max_book_available.is_some() {
+        let limits = ExposureLimits {
+            max_held: args.max_book_held,
+            max_available: args.max_book_available,
+        };
+        app_state.exposure = Some(ExposureGuard::new(limits, &app_state.engine)?);
+    }
+    Ok(app_state)
+}
+
+/// Looks up a currency in the bundled ISO 4217 table, with `--currency-table`
+/// overrides applied.
+///
+/// # Arguments
+/// * `code` - The currency code.
+/// * `table` - The overrides file, if any.
+///
+/// # Returns
+/// * `AppResult<Currency>` - The currency, or an `AppErrors` variant if the
+///   overrides cannot be read or the code is unknown.
+fn load_currency(code: &str, table: Option<&str>) -> AppResult<Currency> {
+    let mut currencies = CurrencyTable::bundled();
+    if let Some(path) = table {
+        currencies = currencies.with_overrides(path)?;
+    }
+    currencies.currency(code)
+}
+
+/// Writes a report to a file, replaced only once complete (see
+/// [`ReportOutput`]), or to stdout.
+///
+/// # Arguments
+/// * `out` - The output path, or `None` for stdout.
+/// * `write` - Writes the report to the output.
+///
+/// # Returns
+/// * `AppResult<T>` - What `write` returned, or an `AppErrors` variant if it
+///   failed (the file is then left untouched) or the output cannot be written.
+fn write_output<T>(
+    out: Option<&str>,
+    write: impl FnOnce(&mut ReportOutput) -> AppResult<T>,
+) -> AppResult<T> {
+    let mut output = ReportOutput::open(out)?;
+    let value = write(&mut output)?;
+    output
+        .flush()
+        .map_err(|e| AppErrors::Io(format!("write {}: {e}", out.unwrap_or("stdout"))))?;
+    output.commit()?;
+    Ok(value)
+}
+
+/// Writes a value as pretty-printed JSON to a file, replaced only once
+/// complete, or to stdout.
+///
+/// # Arguments
+/// * `out` - The output path, or `None` for stdout.
+/// * `value` - The value to write.
+///
+/// # Returns
+/// * `AppResult<()>` - `Ok(())` once written, or an `AppErrors::Io` on failure.
+fn write_json(out: Option<&str>, value: &impl serde::Serialize) -> AppResult<()> {
+    match out {
+        Some(path) => write_json_file(path, value),
+        None => {
+            serde_json::to_writer_pretty(io::stdout().lock(), value)
+                .map_err(|e| AppErrors::Io(format!("write stdout: {e}")))?;
+            println!();
+            Ok(())
+        }
+    }
+}
+
+/// Warn about, or abort on, an input that reuses tx ids seen by earlier runs
+/// beyond `--overlap-threshold` (via [`check_overlap`]). Stdin is skipped, as it
+/// cannot be scanned ahead of processing, and so is non-CSV input.
+///
+/// # Arguments
+/// * `input` - The input path to scan.
+/// * `app_state` - The application state holding the seen tx ids.
+/// * `args` - The `process` arguments (input format, threshold and action).
+///
+/// # Returns
+/// * `AppResult<()>` - Returns `Ok(())` unless the overlap is over the threshold and
+///   `--on-overlap abort` is set (`AppErrors::OverlapDetected`), or the input
+///   cannot be read.
+pub fn check_input_overlap(input: &str, app_state: &AppState, args: &ProcessArgs) -> AppResult<()> {
+    if input == STDIN_PATH {
+        warn!("Skipping the overlap check: stdin cannot be scanned ahead of processing");
+        return Ok(());
+    }
+    if args.source.input_format != InputFormat::Csv {
+        warn!("Skipping the overlap check for {input}: only CSV input can be scanned");
+        return Ok(());
+    }
+    let report = check_overlap(input, &app_state.dialect, &app_state.engine)?;
+    if report.overlapping > 0 && report.ratio() > args.overlap_threshold {
+        warn!(
+            "{input}: {} of {} row(s) ({:.1}%) reuse tx ids seen by earlier runs (range {}..={})",
+            report.overlapping,
+            report.total,
+            report.ratio() * 100.0,
+            report.first.unwrap_or_default(),
+            report.last.unwrap_or_default()
+        );
+        if args.on_overlap == OverlapAction::Abort {
+            return Err(AppErrors::OverlapDetected {
+                overlapping: report.overlapping,
+                total: report.total,
+            });
+        }
+    }
     Ok(())
 }
 
-/// Emit final account states to stdout in CSV format.
+/// Lists the input paths: the given inputs, or the CSV files under `--dir`
+/// (via [`discover_csv_files`]).
+///
+/// # Arguments
+/// * `source` - The input arguments.
+///
+/// # Returns
+/// * `AppResult<Vec<String>>` - The input paths, or an `AppErrors` variant if
+///   the directory cannot be read or there is no input.
+fn input_paths(source: &InputArgs) -> AppResult<Vec<String>> {
+    let inputs = match &source.dir {
+        Some(dir) => {
+            let files = discover_csv_files(Path::new(dir))?;
+            info!("Found {} CSV file(s) under {dir}", files.len());
+            files
+        }
+        None => source.input.clone(),
+    };
+    if inputs.is_empty() {
+        return Err(AppErrors::InvalidInput("missing input path"));
+    }
+    Ok(inputs)
+}
+
+/// Builds the CSV input layout from `--delimiter`, `--no-headers` and `--map`.
+fn input_dialect(source: &InputArgs) -> CsvDialect {
+    CsvDialect {
+        delimiter: source.delimiter,
+        no_headers: source.no_headers,
+        columns: source.map.clone().unwrap_or_default(),
+    }
+}
+
+/// Loads the `--currency` the input amounts are in, if set (via [`load_currency`]).
+fn input_currency(source: &InputArgs) -> AppResult<Option<Currency>> {
+    source
+        .currency
+        .as_deref()
+        .map(|code| load_currency(code, source.currency_table.as_deref()))
+        .transpose()
+}
+
+/// Reads the `--fixed-width-layout`, if set (via [`FixedWidthLayout::read`]).
+fn fixed_width_layout(source: &InputArgs) -> AppResult<Option<FixedWidthLayout>> {
+    source
+        .fixed_width_layout
+        .as_deref()
+        .map(FixedWidthLayout::read)
+        .transpose()
+}
+
+/// Validate the inputs without processing them (`validate`): parse and
+/// type-check every row (via [`validate_csv_path`]), with the input layout and
+/// `--currency`, without loading or changing any state or writing accounts.
+/// Every problem is logged with its file and line, and written to
+/// `--error-report` if set.
+///
+/// # Arguments
+/// * `source` - The input arguments.
+/// * `error_report` - The path to write the problems to as JSON, if any.
+///
+/// # Returns
+/// * `AppResult<()>` - `Ok(())` if every row is valid, `AppErrors::Validation`
+///   counting the invalid rows otherwise, or an `AppErrors` variant if
+///   an input cannot be read or is not CSV.
+fn validate_inputs(source: &InputArgs, error_report: Option<&str>) -> AppResult<()> {
+    if source.input_format != InputFormat::Csv {
+        return Err(AppErrors::InvalidInput("validate supports CSV input only"));
+    }
+    let inputs = input_paths(source)?;
+    let dialect = input_dialect(source);
+    let currency = input_currency(source)?;
+    let (mut rows, mut problems) = (0, Vec::new());
+    for input in &inputs {
+        let report = validate_csv_path(input, &dialect, currency.as_ref())?;
+        for p in &report.problems {
+            error!("{input} line {}: [{}] {}", p.line, p.error.code(), p.error);
+        }
+        info!(
+            "Validated {input}: {} row(s), {} problem(s)",
+            report.rows,
+            report.problems.len()
+        );
+        rows += report.rows;
+        problems.extend(report.problems);
+    }
+    if let Some(path) = error_report {
+        write_error_report(&problems, path)?;
+    }
+    if !problems.is_empty() {
+        return Err(AppErrors::Validation {
+            invalid: problems.len(),
+            rows,
+        });
+    }
+    info!("All {rows} row(s) are valid");
+    Ok(())
+}
+
+/// Replay the inputs onto a state backend and save it back in place
+/// (`replay`): open the state (via [`StoreSpec::open`]), apply every input to
+/// it in order under `on_error` (via [`run_from_path_observed`]), save it, and
+/// emit its accounts as CSV to `output` or stdout (via [`emit_accounts`]).
+///
+/// # Arguments
+/// * `state` - The backend to replay onto.
+/// * `source` - The input arguments.
+/// * `config` - The engine config.
+/// * `output` - The account report path, or `None` for stdout.
+///
+/// # Returns
+/// * `AppResult<()>` - `Ok(())` once the state is saved and the accounts
+///   emitted, or an `AppErrors` variant if an error occurs.
+fn replay(
+    state: &StoreSpec,
+    source: &InputArgs,
+    config: EngineConfig,
+    output: Option<&str>,
+) -> AppResult<()> {
+    let inputs = input_paths(source)?;
+    let report = open_report(output, OutputFormat::Csv, source.delimiter)?;
+    let layout = fixed_width_layout(source)?;
+    let mut app_state = AppState {
+        dialect: input_dialect(source),
+        currency: input_currency(source)?,
+        ..AppState::default()
+    };
+    app_state.engine = state.open(0)?;
+    app_state.engine.set_config(config);
+    info!("Replaying {} input(s) onto {state}", inputs.len());
+    for input in &inputs {
+        let errors = run_from_path_observed(
+            input,
+            source.input_format,
+            layout.as_ref(),
+            &mut app_state,
+            |_, _, _, _| Ok(()),
+        )?;
+        report_row_errors(&errors, None)?;
+        info!("Replayed {input}");
+    }
+    app_state.engine.page_in_all()?;
+    state.save(&app_state.engine)?;
+    info!(
+        "Saved engine state to {state} (hash {:016x})",
+        state_digest(&app_state.engine)
+    );
+    emit_accounts(&app_state, report, false, false)
+}
+
+/// Run a subcommand.
+///
+/// Responsibilities:
+/// - `process`: process input files into an account report (via [`run_process`]).
+/// - `validate`: parse and type-check input files without processing them (via
+///   [`validate_inputs`]).
+/// - `replay`: apply input files to a state backend in place (via [`replay`]).
+/// - `serve`: apply the transaction CSV streamed by each TCP connection to one
+///   engine, saving it to `--state` after each (via [`serve_connection`]), and
+///   emit its accounts after `--connections` connections.
+/// - `process`, `replay`, `serve` and `archive prune` configure the engine from
+///   the shared engine policy flags (via [`cli::EngineArgs::config`]).
+/// - `migrate`: copy state between backends, verified by state hash (via [`migrate`]).
+/// - `backup`: snapshot a backend into a backup directory and prune old backups
+///   (via [`create_backup`]).
+/// - `restore`: write a backup back into a backend (via [`restore_backup`]).
+/// - `follow`: run a read-only replica fed by a leader's `--replicate-to` stream,
+///   then emit its accounts (via [`follow`]).
+/// - `export-client`: write everything stored about a client as JSON
+///   (via [`export_client`]).
+/// - `erase-client`: remove a closed client from a backend and record a tombstone
+///   in the erasure audit log (via [`erase_client`] / [`append_erasure_log`]).
+/// - `annotate`: attach an operator note to an account or tx record in a backend
+///   (via [`Engine::annotate`](payments_engine::state::Engine::annotate)).
+/// - `eod`: close an accounting day of a backend into a day directory of closing
+///   snapshot, balances, manifest and opening/closing totals (via [`close_day`]).
+/// - `archive prune` / `archive query`: move settled tx records to compressed
+///   cold files and search them (via [`archive_final`] / [`query_archive`]).
+/// - `analyze`: report totals, disputes, locks, invariant violations and alert
+///   breaches of a snapshot as JSON (via [`analyze`]).
+/// - `report mt940` / `report ofx`: write a backend's accounts and tx history
+///   as MT940 or OFX statements (via [`write_mt940`] / [`write_ofx`]).
+/// - `report ledger`: write a backend's tx history as beancount or ledger-cli
+///   double-entry text (via [`write_ledger`]).
+/// - `generate`: write synthetic input shaped by workload presets (via
+///   [`Generator`]).
+/// - `shard-map`: write a consistent-hashing map of clients to instances (via
+///   [`ShardMap`]).
+/// - `verify-backups`: load every backup and fail if any is corrupt
+///   (via [`verify_backups`]).
+///
+/// # Arguments
+/// * `command` - The subcommand to run.
+///
+/// # Returns
+/// * `AppResult<()>` - Returns `Ok(())` if the subcommand succeeds,
+///   or an `AppErrors` variant if an error occurs.
+pub fn run_command(command: &Command) -> AppResult<()> {
+    match command {
+        Command::Process(args) => run_process(args)?,
+        Command::Validate {
+            source,
+            error_report,
+        } => validate_inputs(source, error_report.as_deref())?,
+        Command::Replay {
+            state,
+            source,
+            on_error,
+            engine,
+            output,
+        } => replay(state, source, engine.config(*on_error), output.as_deref())?,
+        Command::Serve {
+            listen,
+            state,
+            on_error,
+            engine,
+            connections,
+        } => {
+            let listener = TcpListener::bind(listen)
+                .map_err(|e| AppErrors::Io(format!("bind {listen}: {e}")))?;
+            let mut app_state = AppState::default();
+            if let Some(spec) = state {
+                app_state.engine = spec.open(0)?;
+                app_state.engine.page_in_all()?;
+                info!("Loaded engine state from {spec}");
+            }
+            app_state.engine.set_config(engine.config(*on_error));
+            info!("Serving on {listen}");
+            let mut served = 0;
+            while connections.is_none_or(|max| served < max) {
+                let batch = serve_connection(&listener, &mut app_state)?;
+                served += 1;
+                match &batch.failure {
+                    Some(e) => warn!(
+                        "{}: batch stopped after {} applied row(s): {e}",
+                        batch.peer, batch.applied
+                    ),
+                    None => info!(
+                        "{}: {} row(s) applied, {} row error(s)",
+                        batch.peer, batch.applied, batch.row_errors
+                    ),
+                }
+                if let Some(spec) = state {
+                    spec.save(&app_state.engine)?;
+                }
+            }
+            emit_accounts(
+                &app_state,
+                open_report(None, OutputFormat::Csv, Delimiter::default())?,
+                false,
+                false,
+            )?;
+        }
+        Command::Migrate { from, to } => {
+            info!("Migrating state from {from} to {to}");
+            let report = migrate(from, to)?;
+            info!(
+                "Migrated {} account(s) and {} tx record(s); verification hash {:016x}",
+                report.accounts, report.txs, report.digest
+            );
+        }
+        Command::Backup {
+            state,
+            dir,
+            keep_daily,
+            keep_weekly,
+        } => {
+            let now = unix_now()?;
+            let retention = RetentionPolicy {
+                daily: *keep_daily,
+                weekly: *keep_weekly,
+            };
+            let (backup, pruned) = create_backup(state, Path::new(dir), now, retention)?;
+            info!(
+                "Backed up {state} as {} ({})",
+                backup.id,
+                backup.path.display()
+            );
+            for b in pruned {
+                info!("Pruned backup {}", b.id);
+            }
+        }
+        Command::Restore { dir, backup, to } => {
+            let digest = restore_backup(Path::new(dir), backup, to)?;
+            info!("Restored backup {backup} to {to} (hash {digest:016x})");
+        }
+        Command::Follow { listen, save_state } => {
+            let listener = TcpListener::bind(listen)
+                .map_err(|e| AppErrors::Io(format!("bind {listen}: {e}")))?;
+            info!("Replica listening on {listen}");
+            let mut app_state = AppState::default();
+            let stats = follow(&listener, &mut app_state)?;
+            info!(
+                "Replica applied {} event(s) up to seq {} ({} missing, {} version mismatch(es), max lag {} ms)",
+                stats.applied,
+                stats.last_seq,
+                stats.gaps,
+                stats.version_mismatches,
+                stats.max_lag.as_millis()
+            );
+            emit_accounts(
+                &app_state,
+                open_report(None, OutputFormat::Csv, Delimiter::default())?,
+                false,
+                false,
+            )?;
+            if let Some(spec) = save_state {
+                spec.save(&app_state.engine)?;
+                info!("Saved replica state to {spec}");
+            }
+        }
+        Command::ExportClient { state, client, out } => {
+            let engine = state.load()?;
+            let export = export_client(&engine, *client);
+            if export.account.is_none() && export.transactions.is_empty() {
+                warn!("No data stored for client {client} in {state}");
+            }
+            write_json(out.as_deref(), &export)?;
+            if let Some(path) = out {
+                info!("Exported client {client} to {path}");
+            }
+        }
+        Command::EraseClient {
+            state,
+            client,
+            audit_log,
+        } => {
+            let mut engine = state.load()?;
+            let tombstone = erase_client(&mut engine, *client, unix_now()?)?;
+            state.save(&engine)?;
+            append_erasure_log(audit_log, &tombstone)?;
+            info!(
+                "Erased client {client} ({} tx record(s)) from {state}; tombstone appended to {audit_log}",
+                tombstone.txs
+            );
+        }
+        Command::Annotate {
+            state,
+            client,
+            tx,
+            author,
+            note,
+        } => {
+            let (target, what) = match (client, tx) {
+                (Some(client), _) => (NoteTarget::Account(*client), format!("client {client}")),
+                (None, Some(tx)) => (NoteTarget::Tx(*tx), format!("tx {tx}")),
+                (None, None) => return Err(AppErrors::InvalidInput("nothing to annotate")),
+            };
+            let mut engine = state.load()?;
+            engine.annotate(Annotation {
+                target,
+                author: author.clone(),
+                at: unix_now()?,
+                text: note.clone(),
+            })?;
+            state.save(&engine)?;
+            info!("Annotated {what} in {state}");
+        }
+        Command::Eod { state, dir, date } => {
+            let engine = state.load()?;
+            let now = unix_now()?;
+            let date = date.clone().unwrap_or_else(|| utc_date(now));
+            let day = close_day(&engine, Path::new(dir), &date, now)?;
+            info!(
+                "Closed {date} of {state} into {dir}/{date}: {} account(s), total {} (opened with {})",
+                day.closing.accounts,
+                day.closing.total,
+                day.opening
+                    .map_or_else(|| "nothing".to_string(), |t| t.total)
+            );
+        }
+        Command::Archive {
+            action: ArchiveAction::Prune { state, dir, engine },
+        } => {
+            let config = engine.config(ErrorPolicy::default());
+            let mut engine = state.load()?;
+            engine.set_config(config);
+            let info = archive_final(&mut engine, Path::new(dir), unix_now()?)?;
+            match info.path {
+                Some(path) => {
+                    state.save(&engine)?;
+                    info!(
+                        "Archived {} tx record(s) from {state} to {}",
+                        info.records,
+                        path.display()
+                    );
+                }
+                None => info!("No settled tx records to archive in {state}"),
+            }
+        }
+        Command::Archive {
+            action: ArchiveAction::Query { dir, tx, client },
+        } => {
+            let records = query_archive(Path::new(dir), *tx, *client)?;
+            let mut wtr = WriterBuilder::new().from_writer(io::stdout().lock());
+            for rec in &records {
+                wtr.serialize(rec)
+                    .map_err(|e| AppErrors::Io(format!("write csv: {e}")))?;
+            }
+            wtr.flush()
+                .map_err(|e| AppErrors::Io(format!("flush csv: {e}")))?;
+            info!("Found {} archived tx record(s)", records.len());
+        }
+        Command::Analyze {
+            snapshot,
+            alert,
+            out,
+        } => {
+            let engine = StoreSpec::Snapshot(snapshot.clone()).load()?;
+            let analysis = analyze(&engine, alert)?;
+            info!(
+                "Analyzed {snapshot}: {} account(s), {} open dispute(s), {} locked (hash {})",
+                analysis.balance.accounts,
+                analysis.open_disputes,
+                analysis.locked.len(),
+                analysis.state_hash
+            );
+            if let Some(violations) = &analysis.violations {
+                warn!("{violations}");
+            }
+            for breach in &analysis.breaches {
+                warn!(
+                    "Client {} breaches {} ({})",
+                    breach.client, breach.rule, breach.value
+                );
+            }
+            write_json(out.as_deref(), &analysis)?;
+        }
+        Command::Report {
+            report:
+                ReportKind::Mt940 {
+                    state,
+                    currency,
+                    currency_table,
+                    out,
+                },
+        } => {
+            let currency = load_currency(currency, currency_table.as_deref())?;
+            let engine = state.load()?;
+            let now = unix_now()?;
+            let statements = write_output(out.as_deref(), |output| {
+                write_mt940(output, &engine, &currency, now)
+            })?;
+            info!("Wrote {statements} MT940 statement(s) from {state}");
+        }
+        Command::Report {
+            report:
+                ReportKind::Ofx {
+                    state,
+                    currency,
+                    currency_table,
+                    client,
+                    out,
+                },
+        } => {
+            let currency = load_currency(currency, currency_table.as_deref())?;
+            let engine = state.load()?;
+            let now = unix_now()?;
+            let statements = write_output(out.as_deref(), |output| {
+                write_ofx(output, &engine, *client, &currency, now)
+            })?;
+            info!("Wrote {statements} OFX statement(s) from {state}");
+        }
+        Command::Report {
+            report:
+                ReportKind::Statement {
+                    state,
+                    client,
+                    locale,
+                    out,
+                },
+        } => {
+            let engine = state.load()?;
+            let locale = locale.clone().unwrap_or_default();
+            let entries = write_output(out.as_deref(), |output| {
+                write_statement(output, &engine, *client, &locale)
+            })?;
+            info!("Wrote the statement of client {client} ({entries} entries) from {state}");
+        }
+        Command::Report {
+            report:
+                ReportKind::Ledger {
+                    state,
+                    syntax,
+                    currency,
+                    currency_table,
+                    out,
+                },
+        } => {
+            let currency = load_currency(currency, currency_table.as_deref())?;
+            let engine = state.load()?;
+            let now = unix_now()?;
+            let entries = write_output(out.as_deref(), |output| {
+                write_ledger(output, &engine, *syntax, &currency, now)
+            })?;
+            info!("Wrote {entries} ledger entries from {state}");
+        }
+        Command::Generate {
+            schedule,
+            clients,
+            seed,
+            out,
+        } => {
+            let mut generator = Generator::new(*clients, *seed);
+            let rows = write_output(out.as_deref(), |output| generator.write(output, schedule))?;
+            info!("Generated {rows} row(s) for {clients} client(s) with seed {seed}");
+        }
+        Command::ShardMap {
+            instances,
+            vnodes,
+            out,
+        } => {
+            let map = ShardMap::new(instances, *vnodes)?;
+            write_json(out.as_deref(), &map)?;
+            info!(
+                "Shard map of {} instance(s), {} ring point(s) each",
+                instances.len(),
+                vnodes
+            );
+        }
+        Command::Reconcile { a, b, out } => {
+            let diffs = reconcile(&read_account_report(a)?, &read_account_report(b)?);
+            write_output(out.as_deref(), |output| {
+                for diff in &diffs {
+                    writeln!(output, "{diff}")
+                        .map_err(|e| AppErrors::Io(format!("write mismatches: {e}")))?;
+                }
+                Ok(())
+            })?;
+            if !diffs.is_empty() {
+                return Err(AppErrors::Mismatch(format!(
+                    "{} client(s) differ between {a} and {b}",
+                    diffs.len()
+                )));
+            }
+            info!("{a} and {b} match");
+        }
+        Command::VerifyBackups { dir } => {
+            let mut corrupt = 0;
+            for (backup, res) in verify_backups(Path::new(dir))? {
+                match res {
+                    Ok(digest) => info!("Backup {} ok (hash {digest:016x})", backup.id),
+                    Err(e) => {
+                        error!("Backup {} corrupt: {e}", backup.id);
+                        corrupt += 1;
+                    }
+                }
+            }
+            if corrupt > 0 {
+                return Err(AppErrors::Mismatch(format!("{corrupt} corrupt backup(s)")));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Describes the binary and the behavior-affecting settings of a run, for its
+/// manifest.
+///
+/// # Arguments
+/// * `args` - The run's arguments.
+///
+/// # Returns
+/// * `Provenance` - The run's provenance.
+fn run_provenance(args: &ProcessArgs) -> Provenance {
+    let unset = || "-".to_string();
+    let on_error = args
+        .error_policy()
+        .to_possible_value()
+        .map_or_else(unset, |v| v.get_name().to_string());
+    Provenance::current(&[
+        ("on_error", on_error),
+        (
+            "currency",
+            args.source.currency.clone().unwrap_or_else(unset),
+        ),
+        (
+            "max_book_held",
+            args.max_book_held.map_or_else(unset, |a| a.to_string()),
+        ),
+        (
+            "max_book_available",
+            args.max_book_available
+                .map_or_else(unset, |a| a.to_string()),
+        ),
+        ("shard", args.shard.clone().unwrap_or_else(unset)),
+        (
+            "locked_deposits",
+            args.engine
+                .locked_deposits
+                .to_possible_value()
+                .map_or_else(unset, |v| v.get_name().to_string()),
+        ),
+        (
+            "withdrawal_disputes",
+            args.engine.allow_withdrawal_disputes.to_string(),
+        ),
+        (
+            "strict_duplicates",
+            args.engine.strict_duplicates.to_string(),
+        ),
+        ("orphan_buffer", args.engine.orphan_buffer.to_string()),
+        ("two_pass", args.two_pass.to_string()),
+        (
+            "dispute_window",
+            args.engine
+                .dispute_window
+                .map_or_else(unset, |n| n.to_string()),
+        ),
+        (
+            "unfunded_disputes",
+            args.engine
+                .unfunded_disputes
+                .to_possible_value()
+                .map_or_else(unset, |v| v.get_name().to_string()),
+        ),
+        (
+            "redisputes",
+            args.engine
+                .redisputes
+                .to_possible_value()
+                .map_or_else(unset, |v| v.get_name().to_string()),
+        ),
+    ])
+}
+
+/// Returns the current time in seconds since the Unix epoch.
+///
+/// # Returns
+/// * `AppResult<u64>` - The current time, or `AppErrors::Internal` if the system
+///   clock is before the epoch.
+fn unix_now() -> AppResult<u64> {
+    Ok(SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| AppErrors::Internal(format!("system clock: {e}")))?
+        .as_secs())
+}
+
+/// Open the account report: `--output` if set, written atomically (via
+/// [`AtomicFile`](payments_engine::services::report_output::AtomicFile)),
+/// otherwise stdout.
+///
+/// # Arguments
+/// * `output` - The report path, or `None` for stdout.
+/// * `format` - The report format. Parquet needs a path, as it is binary.
+/// * `delimiter` - The field delimiter of a CSV report.
+///
+/// # Returns
+/// * `AppResult<AccountWriter<ReportOutput>>` - The report writer, or an
+///   `AppErrors` variant if the file cannot be created or the format needs a path.
+pub fn open_report(
+    output: Option<&str>,
+    format: OutputFormat,
+    delimiter: Delimiter,
+) -> AppResult<AccountWriter<ReportOutput>> {
+    #[cfg(feature = "parquet")]
+    if output.is_none() && format == OutputFormat::Parquet {
+        return Err(AppErrors::InvalidInput(
+            "--output-format parquet requires --output",
+        ));
+    }
+    let out = ReportOutput::open(output)?;
+    Ok(AccountWriter::with_delimiter(out, format, delimiter))
+}
+
+/// Open the account report of a `process` run (see [`open_report`]), with its
+/// locale, client filter and amount style.
+///
+/// # Arguments
+/// * `args` - The `process` arguments.
+///
+/// # Returns
+/// * `AppResult<AccountWriter<ReportOutput>>` - The report writer, or an
+///   `AppErrors` variant if `--extended` is used with a format that cannot carry
+///   it or the report cannot be opened.
+fn open_process_report(args: &ProcessArgs) -> AppResult<AccountWriter<ReportOutput>> {
+    if args.extended
+        && !matches!(
+            args.output_format,
+            OutputFormat::Csv | OutputFormat::Json | OutputFormat::Ndjson
+        )
+    {
+        return Err(AppErrors::InvalidInput(
+            "--extended supports csv, json and ndjson output",
+        ));
+    }
+    if let Some(locale) = &args.locale
+        && args.output_format != OutputFormat::Table
+    {
+        // Every other account report format is machine-readable.
+        warn!("--locale {locale} has no effect: no human-facing report is selected");
+    }
+    Ok(open_report(
+        args.output.as_deref(),
+        args.output_format,
+        args.source.delimiter,
+    )?
+    .with_locale(args.locale.clone().unwrap_or_default())
+    .with_clients(&args.client)
+    .with_amount_style(if args.trim_zeros {
+        AmountStyle::Trimmed
+    } else {
+        AmountStyle::Fixed
+    }))
+}
+
+/// Emit final account states to the report.
 ///
 /// Responsibilities:
-/// - Create a CSV writer bound to `stdout`.
-/// - Iterate over all accounts in the engine.
-/// - Serialize each account as an [`OutputRow`] with `available`, `held`, `total`
-///   reported to 4 decimal places, and `locked` as a boolean.
-/// - Flush the writer at the end.
+/// - Iterate over all accounts in the engine, in client id order if `sorted`,
+///   and write each one to `wtr` (CSV rows with balances to 4 decimal places,
+///   JSON / NDJSON objects with numeric balances, or Parquet columns), with the
+///   client's activity appended if `extended`.
+/// - Finish (flush) the writer at the end, and commit a report file into place.
 ///
 /// Logs the number of accounts written.
 ///
 /// # Arguments
 /// * `app_state` - A reference to the application state containing the engine.
+/// * `wtr` - The report writer (see [`open_report`]).
+/// * `sorted` - Whether to emit accounts in client id order; otherwise they come
+///   in the engine's (hash) order.
+/// * `extended` - Whether to append each client's activity (see
+///   [`Engine::activity`](payments_engine::state::Engine::activity)).
 ///
 /// # Returns
 /// * `AppResult<()>` - Returns `Ok(())` if the accounts are successfully emitted,
 ///   or an `AppErrors` variant if an error occurs.
-pub fn emit_accounts_to_stdout(app_state: &AppState) -> AppResult<()> {
-    let out = io::stdout();
-    let handle = out.lock();
-    let mut wtr = WriterBuilder::new().has_headers(true).from_writer(handle);
+pub fn emit_accounts(
+    app_state: &AppState,
+    mut wtr: AccountWriter<ReportOutput>,
+    sorted: bool,
+    extended: bool,
+) -> AppResult<()> {
+    let mut accounts: Vec<_> = app_state.engine.accounts_iter().collect();
+    if sorted {
+        accounts.sort_unstable_by_key(|(client, _)| **client);
+    }
+    for (client, acc) in accounts {
+        if extended {
+            wtr.write_extended(*client, acc, &app_state.engine.activity(*client))?;
+        } else {
+            wtr.write(*client, acc)?;
+        }
+    }
+    let (count, out) = wtr.close()?;
+    out.commit()?;
 
-    let mut count = 0;
+    info!("Emitted {} account(s)", count);
+    Ok(())
+}
+
+/// Process a client-sorted input file and emit each account to the report as
+/// soon as its client is complete.
+///
+/// Responsibilities:
+/// - Drive [`run_from_csv_path_sorted_by_client`], writing each finished account
+///   to `wtr`.
+/// - Emit any accounts still left in the engine at the end, then finish the writer
+///   and commit a report file into place.
+///
+/// Logs the number of accounts written.
+///
+/// # Arguments
+/// * `path` - The file path to the client-sorted CSV input.
+/// * `app_state` - A mutable reference to the application state.
+/// * `wtr` - The report writer (see [`open_report`]).
+///
+/// # Returns
+/// * `AppResult<Vec<RowError>>` - The row errors gathered under `--on-error collect`,
+///   or an `AppErrors` variant if an error occurs.
+pub fn stream_accounts(
+    path: &str,
+    app_state: &mut AppState,
+    mut wtr: AccountWriter<ReportOutput>,
+) -> AppResult<Vec<RowError>> {
+    let errors =
+        run_from_csv_path_sorted_by_client(path, app_state, |client, acc| wtr.write(client, &acc))?;
     for (client, acc) in app_state.engine.accounts_iter() {
-        let row = OutputRow::from((client, acc));
-        wtr.serialize(row)
-            .map_err(|e| AppErrors::Io(format!("write csv: {e}")))?;
-        count += 1;
+        wtr.write(*client, acc)?;
+    }
+    let (count, out) = wtr.close()?;
+    out.commit()?;
+
+    info!("Streamed {} account(s)", count);
+    Ok(errors)
+}
+
+/// The observers a `process` run feeds every applied row to: the replication
+/// sink, the change data capture stream, the alert monitor, the dispute SLA
+/// tracker and the rounding tracker.
+struct RowObservers {
+    /// Streams applied rows to a follower, under `--replicate-to`.
+    sink: Option<ReplicationSink>,
+    /// Writes account changes, under `--cdc`.
+    cdc: Option<CdcStream<BufWriter<File>>>,
+    /// Checks accounts against the `--alert` rules.
+    monitor: AlertMonitor,
+    /// Tracks dispute SLAs, under `--dispute-sla`.
+    sla: Option<DisputeSlaTracker>,
+    /// Accumulates the rounding residual of applied amounts.
+    rounding: RoundingTracker,
+}
+
+impl RowObservers {
+    /// Sets up the observers the arguments ask for.
+    ///
+    /// # Arguments
+    /// * `args` - The `process` arguments.
+    /// * `app_state` - The state as opened, before any input is applied.
+    ///
+    /// # Returns
+    /// * `AppResult<Self>` - The observers, or an `AppErrors` variant if the
+    ///   follower cannot be reached or the change stream cannot be created.
+    fn new(args: &ProcessArgs, app_state: &AppState) -> AppResult<Self> {
+        Ok(Self {
+            sink: args
+                .replicate_to
+                .as_deref()
+                .map(|addr| ReplicationSink::connect(addr, &app_state.engine.config()))
+                .transpose()?,
+            cdc: args
+                .cdc
+                .as_deref()
+                .map(|path| CdcStream::create(path, &app_state.engine))
+                .transpose()?,
+            monitor: AlertMonitor::new(args.alert.clone()),
+            sla: args.dispute_sla.map(DisputeSlaTracker::new),
+            rounding: RoundingTracker::default(),
+        })
+    }
+
+    /// Feeds an executed row to the observers. The replication sink and the
+    /// change stream see every executed row, so a follower ignores the same
+    /// rows its leader did, and so does the dispute SLA tracker, as an ignored
+    /// row can still expire disputes; the alerts and rounding residual only see
+    /// rows the engine applied.
+    ///
+    /// # Arguments
+    /// * `line` - The row's line number in its input.
+    /// * `run_line` - The row's position across all inputs of the run.
+    /// * `row` - The executed row.
+    /// * `outcome` - What applying it did.
+    /// * `app_state` - The state after the row was executed.
+    ///
+    /// # Returns
+    /// * `AppResult<()>` - `Ok(())` once observed, or an `AppErrors` variant if
+    ///   the row cannot be streamed.
+    fn observe(
+        &mut self,
+        line: u64,
+        run_line: u64,
+        row: &InputRow,
+        outcome: Outcome,
+        app_state: &AppState,
+    ) -> AppResult<()> {
+        if outcome == Outcome::Applied {
+            if let Some(acc) = app_state.engine.acct(row.client) {
+                self.monitor.observe(line, row.client, row.tx, acc);
+            }
+            self.rounding.observe(row);
+        }
+        if let Some(sla) = self.sla.as_mut() {
+            sla.observe(run_line, &app_state.engine);
+        }
+        if let Some(cdc) = self.cdc.as_mut() {
+            cdc.observe(row, &app_state.engine)?;
+        }
+        self.sink.as_mut().map_or(Ok(()), |s| {
+            s.send(row, app_state.engine.version(row.client))
+        })
+    }
+
+    /// Finishes the streams and reports the alerts (with the book exposure
+    /// alerts), the dispute SLA compliance and the rounding residual (via
+    /// [`report_alerts`] / [`report_dispute_sla`]).
+    ///
+    /// # Arguments
+    /// * `args` - The `process` arguments.
+    /// * `app_state` - The state after every input was applied.
+    ///
+    /// # Returns
+    /// * `AppResult<()>` - `Ok(())` once reported, or an `AppErrors` variant if
+    ///   a stream or report cannot be written.
+    fn finish(self, args: &ProcessArgs, app_state: &AppState) -> AppResult<()> {
+        if let Some(sink) = self.sink {
+            info!("Replicated {} event(s)", sink.finish()?);
+        }
+        if let (Some(cdc), Some(path)) = (self.cdc, &args.cdc) {
+            info!("Wrote {} change event(s) to {path}", cdc.finish()?);
+        }
+        let book_alerts = app_state.exposure.as_ref().map_or(&[][..], |g| g.alerts());
+        report_alerts(
+            &[self.monitor.alerts(), book_alerts].concat(),
+            args.alerts_report.as_deref(),
+        )?;
+        if let Some(sla) = self.sla {
+            report_dispute_sla(&sla.finish(), args.sla_report.as_deref())?;
+        }
+        if args.track_rounding {
+            info!(
+                "Rounding residual: {} across {} rounded amount(s)",
+                self.rounding.residual(),
+                self.rounding.rounded()
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Log the commands the engine dropped or completed on its own by the end of
+/// the run: disputes still queued for funds, disputes resolved at the end of
+/// their window, buffered rows that never saw their transaction, and rows of
+/// clients owned by other shards.
+///
+/// # Arguments
+/// * `app_state` - The state after every input was applied.
+fn report_dropped_commands(app_state: &AppState) {
+    let pending = app_state.engine.pending_dispute_count();
+    if pending > 0 {
+        warn!("{pending} dispute(s) still queued for funds are dropped at the end of the run");
+    }
+    let expired = app_state.engine.expired_disputes();
+    if expired > 0 {
+        warn!("{expired} dispute(s) were resolved automatically at the end of their window");
+    }
+    let buffered = app_state.engine.buffered_count();
+    if buffered > 0 {
+        warn!("{buffered} buffered row(s) never saw their transaction and are dropped");
+    }
+    if let Some(router) = &app_state.shard {
+        info!(
+            "Skipped {} row(s) of clients owned by other shards",
+            router.foreign()
+        );
+    }
+}
+
+/// Write the run's optional outputs once processing is done: the manifest,
+/// the quarantined rows, the camt.053 statement, the summary, the conservation
+/// check, the fingerprint, the rejects and the transaction dump.
+///
+/// # Arguments
+/// * `args` - The `process` arguments.
+/// * `app_state` - The state after every input was applied.
+/// * `opening_funds` - The book's funds before the run, under `--conservation`.
+///
+/// # Returns
+/// * `AppResult<()>` - `Ok(())` once written, or an `AppErrors` variant if an
+///   output cannot be written.
+fn write_run_reports(
+    args: &ProcessArgs,
+    app_state: &AppState,
+    opening_funds: Option<Amount>,
+) -> AppResult<()> {
+    if let Some(path) = &args.manifest {
+        let mut manifest = RunManifest::closing(&app_state.engine, unix_now()?)?;
+        manifest.provenance = Some(run_provenance(args));
+        if let Some(latency) = &app_state.latency {
+            let summary = latency.summary(args.latency_raw)?;
+            if let Some(all) = summary.get("all") {
+                info!(
+                    "Command latency: p50 {} ns, p99 {} ns, p99.9 {} ns, max {} ns over {} command(s)",
+                    all.p50, all.p99, all.p999, all.max, all.count
+                );
+            }
+            manifest.latency = Some(summary);
+        }
+        manifest.write(path)?;
+        info!("Run manifest written to {path}");
+    }
+    if let Some(guard) = &app_state.exposure {
+        if guard.quarantined() > 0 {
+            warn!(
+                "Quarantined {} row(s) breaching the book exposure caps",
+                guard.quarantined()
+            );
+        }
+        if let Some(path) = &args.quarantine {
+            guard.write_quarantine(path)?;
+            info!("Quarantined rows written to {path}");
+        }
     }
-    wtr.flush()
-        .map_err(|e| AppErrors::Io(format!("flush csv: {e}")))?;
+    if let Some(path) = &args.camt053 {
+        let statements = write_camt053_file(
+            path,
+            &app_state.engine,
+            &args.statement_currency,
+            unix_now()?,
+        )?;
+        info!("Wrote {statements} camt.053 statement(s) to {path}");
+    }
+    if let (Some(path), Some(recorder)) = (&args.summary, &app_state.summary) {
+        let summary = recorder.summary(&app_state.engine)?;
+        summary.write((path != "-").then_some(path.as_str()))?;
+        if path != "-" {
+            info!("Run summary written to {path}");
+        }
+    }
+    if let (Some(path), Some(opening)) = (&args.conservation, opening_funds) {
+        let report = ConservationReport::check(opening, &app_state.engine)?;
+        report.write((path != "-").then_some(path.as_str()))?;
+        if !report.balanced {
+            warn!(
+                "Funds are not conserved: the book is off by {} from its opening funds and flows",
+                report.discrepancy
+            );
+        }
+        if path != "-" {
+            info!("Conservation check written to {path}");
+        }
+    }
+    if args.fingerprint {
+        eprintln!("fingerprint: {}", account_fingerprint(&app_state.engine));
+    }
+    if let (Some(path), Some(rejects)) = (&args.rejects, &app_state.rejects) {
+        rejects.write(path)?;
+        info!("Wrote {} rejected row(s) to {path}", rejects.len());
+    }
+    if let Some(path) = &args.dump_txs {
+        let records = dump_txs(&app_state.engine, path)?;
+        info!("Wrote {records} transaction record(s) to {path}");
+    }
+    Ok(())
+}
 
-    info!("Emitted {} account(s) to stdout", count);
+/// Log the number of alerts raised by `--alert` rules and optionally write them
+/// to a JSON report file. Each alert was already logged when it was raised.
+///
+/// # Arguments
+/// * `alerts` - The alerts raised, in order.
+/// * `json_path` - If set, where to write the alerts as a JSON array of
+///   [`Alert`] objects; the file is replaced only once complete.
+///
+/// # Returns
+/// * `AppResult<()>` - Returns `Ok(())` if the alerts were reported, or an
+///   `AppErrors::Io` if the report file cannot be written.
+pub fn report_alerts(alerts: &[Alert], json_path: Option<&str>) -> AppResult<()> {
+    if !alerts.is_empty() {
+        warn!("{} alert(s) raised", alerts.len());
+    }
+
+    if let Some(path) = json_path {
+        write_json_file(path, &alerts)?;
+        info!("Alerts report written to {path}");
+    }
     Ok(())
 }
+
+/// Log the dispute SLA compliance summary and optionally write it, with every
+/// breach, to a JSON report file.
+///
+/// # Arguments
+/// * `summary` - The SLA summary for the run.
+/// * `json_path` - If set, where to write the [`SlaSummary`] as JSON; the file
+///   is replaced only once complete.
+///
+/// # Returns
+/// * `AppResult<()>` - Returns `Ok(())` if the summary was reported, or an
+///   `AppErrors::Io` if the report file cannot be written.
+pub fn report_dispute_sla(summary: &SlaSummary, json_path: Option<&str>) -> AppResult<()> {
+    info!(
+        "Dispute SLA ({} rows): {} closed, {} within SLA, {} open, {} breach(es); {:.1}% compliant",
+        summary.sla_rows,
+        summary.closed,
+        summary.within_sla,
+        summary.open,
+        summary.breaches.len(),
+        summary.compliance_pct
+    );
+
+    if let Some(path) = json_path {
+        write_json_file(path, summary)?;
+        info!("Dispute SLA report written to {path}");
+    }
+    Ok(())
+}
+
+/// Log the row errors gathered under `--on-error collect`, one per line,
+/// followed by a count, and optionally write them to a JSON report file.
+///
+/// # Arguments
+/// * `errors` - The gathered row errors, in input order.
+/// * `json_path` - If set, where to write the errors as a JSON array of
+///   [`ErrorReport`](payments_engine::models::policy::ErrorReport) objects.
+///
+/// # Returns
+/// * `AppResult<()>` - Returns `Ok(())` if the errors were reported, or an
+///   `AppErrors::Io` if the report file cannot be written.
+pub fn report_row_errors(errors: &[RowError], json_path: Option<&str>) -> AppResult<()> {
+    for e in errors {
+        error!("line {}: [{}] {}", e.line, e.error.code(), e.error);
+    }
+    if !errors.is_empty() {
+        warn!("{} row error(s) collected", errors.len());
+    }
+
+    match json_path {
+        Some(path) => write_error_report(errors, path),
+        None => Ok(()),
+    }
+}
+
+/// Write row errors to a JSON report file as an array of
+/// [`ErrorReport`](payments_engine::models::policy::ErrorReport) objects. The
+/// file is replaced only once the report is complete.
+///
+/// # Arguments
+/// * `errors` - The row errors, in input order.
+/// * `path` - The report file.
+///
+/// # Returns
+/// * `AppResult<()>` - Returns `Ok(())` once written, or an `AppErrors::Io` if
+///   the report file cannot be written.
+fn write_error_report(errors: &[RowError], path: &str) -> AppResult<()> {
+    let reports: Vec<_> = errors.iter().map(RowError::report).collect();
+    write_json_file(path, &reports)?;
+    info!("Error report written to {path}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::error::ErrorKind;
+    use payments_engine::services::csv_service::run_from_reader_observed;
+
+    /// Parses a command line as the binary would.
+    fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        Cli::try_parse_from(std::iter::once("payments-engine").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn observers_skip_rows_the_engine_ignored() {
+        let args = parse(&["in.csv", "--track-rounding", "--alert", "available>2"]).unwrap();
+        let mut app_state = AppState::default();
+        app_state.engine.acct_mut(2).available = Amount(50_000);
+        app_state.engine.acct_mut(2).locked = true;
+        let mut observers = RowObservers::new(&args.process, &app_state).unwrap();
+        let input = "type,client,tx,amount\n\
+                     deposit,1,1,1.00005\n\
+                     deposit,1,1,1.00005\n\
+                     deposit,2,2,1.00005\n";
+
+        run_from_reader_observed(
+            input.as_bytes(),
+            &mut app_state,
+            |line, row, outcome, state| observers.observe(line, line, row, outcome, state),
+        )
+        .unwrap();
+
+        assert_eq!(observers.rounding.rounded(), 1);
+        assert_eq!(observers.rounding.residual().to_string(), "+0.000050000000");
+        // The locked account is over the threshold, but no row changed it.
+        assert!(observers.monitor.alerts().is_empty());
+    }
+
+    #[test]
+    fn sorted_input_conflicts_with_options_needing_the_whole_state() {
+        for flag in ["--verify", "--two-pass", "--sort-output", "--extended"] {
+            let err = parse(&["in.csv", "--sorted-by", "client", flag]).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::ArgumentConflict, "{flag}");
+        }
+        let err = parse(&["--dir", "inputs", "--sorted-by", "client"]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+        assert!(parse(&["in.csv", "--sorted-by", "client", "--strict"]).is_ok());
+    }
+
+    #[test]
+    fn conflicting_process_options_are_rejected() {
+        let conflicts: [&[&str]; 4] = [
+            &["in.csv", "--dir", "inputs"],
+            &["in.csv", "--strict", "--on-error", "collect"],
+            &[
+                "in.csv",
+                "--replicate-to",
+                "127.0.0.1:7100",
+                "--load-state",
+                "snapshot:s.bin",
+            ],
+            &[
+                "in.csv",
+                "--replicate-to",
+                "127.0.0.1:7100",
+                "--seen-ids",
+                "seen.bin",
+            ],
+        ];
+        for args in conflicts {
+            let err = parse(args).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::ArgumentConflict, "{args:?}");
+        }
+    }
+
+    #[test]
+    fn annotate_takes_a_client_or_a_tx() {
+        let base = [
+            "annotate",
+            "--state",
+            "snapshot:s.bin",
+            "--author",
+            "ops",
+            "--note",
+            "n",
+        ];
+        let with = |extra: &[&'static str]| parse(&[&base[..], extra].concat());
+        assert!(with(&["--client", "1"]).is_ok());
+        assert!(with(&["--tx", "7"]).is_ok());
+        assert_eq!(
+            with(&["--client", "1", "--tx", "7"]).unwrap_err().kind(),
+            ErrorKind::ArgumentConflict
+        );
+        assert_eq!(
+            with(&[]).unwrap_err().kind(),
+            ErrorKind::MissingRequiredArgument
+        );
+    }
+
+    #[test]
+    fn parses_report_subcommands() {
+        let args = parse(&[
+            "report",
+            "mt940",
+            "--state",
+            "snapshot:s.bin",
+            "--currency",
+            "EUR",
+        ])
+        .unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Report {
+                report: ReportKind::Mt940 { ref currency, out: None, .. }
+            }) if currency == "EUR"
+        ));
+        let err = parse(&["report", "mt940", "--state", "snapshot:s.bin"]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::MissingRequiredArgument);
+    }
+
+    #[test]
+    fn generated_input_processes_and_reconciles_with_itself() {
+        let dir = std::env::temp_dir().join(format!("pe-{}-main-generate", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_string_lossy().into_owned();
+        let (input, report) = (path("input.csv"), path("accounts.csv"));
+
+        let run = |args: &[&str]| run_app(&parse(args).unwrap());
+        run(&[
+            "generate",
+            "--schedule",
+            "uniform:200,dispute-storm:50",
+            "--clients",
+            "5",
+            "--out",
+            &input,
+        ])
+        .unwrap();
+        run(&[&input, "--output", &report]).unwrap();
+        run(&["reconcile", &report, &report, "--out", &path("diffs.txt")]).unwrap();
+
+        let accounts = std::fs::read_to_string(&report).unwrap();
+        assert!(accounts.starts_with("client,available,held,total,locked"));
+        assert!(
+            std::fs::read_to_string(path("diffs.txt"))
+                .unwrap()
+                .is_empty()
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -8,6 +8,17 @@ use std::str::FromStr;
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Amount(pub i64);
 
+/// How an amount is written in machine-readable reports.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AmountStyle {
+    /// Always four decimal places, e.g. `1.5000` (see [`Amount`]'s `Display`).
+    #[default]
+    Fixed,
+    /// Trailing fractional zeros dropped, e.g. `1.5`, and `2` for `2.0000`, so
+    /// consumers comparing amounts as strings see one spelling per value.
+    Trimmed,
+}
+
 impl Amount {
     /// Creates a new `Amount` with a value of zero.
     #[inline]
@@ -56,6 +67,26 @@ impl Amount {
         self.0 < 0
     }
 
+    /// Formats the amount in the given style.
+    ///
+    /// # Arguments
+    ///
+    /// * `style` - Whether to keep or trim trailing fractional zeros.
+    ///
+    /// # Returns
+    ///
+    /// * `String` - The formatted amount, e.g. `1.5000` or `1.5`.
+    pub fn format(self, style: AmountStyle) -> String {
+        let fixed = self.to_string();
+        match style {
+            AmountStyle::Fixed => fixed,
+            AmountStyle::Trimmed => fixed
+                .trim_end_matches('0')
+                .trim_end_matches('.')
+                .to_string(),
+        }
+    }
+
     /// Parses a string into an `Amount` with up to 4 decimal places.
     /// Rounds the 5th decimal place half-up.
     ///
@@ -166,6 +197,16 @@ mod tests {
     use super::*;
     use std::str::FromStr;
 
+    #[test]
+    fn format_trims_trailing_zeros_only_when_asked() {
+        assert_eq!(Amount(15_000).format(AmountStyle::Fixed), "1.5000");
+        assert_eq!(Amount(15_000).format(AmountStyle::Trimmed), "1.5");
+        assert_eq!(Amount(20_000).format(AmountStyle::Trimmed), "2");
+        assert_eq!(Amount(1_000_000).format(AmountStyle::Trimmed), "100");
+        assert_eq!(Amount(-1).format(AmountStyle::Trimmed), "-0.0001");
+        assert_eq!(Amount(0).format(AmountStyle::Trimmed), "0");
+    }
+
     #[test]
     fn zero_amount_has_value_zero() {
         let amount = Amount::zero();
@@ -1,10 +1,16 @@
-use crate::models::domain_state::Account;
+use crate::errors::{AppErrors, AppResult};
+use crate::models::amount::AmountStyle;
+use crate::models::domain_state::{Account, ClientActivity};
 use crate::models::identifiers::{ClientId, TxId};
+use csv::StringRecord;
+use serde::de::IntoDeserializer;
 use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
 
 /// Represents the kind of transaction in a CSV file.
 /// The variants correspond to different transaction types.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum CsvTxType {
     /// A deposit transaction.
@@ -19,9 +25,37 @@ pub enum CsvTxType {
     Chargeback,
 }
 
+impl CsvTxType {
+    /// Returns the name used in the CSV `type` column, e.g. `chargeback`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CsvTxType::Deposit => "deposit",
+            CsvTxType::Withdrawal => "withdrawal",
+            CsvTxType::Dispute => "dispute",
+            CsvTxType::Resolve => "resolve",
+            CsvTxType::Chargeback => "chargeback",
+        }
+    }
+}
+
+impl FromStr for CsvTxType {
+    type Err = AppErrors;
+
+    /// Parses a transaction type by the name used in the CSV `type` column, for
+    /// input formats that carry it as a plain string.
+    ///
+    /// # Returns
+    /// * `Result<Self, AppErrors>` - The type, or `AppErrors::Decode` for an
+    ///   unknown name.
+    fn from_str(s: &str) -> Result<Self, AppErrors> {
+        Self::deserialize(s.into_deserializer())
+            .map_err(|e: serde::de::value::Error| AppErrors::Decode(e.to_string()))
+    }
+}
+
 /// Represents a row in the input CSV file.
 /// Contains transaction details such as type, client ID, transaction ID, and an optional amount.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InputRow {
     /// The type of the transaction (e.g., deposit, withdrawal).
     #[serde(rename = "type")]
@@ -34,6 +68,150 @@ pub struct InputRow {
     pub amount: Option<String>,
 }
 
+impl Display for InputRow {
+    /// Writes the row as a CSV record, e.g. `deposit,1,2,1.5` (with a trailing
+    /// comma when it has no amount), for logs.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{},{},{},{}",
+            self.t.as_str(),
+            self.client,
+            self.tx,
+            self.amount.as_deref().unwrap_or_default()
+        )
+    }
+}
+
+/// The field delimiter of CSV input and output: a comma by default, or e.g. a
+/// semicolon or tab for partners whose locale uses the comma as decimal mark.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Delimiter(pub u8);
+
+impl Default for Delimiter {
+    fn default() -> Self {
+        Self(b',')
+    }
+}
+
+impl FromStr for Delimiter {
+    type Err = AppErrors;
+
+    /// Parses a delimiter: a single ASCII character, or `tab` / `\t` for a tab.
+    ///
+    /// # Returns
+    /// * `Result<Self, AppErrors>` - The delimiter, or `AppErrors::InvalidInput`
+    ///   if it is not a single ASCII character, or is a quote or line break.
+    fn from_str(s: &str) -> Result<Self, AppErrors> {
+        let byte = match s {
+            "tab" | "\\t" => b'\t',
+            _ if s.len() == 1 && s.is_ascii() => s.as_bytes()[0],
+            _ => {
+                return Err(AppErrors::InvalidInput(
+                    "delimiter must be a single ASCII character or `tab`",
+                ));
+            }
+        };
+        if matches!(byte, b'"' | b'\n' | b'\r') {
+            return Err(AppErrors::InvalidInput(
+                "delimiter cannot be a quote or line break",
+            ));
+        }
+        Ok(Self(byte))
+    }
+}
+
+/// The layout of CSV input.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CsvDialect {
+    /// The field delimiter.
+    pub delimiter: Delimiter,
+    /// Whether the input has no header row, so fields are read by position as
+    /// [`POSITIONAL_HEADERS`].
+    pub no_headers: bool,
+    /// The input's names for the engine's columns, applied to the header row.
+    pub columns: ColumnMap,
+}
+
+/// The column order of headerless input.
+pub const POSITIONAL_HEADERS: [&str; 4] = ["type", "client", "tx", "amount"];
+
+/// Maps the engine's input columns to the names an input file uses for them,
+/// so files with other headers can be read without rewriting them. Columns are
+/// matched by name, so their order does not matter.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ColumnMap(Vec<(&'static str, String)>);
+
+impl ColumnMap {
+    /// Renames a header row to the engine's column names.
+    ///
+    /// Mapped columns take their engine name. A column that already carries
+    /// the engine name of a mapped column is ignored, so it cannot shadow the
+    /// mapped one; other columns are kept as they are.
+    ///
+    /// # Arguments
+    /// * `headers` - The input's header row.
+    ///
+    /// # Returns
+    /// * `AppResult<StringRecord>` - The renamed header row, or
+    ///   `AppErrors::InvalidInput` if a mapped column is not in it.
+    pub fn rename(&self, headers: &StringRecord) -> AppResult<StringRecord> {
+        if self
+            .0
+            .iter()
+            .any(|(_, from)| !headers.iter().any(|field| field == from))
+        {
+            return Err(AppErrors::InvalidInput(
+                "a mapped column is missing from the input header",
+            ));
+        }
+        Ok(headers
+            .iter()
+            .map(
+                |field| match self.0.iter().find(|(_, from)| from == field) {
+                    Some((to, _)) => to,
+                    None if self.0.iter().any(|(to, _)| *to == field) => "",
+                    None => field,
+                },
+            )
+            .collect())
+    }
+}
+
+impl FromStr for ColumnMap {
+    type Err = AppErrors;
+
+    /// Parses `<column>=<input name>` pairs separated by commas, e.g.
+    /// `type=txn_type,client=customer_id`, where each column is one of
+    /// [`POSITIONAL_HEADERS`].
+    ///
+    /// # Returns
+    /// * `Result<Self, AppErrors>` - The map, or `AppErrors::InvalidInput` if a
+    ///   pair is malformed, names an unknown column, or maps one twice.
+    fn from_str(s: &str) -> Result<Self, AppErrors> {
+        let mut pairs = Vec::new();
+        for pair in s.split(',') {
+            let (to, from) = pair.split_once('=').ok_or(AppErrors::InvalidInput(
+                "column mappings must be written as <column>=<input name>",
+            ))?;
+            let (to, from) = (to.trim(), from.trim());
+            let to = POSITIONAL_HEADERS
+                .into_iter()
+                .find(|column| *column == to)
+                .ok_or(AppErrors::InvalidInput(
+                    "mapped columns must be type, client, tx or amount",
+                ))?;
+            if from.is_empty() || pairs.iter().any(|(seen, _)| *seen == to) {
+                return Err(AppErrors::InvalidInput(
+                    "each column must be mapped once, to a non-empty name",
+                ));
+            }
+            pairs.push((to, from.to_string()));
+        }
+        Ok(Self(pairs))
+    }
+}
+
 /// Represents a row in the output CSV file.
 /// Contains account details such as available balance, held balance, total balance, and lock status.
 #[derive(Debug, Serialize)]
@@ -50,6 +228,67 @@ pub struct OutputRow {
     pub locked: bool,
 }
 
+/// Represents a row of the extended output CSV file: an [`OutputRow`] followed
+/// by the client's activity during the run.
+#[derive(Debug, Serialize)]
+pub struct ExtendedOutputRow {
+    /// The ID of the client associated with the account.
+    pub client: ClientId,
+    /// The available balance in the account as a string.
+    pub available: String,
+    /// The held balance in the account as a string.
+    pub held: String,
+    /// The total balance in the account as a string.
+    pub total: String,
+    /// Indicates whether the account is locked.
+    pub locked: bool,
+    /// The sum of the client's applied deposits as a string.
+    pub deposited: String,
+    /// The sum of the client's applied withdrawals as a string.
+    pub withdrawn: String,
+    /// The number of disputes opened on the client's transactions.
+    pub disputes: u64,
+    /// The number of the client's transactions charged back.
+    pub chargebacks: u64,
+}
+
+impl OutputRow {
+    /// Builds the row of a client's account, writing amounts in `style`.
+    pub fn styled(client: ClientId, acc: &Account, style: AmountStyle) -> Self {
+        Self {
+            client,
+            available: acc.available.format(style),
+            held: acc.held.format(style),
+            total: acc.total().format(style),
+            locked: acc.locked,
+        }
+    }
+}
+
+impl ExtendedOutputRow {
+    /// Builds the extended row of a client's account and activity, writing
+    /// amounts in `style`.
+    pub fn new(
+        client: ClientId,
+        acc: &Account,
+        activity: &ClientActivity,
+        style: AmountStyle,
+    ) -> Self {
+        let row = OutputRow::styled(client, acc, style);
+        Self {
+            client,
+            available: row.available,
+            held: row.held,
+            total: row.total,
+            locked: row.locked,
+            deposited: activity.deposited.format(style),
+            withdrawn: activity.withdrawn.format(style),
+            disputes: activity.disputes,
+            chargebacks: activity.chargebacks,
+        }
+    }
+}
+
 impl From<(&ClientId, &Account)> for OutputRow {
     /// Converts a tuple of `ClientId` and `Account` into an `OutputRow`.
     ///
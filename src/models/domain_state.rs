@@ -1,5 +1,6 @@
 use crate::models::amount::Amount;
-use crate::models::identifiers::ClientId;
+use crate::models::identifiers::{ClientId, TxId};
+use crate::models::policy::EngineConfig;
 
 /// Represents the type of a transaction.
 /// A transaction can either be a deposit or a withdrawal.
@@ -11,18 +12,44 @@ pub enum TxKind {
     Withdrawal,
 }
 
+impl TxKind {
+    /// Returns the lowercase name used in reports, e.g. `deposit`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TxKind::Deposit => "deposit",
+            TxKind::Withdrawal => "withdrawal",
+        }
+    }
+}
+
 /// Represents the state of a dispute for a transaction.
-/// A transaction can be in one of three states: normal, disputed, or charged back.
+/// A transaction can be in one of four states: normal, disputed, resolved, or
+/// charged back.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DisputeState {
     /// The transaction is in a normal state (no dispute).
     Normal,
     /// The transaction is currently disputed.
     Disputed,
+    /// The transaction's dispute was resolved; it can be disputed again unless
+    /// the engine forbids re-disputes.
+    Resolved,
     /// The transaction has been charged back.
     ChargedBack,
 }
 
+impl DisputeState {
+    /// Returns the snake_case name used in reports, e.g. `charged_back`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DisputeState::Normal => "normal",
+            DisputeState::Disputed => "disputed",
+            DisputeState::Resolved => "resolved",
+            DisputeState::ChargedBack => "charged_back",
+        }
+    }
+}
+
 /// Represents a record of a transaction.
 /// Contains details about the client, transaction type, amount, and dispute state.
 #[derive(Debug, Clone)]
@@ -37,6 +64,23 @@ pub struct TxRecord {
     pub state: DisputeState,
 }
 
+impl TxRecord {
+    /// Returns `true` if the record can no longer change state under the given
+    /// engine config.
+    ///
+    /// Charged-back transactions are terminal, and withdrawals are too unless
+    /// the config allows withdrawal disputes, so only the tx id of such records
+    /// still matters (for duplicate detection).
+    ///
+    /// # Arguments
+    /// * `config` - The config of the engine holding the record.
+    #[inline]
+    pub fn is_final(&self, config: &EngineConfig) -> bool {
+        self.state == DisputeState::ChargedBack
+            || (self.kind == TxKind::Withdrawal && !config.withdrawal_disputes)
+    }
+}
+
 /// Represents a client's account.
 /// Contains details about the available balance, held balance, and lock status.
 #[derive(Default, Debug, Clone)]
@@ -47,6 +91,35 @@ pub struct Account {
     pub held: Amount,
     /// Indicates whether the account is locked.
     pub locked: bool,
+    /// The account's version, bumped by every command applied to it, so
+    /// downstream systems can order updates and detect missed ones.
+    pub version: u64,
+}
+
+/// What a client did during a run, tallied by the engine from the commands it
+/// applied, for reconciliation.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientActivity {
+    /// The sum of the client's applied deposits.
+    pub deposited: Amount,
+    /// The sum of the client's applied withdrawals.
+    pub withdrawn: Amount,
+    /// The number of disputes opened on the client's transactions.
+    pub disputes: u64,
+    /// The number of the client's transactions charged back.
+    pub chargebacks: u64,
+}
+
+/// The money that moved into and out of the whole book during a run, tallied
+/// by the engine from the commands it applied, for funds conservation checks.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FundFlows {
+    /// The sum of applied deposits.
+    pub deposited: Amount,
+    /// The sum of applied withdrawals.
+    pub withdrawn: Amount,
+    /// The sum of the deposits charged back.
+    pub charged_back: Amount,
 }
 
 impl Account {
@@ -61,3 +134,26 @@ impl Account {
         Amount(self.available.0 + self.held.0)
     }
 }
+
+/// What an operator annotation is attached to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoteTarget {
+    /// A client's account.
+    Account(ClientId),
+    /// A transaction record.
+    Tx(TxId),
+}
+
+/// A free-text note an operator attached to an account or transaction record,
+/// so investigation context is stored, exported and erased with the data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Annotation {
+    /// The account or transaction the note is about.
+    pub target: NoteTarget,
+    /// Who wrote the note.
+    pub author: String,
+    /// When the note was written, in seconds since the Unix epoch.
+    pub at: u64,
+    /// The note itself.
+    pub text: String,
+}
@@ -0,0 +1,162 @@
+use crate::models::amount::Amount;
+
+/// Basis points per whole: 10 000 bps = 100%.
+pub const BPS_PER_WHOLE: i64 = 10_000;
+
+/// Computes `amount * num / den`, rounding half to even (banker's rounding), so
+/// repeated fee, interest or FX computations do not drift in one direction.
+///
+/// The product is taken in 128 bits, so only a result outside the amount range
+/// overflows.
+///
+/// # Arguments
+/// * `amount` - The amount to scale.
+/// * `num` - The numerator of the factor.
+/// * `den` - The denominator of the factor.
+///
+/// # Returns
+/// * `Option<Amount>` - The scaled amount, or `None` if `den` is 0 or the result
+///   does not fit an amount.
+pub fn mul_div(amount: Amount, num: i64, den: i64) -> Option<Amount> {
+    if den == 0 {
+        return None;
+    }
+    let (mut product, mut den) = (i128::from(amount.0) * i128::from(num), i128::from(den));
+    if den < 0 {
+        (product, den) = (-product, -den);
+    }
+    let quotient = product.div_euclid(den);
+    let remainder = product.rem_euclid(den);
+    // The quotient is floored, so round up past the halfway point, and at it
+    // only when the quotient is odd.
+    let round_up = match (2 * remainder).cmp(&den) {
+        std::cmp::Ordering::Greater => true,
+        std::cmp::Ordering::Equal => quotient % 2 != 0,
+        std::cmp::Ordering::Less => false,
+    };
+    i64::try_from(quotient + i128::from(round_up))
+        .ok()
+        .map(Amount)
+}
+
+/// Applies a rate in basis points to an amount, e.g. a 25 bps fee on 100.0000
+/// is 0.2500; rounded half to even (see [`mul_div`]).
+///
+/// # Arguments
+/// * `amount` - The amount the rate applies to.
+/// * `bps` - The rate, in basis points; negative for rebates.
+///
+/// # Returns
+/// * `Option<Amount>` - The rate's share of the amount, or `None` on overflow.
+pub fn apply_bps(amount: Amount, bps: i64) -> Option<Amount> {
+    mul_div(amount, bps, BPS_PER_WHOLE)
+}
+
+/// Splits an amount across parts in proportion to their weights, such that the
+/// parts always sum exactly to the original: no money is created or lost.
+///
+/// Each part first gets its proportional share rounded toward zero; the units
+/// left over go one each to the parts with the largest dropped remainders (ties
+/// to the earlier part), so no part is off by more than one unit from its exact
+/// share.
+///
+/// # Arguments
+/// * `amount` - The amount to split.
+/// * `weights` - The parts' weights, e.g. their balances or basis points.
+///
+/// # Returns
+/// * `Option<Vec<Amount>>` - One amount per weight, in order, or `None` if there
+///   are no weights or they sum to 0.
+pub fn split_pro_rata(amount: Amount, weights: &[u64]) -> Option<Vec<Amount>> {
+    let total: u128 = weights.iter().map(|w| u128::from(*w)).sum();
+    if total == 0 {
+        return None;
+    }
+    let magnitude = u128::from(amount.0.unsigned_abs());
+    let sign = amount.0.signum();
+    let mut parts = Vec::with_capacity(weights.len());
+    let mut remainders = Vec::with_capacity(weights.len());
+    let mut allotted = 0u128;
+    for (i, weight) in weights.iter().enumerate() {
+        let product = magnitude * u128::from(*weight);
+        let share = product / total;
+        allotted += share;
+        parts.push(share);
+        remainders.push((product % total, i));
+    }
+    // Largest remainder first; the sort is stable, so ties keep their order.
+    remainders.sort_by_key(|r| std::cmp::Reverse(r.0));
+    let leftover = (magnitude - allotted) as usize;
+    for (_, i) in remainders.into_iter().take(leftover) {
+        parts[i] += 1;
+    }
+    // Every part is at most the whole, so with its sign it fits an amount.
+    Some(
+        parts
+            .into_iter()
+            .map(|part| Amount((i128::from(sign) * part as i128) as i64))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn rounds_half_to_even() {
+        // 0.0001 * 5 / 10 = 0.00005, a tie: to even (0), then 0.0003 * 5 / 10 to 0.0002.
+        assert_eq!(mul_div(Amount(1), 5, 10), Some(Amount(0)));
+        assert_eq!(mul_div(Amount(3), 5, 10), Some(Amount(2)));
+        assert_eq!(mul_div(Amount(-3), 5, 10), Some(Amount(-2)));
+        assert_eq!(mul_div(Amount(3), 5, -10), Some(Amount(-2)));
+        assert_eq!(mul_div(Amount(7), 1, 3), Some(Amount(2)));
+        assert_eq!(mul_div(Amount(1), 1, 0), None);
+        assert_eq!(mul_div(Amount(i64::MAX), 2, 1), None);
+        assert_eq!(apply_bps(Amount(1_000_000), 25), Some(Amount(2_500)));
+        assert_eq!(
+            split_pro_rata(Amount(100), &[1, 1, 1]),
+            Some(vec![Amount(34), Amount(33), Amount(33)])
+        );
+        assert_eq!(split_pro_rata(Amount(100), &[0, 0]), None);
+        assert_eq!(
+            split_pro_rata(Amount(i64::MIN), &[1]),
+            Some(vec![Amount(i64::MIN)])
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn splits_conserve_money(
+            amount in any::<i64>(),
+            weights in prop::collection::vec(any::<u64>(), 1..16),
+        ) {
+            prop_assume!(weights.iter().any(|w| *w > 0));
+            let parts = split_pro_rata(Amount(amount), &weights).unwrap();
+            let sum: i128 = parts.iter().map(|p| i128::from(p.0)).sum();
+            prop_assert_eq!(sum, i128::from(amount));
+            let total: u128 = weights.iter().map(|w| u128::from(*w)).sum();
+            for (part, weight) in parts.iter().zip(&weights) {
+                // Within one unit of the exact share.
+                let exact = u128::from(amount.unsigned_abs()) * u128::from(*weight);
+                let share = u128::from(part.0.unsigned_abs());
+                prop_assert!(share * total <= exact + total && exact <= share * total + total);
+                prop_assert!(part.0 == 0 || part.0.signum() == amount.signum());
+            }
+        }
+
+        #[test]
+        fn bps_complements_split_the_whole(amount in any::<i64>(), bps in 0..=BPS_PER_WHOLE) {
+            // A fee and the net after it, split pro rata, always add back up.
+            let parts = split_pro_rata(
+                Amount(amount),
+                &[bps as u64, (BPS_PER_WHOLE - bps) as u64],
+            )
+            .unwrap();
+            prop_assert_eq!(i128::from(parts[0].0) + i128::from(parts[1].0), i128::from(amount));
+            let fee = apply_bps(Amount(amount), bps).unwrap();
+            prop_assert!(fee.0.abs_diff(parts[0].0) <= 1);
+        }
+    }
+}
@@ -1,5 +1,8 @@
 pub mod amount;
 pub mod csv_models;
 pub mod domain_state;
+pub mod fixed_point;
 pub mod identifiers;
+pub mod policy;
 pub mod tx_command;
+pub mod tx_store;
@@ -0,0 +1,172 @@
+use crate::errors::{AppErrors, ErrorCategory};
+use crate::models::identifiers::{ClientId, TxId};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// The settings that govern how an [`Engine`](crate::state::Engine) processes
+/// its input: how the row pipeline handles malformed rows and command errors
+/// (such as `Overflow`), and the policies the commands follow. None of it is
+/// part of the persisted state, so the config is set anew for every run (see
+/// [`Engine::builder`](crate::state::Engine::builder)).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EngineConfig {
+    /// How row and command errors are handled.
+    pub error_policy: ErrorPolicy,
+    /// What happens to deposits for locked accounts.
+    pub locked_deposits: LockedDepositPolicy,
+    /// Whether withdrawals can be disputed. A disputed withdrawal holds its
+    /// amount; a resolve releases the hold, and a chargeback returns the amount
+    /// to `available` and locks the account. Off by default: disputes of
+    /// withdrawals are ignored as not disputable.
+    pub withdrawal_disputes: bool,
+    /// Whether a deposit or withdrawal reusing a recorded tx id fails with
+    /// `AppErrors::DuplicateTx` instead of being ignored as a no-op.
+    pub strict_duplicates: bool,
+    /// What happens to disputes the account's available funds do not cover.
+    pub unfunded_disputes: UnfundedDisputePolicy,
+    /// Whether a resolved transaction can be disputed again.
+    pub redisputes: RedisputePolicy,
+    /// The most dispute, resolve and chargeback commands held back because
+    /// their tx id has not been seen yet, to be applied once it arrives, for
+    /// feeds that are not strictly ordered. `0` disables the buffer: such
+    /// commands are ignored as unknown.
+    pub orphan_buffer: usize,
+    /// The number of commands (applied or ignored; failed ones do not count)
+    /// after which a dispute still neither resolved nor charged back is
+    /// resolved automatically, so its funds are not held forever. `None` keeps
+    /// disputes open until resolved or charged back.
+    pub dispute_window: Option<u64>,
+}
+
+/// Controls how errors are handled while processing input rows.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorPolicy {
+    /// Log every error and continue with the next row.
+    #[default]
+    Skip,
+    /// Log every error as a warning rather than an error and continue with the
+    /// next row, for inputs where some bad rows are expected and should not
+    /// trip error-level alerting.
+    Warn,
+    /// Stop at the first engine error (e.g., `Overflow`). Malformed rows are
+    /// still logged and skipped.
+    FailFast,
+    /// Continue processing and return all errors at the end.
+    Collect,
+    /// Stop at the first row that cannot be processed: a malformed record, a
+    /// missing or invalid amount, or an engine error. For batch jobs where no
+    /// row may be skipped silently.
+    Strict,
+}
+
+/// Controls what happens to deposits for locked accounts.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LockedDepositPolicy {
+    /// Ignore the deposit, as for any command on a locked account.
+    #[default]
+    Ignore,
+    /// Credit the deposit to `held`, recorded as a disputed deposit, so the
+    /// funds are kept until a resolve releases them to `available` or a
+    /// chargeback reverses them.
+    Hold,
+}
+
+/// Controls what happens to a dispute of a deposit whose funds were already
+/// spent, i.e. when the account's available funds do not cover it.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UnfundedDisputePolicy {
+    /// Ignore the dispute as `insufficient_funds`, so the client keeps the
+    /// spent funds.
+    #[default]
+    Ignore,
+    /// Apply the dispute anyway, as card networks do: `held` increases by the
+    /// full amount and `available` goes negative by the shortfall, which the
+    /// client owes until later deposits cover it.
+    Negative,
+    /// Queue the dispute and retry it after each later deposit of the client,
+    /// applying it once the available funds cover it. A resolve of a queued
+    /// dispute cancels it.
+    Queue,
+}
+
+/// Controls whether a transaction whose dispute was resolved can be disputed
+/// again.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RedisputePolicy {
+    /// Allow any number of dispute and resolve cycles.
+    #[default]
+    Allow,
+    /// Ignore a dispute of a resolved transaction as `already_resolved`, so
+    /// each transaction can be disputed at most once.
+    Forbid,
+}
+
+/// An error encountered while processing a single input row.
+#[derive(Debug)]
+pub struct RowError {
+    /// The line number of the row in the input.
+    pub line: u64,
+    /// The client referenced by the row, if it could be parsed.
+    pub client: Option<ClientId>,
+    /// The transaction referenced by the row, if it could be parsed.
+    pub tx: Option<TxId>,
+    /// The error raised for the row.
+    pub error: AppErrors,
+}
+
+impl RowError {
+    /// Builds the serializable report for this error.
+    pub fn report(&self) -> ErrorReport {
+        ErrorReport {
+            code: self.error.code(),
+            category: self.error.category(),
+            message: self.error.to_string(),
+            line: self.line,
+            client: self.client,
+            tx: self.tx,
+        }
+    }
+}
+
+/// A machine-readable description of a [`RowError`], serialized to JSON so
+/// downstream automation can route failures by code instead of message text.
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorReport {
+    /// The stable error code (e.g., `E1001` for a malformed amount).
+    pub code: &'static str,
+    /// The error category.
+    pub category: ErrorCategory,
+    /// The human-readable error message.
+    pub message: String,
+    /// The line number of the offending row.
+    pub line: u64,
+    /// The client referenced by the row, if known.
+    pub client: Option<ClientId>,
+    /// The transaction referenced by the row, if known.
+    pub tx: Option<TxId>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_serializes_code_category_and_context() {
+        let e = RowError {
+            line: 3,
+            client: Some(1),
+            tx: Some(9),
+            error: AppErrors::Overflow,
+        };
+        let json = serde_json::to_string(&e.report()).unwrap();
+        assert_eq!(
+            json,
+            r#"{"code":"E2001","category":"engine","message":"Overflow error","line":3,"client":1,"tx":9}"#
+        );
+    }
+}
@@ -54,3 +54,75 @@ pub struct ChargebackCommand {
     /// The unique identifier for the charged-back transaction.
     pub tx: TxId,
 }
+
+/// Any transaction the engine can apply, for callers that want a single typed
+/// entry point ([`Engine::apply`](crate::state::Engine::apply)) instead of
+/// boxed [`TxCommandTrait`](crate::services::commands::traits::tx_command_trait::TxCommandTrait)
+/// objects.
+#[derive(Debug, Clone)]
+pub enum Transaction {
+    /// Credit funds to a client's account.
+    Deposit(DepositCommand),
+    /// Debit funds from a client's account.
+    Withdrawal(WithdrawalCommand),
+    /// Hold the funds of a previous deposit.
+    Dispute(DisputeCommand),
+    /// Release the funds held by a dispute.
+    Resolve(ResolveCommand),
+    /// Reverse a disputed deposit and lock the account.
+    Chargeback(ChargebackCommand),
+}
+
+impl Transaction {
+    /// Returns the client the transaction applies to.
+    pub fn client(&self) -> ClientId {
+        match self {
+            Transaction::Deposit(c) => c.client,
+            Transaction::Withdrawal(c) => c.client,
+            Transaction::Dispute(c) => c.client,
+            Transaction::Resolve(c) => c.client,
+            Transaction::Chargeback(c) => c.client,
+        }
+    }
+
+    /// Returns the transaction id the transaction records or refers to.
+    pub fn tx(&self) -> TxId {
+        match self {
+            Transaction::Deposit(c) => c.tx,
+            Transaction::Withdrawal(c) => c.tx,
+            Transaction::Dispute(c) => c.tx,
+            Transaction::Resolve(c) => c.tx,
+            Transaction::Chargeback(c) => c.tx,
+        }
+    }
+}
+
+impl From<DepositCommand> for Transaction {
+    fn from(cmd: DepositCommand) -> Self {
+        Transaction::Deposit(cmd)
+    }
+}
+
+impl From<WithdrawalCommand> for Transaction {
+    fn from(cmd: WithdrawalCommand) -> Self {
+        Transaction::Withdrawal(cmd)
+    }
+}
+
+impl From<DisputeCommand> for Transaction {
+    fn from(cmd: DisputeCommand) -> Self {
+        Transaction::Dispute(cmd)
+    }
+}
+
+impl From<ResolveCommand> for Transaction {
+    fn from(cmd: ResolveCommand) -> Self {
+        Transaction::Resolve(cmd)
+    }
+}
+
+impl From<ChargebackCommand> for Transaction {
+    fn from(cmd: ChargebackCommand) -> Self {
+        Transaction::Chargeback(cmd)
+    }
+}
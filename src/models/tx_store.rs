@@ -0,0 +1,569 @@
+use crate::models::domain_state::TxRecord;
+use crate::models::identifiers::TxId;
+use crate::models::policy::EngineConfig;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::mem::{replace, size_of};
+
+/// Maximum distance past either end of the dense segment that a new tx id may
+/// land and still be stored densely. Gaps inside the segment are filled with
+/// `None`.
+pub const DENSE_MAX_GAP: usize = 1_024;
+
+/// Once the dense segment is longer than [`DENSE_MAX_GAP`] slots, it only grows
+/// while at least one in this many of its slots holds a record, so strided ids
+/// cannot allocate a run of empty slots per record.
+pub const DENSE_MIN_FILL: usize = 2;
+
+/// The overflow size at which the dense segment is first re-anchored (see
+/// [`TxStore::insert`]).
+const REANCHOR_MIN: usize = 64;
+
+/// A hybrid store of transaction records keyed by `TxId`.
+///
+/// Most feeds use monotonically increasing tx ids, so records are kept in a
+/// contiguous segment that grows at either end as ids arrive. Ids too far from
+/// the segment, or that would leave it mostly empty, go to a `HashMap` overflow
+/// instead. The choice is made automatically on insert; lookups check the
+/// dense segment first and fall back to the overflow, which may also hold ids
+/// the segment grew over later.
+#[derive(Default, Clone, Debug)]
+pub struct TxStore {
+    /// The tx id stored at index `0` of `dense`, if the segment has slots.
+    base: Option<TxId>,
+
+    /// The contiguous segment for the dense id range.
+    dense: VecDeque<Option<TxRecord>>,
+
+    /// The number of records held in `dense`.
+    dense_len: usize,
+
+    /// Records whose ids were outside the dense range when inserted.
+    overflow: HashMap<TxId, TxRecord>,
+
+    /// Ids seen without a live record: records dropped by [`TxStore::retire`] or
+    /// [`TxStore::retire_final`] and ids loaded via [`TxStore::mark_seen`]. Kept
+    /// so the ids are still reported as seen for duplicate detection. Ids far
+    /// from the dense range go here, the others to `retired_bits`.
+    retired: HashSet<TxId>,
+
+    /// Retired ids inside or near the dense range, one bit per id.
+    retired_bits: IdBitmap,
+
+    /// The overflow size at which the dense segment is next re-anchored.
+    reanchor_at: usize,
+}
+
+impl TxStore {
+    /// Returns the dense-segment index for `tx`, if it lies inside the segment.
+    #[inline]
+    fn dense_index(&self, tx: TxId) -> Option<usize> {
+        let base = self.base?;
+        let idx = tx.checked_sub(base)? as usize;
+        (idx < self.dense.len()).then_some(idx)
+    }
+
+    /// Returns `true` if the given tx id has been seen, i.e. a record exists for
+    /// it or its record was retired.
+    pub fn contains_key(&self, tx: &TxId) -> bool {
+        self.get(tx).is_some() || self.retired_bits.contains(*tx) || self.retired.contains(tx)
+    }
+
+    /// Returns an immutable reference to the record for the given tx id, if any.
+    pub fn get(&self, tx: &TxId) -> Option<&TxRecord> {
+        self.dense_index(*tx)
+            .and_then(|idx| self.dense[idx].as_ref())
+            .or_else(|| self.overflow.get(tx))
+    }
+
+    /// Returns a mutable reference to the record for the given tx id, if any.
+    pub fn get_mut(&mut self, tx: &TxId) -> Option<&mut TxRecord> {
+        match self.dense_index(*tx) {
+            Some(idx) if self.dense[idx].is_some() => self.dense[idx].as_mut(),
+            _ => self.overflow.get_mut(tx),
+        }
+    }
+
+    /// Inserts a record, returning the previous record stored under the same id.
+    ///
+    /// The record goes to the dense segment when its id lies inside it, or at
+    /// most [`DENSE_MAX_GAP`] past either end and the grown segment keeps the
+    /// [`DENSE_MIN_FILL`] density; otherwise it goes to the overflow map. When
+    /// the overflow outgrows the dense segment (say, the first id was an
+    /// outlier, or the feed jumped to a new id range), the segment is rebuilt
+    /// around the id just inserted.
+    pub fn insert(&mut self, tx: TxId, rec: TxRecord) -> Option<TxRecord> {
+        if let Some(prev) = self.get_mut(&tx) {
+            return Some(replace(prev, rec));
+        }
+        if !self.place(tx, rec)
+            && self.overflow.len() >= self.reanchor_at.max(REANCHOR_MIN)
+            && self.overflow.len() > self.dense_len
+        {
+            self.reanchor(tx);
+        }
+        None
+    }
+
+    /// Grows the dense segment at either end to cover `tx`, if the gap is at
+    /// most [`DENSE_MAX_GAP`] and the grown segment keeps the
+    /// [`DENSE_MIN_FILL`] density. Returns whether it grew.
+    fn grow_to(&mut self, tx: TxId) -> bool {
+        let Some(base) = self.base else {
+            self.base = Some(tx);
+            self.dense.push_back(None);
+            return true;
+        };
+        let (front, back) = match tx.checked_sub(base) {
+            Some(idx) => (0, idx as usize + 1 - self.dense.len()),
+            None => ((base - tx) as usize, 0),
+        };
+        let grown = self.dense.len() + front + back;
+        if front + back > DENSE_MAX_GAP + 1
+            || (grown > DENSE_MAX_GAP && grown > DENSE_MIN_FILL * (self.dense_len + 1))
+        {
+            return false;
+        }
+        for _ in 0..front {
+            self.dense.push_front(None);
+        }
+        self.dense.resize(grown, None);
+        self.base = Some(base.min(tx));
+        true
+    }
+
+    /// Rebuilds the dense segment around `anchor`, re-inserting every record
+    /// outward from it (ids below in descending order, ids above in ascending
+    /// order) so the run of ids around the anchor is stored densely again.
+    /// The next re-anchor waits until the overflow has doubled, keeping the
+    /// cost amortized for feeds whose ids are sparse throughout.
+    fn reanchor(&mut self, anchor: TxId) {
+        let mut records: Vec<(TxId, TxRecord)> = self.overflow.drain().collect();
+        let base = self.base.take().unwrap_or_default();
+        records.extend(
+            self.dense
+                .drain(..)
+                .enumerate()
+                .filter_map(|(i, rec)| rec.map(|rec| (base + i as TxId, rec))),
+        );
+        self.dense_len = 0;
+        records.sort_unstable_by_key(|(tx, _)| *tx);
+        let split = records.partition_point(|(tx, _)| *tx < anchor);
+        let above = records.split_off(split);
+        for (tx, rec) in above.into_iter().chain(records.into_iter().rev()) {
+            self.place(tx, rec);
+        }
+        self.reanchor_at = 2 * self.overflow.len();
+    }
+
+    /// Stores a record whose id is not stored yet, in the dense segment if it
+    /// covers the id or can grow to, or in the overflow otherwise. Returns
+    /// whether it was stored densely.
+    fn place(&mut self, tx: TxId, rec: TxRecord) -> bool {
+        if self.dense_index(tx).is_none() && !self.grow_to(tx) {
+            self.overflow.insert(tx, rec);
+            return false;
+        }
+        let idx = self.dense_index(tx).expect("the segment covers tx");
+        self.dense[idx] = Some(rec);
+        self.dense_len += 1;
+        true
+    }
+
+    /// Removes and returns the record for the given tx id, if any. Empty slots
+    /// left at either end of the dense segment are trimmed, so their space is
+    /// reused as the segment grows again.
+    pub fn remove(&mut self, tx: &TxId) -> Option<TxRecord> {
+        match self.dense_index(*tx) {
+            Some(idx) if self.dense[idx].is_some() => {
+                self.dense_len -= 1;
+                let rec = self.dense[idx].take();
+                self.trim_dense();
+                rec
+            }
+            _ => self.overflow.remove(tx),
+        }
+    }
+
+    /// Removes and returns the record for the given tx id, if any, keeping the
+    /// id seen so later transactions reusing it are treated as duplicates.
+    pub fn retire(&mut self, tx: TxId) -> Option<TxRecord> {
+        self.get(&tx)?;
+        self.retire_id(tx);
+        self.remove(&tx)
+    }
+
+    /// Records a tx id as retired: as a bit if it lies inside or near the dense
+    /// range, whose ids are mostly used, or in the `retired` set otherwise.
+    fn retire_id(&mut self, tx: TxId) {
+        if self.dense_index(tx).is_some() || self.retired_bits.is_near(tx) {
+            self.retired_bits.insert(tx);
+        } else {
+            self.retired.insert(tx);
+        }
+    }
+
+    /// Drops empty slots from both ends of the dense segment.
+    fn trim_dense(&mut self) {
+        while matches!(self.dense.back(), Some(None)) {
+            self.dense.pop_back();
+        }
+        while matches!(self.dense.front(), Some(None)) {
+            self.dense.pop_front();
+            self.base = self.base.map(|base| base + 1);
+        }
+        if self.dense.is_empty() {
+            self.base = None;
+        }
+    }
+
+    /// Returns the number of records in the store.
+    pub fn len(&self) -> usize {
+        self.dense_len + self.overflow.len()
+    }
+
+    /// Returns `true` if the store holds no records.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of records stored in the overflow map.
+    pub fn overflow_len(&self) -> usize {
+        self.overflow.len()
+    }
+
+    /// Returns the number of slots in the dense segment, filled or not.
+    pub fn dense_slots(&self) -> usize {
+        self.dense.len()
+    }
+
+    /// Returns the number of retired tx ids.
+    pub fn retired_len(&self) -> usize {
+        self.retired_bits.len + self.retired.len()
+    }
+
+    /// Marks a tx id as seen without storing a record for it, so later
+    /// transactions reusing the id are treated as duplicates.
+    pub fn mark_seen(&mut self, tx: TxId) {
+        if !self.contains_key(&tx) {
+            self.retire_id(tx);
+        }
+    }
+
+    /// Returns an iterator over tx ids seen without a live record, in
+    /// unspecified order.
+    pub fn retired_iter(&self) -> impl Iterator<Item = TxId> + '_ {
+        self.retired_bits.iter().chain(self.retired.iter().copied())
+    }
+
+    /// Returns every seen tx id (live and retired), in unspecified order.
+    pub fn seen_ids(&self) -> Vec<TxId> {
+        self.iter()
+            .map(|(tx, _)| tx)
+            .chain(self.retired_iter())
+            .collect()
+    }
+
+    /// Drops overflow records that can no longer change state (see
+    /// [`TxRecord::is_final`]), keeping only their ids for duplicate detection.
+    ///
+    /// Dense records are left in place since clearing a slot frees no memory.
+    ///
+    /// # Arguments
+    /// * `config` - The config of the engine holding the records, which decides
+    ///   whether withdrawals can still be disputed.
+    ///
+    /// # Returns
+    /// * `usize` - The number of records retired.
+    pub fn retire_final(&mut self, config: &EngineConfig) -> usize {
+        let retired: Vec<TxId> = self
+            .overflow
+            .iter()
+            .filter(|(_, rec)| rec.is_final(config))
+            .map(|(tx, _)| *tx)
+            .collect();
+        for tx in &retired {
+            self.overflow.remove(tx);
+            self.retire_id(*tx);
+        }
+        retired.len()
+    }
+
+    /// Trims empty slots from both ends of the dense segment and releases
+    /// excess capacity held by all internal collections.
+    pub fn shrink_to_fit(&mut self) {
+        self.trim_dense();
+        self.dense.shrink_to_fit();
+        self.overflow.shrink_to_fit();
+        self.retired.shrink_to_fit();
+        self.retired_bits.words.shrink_to_fit();
+    }
+
+    /// Returns an estimate of the heap bytes reserved by the store, based on the
+    /// capacity of its collections.
+    pub fn heap_bytes(&self) -> usize {
+        self.dense.capacity() * size_of::<Option<TxRecord>>()
+            + self.overflow.capacity() * size_of::<(TxId, TxRecord)>()
+            + self.retired.capacity() * size_of::<TxId>()
+            + self.retired_bits.words.capacity() * size_of::<u64>()
+    }
+
+    /// Returns an iterator over all `(TxId, &TxRecord)` pairs.
+    ///
+    /// Dense records are yielded in id order, followed by overflow records in
+    /// unspecified order.
+    pub fn iter(&self) -> impl Iterator<Item = (TxId, &TxRecord)> {
+        let base = self.base.unwrap_or_default();
+        self.dense
+            .iter()
+            .enumerate()
+            .filter_map(move |(i, rec)| rec.as_ref().map(|r| (base + i as TxId, r)))
+            .chain(self.overflow.iter().map(|(tx, r)| (*tx, r)))
+    }
+}
+
+/// A set of tx ids stored as one bit per id over the range they span.
+#[derive(Default, Clone, Debug)]
+struct IdBitmap {
+    /// The index (`tx / 64`) of the first word.
+    first_word: usize,
+    /// The words; bit `tx % 64` of word `tx / 64 - first_word` is set for `tx`.
+    words: VecDeque<u64>,
+    /// The number of ids set.
+    len: usize,
+}
+
+impl IdBitmap {
+    /// Returns `true` if the id is set.
+    fn contains(&self, tx: TxId) -> bool {
+        (tx as usize / 64)
+            .checked_sub(self.first_word)
+            .and_then(|w| self.words.get(w))
+            .is_some_and(|word| word & (1 << (tx % 64)) != 0)
+    }
+
+    /// Returns `true` if the id lies inside the bitmap's range or at most
+    /// [`DENSE_MAX_GAP`] ids past either end, so setting it grows the bitmap by
+    /// a bounded number of words.
+    fn is_near(&self, tx: TxId) -> bool {
+        let start = self.first_word * 64;
+        let end = start + self.words.len() * 64;
+        !self.words.is_empty()
+            && tx as usize + DENSE_MAX_GAP >= start
+            && (tx as usize) < end + DENSE_MAX_GAP
+    }
+
+    /// Sets the id, growing the bitmap at either end to cover it.
+    fn insert(&mut self, tx: TxId) {
+        let word = tx as usize / 64;
+        if self.words.is_empty() {
+            self.first_word = word;
+        }
+        while word < self.first_word {
+            self.words.push_front(0);
+            self.first_word -= 1;
+        }
+        if word >= self.first_word + self.words.len() {
+            self.words.resize(word - self.first_word + 1, 0);
+        }
+        let bits = &mut self.words[word - self.first_word];
+        let bit = 1 << (tx % 64);
+        if *bits & bit == 0 {
+            *bits |= bit;
+            self.len += 1;
+        }
+    }
+
+    /// Returns an iterator over the ids set, in ascending order.
+    fn iter(&self) -> impl Iterator<Item = TxId> + '_ {
+        self.words.iter().enumerate().flat_map(move |(i, &word)| {
+            let base = (self.first_word + i) * 64;
+            (0..64)
+                .filter(move |bit| word & (1 << bit) != 0)
+                .map(move |bit| (base + bit) as TxId)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::amount::Amount;
+    use crate::models::domain_state::{DisputeState, TxKind};
+
+    fn rec(amount: i64) -> TxRecord {
+        TxRecord {
+            client: 1,
+            kind: TxKind::Deposit,
+            amount: Amount(amount),
+            state: DisputeState::Normal,
+        }
+    }
+
+    #[test]
+    fn sequential_ids_are_stored_densely() {
+        let mut store = TxStore::default();
+        for tx in 10..20 {
+            store.insert(tx, rec(tx as i64));
+        }
+
+        assert_eq!(store.len(), 10);
+        assert_eq!(store.overflow_len(), 0);
+        assert_eq!(store.get(&15).unwrap().amount, Amount(15));
+        assert!(store.get(&20).is_none());
+    }
+
+    #[test]
+    fn outliers_go_to_overflow() {
+        let far = 5_000 + DENSE_MAX_GAP as TxId + 10;
+        let mut store = TxStore::default();
+        store.insert(5_000, rec(1));
+        store.insert(5, rec(2));
+        store.insert(far, rec(3));
+
+        assert_eq!(store.len(), 3);
+        assert_eq!(store.overflow_len(), 2);
+        assert_eq!(store.get(&5).unwrap().amount, Amount(2));
+        assert_eq!(store.get(&far).unwrap().amount, Amount(3));
+    }
+
+    #[test]
+    fn small_gaps_stay_dense() {
+        let mut store = TxStore::default();
+        store.insert(10, rec(1));
+        store.insert(50, rec(2));
+        // Ids just below the segment extend it downwards.
+        store.insert(1, rec(3));
+
+        assert_eq!(store.overflow_len(), 0);
+        assert!(!store.contains_key(&25));
+        assert!(store.contains_key(&50) && store.contains_key(&1));
+    }
+
+    #[test]
+    fn insert_replaces_and_remove_updates_len() {
+        let mut store = TxStore::default();
+        assert!(store.insert(1, rec(1)).is_none());
+        assert_eq!(store.insert(1, rec(2)).unwrap().amount, Amount(1));
+        assert_eq!(store.len(), 1);
+
+        store.get_mut(&1).unwrap().state = DisputeState::Disputed;
+        assert_eq!(store.get(&1).unwrap().state, DisputeState::Disputed);
+
+        assert!(store.remove(&1).is_some());
+        assert!(store.remove(&1).is_none());
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn retire_final_drops_overflow_records_but_keeps_ids_seen() {
+        let mut store = TxStore::default();
+        store.insert(5_000, rec(1));
+        store.insert(1, rec(2));
+        store.get_mut(&1).unwrap().state = DisputeState::ChargedBack;
+
+        assert_eq!(store.retire_final(&EngineConfig::default()), 1);
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.retired_len(), 1);
+        assert!(store.get(&1).is_none());
+        assert!(store.contains_key(&1));
+    }
+
+    #[test]
+    fn iter_yields_dense_and_overflow_records() {
+        let mut store = TxStore::default();
+        store.insert(3, rec(3));
+        store.insert(4, rec(4));
+        store.insert(1, rec(1));
+
+        let mut ids: Vec<TxId> = store.iter().map(|(tx, _)| tx).collect();
+        ids.sort();
+        assert_eq!(ids, vec![1, 3, 4]);
+    }
+
+    #[test]
+    fn overflow_records_stay_visible_once_the_segment_grows_over_them() {
+        let mut store = TxStore::default();
+        for tx in [1, 2000, 1000, 1900, 2001] {
+            assert!(store.insert(tx, rec(tx as i64)).is_none());
+        }
+
+        assert_eq!(store.len(), 5);
+        for tx in [1, 2000, 1000, 1900, 2001] {
+            assert_eq!(store.get(&tx).unwrap().amount, Amount(tx as i64));
+        }
+        // Re-inserting replaces rather than storing a second record.
+        assert_eq!(store.insert(2000, rec(7)).unwrap().amount, Amount(2000));
+        assert_eq!(store.len(), 5);
+        assert_eq!(store.iter().count(), 5);
+        assert_eq!(store.remove(&2000).unwrap().amount, Amount(7));
+        assert!(!store.contains_key(&2000));
+    }
+
+    #[test]
+    fn strided_ids_do_not_fill_the_segment_with_empty_slots() {
+        let mut store = TxStore::default();
+        for i in 0..500 {
+            store.insert(1 + i * 1_000, rec(i as i64));
+        }
+
+        assert_eq!(store.len(), 500);
+        assert!(store.dense_slots() <= 2 * DENSE_MAX_GAP);
+        for i in 0..500 {
+            assert_eq!(
+                store.get(&(1 + i * 1_000)).unwrap().amount,
+                Amount(i as i64)
+            );
+        }
+    }
+
+    #[test]
+    fn a_large_first_id_does_not_push_later_ids_to_overflow() {
+        let mut store = TxStore::default();
+        store.insert(1_000_000, rec(0));
+        for tx in 1..=1_000 {
+            store.insert(tx, rec(tx as i64));
+        }
+
+        assert_eq!(store.len(), 1_001);
+        assert!(store.overflow_len() < REANCHOR_MIN);
+        assert!(store.contains_key(&1_000_000));
+        assert_eq!(store.get(&500).unwrap().amount, Amount(500));
+    }
+
+    #[test]
+    fn retired_dense_ids_free_their_slots_and_stay_seen() {
+        let mut store = TxStore::default();
+        for tx in 1..=1_000 {
+            store.insert(tx, rec(tx as i64));
+        }
+        for tx in 1..=990 {
+            assert_eq!(store.retire(tx).unwrap().amount, Amount(tx as i64));
+        }
+
+        // The leading run of empty slots is trimmed; the ids are kept as bits.
+        assert_eq!(store.dense_slots(), 10);
+        assert!(store.retired.is_empty());
+        assert_eq!(store.retired_len(), 990);
+        assert!((1..=1_000).all(|tx| store.contains_key(&tx)));
+        assert!(!store.contains_key(&1_001));
+        assert!(store.retire(1).is_none());
+
+        let mut seen = store.seen_ids();
+        seen.sort_unstable();
+        assert_eq!(seen, (1..=1_000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn ids_far_from_the_dense_range_are_retired_in_the_set() {
+        let mut store = TxStore::default();
+        store.insert(10, rec(1));
+        store.insert(12, rec(2));
+        store.mark_seen(11);
+        store.mark_seen(1_000_000);
+
+        assert_eq!(store.retired_bits.len, 1);
+        assert!(store.retired.contains(&1_000_000));
+        assert!(store.contains_key(&11) && store.contains_key(&1_000_000));
+        assert!(!store.contains_key(&13));
+    }
+}
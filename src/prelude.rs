@@ -0,0 +1,22 @@
+//! The stable public surface of the payments engine.
+//!
+//! Importing `prelude::*` brings in the types embedders are expected to depend
+//! on: the engine and its state, the command types, money and identifiers, and
+//! the error taxonomy. Anything not re-exported here is an implementation
+//! detail and may change without notice.
+
+pub use crate::errors::{AmountParseError, AppErrors, AppResult, ErrorCategory};
+pub use crate::models::amount::Amount;
+pub use crate::models::domain_state::{Account, DisputeState, TxKind, TxRecord};
+pub use crate::models::identifiers::{ClientId, TxId};
+pub use crate::models::policy::{EngineConfig, ErrorPolicy, ErrorReport, RowError};
+pub use crate::models::tx_command::{
+    ChargebackCommand, DepositCommand, DisputeCommand, ResolveCommand, Transaction,
+    WithdrawalCommand,
+};
+pub use crate::services::commands::traits::tx_command_trait::TxCommandTrait;
+pub use crate::services::engine_handle::EngineHandle;
+pub use crate::state::{
+    AppState, CompactionStats, DisputeEvent, Engine, EngineBuilder, IgnoreReason, Outcome, Probe,
+    Released,
+};
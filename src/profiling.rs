@@ -0,0 +1,151 @@
+use crate::errors::{AppErrors, AppResult};
+use log::info;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Total number of allocations recorded for the process.
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+
+/// Total number of bytes recorded as requested from the allocator.
+static ALLOCATED_BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// Records an allocation of `bytes` for the per-phase counts of a
+/// [`Profiler`]. Called by a counting global allocator (the binary installs
+/// one with the `profiling` feature); without one, phases report no
+/// allocations.
+///
+/// # Arguments
+/// * `bytes` - The number of bytes requested.
+pub fn record_allocation(bytes: usize) {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    ALLOCATED_BYTES.fetch_add(bytes as u64, Ordering::Relaxed);
+}
+
+/// Returns the current `(allocations, allocated_bytes)` counters.
+fn alloc_counters() -> (u64, u64) {
+    (
+        ALLOCATIONS.load(Ordering::Relaxed),
+        ALLOCATED_BYTES.load(Ordering::Relaxed),
+    )
+}
+
+/// Statistics collected for a single processing phase.
+#[derive(Debug, Clone)]
+pub struct PhaseStats {
+    /// The name of the phase (e.g., `ingest`, `emit`).
+    pub name: &'static str,
+    /// Wall-clock time spent in the phase.
+    pub elapsed: Duration,
+    /// Number of allocations performed during the phase.
+    pub allocations: u64,
+    /// Number of bytes allocated during the phase.
+    pub allocated_bytes: u64,
+}
+
+/// Records per-phase timings and allocation counts for a run.
+#[derive(Debug, Default)]
+pub struct Profiler {
+    /// The phases recorded so far, in execution order.
+    pub phases: Vec<PhaseStats>,
+}
+
+impl Profiler {
+    /// Runs `f` as a named phase and records its timing and allocations.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the phase.
+    /// * `f` - The closure executing the phase.
+    ///
+    /// # Returns
+    /// * `T` - The value returned by `f`.
+    pub fn phase<T>(&mut self, name: &'static str, f: impl FnOnce() -> T) -> T {
+        let (allocs_before, bytes_before) = alloc_counters();
+        let started = Instant::now();
+        let out = f();
+        let elapsed = started.elapsed();
+        let (allocs_after, bytes_after) = alloc_counters();
+        self.phases.push(PhaseStats {
+            name,
+            elapsed,
+            allocations: allocs_after - allocs_before,
+            allocated_bytes: bytes_after - bytes_before,
+        });
+        out
+    }
+
+    /// Logs one INFO line per recorded phase with its timing and allocations.
+    pub fn log_summary(&self) {
+        for p in &self.phases {
+            info!(
+                "profile: phase={} elapsed={:?} allocations={} allocated_bytes={}",
+                p.name, p.elapsed, p.allocations, p.allocated_bytes
+            );
+        }
+    }
+
+    /// Writes the recorded phases as flamegraph-ready folded stacks.
+    ///
+    /// Two files are produced: `path` weighted by wall-clock microseconds, and
+    /// `path.alloc` weighted by allocated bytes. Both can be fed directly to
+    /// `flamegraph.pl` or `inferno-flamegraph`.
+    ///
+    /// # Arguments
+    /// * `path` - The output path for the time profile.
+    ///
+    /// # Returns
+    /// * `AppResult<()>` - Returns `Ok(())` if both files are written,
+    ///   or an `AppErrors::Io` if writing fails.
+    pub fn write_folded(&self, path: &str) -> AppResult<()> {
+        write_folded_file(
+            path,
+            self.phases
+                .iter()
+                .map(|p| (p.name, p.elapsed.as_micros() as u64)),
+        )?;
+        write_folded_file(
+            &format!("{path}.alloc"),
+            self.phases.iter().map(|p| (p.name, p.allocated_bytes)),
+        )
+    }
+}
+
+/// Writes `payments-engine;<phase> <weight>` lines to the given file.
+fn write_folded_file(
+    path: &str,
+    lines: impl Iterator<Item = (&'static str, u64)>,
+) -> AppResult<()> {
+    let file = File::create(path).map_err(|e| AppErrors::Io(format!("create {path}: {e}")))?;
+    let mut out = BufWriter::new(file);
+    for (name, weight) in lines {
+        writeln!(out, "payments-engine;{name} {weight}")
+            .map_err(|e| AppErrors::Io(format!("write {path}: {e}")))?;
+    }
+    out.flush()
+        .map_err(|e| AppErrors::Io(format!("flush {path}: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn phase_records_name_and_returns_value() {
+        let mut profiler = Profiler::default();
+        let v = profiler.phase("work", || vec![1u8; 64].len());
+
+        assert_eq!(v, 64);
+        assert_eq!(profiler.phases.len(), 1);
+        assert_eq!(profiler.phases[0].name, "work");
+    }
+
+    #[test]
+    fn phase_counts_recorded_allocations() {
+        let mut profiler = Profiler::default();
+        profiler.phase("work", || record_allocation(100));
+
+        assert!(profiler.phases[0].allocations >= 1);
+        assert!(profiler.phases[0].allocated_bytes >= 100);
+    }
+}
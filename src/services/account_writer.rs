@@ -0,0 +1,562 @@
+use crate::errors::{AppErrors, AppResult};
+use crate::models::amount::{Amount, AmountStyle};
+use crate::models::csv_models::transaction::{Delimiter, ExtendedOutputRow, OutputRow};
+use crate::models::domain_state::{Account, ClientActivity};
+use crate::models::identifiers::ClientId;
+use crate::services::locale::Locale;
+use clap::ValueEnum;
+use csv::WriterBuilder;
+use serde::Serialize;
+use serde_json::value::RawValue;
+use std::collections::HashSet;
+use std::io::Write;
+
+/// The formats the account report can be written in.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// CSV with a header row; balances as four-decimal strings.
+    #[default]
+    Csv,
+    /// A single JSON array of account objects with numeric balances.
+    Json,
+    /// One JSON account object per line, with numeric balances.
+    Ndjson,
+    /// An aligned, padded text table for reading at a terminal; balances are
+    /// formatted per the writer's locale.
+    Table,
+    /// A Parquet file with `DECIMAL(18,4)` balances. Requires `--output`.
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+/// An account in the JSON report formats.
+///
+/// Balances are JSON numbers written verbatim from the fixed-point amount
+/// (e.g. `1.5000`), so no precision is lost to floating point.
+#[derive(Debug, Serialize)]
+pub struct JsonAccountRow {
+    /// The ID of the client associated with the account.
+    pub client: ClientId,
+    /// The available balance.
+    pub available: Box<RawValue>,
+    /// The held balance.
+    pub held: Box<RawValue>,
+    /// The total balance.
+    pub total: Box<RawValue>,
+    /// Indicates whether the account is locked.
+    pub locked: bool,
+    /// The client's activity, in the extended report.
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub activity: Option<JsonActivity>,
+}
+
+/// The extended report's activity columns in the JSON report formats.
+#[derive(Debug, Serialize)]
+pub struct JsonActivity {
+    /// The sum of the client's applied deposits.
+    pub deposited: Box<RawValue>,
+    /// The sum of the client's applied withdrawals.
+    pub withdrawn: Box<RawValue>,
+    /// The number of disputes opened.
+    pub disputes: u64,
+    /// The number of chargebacks.
+    pub chargebacks: u64,
+}
+
+impl JsonAccountRow {
+    /// Builds the JSON row for a client's account, writing amounts in `style`.
+    ///
+    /// # Returns
+    /// * `AppResult<Self>` - The row, or `AppErrors::Internal` if an amount does
+    ///   not format as a JSON number.
+    pub fn new(client: ClientId, acc: &Account, style: AmountStyle) -> AppResult<Self> {
+        Ok(Self {
+            client,
+            available: json_number(acc.available, style)?,
+            held: json_number(acc.held, style)?,
+            total: json_number(acc.total(), style)?,
+            locked: acc.locked,
+            activity: None,
+        })
+    }
+
+    /// Adds the extended report's activity columns to the row, writing amounts
+    /// in `style`.
+    ///
+    /// # Returns
+    /// * `AppResult<Self>` - The row, or `AppErrors::Internal` if an amount does
+    ///   not format as a JSON number.
+    pub fn with_activity(
+        mut self,
+        activity: &ClientActivity,
+        style: AmountStyle,
+    ) -> AppResult<Self> {
+        self.activity = Some(JsonActivity {
+            deposited: json_number(activity.deposited, style)?,
+            withdrawn: json_number(activity.withdrawn, style)?,
+            disputes: activity.disputes,
+            chargebacks: activity.chargebacks,
+        });
+        Ok(self)
+    }
+}
+
+/// Builds the JSON row for a client's account, with its activity if given.
+fn json_row(
+    client: ClientId,
+    acc: &Account,
+    activity: Option<&ClientActivity>,
+    style: AmountStyle,
+) -> AppResult<JsonAccountRow> {
+    let row = JsonAccountRow::new(client, acc, style)?;
+    match activity {
+        Some(activity) => row.with_activity(activity, style),
+        None => Ok(row),
+    }
+}
+
+/// Formats an amount as a raw JSON number.
+fn json_number(amount: Amount, style: AmountStyle) -> AppResult<Box<RawValue>> {
+    RawValue::from_string(amount.format(style))
+        .map_err(|e| AppErrors::Internal(format!("amount {amount} is not a JSON number: {e}")))
+}
+
+/// The format-specific state of an [`AccountWriter`].
+enum Sink<W: Write> {
+    Csv(Box<csv::Writer<W>>),
+    Json {
+        out: W,
+        empty: bool,
+    },
+    Ndjson(W),
+    Table {
+        out: W,
+        locale: Locale,
+        rows: Vec<[String; 5]>,
+    },
+    #[cfg(feature = "parquet")]
+    Parquet {
+        out: W,
+        rows: Vec<(ClientId, Account)>,
+    },
+}
+
+/// Writes the account report one account at a time in any [`OutputFormat`], so
+/// streamed emission and end-of-run emission share one code path.
+pub struct AccountWriter<W: Write> {
+    /// The underlying writer, wrapped for the chosen format.
+    sink: Sink<W>,
+    /// The number of accounts written so far.
+    count: usize,
+    /// The clients to write, if the report is filtered; others are skipped.
+    clients: Option<HashSet<ClientId>>,
+    /// How the CSV and JSON formats write amounts.
+    style: AmountStyle,
+}
+
+impl<W: Write> AccountWriter<W> {
+    /// Creates a writer emitting the given format to `out`.
+    pub fn new(out: W, format: OutputFormat) -> Self {
+        Self::with_delimiter(out, format, Delimiter::default())
+    }
+
+    /// Creates a writer emitting the given format to `out`, separating CSV
+    /// fields with `delimiter` (other formats ignore it).
+    pub fn with_delimiter(out: W, format: OutputFormat, delimiter: Delimiter) -> Self {
+        let sink = match format {
+            OutputFormat::Csv => Sink::Csv(Box::new(
+                WriterBuilder::new()
+                    .has_headers(true)
+                    .delimiter(delimiter.0)
+                    .from_writer(out),
+            )),
+            OutputFormat::Json => Sink::Json { out, empty: true },
+            OutputFormat::Ndjson => Sink::Ndjson(out),
+            OutputFormat::Table => Sink::Table {
+                out,
+                locale: Locale::default(),
+                rows: Vec::new(),
+            },
+            #[cfg(feature = "parquet")]
+            OutputFormat::Parquet => Sink::Parquet {
+                out,
+                rows: Vec::new(),
+            },
+        };
+        Self {
+            sink,
+            count: 0,
+            clients: None,
+            style: AmountStyle::default(),
+        }
+    }
+
+    /// Sets the locale the table format writes balances in; other formats are
+    /// machine-readable and ignore it.
+    pub fn with_locale(mut self, locale: Locale) -> Self {
+        if let Sink::Table {
+            locale: current, ..
+        } = &mut self.sink
+        {
+            *current = locale;
+        }
+        self
+    }
+
+    /// Sets how the CSV and JSON formats write amounts, e.g. `1.5` instead of
+    /// `1.5000`; the table follows its locale and Parquet stores decimals.
+    pub fn with_amount_style(mut self, style: AmountStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Restricts the report to the given clients, so investigating a few
+    /// accounts does not mean searching the whole book; empty writes every
+    /// account.
+    pub fn with_clients(mut self, clients: &[ClientId]) -> Self {
+        self.clients = (!clients.is_empty()).then(|| clients.iter().copied().collect());
+        self
+    }
+
+    /// Writes one account, unless the report is restricted to other clients
+    /// (see [`AccountWriter::with_clients`]).
+    ///
+    /// # Arguments
+    /// * `client` - The account's client.
+    /// * `acc` - The account.
+    ///
+    /// # Returns
+    /// * `AppResult<()>` - `Ok(())` once written or skipped, or an
+    ///   `AppErrors::Io` on failure.
+    pub fn write(&mut self, client: ClientId, acc: &Account) -> AppResult<()> {
+        self.write_row(client, acc, None)
+    }
+
+    /// Writes one account with the client's activity appended, for the
+    /// extended report. Only the CSV and JSON formats have activity columns; a
+    /// report should be written entirely with or without them.
+    ///
+    /// # Arguments
+    /// * `client` - The account's client.
+    /// * `acc` - The account.
+    /// * `activity` - The client's activity (see [`Engine::activity`](crate::state::Engine::activity)).
+    ///
+    /// # Returns
+    /// * `AppResult<()>` - `Ok(())` once written or skipped, or an
+    ///   `AppErrors::Io` on failure, or `AppErrors::InvalidInput` if the format
+    ///   has no activity columns.
+    pub fn write_extended(
+        &mut self,
+        client: ClientId,
+        acc: &Account,
+        activity: &ClientActivity,
+    ) -> AppResult<()> {
+        self.write_row(client, acc, Some(activity))
+    }
+
+    /// Writes one account, with the client's activity if given.
+    fn write_row(
+        &mut self,
+        client: ClientId,
+        acc: &Account,
+        activity: Option<&ClientActivity>,
+    ) -> AppResult<()> {
+        if self
+            .clients
+            .as_ref()
+            .is_some_and(|clients| !clients.contains(&client))
+        {
+            return Ok(());
+        }
+        let io_err = |e: &dyn std::fmt::Display| AppErrors::Io(format!("write accounts: {e}"));
+        match &mut self.sink {
+            Sink::Csv(wtr) => match activity {
+                Some(activity) => {
+                    wtr.serialize(ExtendedOutputRow::new(client, acc, activity, self.style))
+                }
+                None => wtr.serialize(OutputRow::styled(client, acc, self.style)),
+            }
+            .map_err(|e| io_err(&e))?,
+            Sink::Json { out, empty } => {
+                out.write_all(if *empty { b"[\n" } else { b",\n" })
+                    .map_err(|e| io_err(&e))?;
+                *empty = false;
+                serde_json::to_writer(&mut *out, &json_row(client, acc, activity, self.style)?)
+                    .map_err(|e| io_err(&e))?;
+            }
+            Sink::Ndjson(out) => {
+                serde_json::to_writer(&mut *out, &json_row(client, acc, activity, self.style)?)
+                    .map_err(|e| io_err(&e))?;
+                out.write_all(b"\n").map_err(|e| io_err(&e))?;
+            }
+            _ if activity.is_some() => {
+                return Err(AppErrors::InvalidInput(
+                    "the extended report is only available as csv, json or ndjson",
+                ));
+            }
+            Sink::Table { locale, rows, .. } => rows.push([
+                client.to_string(),
+                locale.format_amount(acc.available),
+                locale.format_amount(acc.held),
+                locale.format_amount(acc.total()),
+                acc.locked.to_string(),
+            ]),
+            #[cfg(feature = "parquet")]
+            Sink::Parquet { rows, .. } => rows.push((client, acc.clone())),
+        }
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Completes the report (closing the JSON array, or writing the buffered
+    /// Parquet file) and flushes it.
+    ///
+    /// # Returns
+    /// * `AppResult<usize>` - The number of accounts written, or an
+    ///   `AppErrors::Io` on failure.
+    pub fn finish(self) -> AppResult<usize> {
+        self.close().map(|(count, _)| count)
+    }
+
+    /// Completes and flushes the report like [`AccountWriter::finish`], handing
+    /// back the underlying writer, e.g. to commit an
+    /// [`AtomicFile`](crate::services::report_output::AtomicFile).
+    ///
+    /// # Returns
+    /// * `AppResult<(usize, W)>` - The number of accounts written and the
+    ///   writer, or an `AppErrors::Io` on failure.
+    pub fn close(self) -> AppResult<(usize, W)> {
+        let io_err = |e: &dyn std::fmt::Display| AppErrors::Io(format!("flush accounts: {e}"));
+        let out = match self.sink {
+            Sink::Csv(wtr) => wtr.into_inner().map_err(|e| io_err(&e))?,
+            Sink::Json { mut out, empty } => {
+                out.write_all(if empty { b"[]\n" } else { b"\n]\n" })
+                    .and_then(|()| out.flush())
+                    .map_err(|e| io_err(&e))?;
+                out
+            }
+            Sink::Ndjson(mut out) => {
+                out.flush().map_err(|e| io_err(&e))?;
+                out
+            }
+            Sink::Table { mut out, rows, .. } => {
+                out.write_all(table_report(TABLE_HEADER, &rows).as_bytes())
+                    .and_then(|()| out.flush())
+                    .map_err(|e| io_err(&e))?;
+                out
+            }
+            #[cfg(feature = "parquet")]
+            Sink::Parquet { mut out, rows } => {
+                let bytes = parquet_report(&rows)?;
+                out.write_all(&bytes)
+                    .and_then(|()| out.flush())
+                    .map_err(|e| io_err(&e))?;
+                out
+            }
+        };
+        Ok((self.count, out))
+    }
+}
+
+/// The column names of the table format.
+const TABLE_HEADER: [&str; 5] = ["client", "available", "held", "total", "locked"];
+
+/// Renders table rows under a header and a rule, each column padded to its
+/// widest cell: numbers right-aligned, the last (text) column left-aligned.
+pub(crate) fn table_report<const N: usize>(header: [&str; N], rows: &[[String; N]]) -> String {
+    let mut widths = header.map(str::len);
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let rule = widths.map(|w| "-".repeat(w));
+    let mut text = String::new();
+    for cells in std::iter::once(header.map(str::to_string))
+        .chain(std::iter::once(rule))
+        .chain(rows.iter().cloned())
+    {
+        let line: Vec<String> = cells
+            .iter()
+            .zip(widths)
+            .enumerate()
+            .map(|(i, (cell, width))| {
+                // `format!` pads by chars, so separators such as U+00A0 align.
+                if i == N - 1 {
+                    format!("{cell:<width$}")
+                } else {
+                    format!("{cell:>width$}")
+                }
+            })
+            .collect();
+        text.push_str(line.join("  ").trim_end());
+        text.push('\n');
+    }
+    text
+}
+
+/// The Parquet schema of the account report. Balances are fixed-point
+/// `DECIMAL(18,4)` stored as `INT64`, matching [`Amount`]'s representation.
+#[cfg(feature = "parquet")]
+const PARQUET_SCHEMA: &str = "message accounts {
+    REQUIRED INT32 client (INTEGER(16,false));
+    REQUIRED INT64 available (DECIMAL(18,4));
+    REQUIRED INT64 held (DECIMAL(18,4));
+    REQUIRED INT64 total (DECIMAL(18,4));
+    REQUIRED BOOLEAN locked;
+}";
+
+/// Encodes accounts as a single-row-group Parquet file.
+///
+/// Columnar files are written whole, so the rows are buffered and encoded in
+/// memory rather than streamed.
+///
+/// # Arguments
+/// * `rows` - The accounts, in report order.
+///
+/// # Returns
+/// * `AppResult<Vec<u8>>` - The encoded file, or an `AppErrors::Io` if encoding fails.
+#[cfg(feature = "parquet")]
+fn parquet_report(rows: &[(ClientId, Account)]) -> AppResult<Vec<u8>> {
+    use parquet::data_type::{BoolType, Int32Type, Int64Type};
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::schema::parser::parse_message_type;
+    use std::sync::Arc;
+
+    let pq_err = |e: parquet::errors::ParquetError| AppErrors::Io(format!("write parquet: {e}"));
+    let schema = Arc::new(parse_message_type(PARQUET_SCHEMA).map_err(pq_err)?);
+    let props = Arc::new(WriterProperties::builder().build());
+    let mut buf = Vec::new();
+    let mut writer = SerializedFileWriter::new(&mut buf, schema, props).map_err(pq_err)?;
+
+    let clients: Vec<i32> = rows.iter().map(|(c, _)| i32::from(*c)).collect();
+    let balances: [Vec<i64>; 3] = [
+        rows.iter().map(|(_, a)| a.available.0).collect(),
+        rows.iter().map(|(_, a)| a.held.0).collect(),
+        rows.iter().map(|(_, a)| a.total().0).collect(),
+    ];
+    let locked: Vec<bool> = rows.iter().map(|(_, a)| a.locked).collect();
+
+    let mut group = writer.next_row_group().map_err(pq_err)?;
+    let mut index = 0;
+    while let Some(mut col) = group.next_column().map_err(pq_err)? {
+        match index {
+            0 => col.typed::<Int32Type>().write_batch(&clients, None, None),
+            1..=3 => col
+                .typed::<Int64Type>()
+                .write_batch(&balances[index - 1], None, None),
+            _ => col.typed::<BoolType>().write_batch(&locked, None, None),
+        }
+        .map_err(pq_err)?;
+        col.close().map_err(pq_err)?;
+        index += 1;
+    }
+    group.close().map_err(pq_err)?;
+    writer.close().map_err(pq_err)?;
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(format: OutputFormat, accounts: &[(ClientId, i64)]) -> String {
+        let mut buf = Vec::new();
+        let mut wtr = AccountWriter::new(&mut buf, format);
+        for &(client, available) in accounts {
+            let acc = Account {
+                available: Amount(available),
+                ..Account::default()
+            };
+            wtr.write(client, &acc).unwrap();
+        }
+        assert_eq!(wtr.finish().unwrap(), accounts.len());
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn json_formats_use_exact_numeric_balances() {
+        let json = render(OutputFormat::Json, &[(1, 15_000), (2, 1)]);
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.as_array().unwrap().len(), 2);
+        assert!(json.contains(
+            r#"{"client":1,"available":1.5000,"held":0.0000,"total":1.5000,"locked":false}"#
+        ));
+
+        assert_eq!(render(OutputFormat::Json, &[]), "[]\n");
+        assert_eq!(
+            render(OutputFormat::Ndjson, &[(1, 1), (2, 2)])
+                .lines()
+                .count(),
+            2
+        );
+        assert!(render(OutputFormat::Csv, &[(1, 1)]).starts_with("client,available"));
+    }
+
+    #[test]
+    fn table_aligns_localized_columns() {
+        assert_eq!(
+            render(OutputFormat::Table, &[(1, 15_000), (22, 12_345_675_000)]),
+            "client     available    held         total  locked\n\
+             ------  ------------  ------  ------------  ------\n\
+             \x20    1        1.5000  0.0000        1.5000  false\n\
+             \x20   22  1234567.5000  0.0000  1234567.5000  false\n"
+        );
+
+        let mut buf = Vec::new();
+        let mut wtr =
+            AccountWriter::new(&mut buf, OutputFormat::Table).with_locale("de-DE".parse().unwrap());
+        let acc = Account {
+            available: Amount(12_345_675_000),
+            ..Account::default()
+        };
+        wtr.write(3, &acc).unwrap();
+        wtr.finish().unwrap();
+        assert!(String::from_utf8(buf).unwrap().contains("1.234.567,5000"));
+    }
+
+    #[test]
+    fn client_filter_skips_other_accounts() {
+        let mut buf = Vec::new();
+        let mut wtr = AccountWriter::new(&mut buf, OutputFormat::Csv).with_clients(&[5, 42]);
+        for client in [1, 5, 7, 42] {
+            wtr.write(client, &Account::default()).unwrap();
+        }
+        assert_eq!(wtr.finish().unwrap(), 2);
+        let report = String::from_utf8(buf).unwrap();
+        let clients: Vec<_> = report
+            .lines()
+            .skip(1)
+            .map(|l| &l[..l.find(',').unwrap()])
+            .collect();
+        assert_eq!(clients, ["5", "42"]);
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn parquet_report_uses_decimal_balances() {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+
+        let mut buf = Vec::new();
+        let mut wtr = AccountWriter::new(&mut buf, OutputFormat::Parquet);
+        let acc = Account {
+            available: Amount(15_000),
+            ..Account::default()
+        };
+        wtr.write(7, &acc).unwrap();
+        wtr.finish().unwrap();
+
+        let path = std::env::temp_dir().join(format!("pe-{}-accounts.parquet", std::process::id()));
+        std::fs::write(&path, &buf).unwrap();
+        let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let meta = reader.metadata();
+        assert_eq!(meta.file_metadata().num_rows(), 1);
+        let available = &meta.file_metadata().schema_descr().columns()[1];
+        assert_eq!(
+            (available.type_precision(), available.type_scale()),
+            (18, 4)
+        );
+    }
+}
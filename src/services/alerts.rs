@@ -0,0 +1,212 @@
+use crate::errors::AppErrors;
+use crate::models::amount::Amount;
+use crate::models::domain_state::Account;
+use crate::models::identifiers::{ClientId, TxId};
+use log::warn;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// The account balance an alert rule watches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BalanceField {
+    /// Funds available for withdrawal.
+    Available,
+    /// Funds held by open disputes.
+    Held,
+    /// Available plus held funds.
+    Total,
+}
+
+/// A balance threshold rule such as `held>1000` or `available<0`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AlertRule {
+    /// The balance the rule watches.
+    pub field: BalanceField,
+    /// `true` to alert when the balance rises above the threshold, `false` when
+    /// it drops below it.
+    pub above: bool,
+    /// The threshold amount.
+    pub threshold: Amount,
+}
+
+impl AlertRule {
+    /// Returns the watched balance of `acc` if it breaches the rule.
+    pub fn breach(&self, acc: &Account) -> Option<Amount> {
+        let value = match self.field {
+            BalanceField::Available => acc.available,
+            BalanceField::Held => acc.held,
+            BalanceField::Total => acc.total(),
+        };
+        let breached = if self.above {
+            value > self.threshold
+        } else {
+            value < self.threshold
+        };
+        breached.then_some(value)
+    }
+}
+
+impl FromStr for AlertRule {
+    type Err = AppErrors;
+
+    /// Parses a `<field><op><amount>` rule, where `field` is `available`, `held` or
+    /// `total` and `op` is `>` or `<`.
+    ///
+    /// # Errors
+    /// * `AppErrors::InvalidInput` if the field or operator is unknown.
+    /// * `AppErrors::AmountParseError` if the threshold is not a valid amount.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (pos, above) = match (s.find('>'), s.find('<')) {
+            (Some(i), None) => (i, true),
+            (None, Some(i)) => (i, false),
+            _ => {
+                return Err(AppErrors::InvalidInput(
+                    "alert rule must be written as <field>><amount> or <field><<amount>",
+                ));
+            }
+        };
+        let field = match s[..pos].trim() {
+            "available" => BalanceField::Available,
+            "held" => BalanceField::Held,
+            "total" => BalanceField::Total,
+            _ => {
+                return Err(AppErrors::InvalidInput(
+                    "alert field must be available, held or total",
+                ));
+            }
+        };
+        Ok(Self {
+            field,
+            above,
+            threshold: Amount::from_str(s[pos + 1..].trim())?,
+        })
+    }
+}
+
+impl Display for AlertRule {
+    /// Formats the rule back into its `<field><op><amount>` form.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let field = match self.field {
+            BalanceField::Available => "available",
+            BalanceField::Held => "held",
+            BalanceField::Total => "total",
+        };
+        let op = if self.above { '>' } else { '<' };
+        write!(f, "{field}{op}{}", self.threshold)
+    }
+}
+
+/// An alert raised when an account starts breaching a rule.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Alert {
+    /// The rule that was breached, e.g. `held>1000.0000`.
+    pub rule: String,
+    /// The client whose account breached the rule.
+    pub client: ClientId,
+    /// The transaction that caused the breach.
+    pub tx: TxId,
+    /// The input line of that transaction.
+    pub line: u64,
+    /// The watched balance after the transaction.
+    pub value: String,
+}
+
+/// Evaluates alert rules as commands are applied.
+///
+/// Alerts are edge-triggered: an account raises an alert when it starts
+/// breaching a rule, and again only after it has stopped breaching it.
+#[derive(Debug, Default)]
+pub struct AlertMonitor {
+    /// The rules to evaluate.
+    rules: Vec<AlertRule>,
+    /// The `(rule index, client)` pairs currently in breach.
+    breached: HashSet<(usize, ClientId)>,
+    /// The alerts raised so far, in order.
+    alerts: Vec<Alert>,
+}
+
+impl AlertMonitor {
+    /// Creates a monitor for the given rules.
+    pub fn new(rules: Vec<AlertRule>) -> Self {
+        Self {
+            rules,
+            ..Self::default()
+        }
+    }
+
+    /// Evaluates every rule against a client's account after a transaction,
+    /// logging a warning for each new breach.
+    ///
+    /// # Arguments
+    /// * `line` - The input line of the transaction.
+    /// * `client` - The client the transaction applied to.
+    /// * `tx` - The transaction id.
+    /// * `acc` - The client's account after the transaction.
+    pub fn observe(&mut self, line: u64, client: ClientId, tx: TxId, acc: &Account) {
+        for (i, rule) in self.rules.iter().enumerate() {
+            match rule.breach(acc) {
+                Some(value) if self.breached.insert((i, client)) => {
+                    warn!(
+                        "alert: client {client} {rule} (now {value}) after tx {tx} on line {line}"
+                    );
+                    self.alerts.push(Alert {
+                        rule: rule.to_string(),
+                        client,
+                        tx,
+                        line,
+                        value: value.to_string(),
+                    });
+                }
+                Some(_) => {}
+                None => {
+                    self.breached.remove(&(i, client));
+                }
+            }
+        }
+    }
+
+    /// Returns the alerts raised so far, in order.
+    pub fn alerts(&self) -> &[Alert] {
+        &self.alerts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn acct(available: i64, held: i64) -> Account {
+        Account {
+            available: Amount(available),
+            held: Amount(held),
+            ..Account::default()
+        }
+    }
+
+    #[test]
+    fn parses_rules() {
+        let rule: AlertRule = "held>1000".parse().unwrap();
+        assert_eq!(rule.field, BalanceField::Held);
+        assert!(rule.above);
+        assert_eq!(rule.threshold, Amount(10_000_000));
+        assert_eq!(rule.to_string(), "held>1000.0000");
+
+        assert!(!"available<0".parse::<AlertRule>().unwrap().above);
+        assert!("held=5".parse::<AlertRule>().is_err());
+        assert!("locked>1".parse::<AlertRule>().is_err());
+    }
+
+    #[test]
+    fn alerts_are_edge_triggered() {
+        let mut monitor = AlertMonitor::new(vec!["available<0".parse().unwrap()]);
+        monitor.observe(2, 1, 10, &acct(-1, 0));
+        monitor.observe(3, 1, 11, &acct(-5, 0));
+        monitor.observe(4, 1, 12, &acct(5, 0));
+        monitor.observe(5, 1, 13, &acct(-2, 0));
+
+        let lines: Vec<_> = monitor.alerts().iter().map(|a| a.line).collect();
+        assert_eq!(lines, vec![2, 5]);
+    }
+}
@@ -0,0 +1,164 @@
+use crate::errors::{AppErrors, AppResult};
+use crate::models::amount::Amount;
+use crate::models::domain_state::DisputeState;
+use crate::models::identifiers::ClientId;
+use crate::services::alerts::AlertRule;
+use crate::services::run_manifest::{TrialBalance, trial_balance};
+use crate::services::state_store::state_digest;
+use crate::state::Engine;
+use serde::Serialize;
+
+/// An account breaching an alert rule at the time of the analysis.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Breach {
+    /// The rule that is breached, e.g. `held>1000.0000`.
+    pub rule: String,
+    /// The client whose account breaches it.
+    pub client: ClientId,
+    /// The watched balance.
+    pub value: String,
+}
+
+/// The findings of a read-only analysis of existing engine state.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Analysis {
+    /// The state hash ([`state_digest`]), as 16 hex digits.
+    pub state_hash: String,
+    /// The ledger totals.
+    pub balance: TrialBalance,
+    /// The number of open disputes.
+    pub open_disputes: usize,
+    /// The amount held by open disputes.
+    pub disputed: String,
+    /// The locked accounts, sorted by client.
+    pub locked: Vec<ClientId>,
+    /// The invariant violations [`Engine::verify`] found, if any.
+    pub violations: Option<String>,
+    /// The accounts breaching the alert rules, by rule, then client.
+    pub breaches: Vec<Breach>,
+}
+
+/// Runs the reporting, invariant and risk checks over engine state without
+/// applying any input.
+///
+/// Alert rules are evaluated against every account's current balances, so an
+/// account is listed for each rule it breaches now, however it got there.
+///
+/// # Arguments
+/// * `engine` - The state to analyze, e.g. loaded from a snapshot.
+/// * `rules` - The alert rules to evaluate.
+///
+/// # Returns
+/// * `AppResult<Analysis>` - The findings, or `AppErrors::Overflow` if a total does
+///   not fit an amount. Invariant violations are findings, not errors.
+pub fn analyze(engine: &Engine, rules: &[AlertRule]) -> AppResult<Analysis> {
+    let mut accounts: Vec<_> = engine.accounts_iter().collect();
+    accounts.sort_by_key(|(c, _)| **c);
+
+    let (mut open_disputes, mut disputed) = (0, Amount::zero());
+    for (_, rec) in engine.txs.iter() {
+        if rec.state == DisputeState::Disputed {
+            open_disputes += 1;
+            disputed = disputed
+                .checked_add(rec.amount)
+                .ok_or(AppErrors::Overflow)?;
+        }
+    }
+
+    let breaches = rules
+        .iter()
+        .flat_map(|rule| {
+            accounts.iter().filter_map(move |(client, acc)| {
+                rule.breach(acc).map(|value| Breach {
+                    rule: rule.to_string(),
+                    client: **client,
+                    value: value.to_string(),
+                })
+            })
+        })
+        .collect();
+
+    Ok(Analysis {
+        state_hash: format!("{:016x}", state_digest(engine)),
+        balance: trial_balance(engine)?,
+        open_disputes,
+        disputed: disputed.to_string(),
+        locked: accounts
+            .iter()
+            .filter(|(_, acc)| acc.locked)
+            .map(|(c, _)| **c)
+            .collect(),
+        violations: engine.verify().err().map(|e| e.to_string()),
+        breaches,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::domain_state::{TxKind, TxRecord};
+
+    #[test]
+    fn reports_disputes_locks_violations_and_breaches() {
+        let mut engine = Engine::default();
+        engine.acct_mut(1).held = Amount(20_000);
+        engine.txs.insert(
+            7,
+            TxRecord {
+                client: 1,
+                kind: TxKind::Deposit,
+                amount: Amount(20_000),
+                state: DisputeState::Disputed,
+            },
+        );
+        engine.acct_mut(2).locked = true;
+        engine.acct_mut(2).held = Amount(5);
+        let rules = ["held>1".parse().unwrap(), "available<0".parse().unwrap()];
+
+        let analysis = analyze(&engine, &rules).unwrap();
+
+        assert_eq!(analysis.open_disputes, 1);
+        assert_eq!(analysis.disputed, "2.0000");
+        assert_eq!(analysis.locked, vec![2]);
+        assert_eq!(analysis.balance.total, "2.0005");
+        let violations = analysis.violations.unwrap();
+        assert!(violations.contains("client 2: held 0.0005"), "{violations}");
+        assert_eq!(analysis.breaches.len(), 1);
+        assert_eq!(analysis.breaches[0].client, 1);
+        assert_eq!(analysis.breaches[0].rule, "held>1.0000");
+    }
+
+    #[test]
+    fn empty_state_has_no_findings() {
+        let rules = ["held>1".parse().unwrap()];
+
+        let analysis = analyze(&Engine::default(), &rules).unwrap();
+
+        assert_eq!(analysis.balance.accounts, 0);
+        assert_eq!(analysis.balance.total, "0.0000");
+        assert_eq!(
+            (analysis.open_disputes, analysis.disputed.as_str()),
+            (0, "0.0000")
+        );
+        assert!(analysis.locked.is_empty());
+        assert_eq!(analysis.violations, None);
+        assert!(analysis.breaches.is_empty());
+    }
+
+    #[test]
+    fn locked_account_is_listed_and_still_checked_against_rules() {
+        let mut engine = Engine::default();
+        let acc = engine.acct_mut(3);
+        acc.available = Amount(10_000);
+        acc.locked = true;
+        let rules = ["available>0.5".parse().unwrap()];
+
+        let analysis = analyze(&engine, &rules).unwrap();
+
+        assert_eq!(analysis.locked, vec![3]);
+        assert_eq!(analysis.balance.locked, 1);
+        assert_eq!(analysis.violations, None);
+        assert_eq!(analysis.breaches.len(), 1);
+        assert_eq!(analysis.breaches[0].value, "1.0000");
+    }
+}
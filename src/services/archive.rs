@@ -0,0 +1,291 @@
+use crate::errors::{AppErrors, AppResult};
+use crate::models::domain_state::TxRecord;
+use crate::models::identifiers::{ClientId, TxId};
+use crate::services::backup::backup_id;
+use crate::services::report_output::AtomicFile;
+use crate::state::Engine;
+use csv::{ReaderBuilder, WriterBuilder};
+use flate2::Compression;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+/// File name prefix of archive files inside an archive directory.
+const ARCHIVE_PREFIX: &str = "archive-";
+
+/// File name suffix of archive files: gzip-compressed CSV.
+const ARCHIVE_SUFFIX: &str = ".csv.gz";
+
+/// A transaction record moved to cold storage.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchivedTx {
+    /// The transaction id.
+    pub tx: TxId,
+    /// The client the transaction belonged to.
+    pub client: ClientId,
+    /// `deposit` or `withdrawal`.
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// The transaction amount.
+    pub amount: String,
+    /// The dispute state the record was archived in.
+    pub state: String,
+}
+
+impl ArchivedTx {
+    /// Describes a transaction record in the archive layout.
+    fn of(tx: TxId, rec: &TxRecord) -> Self {
+        Self {
+            tx,
+            client: rec.client,
+            kind: rec.kind.as_str().to_string(),
+            amount: rec.amount.to_string(),
+            state: rec.state.as_str().to_string(),
+        }
+    }
+}
+
+/// The result of an archival pass.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveInfo {
+    /// The archive file written, or `None` if there was nothing to archive.
+    pub path: Option<PathBuf>,
+    /// The number of records archived and pruned from the engine.
+    pub records: usize,
+}
+
+/// Moves every transaction record that can no longer change state (see
+/// [`TxRecord::is_final`](crate::models::domain_state::TxRecord::is_final)) to a
+/// compressed cold file in `dir`, then prunes them from the engine. Withdrawals
+/// are only final if the engine's config does not allow withdrawal disputes.
+///
+/// Pruned ids stay seen, so replayed transactions are still rejected as
+/// duplicates. The archive is written to a temporary file and renamed into
+/// place before anything is pruned, so a failed pass loses no records.
+///
+/// # Arguments
+/// * `engine` - The engine to archive from.
+/// * `dir` - The archive directory (created if missing).
+/// * `now` - The archival time in seconds since the Unix epoch; names the file.
+///
+/// # Returns
+/// * `AppResult<ArchiveInfo>` - The archive written and the number of records
+///   moved, or an `AppErrors::Io` on failure.
+pub fn archive_final(engine: &mut Engine, dir: &Path, now: u64) -> AppResult<ArchiveInfo> {
+    let config = engine.config();
+    let mut archived: Vec<ArchivedTx> = engine
+        .txs
+        .iter()
+        .filter(|(_, rec)| rec.is_final(&config))
+        .map(|(tx, rec)| ArchivedTx::of(tx, rec))
+        .collect();
+    if archived.is_empty() {
+        return Ok(ArchiveInfo {
+            path: None,
+            records: 0,
+        });
+    }
+    archived.sort_by_key(|a| a.tx);
+
+    fs::create_dir_all(dir).map_err(|e| AppErrors::Io(format!("create {}: {e}", dir.display())))?;
+    let path = dir.join(format!(
+        "{ARCHIVE_PREFIX}{}{ARCHIVE_SUFFIX}",
+        backup_id(now)
+    ));
+    let tmp = path.with_extension("tmp");
+    write_archive(&tmp, &archived)?;
+    fs::rename(&tmp, &path).map_err(|e| AppErrors::Io(format!("rename {}: {e}", tmp.display())))?;
+
+    for a in &archived {
+        engine.txs.retire(a.tx);
+    }
+    Ok(ArchiveInfo {
+        path: Some(path),
+        records: archived.len(),
+    })
+}
+
+/// Writes the engine's whole transaction log as plain CSV in the archive
+/// layout (`tx,client,type,amount,state`), sorted by tx id, so auditors can
+/// see which transactions ended up disputed or charged back. Records already
+/// archived or retired by compaction are not in the log.
+///
+/// The file is written to a temporary file renamed into place once complete.
+///
+/// # Arguments
+/// * `engine` - The engine to dump.
+/// * `path` - The CSV file to write.
+///
+/// # Returns
+/// * `AppResult<usize>` - The number of records written, or an `AppErrors::Io`
+///   on failure.
+pub fn dump_txs(engine: &Engine, path: &str) -> AppResult<usize> {
+    let mut records: Vec<ArchivedTx> = engine
+        .txs
+        .iter()
+        .map(|(tx, rec)| ArchivedTx::of(tx, rec))
+        .collect();
+    records.sort_by_key(|a| a.tx);
+    let io_err = |e: &dyn std::fmt::Display| AppErrors::Io(format!("write {path}: {e}"));
+    let mut wtr = WriterBuilder::new().from_writer(AtomicFile::create(path)?);
+    for rec in &records {
+        wtr.serialize(rec).map_err(|e| io_err(&e))?;
+    }
+    wtr.into_inner().map_err(|e| io_err(&e))?.commit()?;
+    Ok(records.len())
+}
+
+/// Searches every archive file in `dir` for records matching the filters.
+///
+/// # Arguments
+/// * `dir` - The archive directory.
+/// * `tx` - If set, only the record with this tx id.
+/// * `client` - If set, only records of this client.
+///
+/// # Returns
+/// * `AppResult<Vec<ArchivedTx>>` - The matching records, oldest archive first,
+///   or an `AppErrors` variant if an archive cannot be read.
+pub fn query_archive(
+    dir: &Path,
+    tx: Option<TxId>,
+    client: Option<ClientId>,
+) -> AppResult<Vec<ArchivedTx>> {
+    let mut matches = Vec::new();
+    for path in list_archives(dir)? {
+        let file = File::open(&path)
+            .map_err(|e| AppErrors::Io(format!("open {}: {e}", path.display())))?;
+        let mut rdr = ReaderBuilder::new().from_reader(MultiGzDecoder::new(BufReader::new(file)));
+        for rec in rdr.deserialize::<ArchivedTx>() {
+            let rec = rec.map_err(|e| AppErrors::Csv(format!("{}: {e}", path.display())))?;
+            if tx.is_none_or(|t| t == rec.tx) && client.is_none_or(|c| c == rec.client) {
+                matches.push(rec);
+            }
+        }
+    }
+    Ok(matches)
+}
+
+/// Returns the archive files in `dir`, oldest first (their names sort by time).
+fn list_archives(dir: &Path) -> AppResult<Vec<PathBuf>> {
+    let mut archives = Vec::new();
+    for entry in
+        fs::read_dir(dir).map_err(|e| AppErrors::Io(format!("read {}: {e}", dir.display())))?
+    {
+        let path = entry
+            .map_err(|e| AppErrors::Io(format!("read {}: {e}", dir.display())))?
+            .path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if name.starts_with(ARCHIVE_PREFIX) && name.ends_with(ARCHIVE_SUFFIX) {
+            archives.push(path);
+        }
+    }
+    archives.sort();
+    Ok(archives)
+}
+
+/// Writes records to `path` as gzip-compressed CSV.
+fn write_archive(path: &Path, records: &[ArchivedTx]) -> AppResult<()> {
+    let io_err =
+        |e: &dyn std::fmt::Display| AppErrors::Io(format!("write {}: {e}", path.display()));
+    let file = File::create(path).map_err(|e| io_err(&e))?;
+    let mut wtr = WriterBuilder::new().from_writer(GzEncoder::new(file, Compression::default()));
+    for rec in records {
+        wtr.serialize(rec).map_err(|e| io_err(&e))?;
+    }
+    let gz = wtr.into_inner().map_err(|e| io_err(&e))?;
+    gz.finish()
+        .and_then(|file| file.sync_all())
+        .map_err(|e| io_err(&e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::amount::Amount;
+    use crate::models::domain_state::{DisputeState, TxKind, TxRecord};
+
+    #[test]
+    fn archives_final_records_and_answers_queries() {
+        let mut engine = Engine::default();
+        for (tx, client, kind, state) in [
+            (1, 1, TxKind::Deposit, DisputeState::Normal),
+            (2, 1, TxKind::Withdrawal, DisputeState::Normal),
+            (3, 2, TxKind::Deposit, DisputeState::ChargedBack),
+        ] {
+            engine.txs.insert(
+                tx,
+                TxRecord {
+                    client,
+                    kind,
+                    amount: Amount(10_000),
+                    state,
+                },
+            );
+        }
+        let dir = std::env::temp_dir().join(format!("pe-{}-archive", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("txs.csv").display().to_string();
+        assert_eq!(dump_txs(&engine, &log).unwrap(), 3);
+        assert_eq!(
+            fs::read_to_string(&log).unwrap(),
+            "tx,client,type,amount,state\n\
+             1,1,deposit,1.0000,normal\n\
+             2,1,withdrawal,1.0000,normal\n\
+             3,2,deposit,1.0000,charged_back\n"
+        );
+
+        let info = archive_final(&mut engine, &dir, 1_700_000_000).unwrap();
+        let by_client = query_archive(&dir, None, Some(1)).unwrap();
+        let by_tx = query_archive(&dir, Some(3), None).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(info.records, 2);
+        assert!(engine.txs.get(&1).is_some());
+        assert!(engine.txs.get(&2).is_none());
+        assert!(engine.txs.contains_key(&2));
+        assert_eq!(by_client.len(), 1);
+        assert_eq!(by_client[0].kind, "withdrawal");
+        assert_eq!(by_tx[0].state, "charged_back");
+    }
+
+    #[test]
+    fn nothing_final_writes_no_archive() {
+        let dir = std::env::temp_dir().join(format!("pe-{}-archive-empty", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut engine = Engine::default();
+
+        let info = archive_final(&mut engine, &dir, 1_700_000_000).unwrap();
+        assert_eq!((info.path, info.records), (None, 0));
+        assert!(!dir.exists());
+        assert!(query_archive(&dir, None, None).is_err());
+
+        fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("txs.csv").display().to_string();
+        assert_eq!(dump_txs(&engine, &log).unwrap(), 0);
+        assert!(query_archive(&dir, None, None).unwrap().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn malformed_archive_is_an_error() {
+        let dir = std::env::temp_dir().join(format!("pe-{}-archive-bad", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(format!("{ARCHIVE_PREFIX}0{ARCHIVE_SUFFIX}"));
+        let mut gz = GzEncoder::new(File::create(&path).unwrap(), Compression::default());
+        std::io::Write::write_all(
+            &mut gz,
+            b"tx,client,type,amount,state\nx,1,deposit,1,normal\n",
+        )
+        .unwrap();
+        gz.finish().unwrap();
+
+        let res = query_archive(&dir, None, None);
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(matches!(res, Err(AppErrors::Csv(_))));
+    }
+}
@@ -0,0 +1,220 @@
+use crate::errors::{AppErrors, AppResult};
+use crate::models::csv_models::transaction::{CsvTxType, InputRow};
+use crate::services::csv_service::open_input;
+use arrow_array::cast::AsArray;
+use arrow_array::types::{UInt16Type, UInt32Type};
+use arrow_array::{Array, ArrayRef, RecordBatch};
+use arrow_cast::cast;
+use arrow_ipc::reader::StreamReader;
+use arrow_schema::{ArrowError, DataType};
+use std::io::{BufRead, BufReader, Read};
+use std::vec;
+
+/// The magic bytes that open an Arrow IPC file (Feather v2). After them and
+/// their zero padding, the file body is an IPC stream (its messages start with a
+/// `0xFFFFFFFF` continuation marker), so both layouts are read as streams.
+const ARROW_FILE_MAGIC: &[u8; 6] = b"ARROW1";
+
+/// Reads transaction rows from an Arrow IPC stream or file (Feather v2).
+///
+/// Each record batch is converted column-wise: the `type`, `client`, `tx` and
+/// (optional) `amount` columns are cast to strings and unsigned integers in
+/// bulk, then zipped into rows, so no per-row deserialization takes place. Any
+/// type Arrow can cast is accepted, e.g. integer ids of any width, or a
+/// `Decimal128(_, 4)` amount. Extra columns are ignored.
+///
+/// Rows are numbered from 1 across batches, in place of CSV line numbers. A row
+/// with a null or out-of-range value is yielded as a row error; a missing column
+/// or an undecodable batch is an outer error that aborts processing.
+pub struct ArrowRows<R: Read> {
+    /// The record batches.
+    batches: StreamReader<R>,
+    /// The rows of the current batch.
+    rows: vec::IntoIter<AppResult<InputRow>>,
+    /// The number of the last row read.
+    row: u64,
+    /// Set once the input is exhausted or an outer error was returned.
+    done: bool,
+}
+
+/// Opens an Arrow IPC stream or file of transaction rows.
+///
+/// # Arguments
+/// * `path` - The file path, or [`STDIN_PATH`](crate::consts::STDIN_PATH) (`-`)
+///   to read stdin. A gzip- or zstd-compressed file is decompressed first.
+///
+/// # Returns
+/// * `AppResult<ArrowRows<BufReader<Box<dyn Read>>>>` - The row reader, or an
+///   `AppErrors` variant if the file cannot be opened or has no valid schema.
+pub fn open_arrow(path: &str) -> AppResult<ArrowRows<BufReader<Box<dyn Read>>>> {
+    ArrowRows::new(BufReader::new(open_input(path)?)).map_err(|e| match e {
+        AppErrors::Decode(msg) => AppErrors::Decode(format!("{path}: {msg}")),
+        e => e,
+    })
+}
+
+impl<R: BufRead> ArrowRows<R> {
+    /// Reads the schema of an Arrow IPC stream or file.
+    ///
+    /// # Returns
+    /// * `AppResult<Self>` - The row reader, or an `AppErrors::Decode` if the
+    ///   input does not start with a valid schema.
+    pub fn new(mut input: R) -> AppResult<Self> {
+        let head = input.fill_buf().map_err(|e| arrow_err(e.into()))?;
+        if head.starts_with(ARROW_FILE_MAGIC) {
+            input.consume(ARROW_FILE_MAGIC.len());
+            loop {
+                let head = input.fill_buf().map_err(|e| arrow_err(e.into()))?;
+                let (padding, buffered) =
+                    (head.iter().take_while(|b| **b == 0).count(), head.len());
+                input.consume(padding);
+                if padding < buffered || buffered == 0 {
+                    break;
+                }
+            }
+        }
+        Ok(Self {
+            batches: StreamReader::try_new(input, None).map_err(arrow_err)?,
+            rows: Vec::new().into_iter(),
+            row: 0,
+            done: false,
+        })
+    }
+}
+
+impl<R: Read> Iterator for ArrowRows<R> {
+    type Item = AppResult<(u64, AppResult<InputRow>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            if let Some(row) = self.rows.next() {
+                self.row += 1;
+                return Some(Ok((self.row, row)));
+            }
+            match self.batches.next() {
+                Some(Ok(batch)) => match batch_rows(&batch) {
+                    Ok(rows) => self.rows = rows.into_iter(),
+                    Err(e) => {
+                        self.done = true;
+                        return Some(Err(e));
+                    }
+                },
+                Some(Err(e)) => {
+                    self.done = true;
+                    return Some(Err(arrow_err(e)));
+                }
+                None => self.done = true,
+            }
+        }
+        None
+    }
+}
+
+/// Maps an Arrow error to a decode error.
+fn arrow_err(e: ArrowError) -> AppErrors {
+    AppErrors::Decode(format!("arrow: {e}"))
+}
+
+/// Casts a batch's named column to `to`; values that do not fit become null.
+fn column(batch: &RecordBatch, name: &str, to: &DataType) -> AppResult<ArrayRef> {
+    let col = batch
+        .column_by_name(name)
+        .ok_or_else(|| AppErrors::Decode(format!("arrow: missing column `{name}`")))?;
+    cast(col, to).map_err(arrow_err)
+}
+
+/// Converts a record batch into transaction rows.
+///
+/// # Returns
+/// * `AppResult<Vec<AppResult<InputRow>>>` - One row or row error per batch row,
+///   or an `AppErrors::Decode` if a required column is missing or cannot be cast.
+fn batch_rows(batch: &RecordBatch) -> AppResult<Vec<AppResult<InputRow>>> {
+    let types = column(batch, "type", &DataType::Utf8)?;
+    let clients = column(batch, "client", &DataType::UInt16)?;
+    let txs = column(batch, "tx", &DataType::UInt32)?;
+    let amounts = match batch.column_by_name("amount") {
+        Some(_) => Some(column(batch, "amount", &DataType::Utf8)?),
+        None => None,
+    };
+    let (types, clients, txs) = (
+        types.as_string::<i32>(),
+        clients.as_primitive::<UInt16Type>(),
+        txs.as_primitive::<UInt32Type>(),
+    );
+    let amounts = amounts.as_ref().map(|a| a.as_string::<i32>());
+
+    Ok((0..batch.num_rows())
+        .map(|i| {
+            if types.is_null(i) {
+                return Err(AppErrors::InvalidInput("missing transaction type"));
+            }
+            if clients.is_null(i) {
+                return Err(AppErrors::InvalidInput("client id missing or out of range"));
+            }
+            if txs.is_null(i) {
+                return Err(AppErrors::InvalidInput("tx id missing or out of range"));
+            }
+            Ok(InputRow {
+                t: types.value(i).parse::<CsvTxType>()?,
+                client: clients.value(i),
+                tx: txs.value(i),
+                amount: amounts
+                    .filter(|a| a.is_valid(i))
+                    .map(|a| a.value(i).to_string()),
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::{Decimal128Array, Int64Array, StringArray};
+    use arrow_ipc::writer::FileWriter;
+    use std::sync::Arc;
+
+    #[test]
+    fn reads_batches_from_an_ipc_file() {
+        let batch = RecordBatch::try_from_iter([
+            (
+                "type",
+                Arc::new(StringArray::from(vec!["deposit", "deposit", "dispute"])) as ArrayRef,
+            ),
+            (
+                "client",
+                Arc::new(Int64Array::from(vec![1, 70_000, 1])) as _,
+            ),
+            ("tx", Arc::new(Int64Array::from(vec![1, 2, 1])) as _),
+            (
+                "amount",
+                Arc::new(
+                    Decimal128Array::from(vec![Some(25_000), Some(10_000), None])
+                        .with_precision_and_scale(18, 4)
+                        .unwrap(),
+                ) as _,
+            ),
+        ])
+        .unwrap();
+        let mut file = Vec::new();
+        let mut writer = FileWriter::try_new(&mut file, &batch.schema()).unwrap();
+        writer.write(&batch).unwrap();
+        writer.write(&batch).unwrap();
+        writer.finish().unwrap();
+        drop(writer);
+
+        let rows: Vec<_> = ArrowRows::new(file.as_slice())
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+
+        assert_eq!(rows.len(), 6);
+        let deposit = rows[0].1.as_ref().unwrap();
+        assert_eq!(deposit.amount.as_deref(), Some("2.5000"));
+        assert!(rows[1].1.is_err());
+        let dispute = rows[5].1.as_ref().unwrap();
+        assert_eq!(
+            (rows[5].0, dispute.t, dispute.amount.is_none()),
+            (6, CsvTxType::Dispute, true)
+        );
+    }
+}
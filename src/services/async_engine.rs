@@ -0,0 +1,127 @@
+use crate::errors::AppResult;
+use crate::models::domain_state::Account;
+use crate::models::identifiers::ClientId;
+use crate::models::policy::RowError;
+use crate::models::tx_command::Transaction;
+use crate::services::csv_service::{AsyncRows, apply_row, handle_malformed};
+use crate::state::{AppState, Outcome};
+use tokio::io::AsyncRead;
+use tokio::sync::Mutex;
+
+/// An engine that can be shared between async tasks.
+///
+/// The state sits behind an async mutex that is held for one transaction at a
+/// time, so a slow input stream never blocks other callers: [`AsyncEngine::ingest`]
+/// awaits each row without the lock and only takes it to apply the row.
+#[derive(Default)]
+pub struct AsyncEngine {
+    /// The application state.
+    state: Mutex<AppState>,
+}
+
+impl AsyncEngine {
+    /// Creates an async engine over an existing state.
+    pub fn new(state: AppState) -> Self {
+        Self {
+            state: Mutex::new(state),
+        }
+    }
+
+    /// Applies a single transaction.
+    ///
+    /// # Arguments
+    /// * `tx` - The transaction to apply.
+    ///
+    /// # Returns
+    /// * `AppResult<Outcome>` - Whether the transaction was applied or ignored,
+    ///   or an `AppErrors` variant if an error occurs.
+    pub async fn apply(&self, tx: Transaction) -> AppResult<Outcome> {
+        self.state.lock().await.engine.apply(tx)
+    }
+
+    /// Applies every row of an async CSV source, interleaving with other callers.
+    /// Row and command errors are handled by the engine's error policy (see
+    /// [`Engine::config`](crate::state::Engine::config)).
+    ///
+    /// # Arguments
+    /// * `input` - The CSV data, including the header row.
+    ///
+    /// # Returns
+    /// * `AppResult<Vec<RowError>>` - The errors gathered under
+    ///   [`ErrorPolicy::Collect`](crate::models::policy::ErrorPolicy::Collect)
+    ///   (empty otherwise), or an `AppErrors` variant if processing was aborted.
+    pub async fn ingest<R: AsyncRead + Unpin>(&self, input: R) -> AppResult<Vec<RowError>> {
+        let (dialect, policy) = {
+            let state = self.state.lock().await;
+            (state.dialect.clone(), state.engine.error_policy())
+        };
+        let mut rows = AsyncRows::open(input, dialect).await?;
+        let mut errors = Vec::new();
+        while let Some((line, rec)) = rows.next().await? {
+            match rec {
+                Ok(row) => {
+                    let mut state = self.state.lock().await;
+                    apply_row(
+                        line,
+                        row,
+                        &mut state,
+                        policy,
+                        &mut errors,
+                        &mut |_, _, _, _| Ok(()),
+                    )?;
+                }
+                Err(e) => {
+                    handle_malformed(line, e, &mut *self.state.lock().await, policy, &mut errors)?
+                }
+            }
+        }
+        Ok(errors)
+    }
+
+    /// Returns a copy of every account.
+    pub async fn accounts(&self) -> Vec<(ClientId, Account)> {
+        let state = self.state.lock().await;
+        state
+            .engine
+            .accounts_iter()
+            .map(|(c, a)| (*c, a.clone()))
+            .collect()
+    }
+
+    /// Consumes the engine and returns its state.
+    pub fn into_inner(self) -> AppState {
+        self.state.into_inner()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::amount::Amount;
+    use crate::models::tx_command::DepositCommand;
+
+    #[tokio::test]
+    async fn ingest_and_apply_share_one_engine() {
+        let engine = AsyncEngine::default();
+        let input = "type,client,tx,amount\ndeposit,1,1,2.0\ndeposit,2,2,1.0\n";
+        engine.ingest(input.as_bytes()).await.unwrap();
+
+        let outcome = engine
+            .apply(
+                DepositCommand {
+                    client: 1,
+                    tx: 3,
+                    amount: Amount(5_000),
+                }
+                .into(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(outcome, Outcome::Applied);
+
+        let mut accounts = engine.accounts().await;
+        accounts.sort_by_key(|(c, _)| *c);
+        let balances: Vec<_> = accounts.iter().map(|(c, a)| (*c, a.available)).collect();
+        assert_eq!(balances, vec![(1, Amount(25_000)), (2, Amount(10_000))]);
+    }
+}
@@ -0,0 +1,501 @@
+use crate::errors::{AppErrors, AppResult};
+use crate::models::csv_models::transaction::{CsvTxType, InputRow};
+use crate::services::csv_service::open_input;
+use flate2::read::DeflateDecoder;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::{self, Cursor, Read};
+
+/// The first bytes of every Avro object container file.
+const AVRO_MAGIC: [u8; 4] = *b"Obj\x01";
+
+/// The length of the sync marker that ends every data block.
+const SYNC_LEN: usize = 16;
+
+/// The block compression codecs the reader supports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Codec {
+    /// Uncompressed blocks.
+    Null,
+    /// Raw (RFC 1951) deflate blocks.
+    Deflate,
+}
+
+/// The Avro types accepted for the transaction row's fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum FieldType {
+    /// `int` or `long` (both zigzag varints on the wire).
+    Long,
+    /// `string`.
+    String,
+    /// An `enum`, decoded to its symbol.
+    Enum(Vec<String>),
+    /// A union of `null` and `string`; `null_index` is the branch of `null`.
+    NullableString { null_index: i64 },
+}
+
+/// The transaction row fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Field {
+    Type,
+    Client,
+    Tx,
+    Amount,
+}
+
+/// A decoded field value.
+enum Datum {
+    Null,
+    Long(i64),
+    Str(String),
+}
+
+/// Reads transaction rows from an Avro object container file.
+///
+/// The writer schema must be a record with exactly the fields of the CSV row:
+/// `type` (`string` or an `enum` of the transaction types), `client` and `tx`
+/// (`int` or `long`), and `amount` (`string`, or a union of `null` and `string`
+/// for dispute rows), in any order. Blocks may be uncompressed or deflated.
+///
+/// Rows are numbered from 1 in file order, in place of CSV line numbers. A row
+/// whose values do not fit the engine's types (an unknown transaction type, an
+/// out-of-range client id, ...) is yielded as a row error; a corrupt or
+/// truncated file is an outer error that aborts processing.
+pub struct AvroRows<R: Read> {
+    /// The container file, positioned after the header or the last block read.
+    input: R,
+    /// The row fields, in writer schema order, with their types.
+    fields: Vec<(Field, FieldType)>,
+    /// The block compression codec.
+    codec: Codec,
+    /// The file's sync marker.
+    sync: [u8; SYNC_LEN],
+    /// The (decompressed) current block.
+    block: Cursor<Vec<u8>>,
+    /// The number of rows left in the current block.
+    remaining: u64,
+    /// The number of the last row read.
+    row: u64,
+    /// Set once the file is exhausted or an outer error was returned.
+    done: bool,
+}
+
+/// Opens an Avro container file of transaction rows.
+///
+/// # Arguments
+/// * `path` - The file path, or [`STDIN_PATH`](crate::consts::STDIN_PATH) (`-`)
+///   to read stdin. A gzip- or zstd-compressed file is decompressed first.
+///
+/// # Returns
+/// * `AppResult<AvroRows<Box<dyn Read>>>` - The row reader, or an `AppErrors`
+///   variant if the file cannot be opened or its header or schema is invalid.
+pub fn open_avro(path: &str) -> AppResult<AvroRows<Box<dyn Read>>> {
+    AvroRows::new(open_input(path)?).map_err(|e| match e {
+        AppErrors::Decode(msg) => AppErrors::Decode(format!("{path}: {msg}")),
+        e => e,
+    })
+}
+
+impl<R: Read> AvroRows<R> {
+    /// Reads the container header and checks the writer schema.
+    ///
+    /// # Arguments
+    /// * `input` - The container file.
+    ///
+    /// # Returns
+    /// * `AppResult<Self>` - The row reader, or an `AppErrors::Decode` if the header
+    ///   is malformed, the codec is unsupported, or the schema does not match the
+    ///   transaction row.
+    pub fn new(mut input: R) -> AppResult<Self> {
+        let mut magic = [0; 4];
+        input.read_exact(&mut magic).map_err(decode_err)?;
+        if magic != AVRO_MAGIC {
+            return Err(AppErrors::Decode("not an Avro container file".into()));
+        }
+        let meta = read_metadata(&mut input)?;
+        let mut sync = [0; SYNC_LEN];
+        input.read_exact(&mut sync).map_err(decode_err)?;
+
+        let codec = match meta.get("avro.codec").map(Vec::as_slice) {
+            None | Some(b"null") => Codec::Null,
+            Some(b"deflate") => Codec::Deflate,
+            Some(other) => {
+                return Err(AppErrors::Decode(format!(
+                    "unsupported codec {}",
+                    String::from_utf8_lossy(other)
+                )));
+            }
+        };
+        let schema = meta
+            .get("avro.schema")
+            .ok_or_else(|| AppErrors::Decode("missing avro.schema".into()))?;
+        let schema: Value = serde_json::from_slice(schema)
+            .map_err(|e| AppErrors::Decode(format!("invalid avro.schema: {e}")))?;
+
+        Ok(Self {
+            input,
+            fields: row_fields(&schema)?,
+            codec,
+            sync,
+            block: Cursor::new(Vec::new()),
+            remaining: 0,
+            row: 0,
+            done: false,
+        })
+    }
+
+    /// Loads the next data block, or returns `false` at the end of the file.
+    fn next_block(&mut self) -> AppResult<bool> {
+        let mut first = [0; 1];
+        if self.input.read(&mut first).map_err(decode_err)? == 0 {
+            return Ok(false);
+        }
+        let count = read_long(&mut first.chain(&mut self.input)).map_err(decode_err)?;
+        let size = read_long(&mut self.input).map_err(decode_err)?;
+        if count < 0 || size < 0 {
+            return Err(AppErrors::Decode("negative block count or size".into()));
+        }
+        let mut raw = Vec::new();
+        (&mut self.input)
+            .take(size as u64)
+            .read_to_end(&mut raw)
+            .map_err(decode_err)?;
+        if raw.len() as u64 != size as u64 {
+            return Err(AppErrors::Decode("truncated block".into()));
+        }
+        let mut sync = [0; SYNC_LEN];
+        self.input.read_exact(&mut sync).map_err(decode_err)?;
+        if sync != self.sync {
+            return Err(AppErrors::Decode("sync marker mismatch".into()));
+        }
+
+        let data = match self.codec {
+            Codec::Null => raw,
+            Codec::Deflate => {
+                let mut data = Vec::new();
+                DeflateDecoder::new(raw.as_slice())
+                    .read_to_end(&mut data)
+                    .map_err(decode_err)?;
+                data
+            }
+        };
+        self.block = Cursor::new(data);
+        self.remaining = count as u64;
+        Ok(true)
+    }
+
+    /// Decodes the next row of the current block.
+    ///
+    /// # Returns
+    /// * `AppResult<AppResult<InputRow>>` - The row or its row error, or an outer
+    ///   `AppErrors::Decode` if the block is corrupt.
+    fn read_row(&mut self) -> AppResult<AppResult<InputRow>> {
+        let mut values = HashMap::with_capacity(self.fields.len());
+        for (field, ty) in &self.fields {
+            values.insert(*field, read_datum(&mut self.block, ty).map_err(decode_err)?);
+        }
+        Ok(to_input_row(values))
+    }
+}
+
+impl<R: Read> Iterator for AvroRows<R> {
+    type Item = AppResult<(u64, AppResult<InputRow>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let step = (|| {
+            while self.remaining == 0 {
+                if !self.next_block()? {
+                    return Ok(None);
+                }
+            }
+            self.remaining -= 1;
+            self.row += 1;
+            Ok(Some((self.row, self.read_row()?)))
+        })();
+        match step {
+            Ok(Some(row)) => Some(Ok(row)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// Maps a read error to a decode error.
+fn decode_err(e: io::Error) -> AppErrors {
+    AppErrors::Decode(format!("avro: {e}"))
+}
+
+/// Reads a zigzag-encoded variable-length `long`.
+fn read_long(input: &mut impl Read) -> io::Result<i64> {
+    let mut value: u64 = 0;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0; 1];
+        input.read_exact(&mut byte)?;
+        value |= u64::from(byte[0] & 0x7f) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok((value >> 1) as i64 ^ -((value & 1) as i64));
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "varint too long",
+    ))
+}
+
+/// Reads length-prefixed `bytes`.
+fn read_bytes(input: &mut impl Read) -> io::Result<Vec<u8>> {
+    let len = read_long(input)?;
+    if len < 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "negative length",
+        ));
+    }
+    let mut buf = Vec::new();
+    input.take(len as u64).read_to_end(&mut buf)?;
+    if buf.len() as u64 != len as u64 {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(buf)
+}
+
+/// Reads the header's metadata map (`map<bytes>`).
+fn read_metadata(input: &mut impl Read) -> AppResult<HashMap<String, Vec<u8>>> {
+    let mut meta = HashMap::new();
+    loop {
+        let mut count = read_long(input).map_err(decode_err)?;
+        if count == 0 {
+            return Ok(meta);
+        }
+        if count < 0 {
+            // A negative count is followed by the block's size in bytes.
+            count = -count;
+            read_long(input).map_err(decode_err)?;
+        }
+        for _ in 0..count {
+            let key = read_bytes(input).map_err(decode_err)?;
+            let value = read_bytes(input).map_err(decode_err)?;
+            meta.insert(String::from_utf8_lossy(&key).into_owned(), value);
+        }
+    }
+}
+
+/// Reads one field value of the given type.
+fn read_datum(input: &mut impl Read, ty: &FieldType) -> io::Result<Datum> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+    Ok(match ty {
+        FieldType::Long => Datum::Long(read_long(input)?),
+        FieldType::String => {
+            Datum::Str(String::from_utf8(read_bytes(input)?).map_err(|_| invalid("invalid UTF-8"))?)
+        }
+        FieldType::Enum(symbols) => {
+            let index = read_long(input)?;
+            let symbol = usize::try_from(index)
+                .ok()
+                .and_then(|i| symbols.get(i))
+                .ok_or_else(|| invalid("enum index out of range"))?;
+            Datum::Str(symbol.clone())
+        }
+        FieldType::NullableString { null_index } => match read_long(input)? {
+            i if i == *null_index => Datum::Null,
+            i if i == 1 - null_index => read_datum(input, &FieldType::String)?,
+            _ => return Err(invalid("union branch out of range")),
+        },
+    })
+}
+
+/// Converts a decoded record into a transaction row.
+fn to_input_row(mut values: HashMap<Field, Datum>) -> AppResult<InputRow> {
+    let mut take = |field| values.remove(&field).unwrap_or(Datum::Null);
+    let t = match take(Field::Type) {
+        Datum::Str(s) => s.parse::<CsvTxType>()?,
+        _ => return Err(AppErrors::InvalidInput("missing transaction type")),
+    };
+    let client = match take(Field::Client) {
+        Datum::Long(n) => n.try_into().ok(),
+        _ => None,
+    }
+    .ok_or(AppErrors::InvalidInput("client id out of range"))?;
+    let tx = match take(Field::Tx) {
+        Datum::Long(n) => n.try_into().ok(),
+        _ => None,
+    }
+    .ok_or(AppErrors::InvalidInput("tx id out of range"))?;
+    let amount = match take(Field::Amount) {
+        Datum::Str(s) => Some(s),
+        _ => None,
+    };
+    Ok(InputRow {
+        t,
+        client,
+        tx,
+        amount,
+    })
+}
+
+/// Checks that the writer schema is a record of exactly the transaction row's
+/// fields, and returns them in writer order with their types.
+fn row_fields(schema: &Value) -> AppResult<Vec<(Field, FieldType)>> {
+    let mismatch = |msg: &str| AppErrors::Decode(format!("schema does not match the row: {msg}"));
+    if schema["type"] != "record" {
+        return Err(mismatch("not a record"));
+    }
+    let fields = schema["fields"]
+        .as_array()
+        .ok_or_else(|| mismatch("no fields"))?;
+    let mut row = Vec::with_capacity(fields.len());
+    for f in fields {
+        let name = f["name"].as_str().unwrap_or_default();
+        let Some(ty) = field_type(&f["type"]) else {
+            return Err(mismatch(&format!("unsupported type for `{name}`")));
+        };
+        let field = match (name, &ty) {
+            ("type", FieldType::String | FieldType::Enum(_)) => Field::Type,
+            ("client", FieldType::Long) => Field::Client,
+            ("tx", FieldType::Long) => Field::Tx,
+            ("amount", FieldType::String | FieldType::NullableString { .. }) => Field::Amount,
+            _ => return Err(mismatch(&format!("unexpected field `{name}`"))),
+        };
+        if row.iter().any(|(f, _)| *f == field) {
+            return Err(mismatch(&format!("duplicate field `{name}`")));
+        }
+        row.push((field, ty));
+    }
+    if row.len() != 4 {
+        return Err(mismatch("expected fields type, client, tx and amount"));
+    }
+    Ok(row)
+}
+
+/// Maps a field's Avro type to a supported [`FieldType`], if it is one.
+fn field_type(ty: &Value) -> Option<FieldType> {
+    match ty {
+        Value::String(name) => match name.as_str() {
+            "int" | "long" => Some(FieldType::Long),
+            "string" => Some(FieldType::String),
+            _ => None,
+        },
+        Value::Object(obj) if obj.get("type").is_some_and(|t| t == "enum") => {
+            let symbols = obj.get("symbols")?.as_array()?;
+            symbols
+                .iter()
+                .map(|s| s.as_str().map(str::to_string))
+                .collect::<Option<_>>()
+                .map(FieldType::Enum)
+        }
+        Value::Object(obj) if obj.get("logicalType").is_none() => field_type(obj.get("type")?),
+        Value::Array(branches) if branches.len() == 2 => {
+            let null_index = branches.iter().position(|b| b == "null")? as i64;
+            (field_type(&branches[1 - null_index as usize]) == Some(FieldType::String))
+                .then_some(FieldType::NullableString { null_index })
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::Compression;
+    use flate2::write::DeflateEncoder;
+    use std::io::Write;
+
+    const SCHEMA: &str = r#"{"type":"record","name":"Tx","fields":[
+        {"name":"type","type":{"type":"enum","name":"Kind",
+            "symbols":["deposit","withdrawal","dispute","resolve","chargeback"]}},
+        {"name":"client","type":"int"},
+        {"name":"tx","type":"long"},
+        {"name":"amount","type":["null","string"]}]}"#;
+
+    fn long(out: &mut Vec<u8>, n: i64) {
+        let mut z = ((n << 1) ^ (n >> 63)) as u64;
+        while z >= 0x80 {
+            out.push(z as u8 | 0x80);
+            z >>= 7;
+        }
+        out.push(z as u8);
+    }
+
+    fn bytes(out: &mut Vec<u8>, b: &[u8]) {
+        long(out, b.len() as i64);
+        out.extend_from_slice(b);
+    }
+
+    /// A row as `(type symbol index, client, tx, amount)`.
+    type Row<'a> = (i64, i64, i64, Option<&'a str>);
+
+    /// Encodes a container with one deflated block per slice of rows.
+    fn container(blocks: &[&[Row]]) -> Vec<u8> {
+        let sync = [7u8; SYNC_LEN];
+        let mut out = AVRO_MAGIC.to_vec();
+        long(&mut out, 2);
+        bytes(&mut out, b"avro.schema");
+        bytes(&mut out, SCHEMA.as_bytes());
+        bytes(&mut out, b"avro.codec");
+        bytes(&mut out, b"deflate");
+        long(&mut out, 0);
+        out.extend_from_slice(&sync);
+        for rows in blocks {
+            let mut data = Vec::new();
+            for &(kind, client, tx, amount) in *rows {
+                long(&mut data, kind);
+                long(&mut data, client);
+                long(&mut data, tx);
+                match amount {
+                    None => long(&mut data, 0),
+                    Some(a) => {
+                        long(&mut data, 1);
+                        bytes(&mut data, a.as_bytes());
+                    }
+                }
+            }
+            let mut enc = DeflateEncoder::new(Vec::new(), Compression::default());
+            enc.write_all(&data).unwrap();
+            let data = enc.finish().unwrap();
+            long(&mut out, rows.len() as i64);
+            bytes(&mut out, &data);
+            out.extend_from_slice(&sync);
+        }
+        out
+    }
+
+    #[test]
+    fn reads_rows_across_deflated_blocks() {
+        let file = container(&[
+            &[(0, 1, 1, Some("2.5")), (0, 70_000, 2, Some("1.0"))],
+            &[(2, 1, 1, None)],
+        ]);
+
+        let rows: Vec<_> = AvroRows::new(file.as_slice())
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+
+        assert_eq!(rows.len(), 3);
+        let (line, first) = &rows[0];
+        let first = first.as_ref().unwrap();
+        assert_eq!(*line, 1);
+        assert_eq!(first.t, CsvTxType::Deposit);
+        assert_eq!(first.amount.as_deref(), Some("2.5"));
+        assert!(rows[1].1.is_err());
+        let dispute = rows[2].1.as_ref().unwrap();
+        assert_eq!(
+            (dispute.t, dispute.tx, dispute.amount.is_none()),
+            (CsvTxType::Dispute, 1, true)
+        );
+
+        let mut truncated = file.clone();
+        truncated.truncate(file.len() - 3);
+        let last = AvroRows::new(truncated.as_slice()).unwrap().last().unwrap();
+        assert!(matches!(last, Err(AppErrors::Decode(_))));
+    }
+}
@@ -0,0 +1,286 @@
+use crate::errors::{AppErrors, AppResult};
+use crate::services::state_store::{StoreSpec, state_digest};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// File extension used for backup snapshots inside a backup directory.
+const BACKUP_EXT: &str = "snap";
+
+/// Seconds in one day.
+const SECS_PER_DAY: u64 = 86_400;
+
+/// A backup found in a backup directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupInfo {
+    /// The backup id: its UTC creation time as `YYYYMMDDTHHMMSSZ`.
+    pub id: String,
+    /// The creation time in seconds since the Unix epoch.
+    pub created: u64,
+    /// The snapshot file holding the backup.
+    pub path: PathBuf,
+}
+
+/// How many backups to keep when pruning a backup directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Keep the newest backup of each of the last `daily` days that have one.
+    pub daily: usize,
+    /// Keep the newest backup of each of the last `weekly` weeks that have one.
+    pub weekly: usize,
+}
+
+/// Creates a backup of the given state in `dir`, then prunes old backups
+/// according to `retention`.
+///
+/// The state is loaded (and therefore integrity-checked) before it is copied,
+/// and the written backup is re-read and compared by state hash.
+///
+/// # Arguments
+/// * `state` - The backend holding the state to back up.
+/// * `dir` - The backup directory (created if missing).
+/// * `now` - The creation time in seconds since the Unix epoch.
+/// * `retention` - Which backups to keep after this one is written.
+///
+/// # Returns
+/// * `AppResult<(BackupInfo, Vec<BackupInfo>)>` - The new backup and the backups
+///   that were pruned, or an `AppErrors` variant on failure.
+pub fn create_backup(
+    state: &StoreSpec,
+    dir: &Path,
+    now: u64,
+    retention: RetentionPolicy,
+) -> AppResult<(BackupInfo, Vec<BackupInfo>)> {
+    fs::create_dir_all(dir).map_err(|e| AppErrors::Io(format!("create {}: {e}", dir.display())))?;
+    let engine = state.load()?;
+
+    let id = backup_id(now);
+    let path = dir.join(format!("{id}.{BACKUP_EXT}"));
+    let target = StoreSpec::Snapshot(path.display().to_string());
+    target.save(&engine)?;
+    if state_digest(&target.load()?) != state_digest(&engine) {
+        return Err(AppErrors::Internal(format!(
+            "backup {id} failed verification"
+        )));
+    }
+
+    let pruned = prune_backups(dir, retention)?;
+    Ok((
+        BackupInfo {
+            id,
+            created: now,
+            path,
+        },
+        pruned,
+    ))
+}
+
+/// Restores the backup with the given id from `dir` into the target backend.
+///
+/// # Arguments
+/// * `dir` - The backup directory.
+/// * `id` - The backup id to restore.
+/// * `to` - The backend to restore into.
+///
+/// # Returns
+/// * `AppResult<u64>` - The state hash of the restored state, or an `AppErrors`
+///   variant if the backup is missing, corrupt, or cannot be written.
+pub fn restore_backup(dir: &Path, id: &str, to: &StoreSpec) -> AppResult<u64> {
+    let backup = list_backups(dir)?
+        .into_iter()
+        .find(|b| b.id == id)
+        .ok_or_else(|| AppErrors::Io(format!("backup {id} not found in {}", dir.display())))?;
+    let engine = StoreSpec::Snapshot(backup.path.display().to_string()).load()?;
+    to.save(&engine)?;
+    Ok(state_digest(&engine))
+}
+
+/// Checks the integrity of every backup in `dir` by loading it.
+///
+/// # Returns
+/// * `AppResult<Vec<(BackupInfo, AppResult<u64>)>>` - Each backup with its state hash,
+///   or the error that made it unreadable.
+pub fn verify_backups(dir: &Path) -> AppResult<Vec<(BackupInfo, AppResult<u64>)>> {
+    Ok(list_backups(dir)?
+        .into_iter()
+        .map(|b| {
+            let res = StoreSpec::Snapshot(b.path.display().to_string())
+                .load()
+                .map(|engine| state_digest(&engine));
+            (b, res)
+        })
+        .collect())
+}
+
+/// Lists the backups in `dir`, oldest first. Files that are not backups are ignored.
+pub fn list_backups(dir: &Path) -> AppResult<Vec<BackupInfo>> {
+    let entries =
+        fs::read_dir(dir).map_err(|e| AppErrors::Io(format!("read {}: {e}", dir.display())))?;
+    let mut out = Vec::new();
+    for entry in entries {
+        let path = entry
+            .map_err(|e| AppErrors::Io(format!("read {}: {e}", dir.display())))?
+            .path();
+        if path.extension().and_then(|e| e.to_str()) != Some(BACKUP_EXT) {
+            continue;
+        }
+        let Some(id) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        if let Some(created) = parse_backup_id(id) {
+            out.push(BackupInfo {
+                id: id.to_string(),
+                created,
+                path,
+            });
+        }
+    }
+    out.sort_by_key(|b| b.created);
+    Ok(out)
+}
+
+/// Deletes backups not selected by the retention policy.
+fn prune_backups(dir: &Path, retention: RetentionPolicy) -> AppResult<Vec<BackupInfo>> {
+    let backups = list_backups(dir)?;
+    let keep = select_retained(&backups, retention);
+    let mut pruned = Vec::new();
+    for b in backups {
+        if !keep.contains(&b.id) {
+            fs::remove_file(&b.path)
+                .map_err(|e| AppErrors::Io(format!("remove {}: {e}", b.path.display())))?;
+            pruned.push(b);
+        }
+    }
+    Ok(pruned)
+}
+
+/// Returns the ids of the backups to keep: the newest backup of each of the
+/// last `daily` days and of each of the last `weekly` weeks.
+fn select_retained(backups: &[BackupInfo], retention: RetentionPolicy) -> HashSet<String> {
+    let mut keep = HashSet::new();
+    for (period_secs, count) in [
+        (SECS_PER_DAY, retention.daily),
+        (7 * SECS_PER_DAY, retention.weekly),
+    ] {
+        let mut periods = HashSet::new();
+        for b in backups.iter().rev() {
+            if periods.len() == count {
+                break;
+            }
+            if periods.insert(b.created / period_secs) {
+                keep.insert(b.id.clone());
+            }
+        }
+    }
+    keep
+}
+
+/// Formats a Unix timestamp as a backup id, `YYYYMMDDTHHMMSSZ` in UTC.
+pub fn backup_id(secs: u64) -> String {
+    let (y, m, d) = civil_from_days((secs / SECS_PER_DAY) as i64);
+    let rem = secs % SECS_PER_DAY;
+    format!(
+        "{y:04}{m:02}{d:02}T{:02}{:02}{:02}Z",
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60
+    )
+}
+
+/// Formats a Unix timestamp as a UTC calendar date, `YYYY-MM-DD`.
+pub fn utc_date(secs: u64) -> String {
+    let (y, m, d) = civil_from_days((secs / SECS_PER_DAY) as i64);
+    format!("{y:04}-{m:02}-{d:02}")
+}
+
+/// Formats a Unix timestamp as a UTC ISO 8601 date-time, `YYYY-MM-DDTHH:MM:SSZ`.
+pub fn utc_datetime(secs: u64) -> String {
+    let rem = secs % SECS_PER_DAY;
+    format!(
+        "{}T{:02}:{:02}:{:02}Z",
+        utc_date(secs),
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60
+    )
+}
+
+/// Parses a backup id back into a Unix timestamp.
+fn parse_backup_id(id: &str) -> Option<u64> {
+    let b = id.as_bytes();
+    if b.len() != 16 || b[8] != b'T' || b[15] != b'Z' {
+        return None;
+    }
+    let num = |r: std::ops::Range<usize>| id.get(r)?.parse::<u64>().ok();
+    let days = days_from_civil(num(0..4)? as i64, num(4..6)?, num(6..8)?);
+    let secs = u64::try_from(days).ok()? * SECS_PER_DAY;
+    Some(secs + num(9..11)? * 3_600 + num(11..13)? * 60 + num(13..15)?)
+}
+
+/// Converts days since 1970-01-01 to a `(year, month, day)` civil date.
+fn civil_from_days(z: i64) -> (i64, u64, u64) {
+    let z = z + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097) as u64;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe as i64 + era * 400 + i64::from(m <= 2);
+    (y, m, d)
+}
+
+/// Converts a civil date to days since 1970-01-01.
+fn days_from_civil(y: i64, m: u64, d: u64) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400) as u64;
+    let mp = if m > 2 { m - 3 } else { m + 9 };
+    let doy = (153 * mp + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe as i64 - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(created: u64) -> BackupInfo {
+        BackupInfo {
+            id: backup_id(created),
+            created,
+            path: PathBuf::new(),
+        }
+    }
+
+    #[test]
+    fn backup_id_roundtrips() {
+        let secs = 1_792_203_016; // 2026-10-17T02:10:16Z
+        assert_eq!(backup_id(secs), "20261017T021016Z");
+        assert_eq!(utc_datetime(secs), "2026-10-17T02:10:16Z");
+        assert_eq!(parse_backup_id("20261017T021016Z"), Some(secs));
+        assert_eq!(parse_backup_id("not-a-backup"), None);
+    }
+
+    #[test]
+    fn retention_keeps_newest_per_day_and_week() {
+        // two backups per day for 21 days
+        let backups: Vec<_> = (0..42).map(|i| info(i * SECS_PER_DAY / 2 + 60)).collect();
+        let keep = select_retained(
+            &backups,
+            RetentionPolicy {
+                daily: 3,
+                weekly: 2,
+            },
+        );
+
+        // the last 3 days' newest backups, plus the newest of the previous week
+        // (the current week's newest is already kept as a daily)
+        assert_eq!(keep.len(), 4);
+        assert!(keep.contains(&backups[41].id));
+        assert!(keep.contains(&backups[39].id));
+        assert!(keep.contains(&backups[37].id));
+        assert!(!keep.contains(&backups[40].id));
+    }
+}
@@ -0,0 +1,237 @@
+use crate::errors::{AppErrors, AppResult};
+use crate::models::amount::Amount;
+use crate::models::domain_state::{DisputeState, TxKind};
+use crate::models::identifiers::TxId;
+use crate::services::backup::{backup_id, utc_datetime};
+use crate::state::Engine;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+/// The camt.053 message version written.
+const CAMT053_NAMESPACE: &str = "urn:iso:std:iso:20022:tech:xsd:camt.053.001.02";
+
+/// Writes the engine's accounts and tx history as an ISO 20022 camt.053
+/// (`BkToCstmrStmt`) bank-to-customer statement, so the output can be fed into
+/// standard bank reconciliation tooling.
+///
+/// Each client gets a `Stmt`, sorted by client, identified by the client id:
+/// - Two balances: `CLBD` (closing booked) is the total, `CLAV` (closing
+///   available) the available funds. Negative balances are written as `DBIT`.
+/// - One `Ntry` per tx record, by tx id: deposits are `CRDT`, withdrawals `DBIT`,
+///   with the tx id as `NtryRef` and the type as proprietary bank transaction
+///   code. A disputed deposit carries `AddtlNtryInf` `disputed`; a charged-back
+///   one is followed by a reversing `DBIT` entry (`RvslInd`, code `chargeback`).
+/// - A locked account carries `AddtlStmtInf` `locked`.
+///
+/// The engine keeps current state only, so resolved disputes are plain entries,
+/// and tx records retired by compaction are not listed.
+///
+/// # Arguments
+/// * `out` - The XML sink.
+/// * `engine` - The engine at the end of the run.
+/// * `currency` - The ISO 4217 code the amounts are in, e.g. `EUR`.
+/// * `created_at` - The statement time, in seconds since the Unix epoch.
+///
+/// # Returns
+/// * `AppResult<usize>` - The number of statements written, or
+///   `AppErrors::InvalidInput` for a malformed currency code, or an
+///   `AppErrors::Io` on write failure.
+pub fn write_camt053(
+    mut out: impl Write,
+    engine: &Engine,
+    currency: &str,
+    created_at: u64,
+) -> AppResult<usize> {
+    if currency.len() != 3 || !currency.bytes().all(|b| b.is_ascii_uppercase()) {
+        return Err(AppErrors::InvalidInput(
+            "statement currency must be a three-letter ISO 4217 code",
+        ));
+    }
+    let mut accounts: Vec<_> = engine.accounts_iter().collect();
+    accounts.sort_by_key(|(c, _)| **c);
+    let mut history = engine.tx_history();
+
+    let created = utc_datetime(created_at);
+    let msg_id = backup_id(created_at);
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!("<Document xmlns=\"{CAMT053_NAMESPACE}\">\n"));
+    xml.push_str("  <BkToCstmrStmt>\n");
+    xml.push_str(&format!(
+        "    <GrpHdr><MsgId>PE-{msg_id}</MsgId><CreDtTm>{created}</CreDtTm></GrpHdr>\n"
+    ));
+    for (client, acc) in &accounts {
+        xml.push_str("    <Stmt>\n");
+        xml.push_str(&format!("      <Id>PE-{msg_id}-{client}</Id>\n"));
+        xml.push_str(&format!("      <CreDtTm>{created}</CreDtTm>\n"));
+        xml.push_str(&format!(
+            "      <Acct><Id><Othr><Id>{client}</Id></Othr></Id><Ccy>{currency}</Ccy></Acct>\n"
+        ));
+        for (code, amount) in [("CLBD", acc.total()), ("CLAV", acc.available)] {
+            xml.push_str(&format!(
+                "      <Bal><Tp><CdOrPrtry><Cd>{code}</Cd></CdOrPrtry></Tp>{}<Dt><DtTm>{created}</DtTm></Dt></Bal>\n",
+                signed_amount(amount, currency)
+            ));
+        }
+        let txs = history.remove(client).unwrap_or_default();
+        for (tx, rec) in txs {
+            let (ind, code) = match rec.kind {
+                TxKind::Deposit => ("CRDT", "deposit"),
+                TxKind::Withdrawal => ("DBIT", "withdrawal"),
+            };
+            let info = if rec.state == DisputeState::Disputed {
+                "<AddtlNtryInf>disputed</AddtlNtryInf>"
+            } else {
+                ""
+            };
+            xml.push_str(&entry(tx, rec.amount, currency, ind, false, code, info));
+            if rec.state == DisputeState::ChargedBack {
+                xml.push_str(&entry(
+                    tx,
+                    rec.amount,
+                    currency,
+                    "DBIT",
+                    true,
+                    "chargeback",
+                    "",
+                ));
+            }
+        }
+        if acc.locked {
+            xml.push_str("      <AddtlStmtInf>locked</AddtlStmtInf>\n");
+        }
+        xml.push_str("    </Stmt>\n");
+    }
+    xml.push_str("  </BkToCstmrStmt>\n</Document>\n");
+    out.write_all(xml.as_bytes())
+        .and_then(|_| out.flush())
+        .map_err(|e| AppErrors::Io(format!("write statement: {e}")))?;
+    Ok(accounts.len())
+}
+
+/// Writes the camt.053 statement to a file (see [`write_camt053`]).
+pub fn write_camt053_file(
+    path: &str,
+    engine: &Engine,
+    currency: &str,
+    created_at: u64,
+) -> AppResult<usize> {
+    let file = File::create(path).map_err(|e| AppErrors::Io(format!("create {path}: {e}")))?;
+    write_camt053(BufWriter::new(file), engine, currency, created_at)
+}
+
+/// Formats an amount and its credit/debit indicator; camt amounts are unsigned.
+fn signed_amount(amount: Amount, currency: &str) -> String {
+    let (abs, ind) = if amount.is_negative() {
+        (Amount(-amount.0), "DBIT")
+    } else {
+        (amount, "CRDT")
+    };
+    format!("<Amt Ccy=\"{currency}\">{abs}</Amt><CdtDbtInd>{ind}</CdtDbtInd>")
+}
+
+/// Formats a booked statement entry.
+fn entry(
+    tx: TxId,
+    amount: Amount,
+    currency: &str,
+    ind: &str,
+    reversal: bool,
+    code: &str,
+    info: &str,
+) -> String {
+    let reversal = if reversal {
+        "<RvslInd>true</RvslInd>"
+    } else {
+        ""
+    };
+    format!(
+        "      <Ntry><NtryRef>{tx}</NtryRef><Amt Ccy=\"{currency}\">{amount}</Amt><CdtDbtInd>{ind}</CdtDbtInd>{reversal}<Sts>BOOK</Sts><BkTxCd><Prtry><Cd>{code}</Cd></Prtry></BkTxCd>{info}</Ntry>\n"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::domain_state::TxRecord;
+
+    #[test]
+    fn renders_balances_entries_and_reversals() {
+        let mut engine = Engine::default();
+        engine.acct_mut(1).available = Amount(15_000);
+        engine.acct_mut(1).locked = true;
+        for (tx, kind, state) in [
+            (2, TxKind::Withdrawal, DisputeState::Normal),
+            (1, TxKind::Deposit, DisputeState::ChargedBack),
+        ] {
+            engine.txs.insert(
+                tx,
+                TxRecord {
+                    client: 1,
+                    kind,
+                    amount: Amount(5_000),
+                    state,
+                },
+            );
+        }
+        let mut out = Vec::new();
+
+        let written = write_camt053(&mut out, &engine, "EUR", 1_792_203_016).unwrap();
+
+        let xml = String::from_utf8(out).unwrap();
+        assert_eq!(written, 1);
+        assert!(xml.contains("<CreDtTm>2026-10-17T02:10:16Z</CreDtTm>"));
+        assert!(xml.contains(
+            "<Cd>CLAV</Cd></CdOrPrtry></Tp><Amt Ccy=\"EUR\">1.5000</Amt><CdtDbtInd>CRDT</CdtDbtInd>"
+        ));
+        let deposit = xml.find("<NtryRef>1</NtryRef>").unwrap();
+        let withdrawal = xml.find("<NtryRef>2</NtryRef>").unwrap();
+        assert!(deposit < withdrawal, "entries are ordered by tx id");
+        assert!(
+            xml.contains("<RvslInd>true</RvslInd><Sts>BOOK</Sts><BkTxCd><Prtry><Cd>chargeback")
+        );
+        assert!(xml.contains("<AddtlStmtInf>locked</AddtlStmtInf>"));
+        assert!(write_camt053(Vec::new(), &engine, "eur", 0).is_err());
+    }
+
+    #[test]
+    fn empty_engine_writes_a_statement_free_document() {
+        let mut out = Vec::new();
+
+        let written = write_camt053(&mut out, &Engine::default(), "USD", 0).unwrap();
+
+        let xml = String::from_utf8(out).unwrap();
+        assert_eq!(written, 0);
+        assert!(!xml.contains("<Stmt>"));
+        assert!(xml.ends_with("</BkToCstmrStmt>\n</Document>\n"));
+    }
+
+    #[test]
+    fn malformed_currency_writes_nothing() {
+        let mut engine = Engine::default();
+        engine.acct_mut(1).available = Amount(1);
+        for currency in ["", "EU", "EURO", "E1R"] {
+            let mut out = Vec::new();
+            assert!(write_camt053(&mut out, &engine, currency, 0).is_err());
+            assert!(out.is_empty());
+        }
+    }
+
+    #[test]
+    fn negative_balances_are_debits() {
+        let mut engine = Engine::default();
+        engine.acct_mut(1).available = Amount(-25_000);
+        engine.acct_mut(1).held = Amount(5_000);
+        let mut out = Vec::new();
+
+        write_camt053(&mut out, &engine, "EUR", 0).unwrap();
+
+        let xml = String::from_utf8(out).unwrap();
+        assert!(xml.contains(
+            "<Cd>CLBD</Cd></CdOrPrtry></Tp><Amt Ccy=\"EUR\">2.0000</Amt><CdtDbtInd>DBIT</CdtDbtInd>"
+        ));
+        assert!(xml.contains(
+            "<Cd>CLAV</Cd></CdOrPrtry></Tp><Amt Ccy=\"EUR\">2.5000</Amt><CdtDbtInd>DBIT</CdtDbtInd>"
+        ));
+    }
+}
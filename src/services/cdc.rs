@@ -0,0 +1,232 @@
+use crate::errors::{AppErrors, AppResult};
+use crate::models::csv_models::transaction::InputRow;
+use crate::models::domain_state::Account;
+use crate::models::identifiers::{ClientId, TxId};
+use crate::state::Engine;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+/// Whether a change event creates or modifies an account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CdcOp {
+    /// The account did not exist before the change.
+    Insert,
+    /// The account existed and its image changed.
+    Update,
+}
+
+/// An account image in a change event. Amounts are formatted with four decimals.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AccountImage {
+    /// Funds available for withdrawal.
+    pub available: String,
+    /// Funds held by open disputes.
+    pub held: String,
+    /// Available plus held funds.
+    pub total: String,
+    /// Whether the account is locked.
+    pub locked: bool,
+    /// The account version (see [`Engine::version`]).
+    pub version: u64,
+}
+
+impl From<&Account> for AccountImage {
+    fn from(acc: &Account) -> Self {
+        Self {
+            available: acc.available.to_string(),
+            held: acc.held.to_string(),
+            total: acc.total().to_string(),
+            locked: acc.locked,
+            version: acc.version,
+        }
+    }
+}
+
+/// One account change: a line of the change data capture stream.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CdcEvent {
+    /// 1-based position of the event in the stream.
+    pub seq: u64,
+    /// Whether the account was created or modified.
+    pub op: CdcOp,
+    /// The changed account.
+    pub client: ClientId,
+    /// The transaction that caused the change.
+    pub tx: TxId,
+    /// The account before the change; `None` for inserts.
+    pub before: Option<AccountImage>,
+    /// The account after the change.
+    pub after: AccountImage,
+}
+
+/// Writes a change data capture stream: one JSON event per line for every
+/// account an applied row changed, with its before and after images and the
+/// causing tx id, so a warehouse can consume incremental changes instead of
+/// full dumps.
+///
+/// Each account's last image is kept and diffed against its state after every
+/// row; rows the engine ignored leave the account unchanged and emit nothing.
+/// Images start from the state the run loaded (e.g. `--load-state`), so the
+/// first change to a carried-over account is an update, not an insert.
+pub struct CdcStream<W: Write> {
+    /// The event sink.
+    out: W,
+    /// The last emitted image of every account.
+    images: HashMap<ClientId, Account>,
+    /// The sequence number of the last event written.
+    seq: u64,
+}
+
+impl CdcStream<BufWriter<File>> {
+    /// Creates a change stream file, replacing any existing one.
+    ///
+    /// # Arguments
+    /// * `path` - The stream path.
+    /// * `baseline` - The engine as loaded, before any input is applied.
+    ///
+    /// # Returns
+    /// * `AppResult<Self>` - The stream, or an `AppErrors::Io` if the file cannot
+    ///   be created.
+    pub fn create(path: &str, baseline: &Engine) -> AppResult<Self> {
+        let file = File::create(path).map_err(|e| AppErrors::Io(format!("create {path}: {e}")))?;
+        Ok(Self::new(BufWriter::new(file), baseline))
+    }
+}
+
+impl<W: Write> CdcStream<W> {
+    /// Creates a change stream over any writer.
+    ///
+    /// # Arguments
+    /// * `out` - The event sink.
+    /// * `baseline` - The engine as loaded, before any input is applied.
+    pub fn new(out: W, baseline: &Engine) -> Self {
+        Self {
+            out,
+            images: baseline
+                .accounts_iter()
+                .map(|(c, a)| (*c, a.clone()))
+                .collect(),
+            seq: 0,
+        }
+    }
+
+    /// Emits the change an applied row made to its client's account, if any.
+    ///
+    /// # Arguments
+    /// * `row` - The applied row.
+    /// * `engine` - The engine after applying it.
+    ///
+    /// # Returns
+    /// * `AppResult<()>` - `Ok(())` once the event (if any) is written, or an
+    ///   `AppErrors::Io` if writing failed.
+    pub fn observe(&mut self, row: &InputRow, engine: &Engine) -> AppResult<()> {
+        let Some(acc) = engine.acct(row.client) else {
+            return Ok(());
+        };
+        let before = self.images.get(&row.client);
+        // Every applied command bumps the version; an ignored row on an existing
+        // account leaves it alone.
+        if before.is_some_and(|b| b.version == acc.version) {
+            return Ok(());
+        }
+        self.seq += 1;
+        let event = CdcEvent {
+            seq: self.seq,
+            op: if before.is_some() {
+                CdcOp::Update
+            } else {
+                CdcOp::Insert
+            },
+            client: row.client,
+            tx: row.tx,
+            before: before.map(AccountImage::from),
+            after: AccountImage::from(acc),
+        };
+        serde_json::to_writer(&mut self.out, &event)
+            .map_err(|e| AppErrors::Io(format!("cdc: {e}")))?;
+        self.out
+            .write_all(b"\n")
+            .map_err(|e| AppErrors::Io(format!("cdc: {e}")))?;
+        self.images.insert(row.client, acc.clone());
+        Ok(())
+    }
+
+    /// Flushes the stream.
+    ///
+    /// # Returns
+    /// * `AppResult<u64>` - The number of events written, or an `AppErrors::Io`
+    ///   if the final flush failed.
+    pub fn finish(mut self) -> AppResult<u64> {
+        self.out
+            .flush()
+            .map_err(|e| AppErrors::Io(format!("cdc: {e}")))?;
+        Ok(self.seq)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::amount::Amount;
+    use crate::models::csv_models::transaction::CsvTxType;
+    use crate::models::tx_command::{DepositCommand, WithdrawalCommand};
+
+    fn row(t: CsvTxType, client: ClientId, tx: TxId) -> InputRow {
+        InputRow {
+            t,
+            client,
+            tx,
+            amount: None,
+        }
+    }
+
+    #[test]
+    fn emits_inserts_and_updates_against_the_loaded_state() {
+        let mut engine = Engine::default();
+        engine.acct_mut(1).available = Amount(50_000);
+        let mut out = Vec::new();
+        let mut cdc = CdcStream::new(&mut out, &engine);
+
+        let deposit = DepositCommand {
+            client: 1,
+            tx: 1,
+            amount: Amount(10_000),
+        };
+        engine.apply(deposit.into()).unwrap();
+        cdc.observe(&row(CsvTxType::Deposit, 1, 1), &engine)
+            .unwrap();
+        let overdraw = WithdrawalCommand {
+            client: 1,
+            tx: 2,
+            amount: Amount(90_000),
+        };
+        engine.apply(overdraw.into()).unwrap();
+        cdc.observe(&row(CsvTxType::Withdrawal, 1, 2), &engine)
+            .unwrap();
+        let deposit = DepositCommand {
+            client: 2,
+            tx: 3,
+            amount: Amount(5_000),
+        };
+        engine.apply(deposit.into()).unwrap();
+        cdc.observe(&row(CsvTxType::Deposit, 2, 3), &engine)
+            .unwrap();
+        assert_eq!(cdc.finish().unwrap(), 2);
+
+        let events: Vec<serde_json::Value> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(events[0]["op"], "update");
+        assert_eq!(events[0]["tx"], 1);
+        assert_eq!(events[0]["before"]["available"], "5.0000");
+        assert_eq!(events[0]["after"]["available"], "6.0000");
+        assert_eq!(events[1]["op"], "insert");
+        assert_eq!(events[1]["seq"], 2);
+        assert!(events[1]["before"].is_null());
+    }
+}
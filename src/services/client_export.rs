@@ -0,0 +1,200 @@
+use crate::models::domain_state::{DisputeState, NoteTarget};
+use crate::models::identifiers::{ClientId, TxId};
+use crate::state::Engine;
+use serde::Serialize;
+
+/// Version of the export document layout, bumped on incompatible changes.
+pub const EXPORT_FORMAT_VERSION: u32 = 1;
+
+/// Everything the engine stores about one client, for data-subject access
+/// requests.
+///
+/// The engine keeps current state only: a transaction's dispute history is
+/// its current dispute state, and no audit log is kept. Tx ids retired by
+/// compaction no longer record their client, so they cannot be attributed and
+/// are not exported.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ClientExport {
+    /// The layout version of this document ([`EXPORT_FORMAT_VERSION`]).
+    pub format_version: u32,
+    /// The client the export is about.
+    pub client: ClientId,
+    /// The client's account, or `None` if the engine has no account for it.
+    pub account: Option<ExportedAccount>,
+    /// The client's transaction records, sorted by tx id.
+    pub transactions: Vec<ExportedTx>,
+    /// The client's transactions that are, or were, disputed, sorted by tx id.
+    pub disputes: Vec<ExportedDispute>,
+    /// Operator annotations on the client's account and transactions, oldest
+    /// first.
+    pub notes: Vec<ExportedNote>,
+}
+
+/// An account in a [`ClientExport`]. Amounts are formatted with four decimals.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ExportedAccount {
+    /// Funds available for withdrawal.
+    pub available: String,
+    /// Funds held by open disputes.
+    pub held: String,
+    /// Available plus held funds.
+    pub total: String,
+    /// Whether the account was locked by a chargeback.
+    pub locked: bool,
+    /// The account version (see [`Engine::version`]).
+    pub version: u64,
+}
+
+/// A transaction record in a [`ClientExport`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ExportedTx {
+    /// The transaction id.
+    pub tx: TxId,
+    /// `deposit` or `withdrawal`.
+    pub kind: &'static str,
+    /// The transaction amount.
+    pub amount: String,
+    /// `normal`, `disputed` or `charged_back`.
+    pub dispute_state: &'static str,
+}
+
+/// A dispute in a [`ClientExport`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ExportedDispute {
+    /// The disputed transaction.
+    pub tx: TxId,
+    /// The disputed amount.
+    pub amount: String,
+    /// `open` while the funds are held, `resolved` once released, or
+    /// `charged_back` once reversed.
+    pub status: &'static str,
+}
+
+/// An operator annotation in a [`ClientExport`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ExportedNote {
+    /// The annotated transaction, or `None` for a note on the account.
+    pub tx: Option<TxId>,
+    /// Who wrote the note.
+    pub author: String,
+    /// When the note was written, in seconds since the Unix epoch.
+    pub at: u64,
+    /// The note itself.
+    pub note: String,
+}
+
+/// Collects everything the engine stores about a client.
+///
+/// Resolved disputes return a transaction to its normal state, so they are
+/// indistinguishable from transactions that were never disputed and only open
+/// and charged-back disputes are listed.
+///
+/// # Arguments
+/// * `engine` - The engine to export from.
+/// * `client` - The client to export.
+///
+/// # Returns
+/// * `ClientExport` - The client's export package; empty (no account, no
+///   transactions) if the engine knows nothing about the client.
+pub fn export_client(engine: &Engine, client: ClientId) -> ClientExport {
+    let account = engine.acct(client).map(|acc| ExportedAccount {
+        available: acc.available.to_string(),
+        held: acc.held.to_string(),
+        total: acc.total().to_string(),
+        locked: acc.locked,
+        version: acc.version,
+    });
+
+    let mut records: Vec<_> = engine
+        .txs
+        .iter()
+        .filter(|(_, rec)| rec.client == client)
+        .collect();
+    records.sort_by_key(|(tx, _)| *tx);
+
+    let transactions = records
+        .iter()
+        .map(|(tx, rec)| ExportedTx {
+            tx: *tx,
+            kind: rec.kind.as_str(),
+            amount: rec.amount.to_string(),
+            dispute_state: rec.state.as_str(),
+        })
+        .collect();
+    let disputes = records
+        .iter()
+        .filter_map(|(tx, rec)| {
+            let status = match rec.state {
+                DisputeState::Normal => return None,
+                DisputeState::Disputed => "open",
+                DisputeState::Resolved => "resolved",
+                DisputeState::ChargedBack => "charged_back",
+            };
+            Some(ExportedDispute {
+                tx: *tx,
+                amount: rec.amount.to_string(),
+                status,
+            })
+        })
+        .collect();
+    let notes = engine
+        .client_annotations(client)
+        .map(|n| ExportedNote {
+            tx: match n.target {
+                NoteTarget::Account(_) => None,
+                NoteTarget::Tx(tx) => Some(tx),
+            },
+            author: n.author.clone(),
+            at: n.at,
+            note: n.text.clone(),
+        })
+        .collect();
+
+    ClientExport {
+        format_version: EXPORT_FORMAT_VERSION,
+        client,
+        account,
+        transactions,
+        disputes,
+        notes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::amount::Amount;
+    use crate::models::domain_state::{TxKind, TxRecord};
+
+    #[test]
+    fn exports_only_the_requested_client() {
+        let mut engine = Engine::default();
+        engine.acct_mut(1).available = Amount(15_000);
+        engine.acct_mut(1).held = Amount(10_000);
+        engine.acct_mut(2).available = Amount(1);
+        for (tx, client, state) in [
+            (3, 1, DisputeState::Disputed),
+            (1, 1, DisputeState::Normal),
+            (2, 2, DisputeState::Normal),
+        ] {
+            engine.txs.insert(
+                tx,
+                TxRecord {
+                    client,
+                    kind: TxKind::Deposit,
+                    amount: Amount(10_000),
+                    state,
+                },
+            );
+        }
+
+        let export = export_client(&engine, 1);
+
+        assert_eq!(export.account.as_ref().unwrap().total, "2.5000");
+        let txs: Vec<_> = export.transactions.iter().map(|t| t.tx).collect();
+        assert_eq!(txs, vec![1, 3]);
+        assert_eq!(export.disputes.len(), 1);
+        assert_eq!(export.disputes[0].status, "open");
+        assert!(export_client(&engine, 9).account.is_none());
+    }
+}
@@ -1,71 +1,76 @@
 use crate::errors::{AppErrors, AppResult};
-use crate::models::amount::Amount;
-use crate::models::domain_state::DisputeState;
+use crate::models::domain_state::{DisputeState, TxKind};
+use crate::models::identifiers::ClientId;
 use crate::models::tx_command::ChargebackCommand;
 use crate::services::commands::traits::tx_command_trait::TxCommandTrait;
-use crate::state::AppState;
+use crate::state::{AppState, Engine, IgnoreReason, Outcome};
 
 /// Implements the `TxCommandTrait` for the `ChargebackCommand` struct.
 /// This enables execution of chargeback commands within the application state.
 impl TxCommandTrait for ChargebackCommand {
-    /// Executes the chargeback command by processing it and updating the application state.
-    ///
-    /// # Arguments
-    /// * `app_state` - A mutable reference to the application state.
-    ///
-    /// # Returns
-    /// * `AppResult<()>` - Returns `Ok(())` if the command is successfully executed,
-    ///   or an `AppErrors` variant if an error occurs.
-    fn execute(&self, app_state: &mut AppState) -> AppResult<()> {
-        process_chargeback_command(app_state, self)
+    /// Executes the chargeback command through [`Engine::apply`].
+    fn execute(&self, app_state: &mut AppState) -> AppResult<Outcome> {
+        app_state.engine.apply(self.clone().into())
+    }
+
+    /// Returns the client the command applies to.
+    fn client(&self) -> ClientId {
+        self.client
     }
 }
 
-/// Processes a chargeback command and updates the application state.
+/// Processes a chargeback command and updates the engine.
 ///
 /// A chargeback finalizes a dispute: the disputed amount is removed from `held`,
-/// the transaction state is set to `ChargedBack`, and the account is locked.
+/// the transaction state is set to `ChargedBack`, and the account is locked. A
+/// charged back withdrawal is reversed: its amount returns to `available`.
 ///
 /// # Arguments
-/// * `app_state` - A mutable reference to the application state.
+/// * `engine` - A mutable reference to the engine.
 /// * `cmd` - A reference to the `ChargebackCommand` containing client and transaction details.
 ///
 /// # Returns
-/// * `AppResult<()>` - Returns `Ok(())` if the chargeback is processed successfully,
-///   or an `AppErrors` variant if an error occurs.
-fn process_chargeback_command(app_state: &mut AppState, cmd: &ChargebackCommand) -> AppResult<()> {
+/// * `AppResult<Outcome>` - `Outcome::Applied` if the chargeback changed the engine,
+///   `Outcome::Ignored` with the reason if it was a no-op, or an `AppErrors` variant
+///   if an error occurs.
+pub(crate) fn process_chargeback_command(
+    engine: &mut Engine,
+    cmd: &ChargebackCommand,
+) -> AppResult<Outcome> {
     let client = cmd.client;
     let tx = cmd.tx;
 
-    let (amount, ok) = if let Some(rec) = app_state.engine.txs.get(&tx) {
-        if rec.client != client || rec.state != DisputeState::Disputed {
-            (Amount::zero(), false)
-        } else {
-            (rec.amount, true)
-        }
-    } else {
-        (Amount::zero(), false)
+    let Some((rec, acc)) = engine.tx_and_acct_mut(&tx) else {
+        return Ok(Outcome::Ignored(IgnoreReason::UnknownTx));
     };
-    if !ok {
-        return Ok(());
+    if rec.client != client {
+        return Ok(Outcome::Ignored(IgnoreReason::ClientMismatch));
     }
-
-    {
-        let acc = app_state.engine.acct_mut(client);
-        acc.held = acc.held.checked_sub(amount).ok_or(AppErrors::Overflow)?;
-        acc.locked = true;
+    if rec.state != DisputeState::Disputed {
+        return Ok(Outcome::Ignored(IgnoreReason::NotDisputed));
     }
 
-    if let Some(rec) = app_state.engine.txs.get_mut(&tx) {
-        rec.state = DisputeState::ChargedBack;
+    acc.held = acc
+        .held
+        .checked_sub(rec.amount)
+        .ok_or(AppErrors::Overflow)?;
+    if rec.kind == TxKind::Withdrawal {
+        acc.available = acc
+            .available
+            .checked_add(rec.amount)
+            .ok_or(AppErrors::Overflow)?;
     }
+    acc.locked = true;
+    acc.version += 1;
+    rec.state = DisputeState::ChargedBack;
 
-    Ok(())
+    Ok(Outcome::Applied)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::amount::Amount;
     use crate::models::domain_state::{TxKind, TxRecord};
     use crate::models::identifiers::{ClientId, TxId};
 
@@ -95,7 +100,8 @@ mod tests {
         }
 
         // act
-        let res = process_chargeback_command(&mut state, &ChargebackCommand { client: c, tx });
+        let res =
+            process_chargeback_command(&mut state.engine, &ChargebackCommand { client: c, tx });
 
         // assert
         assert!(res.is_ok());
@@ -115,7 +121,8 @@ mod tests {
         let tx: TxId = 300;
 
         // act
-        let res = process_chargeback_command(&mut state, &ChargebackCommand { client: c, tx });
+        let res =
+            process_chargeback_command(&mut state.engine, &ChargebackCommand { client: c, tx });
 
         // assert
         assert!(res.is_ok());
@@ -148,7 +155,8 @@ mod tests {
         }
 
         // act (client mismatch and not disputed)
-        let res = process_chargeback_command(&mut state, &ChargebackCommand { client: c, tx });
+        let res =
+            process_chargeback_command(&mut state.engine, &ChargebackCommand { client: c, tx });
 
         // assert
         assert!(res.is_ok()); // ignored
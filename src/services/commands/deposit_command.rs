@@ -1,65 +1,84 @@
 use crate::errors::{AppErrors, AppResult};
 use crate::models::domain_state::{DisputeState, TxKind, TxRecord};
+use crate::models::identifiers::{ClientId, TxId};
+use crate::models::policy::LockedDepositPolicy;
 use crate::models::tx_command::DepositCommand;
 use crate::services::commands::traits::tx_command_trait::TxCommandTrait;
-use crate::state::AppState;
+use crate::state::{AppState, Engine, IgnoreReason, Outcome};
+use log::warn;
 
 /// Implements the `TxCommandTrait` for the `DepositCommand` struct.
 /// This allows the execution of deposit commands within the application state.
 impl TxCommandTrait for DepositCommand {
-    /// Executes the deposit command by processing it and updating the application state.
-    ///
-    /// # Arguments
-    /// * `app_state` - A mutable reference to the application state.
-    ///
-    /// # Returns
-    /// * `AppResult<()>` - Returns `Ok(())` if the command is successfully executed,
-    ///   or an `AppErrors` variant if an error occurs.
-    fn execute(&self, app_state: &mut AppState) -> AppResult<()> {
-        process_deposit_command(app_state, self)
+    /// Executes the deposit command through [`Engine::apply`].
+    fn execute(&self, app_state: &mut AppState) -> AppResult<Outcome> {
+        app_state.engine.apply(self.clone().into())
+    }
+
+    /// Returns the client the command applies to.
+    fn client(&self) -> ClientId {
+        self.client
+    }
+
+    /// Returns the id of the transaction the command records.
+    fn new_tx(&self) -> Option<TxId> {
+        Some(self.tx)
     }
 }
 
-/// Processes a deposit command and updates the application state.
+/// Processes a deposit command and updates the engine.
 ///
 /// A deposit increases the `available` funds of the client account and
-/// records the transaction as a deposit in the transaction log.
+/// records the transaction as a deposit in the transaction log. A deposit for a
+/// locked account is ignored, or under [`LockedDepositPolicy::Hold`] credited
+/// to `held` and recorded as disputed, so it can be released by a resolve.
 ///
 /// # Arguments
-/// * `app_state` - A mutable reference to the application state.
+/// * `engine` - A mutable reference to the engine.
 /// * `cmd` - A reference to the `DepositCommand` containing client, transaction, and amount details.
 ///
 /// # Returns
-/// * `AppResult<()>` - Returns `Ok(())` if the deposit is processed successfully,
-///   or an `AppErrors` variant if an error occurs.
-fn process_deposit_command(app_state: &mut AppState, cmd: &DepositCommand) -> AppResult<()> {
+/// * `AppResult<Outcome>` - `Outcome::Applied` if the deposit changed the engine,
+///   `Outcome::Ignored` with the reason if it was a no-op, or an `AppErrors` variant
+///   if an error occurs.
+pub(crate) fn process_deposit_command(
+    engine: &mut Engine,
+    cmd: &DepositCommand,
+) -> AppResult<Outcome> {
     let client = cmd.client;
     let tx = cmd.tx;
 
-    if app_state.engine.txs.contains_key(&tx) {
-        return Ok(());
-    }
-
-    let acc = app_state.engine.acct_mut(client);
-    if acc.locked {
-        return Ok(());
+    if engine.txs.contains_key(&tx) {
+        return engine.duplicate_tx(tx);
     }
 
+    let policy = engine.locked_deposit_policy();
+    let acc = engine.acct_mut(client);
     let amount = cmd.amount;
-    acc.available = acc
-        .available
-        .checked_add(amount)
-        .ok_or(AppErrors::Overflow)?;
-    app_state.engine.txs.insert(
+    let state = if !acc.locked {
+        acc.available = acc
+            .available
+            .checked_add(amount)
+            .ok_or(AppErrors::Overflow)?;
+        DisputeState::Normal
+    } else if policy == LockedDepositPolicy::Hold {
+        acc.held = acc.held.checked_add(amount).ok_or(AppErrors::Overflow)?;
+        warn!("client {client} is locked: deposit {tx} of {amount} credited to held");
+        DisputeState::Disputed
+    } else {
+        return Ok(Outcome::Ignored(IgnoreReason::AccountLocked));
+    };
+    acc.version += 1;
+    engine.txs.insert(
         tx,
         TxRecord {
             client,
             kind: TxKind::Deposit,
             amount,
-            state: DisputeState::Normal,
+            state,
         },
     );
-    Ok(())
+    Ok(Outcome::Applied)
 }
 
 #[cfg(test)]
@@ -86,7 +105,7 @@ mod tests {
 
         // act
         let res = process_deposit_command(
-            &mut state,
+            &mut state.engine,
             &DepositCommand {
                 client: c,
                 tx,
@@ -119,11 +138,11 @@ mod tests {
         let tx: TxId = 42;
 
         let first = Amount(10_000);
-        process_deposit_command(&mut state, &cmd(c, tx, first.0)).unwrap();
+        process_deposit_command(&mut state.engine, &cmd(c, tx, first.0)).unwrap();
 
         // act
         let second = Amount(5_000);
-        process_deposit_command(&mut state, &cmd(c, tx, second.0)).unwrap();
+        process_deposit_command(&mut state.engine, &cmd(c, tx, second.0)).unwrap();
 
         // assert
         let acc = state.engine.acct_mut_if_exists(&c).unwrap();
@@ -149,7 +168,7 @@ mod tests {
 
         // act
         process_deposit_command(
-            &mut state,
+            &mut state.engine,
             &DepositCommand {
                 client: c,
                 tx,
@@ -167,6 +186,27 @@ mod tests {
         assert!(!state.engine.txs.contains_key(&tx));
     }
 
+    #[test]
+    fn deposit_to_locked_account_held_under_hold_policy() {
+        let mut state = AppState::default();
+        state.engine.acct_mut(7).locked = true;
+        state
+            .engine
+            .set_locked_deposit_policy(LockedDepositPolicy::Hold);
+
+        let outcome = process_deposit_command(&mut state.engine, &cmd(7, 2, 20_000)).unwrap();
+
+        assert_eq!(outcome, Outcome::Applied);
+        let acc = state.engine.acct_mut_if_exists(&7).unwrap();
+        assert_eq!(acc.available, Amount::zero());
+        assert_eq!(acc.held, Amount(20_000));
+        assert_eq!(
+            state.engine.txs.get(&2).unwrap().state,
+            DisputeState::Disputed
+        );
+        state.engine.verify().unwrap();
+    }
+
     #[test]
     fn deposit_fails_with_overflow() {
         // arrange
@@ -183,7 +223,7 @@ mod tests {
 
         // act
         let res = process_deposit_command(
-            &mut state,
+            &mut state.engine,
             &DepositCommand {
                 client: c,
                 tx,
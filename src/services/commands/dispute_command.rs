@@ -1,78 +1,110 @@
 use crate::errors::{AppErrors, AppResult};
-use crate::models::amount::Amount;
 use crate::models::domain_state::{DisputeState, TxKind};
+use crate::models::identifiers::ClientId;
+use crate::models::policy::{RedisputePolicy, UnfundedDisputePolicy};
 use crate::models::tx_command::DisputeCommand;
 use crate::services::commands::traits::tx_command_trait::TxCommandTrait;
-use crate::state::AppState;
+use crate::state::{AppState, Engine, IgnoreReason, Outcome};
 
 /// Implements the `TxCommandTrait` for the `DisputeCommand` struct.
 /// This allows the execution of dispute commands within the application state.
 impl TxCommandTrait for DisputeCommand {
-    /// Executes the dispute command by processing it and updating the application state.
-    ///
-    /// # Arguments
-    /// * `app_state` - A mutable reference to the application state.
-    ///
-    /// # Returns
-    /// * `AppResult<()>` - Returns `Ok(())` if the command is successfully executed,
-    ///   or an `AppErrors` variant if an error occurs.
-    fn execute(&self, app_state: &mut AppState) -> AppResult<()> {
-        process_dispute_command(app_state, self)
+    /// Executes the dispute command through [`Engine::apply`].
+    fn execute(&self, app_state: &mut AppState) -> AppResult<Outcome> {
+        app_state.engine.apply(self.clone().into())
+    }
+
+    /// Returns the client the command applies to.
+    fn client(&self) -> ClientId {
+        self.client
     }
 }
 
-/// Processes a dispute command and updates the application state.
+/// Processes a dispute command and updates the engine.
 ///
 /// A dispute moves funds from `available` to `held` for a given deposit transaction,
-/// and marks the transaction state as `Disputed`.
+/// and marks the transaction state as `Disputed`. If the engine allows
+/// withdrawal disputes, a disputed withdrawal's amount is added to `held`
+/// instead, pending a resolve or chargeback; otherwise withdrawals are not
+/// disputable. A deposit dispute the available funds do not cover is ignored,
+/// under [`UnfundedDisputePolicy::Negative`] applied in full, taking
+/// `available` negative, or under [`UnfundedDisputePolicy::Queue`] queued until
+/// a later deposit covers it. A resolved transaction can be disputed again
+/// unless the engine's [`RedisputePolicy`] forbids it.
 ///
 /// # Arguments
-/// * `app_state` - A mutable reference to the application state.
+/// * `engine` - A mutable reference to the engine.
 /// * `cmd` - A reference to the `DisputeCommand` containing client and transaction details.
 ///
 /// # Returns
-/// * `AppResult<()>` - Returns `Ok(())` if the dispute is processed successfully,
-///   or an `AppErrors` variant if an error occurs.
-fn process_dispute_command(app_state: &mut AppState, cmd: &DisputeCommand) -> AppResult<()> {
+/// * `AppResult<Outcome>` - `Outcome::Applied` if the dispute changed the engine,
+///   `Outcome::Ignored` with the reason if it was a no-op, or an `AppErrors` variant
+///   if an error occurs.
+pub(crate) fn process_dispute_command(
+    engine: &mut Engine,
+    cmd: &DisputeCommand,
+) -> AppResult<Outcome> {
     let client = cmd.client;
     let tx = cmd.tx;
+    let withdrawal_disputes = engine.withdrawal_disputes();
+    let unfunded = engine.unfunded_dispute_policy();
+    let redisputes = engine.redispute_policy();
 
-    let (amount, ok) = if let Some(rec) = app_state.engine.txs.get(&tx) {
-        if rec.client != client || rec.kind != TxKind::Deposit || rec.state != DisputeState::Normal
-        {
-            (Amount::zero(), false)
-        } else {
-            (rec.amount, true)
-        }
-    } else {
-        (Amount::zero(), false)
+    let Some((rec, acc)) = engine.tx_and_acct_mut(&tx) else {
+        return Ok(Outcome::Ignored(IgnoreReason::UnknownTx));
     };
-    if !ok {
-        return Ok(());
+    if rec.client != client {
+        return Ok(Outcome::Ignored(IgnoreReason::ClientMismatch));
     }
-
-    {
-        let acc = app_state.engine.acct_mut(client);
-        if acc.available.0 < amount.0 {
-            return Ok(());
+    if rec.kind == TxKind::Withdrawal && !withdrawal_disputes {
+        return Ok(Outcome::Ignored(IgnoreReason::NotDisputable));
+    }
+    match rec.state {
+        DisputeState::Normal => {}
+        DisputeState::Resolved if redisputes == RedisputePolicy::Allow => {}
+        DisputeState::Resolved => return Ok(Outcome::Ignored(IgnoreReason::AlreadyResolved)),
+        DisputeState::Disputed | DisputeState::ChargedBack => {
+            return Ok(Outcome::Ignored(IgnoreReason::AlreadyDisputed));
         }
-        acc.available = acc
-            .available
-            .checked_sub(amount)
-            .ok_or(AppErrors::Overflow)?;
-        acc.held = acc.held.checked_add(amount).ok_or(AppErrors::Overflow)?;
     }
 
-    if let Some(rec) = app_state.engine.txs.get_mut(&tx) {
+    let amount = rec.amount;
+    if rec.kind == TxKind::Withdrawal {
+        acc.held = acc.held.checked_add(amount).ok_or(AppErrors::Overflow)?;
+        acc.version += 1;
         rec.state = DisputeState::Disputed;
+        return Ok(Outcome::Applied);
     }
-
-    Ok(())
+    if acc.available.0 < amount.0 {
+        match unfunded {
+            UnfundedDisputePolicy::Ignore => {
+                return Ok(Outcome::Ignored(IgnoreReason::InsufficientFunds));
+            }
+            UnfundedDisputePolicy::Queue => {
+                return Ok(Outcome::Ignored(if engine.queue_dispute(client, tx) {
+                    IgnoreReason::DisputeQueued
+                } else {
+                    IgnoreReason::AlreadyDisputed
+                }));
+            }
+            UnfundedDisputePolicy::Negative => {}
+        }
+    }
+    acc.available = acc
+        .available
+        .checked_sub(amount)
+        .ok_or(AppErrors::Overflow)?;
+    acc.held = acc.held.checked_add(amount).ok_or(AppErrors::Overflow)?;
+    acc.version += 1;
+    rec.state = DisputeState::Disputed;
+
+    Ok(Outcome::Applied)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::amount::Amount;
     use crate::models::domain_state::TxRecord;
     use crate::models::identifiers::{ClientId, TxId};
 
@@ -110,7 +142,7 @@ mod tests {
         }
 
         // act
-        let res = process_dispute_command(&mut state, &DisputeCommand { client: c, tx });
+        let res = process_dispute_command(&mut state.engine, &DisputeCommand { client: c, tx });
 
         // assert
         assert!(res.is_ok());
@@ -127,7 +159,7 @@ mod tests {
         let c: ClientId = 2;
         let tx: TxId = 200;
 
-        let res = process_dispute_command(&mut state, &DisputeCommand { client: c, tx });
+        let res = process_dispute_command(&mut state.engine, &DisputeCommand { client: c, tx });
         assert!(res.is_ok());
         assert!(!state.engine.txs.contains_key(&tx));
         assert!(state.engine.acct(c).is_none());
@@ -147,7 +179,8 @@ mod tests {
             acc.available = Amount(10_000);
         }
 
-        let res = process_dispute_command(&mut state, &DisputeCommand { client: caller, tx });
+        let res =
+            process_dispute_command(&mut state.engine, &DisputeCommand { client: caller, tx });
         assert!(res.is_ok());
 
         let acc = state.engine.acct(owner).unwrap();
@@ -172,7 +205,7 @@ mod tests {
             acc.available = Amount(10_000);
         }
 
-        let res = process_dispute_command(&mut state, &DisputeCommand { client: c, tx });
+        let res = process_dispute_command(&mut state.engine, &DisputeCommand { client: c, tx });
         assert!(res.is_ok());
 
         let acc = state.engine.acct(c).unwrap();
@@ -206,7 +239,7 @@ mod tests {
             acc.held = amt;
         }
 
-        let res = process_dispute_command(&mut state, &DisputeCommand { client: c, tx });
+        let res = process_dispute_command(&mut state.engine, &DisputeCommand { client: c, tx });
         assert!(res.is_ok());
 
         let acc = state.engine.acct(c).unwrap();
@@ -232,7 +265,7 @@ mod tests {
             acc.held = Amount(0);
         }
 
-        let res = process_dispute_command(&mut state, &DisputeCommand { client: c, tx });
+        let res = process_dispute_command(&mut state.engine, &DisputeCommand { client: c, tx });
         assert!(res.is_ok());
 
         let acc = state.engine.acct(c).unwrap();
@@ -244,6 +277,149 @@ mod tests {
         );
     }
 
+    #[test]
+    fn spent_deposit_dispute_takes_available_negative_under_negative_policy() {
+        use crate::models::tx_command::{ChargebackCommand, DepositCommand, WithdrawalCommand};
+
+        let mut engine = Engine::builder()
+            .unfunded_disputes(UnfundedDisputePolicy::Negative)
+            .build();
+        let deposit = |tx, amount| DepositCommand {
+            client: 1,
+            tx,
+            amount: Amount(amount),
+        };
+        engine.apply(deposit(1, 100_000).into()).unwrap();
+        let withdrawal = WithdrawalCommand {
+            client: 1,
+            tx: 2,
+            amount: Amount(70_000),
+        };
+        engine.apply(withdrawal.into()).unwrap();
+
+        // The deposit was mostly spent: the full amount is held regardless.
+        let dispute = DisputeCommand { client: 1, tx: 1 };
+        assert_eq!(engine.apply(dispute.into()).unwrap(), Outcome::Applied);
+        let acc = engine.acct(1).unwrap();
+        assert_eq!(
+            (acc.available, acc.held, acc.total()),
+            (Amount(-70_000), Amount(100_000), Amount(30_000))
+        );
+        engine.verify().unwrap();
+
+        // A later deposit pays down the shortfall; an overdraft is still refused.
+        engine.apply(deposit(3, 50_000).into()).unwrap();
+        assert_eq!(engine.acct(1).unwrap().available, Amount(-20_000));
+        let overdraft = WithdrawalCommand {
+            client: 1,
+            tx: 4,
+            amount: Amount(1),
+        };
+        assert_eq!(
+            engine.apply(overdraft.into()).unwrap(),
+            Outcome::Ignored(IgnoreReason::InsufficientFunds)
+        );
+
+        // The chargeback removes the held funds and leaves the debt.
+        let chargeback = ChargebackCommand { client: 1, tx: 1 };
+        assert_eq!(engine.apply(chargeback.into()).unwrap(), Outcome::Applied);
+        let acc = engine.acct(1).unwrap();
+        assert_eq!(
+            (acc.available, acc.held, acc.locked),
+            (Amount(-20_000), Amount(0), true)
+        );
+        engine.verify().unwrap();
+    }
+
+    #[test]
+    fn resolved_deposit_can_be_redisputed_only_when_allowed() {
+        use crate::models::tx_command::{DepositCommand, ResolveCommand};
+
+        for (policy, redispute) in [
+            (RedisputePolicy::Allow, Outcome::Applied),
+            (
+                RedisputePolicy::Forbid,
+                Outcome::Ignored(IgnoreReason::AlreadyResolved),
+            ),
+        ] {
+            let mut engine = Engine::builder().redisputes(policy).build();
+            let deposit = DepositCommand {
+                client: 1,
+                tx: 1,
+                amount: Amount(10_000),
+            };
+            engine.apply(deposit.into()).unwrap();
+            let dispute = DisputeCommand { client: 1, tx: 1 };
+            engine.apply(dispute.clone().into()).unwrap();
+            let resolve = ResolveCommand { client: 1, tx: 1 };
+            engine.apply(resolve.into()).unwrap();
+            assert_eq!(engine.txs.get(&1).unwrap().state, DisputeState::Resolved);
+
+            assert_eq!(engine.apply(dispute.into()).unwrap(), redispute);
+            let held = engine.acct(1).unwrap().held;
+            assert_eq!(held == Amount(10_000), redispute == Outcome::Applied);
+        }
+    }
+
+    #[test]
+    fn unfunded_dispute_is_queued_and_retried_after_deposits() {
+        use crate::models::tx_command::{
+            DepositCommand, ResolveCommand, Transaction, WithdrawalCommand,
+        };
+
+        let mut engine = Engine::builder()
+            .unfunded_disputes(UnfundedDisputePolicy::Queue)
+            .build();
+        let deposit = |tx, amount| -> Transaction {
+            DepositCommand {
+                client: 1,
+                tx,
+                amount: Amount(amount),
+            }
+            .into()
+        };
+        engine.apply(deposit(1, 100_000)).unwrap();
+        engine.apply(deposit(2, 50_000)).unwrap();
+        let withdrawal = WithdrawalCommand {
+            client: 1,
+            tx: 3,
+            amount: Amount(120_000),
+        };
+        engine.apply(withdrawal.into()).unwrap();
+
+        let dispute = |tx| -> Transaction { DisputeCommand { client: 1, tx }.into() };
+        assert_eq!(
+            engine.apply(dispute(1)).unwrap(),
+            Outcome::Ignored(IgnoreReason::DisputeQueued)
+        );
+        assert_eq!(
+            engine.apply(dispute(1)).unwrap(),
+            Outcome::Ignored(IgnoreReason::AlreadyDisputed)
+        );
+        assert_eq!(
+            engine.apply(dispute(2)).unwrap(),
+            Outcome::Ignored(IgnoreReason::DisputeQueued)
+        );
+        assert_eq!(engine.pending_disputes(1), [1, 2]);
+
+        // A resolve cancels a queued dispute.
+        let resolve = ResolveCommand { client: 1, tx: 2 };
+        assert_eq!(engine.apply(resolve.into()).unwrap(), Outcome::Applied);
+        assert_eq!(engine.pending_disputes(1), [1]);
+
+        // Not covered yet: 3 + 0.5 < 10.
+        engine.apply(deposit(4, 5_000)).unwrap();
+        assert_eq!(engine.pending_dispute_count(), 1);
+
+        engine.apply(deposit(5, 70_000)).unwrap();
+        assert_eq!(engine.pending_dispute_count(), 0);
+        let acc = engine.acct(1).unwrap();
+        assert_eq!((acc.available, acc.held), (Amount(5_000), Amount(100_000)));
+        assert_eq!(engine.txs.get(&1).unwrap().state, DisputeState::Disputed);
+        assert_eq!(engine.activity(1).disputes, 1);
+        engine.verify().unwrap();
+    }
+
     #[test]
     fn dispute_errors_on_held_overflow() {
         let mut state = AppState::default();
@@ -258,7 +434,35 @@ mod tests {
             acc.held = Amount(i64::MAX - 5);
         }
 
-        let res = process_dispute_command(&mut state, &DisputeCommand { client: c, tx });
+        let res = process_dispute_command(&mut state.engine, &DisputeCommand { client: c, tx });
         assert!(matches!(res, Err(AppErrors::Overflow)));
     }
+
+    #[test]
+    fn execute_goes_through_the_engine() {
+        use crate::models::tx_command::DepositCommand;
+        use crate::state::Engine;
+
+        let mut state = AppState {
+            engine: Engine::builder().orphan_buffer(1).build(),
+            ..AppState::default()
+        };
+        let dispute = DisputeCommand { client: 1, tx: 1 };
+
+        // The dispute arrives before its deposit and is held back, not dropped.
+        assert_eq!(
+            dispute.execute(&mut state).unwrap(),
+            Outcome::Ignored(IgnoreReason::Buffered)
+        );
+        let deposit = DepositCommand {
+            client: 1,
+            tx: 1,
+            amount: Amount(10_000),
+        };
+        assert_eq!(deposit.execute(&mut state).unwrap(), Outcome::Applied);
+
+        let acc = state.engine.acct(1).unwrap();
+        assert_eq!((acc.available, acc.held), (Amount::zero(), Amount(10_000)));
+        assert_eq!(state.engine.activity(1).disputes, 1);
+    }
 }
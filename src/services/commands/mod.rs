@@ -1,6 +1,7 @@
-mod chargeback_command;
+pub(crate) mod chargeback_command;
 pub mod deposit_command;
-mod dispute_command;
-mod resolve_command;
+pub(crate) mod dispute_command;
+pub(crate) mod resolve_command;
 pub mod traits;
+mod transaction_command;
 pub mod withdrawal_command;
@@ -1,77 +1,82 @@
 use crate::errors::{AppErrors, AppResult};
-use crate::models::amount::Amount;
-use crate::models::domain_state::DisputeState;
+use crate::models::domain_state::{DisputeState, TxKind};
+use crate::models::identifiers::ClientId;
 use crate::models::tx_command::ResolveCommand;
 use crate::services::commands::traits::tx_command_trait::TxCommandTrait;
-use crate::state::AppState;
+use crate::state::{AppState, Engine, IgnoreReason, Outcome};
 
 /// Implements the `TxCommandTrait` for the `ResolveCommand` struct.
 /// This allows the execution of resolve commands within the application state.
 impl TxCommandTrait for ResolveCommand {
-    /// Executes the resolve command by processing it and updating the application state.
-    ///
-    /// # Arguments
-    /// * `app_state` - A mutable reference to the application state.
-    ///
-    /// # Returns
-    /// * `AppResult<()>` - Returns `Ok(())` if the command is successfully executed,
-    ///   or an `AppErrors` variant if an error occurs.
-    fn execute(&self, app_state: &mut AppState) -> AppResult<()> {
-        process_resolve_command(app_state, self)
+    /// Executes the resolve command through [`Engine::apply`].
+    fn execute(&self, app_state: &mut AppState) -> AppResult<Outcome> {
+        app_state.engine.apply(self.clone().into())
+    }
+
+    /// Returns the client the command applies to.
+    fn client(&self) -> ClientId {
+        self.client
     }
 }
 
-/// Processes a resolve command and updates the application state.
+/// Processes a resolve command and updates the engine.
+///
+/// A resolve releases a disputed deposit's held funds back to `available`, or
+/// drops a disputed withdrawal's hold, the withdrawal standing, and marks the
+/// transaction `Resolved`. A resolve of a dispute still queued for funds
+/// cancels it.
 ///
 /// # Arguments
-/// * `app_state` - A mutable reference to the application state.
+/// * `engine` - A mutable reference to the engine.
 /// * `cmd` - A reference to the `ResolveCommand` to be processed.
 ///
 /// # Returns
-/// * `AppResult<()>` - Returns `Ok(())` if the command is successfully processed,
-///   or an `AppErrors` variant if an error occurs.
-fn process_resolve_command(app_state: &mut AppState, cmd: &ResolveCommand) -> AppResult<()> {
+/// * `AppResult<Outcome>` - `Outcome::Applied` if the dispute was resolved or a
+///   queued dispute cancelled; `Outcome::Ignored` with `UnknownTx`,
+///   `ClientMismatch` or `NotDisputed` if there is nothing to resolve; or
+///   `AppErrors::Overflow` if the account holds less than the disputed amount
+///   or `available` would overflow.
+pub(crate) fn process_resolve_command(
+    engine: &mut Engine,
+    cmd: &ResolveCommand,
+) -> AppResult<Outcome> {
     let client = cmd.client;
     let tx = cmd.tx;
 
-    let (amount, ok) = if let Some(rec) = app_state.engine.txs.get(&tx) {
-        if rec.client != client || rec.state != DisputeState::Disputed {
-            (Amount::zero(), false)
-        } else {
-            (rec.amount, true)
-        }
-    } else {
-        (Amount::zero(), false)
+    if engine.cancel_queued_dispute(client, tx) {
+        return Ok(Outcome::Applied);
+    }
+    let Some((rec, acc)) = engine.tx_and_acct_mut(&tx) else {
+        return Ok(Outcome::Ignored(IgnoreReason::UnknownTx));
     };
-
-    if !ok {
-        return Ok(());
+    if rec.client != client {
+        return Ok(Outcome::Ignored(IgnoreReason::ClientMismatch));
+    }
+    if rec.state != DisputeState::Disputed {
+        return Ok(Outcome::Ignored(IgnoreReason::NotDisputed));
     }
 
-    {
-        let acc = app_state.engine.acct_mut(client);
-
-        if acc.held.0 < amount.0 {
-            return Err(AppErrors::Overflow);
-        }
-
-        acc.held = acc.held.checked_sub(amount).ok_or(AppErrors::Overflow)?;
+    let amount = rec.amount;
+    if acc.held.0 < amount.0 {
+        return Err(AppErrors::Overflow);
+    }
+    acc.held = acc.held.checked_sub(amount).ok_or(AppErrors::Overflow)?;
+    if rec.kind == TxKind::Deposit {
         acc.available = acc
             .available
             .checked_add(amount)
             .ok_or(AppErrors::Overflow)?;
     }
+    acc.version += 1;
+    rec.state = DisputeState::Resolved;
 
-    if let Some(rec) = app_state.engine.txs.get_mut(&tx) {
-        rec.state = DisputeState::Normal;
-    }
-
-    Ok(())
+    Ok(Outcome::Applied)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::amount::Amount;
     use crate::models::domain_state::{TxKind, TxRecord};
     use crate::models::identifiers::{ClientId, TxId};
 
@@ -94,7 +99,7 @@ mod tests {
     }
 
     #[test]
-    fn resolve_happy_path_moves_held_to_available_and_marks_normal() {
+    fn resolve_happy_path_moves_held_to_available_and_marks_resolved() {
         // arrange
         let mut state = AppState::default();
         let c: ClientId = 1;
@@ -109,7 +114,7 @@ mod tests {
         }
 
         // act
-        let res = process_resolve_command(&mut state, &ResolveCommand { client: c, tx });
+        let res = process_resolve_command(&mut state.engine, &ResolveCommand { client: c, tx });
 
         // assert
         assert!(res.is_ok());
@@ -117,7 +122,39 @@ mod tests {
         assert_eq!(acc.held, Amount(0));
         assert_eq!(acc.available, Amount(62_345));
         let rec = state.engine.txs.get(&tx).expect("tx exists");
-        assert_eq!(rec.state, DisputeState::Normal);
+        assert_eq!(rec.state, DisputeState::Resolved);
+    }
+
+    #[test]
+    fn resolve_of_a_disputed_withdrawal_drops_the_hold() {
+        // arrange
+        let mut state = AppState::default();
+        let c: ClientId = 1;
+        let tx: TxId = 100;
+        let amt = Amount(12_345);
+
+        state.engine.txs.insert(
+            tx,
+            TxRecord {
+                client: c,
+                kind: TxKind::Withdrawal,
+                amount: amt,
+                state: DisputeState::Disputed,
+            },
+        );
+        {
+            let acc = state.engine.acct_mut(c);
+            acc.available = Amount(50_000);
+            acc.held = amt;
+        }
+
+        // act
+        let res = process_resolve_command(&mut state.engine, &ResolveCommand { client: c, tx });
+
+        // assert
+        assert_eq!(res.unwrap(), Outcome::Applied);
+        let acc = state.engine.acct(c).expect("account exists");
+        assert_eq!((acc.available, acc.held), (Amount(50_000), Amount(0)));
     }
 
     #[test]
@@ -126,7 +163,7 @@ mod tests {
         let c: ClientId = 2;
         let tx: TxId = 200;
 
-        let res = process_resolve_command(&mut state, &ResolveCommand { client: c, tx });
+        let res = process_resolve_command(&mut state.engine, &ResolveCommand { client: c, tx });
         assert!(res.is_ok());
         assert!(!state.engine.txs.contains_key(&tx));
         assert!(state.engine.acct(c).is_none());
@@ -146,7 +183,8 @@ mod tests {
             acc.held = amt;
         }
 
-        let res = process_resolve_command(&mut state, &ResolveCommand { client: caller, tx });
+        let res =
+            process_resolve_command(&mut state.engine, &ResolveCommand { client: caller, tx });
         assert!(res.is_ok());
 
         let acc = state.engine.acct(owner).unwrap();
@@ -171,7 +209,7 @@ mod tests {
             acc.held = amt;
         }
 
-        let res = process_resolve_command(&mut state, &ResolveCommand { client: c, tx });
+        let res = process_resolve_command(&mut state.engine, &ResolveCommand { client: c, tx });
         assert!(res.is_ok());
 
         let acc = state.engine.acct(c).unwrap();
@@ -196,7 +234,7 @@ mod tests {
             acc.held = Amount(1_000);
         }
 
-        let res = process_resolve_command(&mut state, &ResolveCommand { client: c, tx });
+        let res = process_resolve_command(&mut state.engine, &ResolveCommand { client: c, tx });
         assert!(matches!(res, Err(AppErrors::Overflow)));
     }
 
@@ -214,7 +252,7 @@ mod tests {
             acc.available = Amount(i64::MAX - 5);
         }
 
-        let res = process_resolve_command(&mut state, &ResolveCommand { client: c, tx });
+        let res = process_resolve_command(&mut state.engine, &ResolveCommand { client: c, tx });
         assert!(matches!(res, Err(AppErrors::Overflow)));
     }
 }
@@ -1,5 +1,6 @@
 use crate::errors::AppResult;
-use crate::state::AppState;
+use crate::models::identifiers::{ClientId, TxId};
+use crate::state::{AppState, Outcome};
 
 /// A trait that defines the behavior of transaction commands in the application.
 /// Implementors of this trait can execute specific transaction commands,
@@ -7,11 +8,27 @@ use crate::state::AppState;
 pub trait TxCommandTrait {
     /// Executes the transaction command and updates the application state.
     ///
+    /// The commands of this crate apply themselves through
+    /// [`Engine::apply`](crate::state::Engine::apply), so the engine's orphan
+    /// buffer, dispute window and activity tallies see a command executed on
+    /// its own like any other.
+    ///
     /// # Arguments
     /// * `app_state` - A mutable reference to the application state.
     ///
     /// # Returns
-    /// * `AppResult<()>` - Returns `Ok(())` if the command is successfully executed,
-    ///   or an `AppErrors` variant if an error occurs.
-    fn execute(&self, app_state: &mut AppState) -> AppResult<()>;
+    /// * `AppResult<Outcome>` - [`Outcome::Applied`] if the command changed the
+    ///   state, [`Outcome::Ignored`] with the reason if it was a no-op, or an
+    ///   `AppErrors` variant if an error occurs.
+    fn execute(&self, app_state: &mut AppState) -> AppResult<Outcome>;
+
+    /// Returns the client the command applies to.
+    fn client(&self) -> ClientId;
+
+    /// Returns the id of the transaction this command would record, for commands
+    /// that create new transactions (deposits and withdrawals). Commands that
+    /// reference an existing transaction return `None`.
+    fn new_tx(&self) -> Option<TxId> {
+        None
+    }
 }
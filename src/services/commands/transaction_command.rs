@@ -0,0 +1,28 @@
+use crate::errors::AppResult;
+use crate::models::identifiers::{ClientId, TxId};
+use crate::models::tx_command::Transaction;
+use crate::services::commands::traits::tx_command_trait::TxCommandTrait;
+use crate::state::{AppState, Outcome};
+
+/// Implements the `TxCommandTrait` for the `Transaction` enum, so a typed
+/// transaction can be passed wherever a command is expected (e.g.,
+/// [`EngineHandle::execute`](crate::services::engine_handle::EngineHandle::execute)).
+impl TxCommandTrait for Transaction {
+    /// Executes the transaction through [`Engine::apply`](crate::state::Engine::apply).
+    fn execute(&self, app_state: &mut AppState) -> AppResult<Outcome> {
+        app_state.engine.apply(self.clone())
+    }
+
+    /// Returns the client the transaction applies to.
+    fn client(&self) -> ClientId {
+        Transaction::client(self)
+    }
+
+    /// Returns the id of the transaction recorded, for deposits and withdrawals.
+    fn new_tx(&self) -> Option<TxId> {
+        match self {
+            Transaction::Deposit(_) | Transaction::Withdrawal(_) => Some(self.tx()),
+            _ => None,
+        }
+    }
+}
@@ -1,59 +1,70 @@
 use crate::errors::{AppErrors, AppResult};
 use crate::models::domain_state::{DisputeState, TxKind, TxRecord};
+use crate::models::identifiers::{ClientId, TxId};
 use crate::models::tx_command::WithdrawalCommand;
 use crate::services::commands::traits::tx_command_trait::TxCommandTrait;
-use crate::state::AppState;
+use crate::state::{AppState, Engine, IgnoreReason, Outcome};
 
 /// Implements the `TxCommandTrait` for the `WithdrawalCommand` struct.
 /// This allows the execution of withdrawal commands within the application state.
 impl TxCommandTrait for WithdrawalCommand {
-    /// Executes the withdrawal command by processing it and updating the application state.
-    ///
-    /// # Arguments
-    /// * `app_state` - A mutable reference to the application state.
-    ///
-    /// # Returns
-    /// * `AppResult<()>` - Returns `Ok(())` if the command is successfully executed,
-    ///   or an `AppErrors` variant if an error occurs.
-    fn execute(&self, app_state: &mut AppState) -> AppResult<()> {
-        process_withdrawal_command(app_state, self)
+    /// Executes the withdrawal command through [`Engine::apply`].
+    fn execute(&self, app_state: &mut AppState) -> AppResult<Outcome> {
+        app_state.engine.apply(self.clone().into())
+    }
+
+    /// Returns the client the command applies to.
+    fn client(&self) -> ClientId {
+        self.client
+    }
+
+    /// Returns the id of the transaction the command records.
+    fn new_tx(&self) -> Option<TxId> {
+        Some(self.tx)
     }
 }
 
-/// Processes a withdrawal command and updates the application state.
+/// Processes a withdrawal command and updates the engine.
 ///
 /// # Arguments
-/// * `app_state` - A mutable reference to the application state.
+/// * `engine` - A mutable reference to the engine.
 /// * `cmd` - A reference to the `WithdrawalCommand` to be processed.
 ///
 /// # Returns
-/// * `AppResult<()>` - Returns `Ok(())` if the command is successfully processed,
-///   or an `AppErrors` variant if an error occurs.
-fn process_withdrawal_command(app_state: &mut AppState, cmd: &WithdrawalCommand) -> AppResult<()> {
+/// * `AppResult<Outcome>` - `Outcome::Applied` if the funds were withdrawn and
+///   the transaction recorded; `Outcome::Ignored` with `DuplicateTx`,
+///   `AccountLocked` or `InsufficientFunds` if it was a no-op;
+///   `AppErrors::DuplicateTx` for a reused tx id under strict duplicates; or
+///   `AppErrors::Overflow` if `available` would overflow.
+pub(crate) fn process_withdrawal_command(
+    engine: &mut Engine,
+    cmd: &WithdrawalCommand,
+) -> AppResult<Outcome> {
     let client = cmd.client;
     let tx = cmd.tx;
 
-    if app_state.engine.txs.contains_key(&tx) {
-        return Ok(());
+    if engine.txs.contains_key(&tx) {
+        return engine.duplicate_tx(tx);
     }
 
-    let acc = app_state.engine.acct_mut(client);
+    let acc = engine.acct_mut(client);
 
     if acc.locked {
-        return Ok(());
+        return Ok(Outcome::Ignored(IgnoreReason::AccountLocked));
     }
 
     let amount = cmd.amount;
     if acc.available.0 < amount.0 {
-        return Ok(());
+        return Ok(Outcome::Ignored(IgnoreReason::InsufficientFunds));
     }
 
     acc.available = acc
         .available
         .checked_sub(amount)
         .ok_or(AppErrors::Overflow)?;
+    acc.version += 1;
 
-    app_state.engine.txs.insert(
+    engine.txs.insert(
         tx,
         TxRecord {
             client,
@@ -62,7 +73,7 @@ fn process_withdrawal_command(app_state: &mut AppState, cmd: &WithdrawalCommand)
             state: DisputeState::Normal,
         },
     );
-    Ok(())
+    Ok(Outcome::Applied)
 }
 
 #[cfg(test)]
@@ -92,7 +103,7 @@ mod tests {
         }
 
         // act
-        let res = process_withdrawal_command(&mut state, &wc(c, tx, 12_500));
+        let res = process_withdrawal_command(&mut state.engine, &wc(c, tx, 12_500));
 
         // assert
         assert!(res.is_ok());
@@ -121,7 +132,7 @@ mod tests {
         }
 
         // act
-        let res = process_withdrawal_command(&mut state, &wc(c, tx, 10_001));
+        let res = process_withdrawal_command(&mut state.engine, &wc(c, tx, 10_001));
 
         // assert
         assert!(
@@ -147,7 +158,7 @@ mod tests {
         }
 
         // act
-        let res = process_withdrawal_command(&mut state, &wc(c, tx, 10_000));
+        let res = process_withdrawal_command(&mut state.engine, &wc(c, tx, 10_000));
 
         // assert
         assert!(res.is_ok());
@@ -169,9 +180,9 @@ mod tests {
         }
 
         // act
-        process_withdrawal_command(&mut state, &wc(c, tx, 10_000)).unwrap();
+        process_withdrawal_command(&mut state.engine, &wc(c, tx, 10_000)).unwrap();
 
-        process_withdrawal_command(&mut state, &wc(c, tx, 5_000)).unwrap();
+        process_withdrawal_command(&mut state.engine, &wc(c, tx, 5_000)).unwrap();
 
         // assert: only first one applied
         let acc = state.engine.acct(c).unwrap();
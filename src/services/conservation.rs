@@ -0,0 +1,212 @@
+use crate::errors::{AppErrors, AppResult};
+use crate::models::amount::Amount;
+use crate::state::Engine;
+use serde::Serialize;
+use std::fmt::{Display, Formatter};
+use std::fs;
+
+/// The funds conservation check of a run, written by `--conservation`: the
+/// book's closing funds must equal its opening funds plus the deposits, less
+/// the withdrawals and the chargebacks the run applied.
+///
+/// Amounts are decimal strings, as in the run manifest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConservationReport {
+    /// The sum of every account's total funds before the run.
+    pub opening: String,
+    /// The sum of applied deposits.
+    pub deposited: String,
+    /// The sum of applied withdrawals.
+    pub withdrawn: String,
+    /// The sum of the deposits charged back.
+    pub charged_back: String,
+    /// What the book should hold: opening + deposited - withdrawn - charged back.
+    pub expected: String,
+    /// The sum of available funds after the run.
+    pub available: String,
+    /// The sum of held funds after the run.
+    pub held: String,
+    /// What the book holds: available + held.
+    pub closing: String,
+    /// The closing funds less the expected funds; `0.0000` when funds are
+    /// conserved.
+    pub discrepancy: String,
+    /// Whether funds are conserved.
+    pub balanced: bool,
+}
+
+/// Sums the total funds of every account in an engine.
+///
+/// # Returns
+/// * `AppResult<(Amount, Amount)>` - The sums of available and held funds, or
+///   `AppErrors::Overflow` if a sum does not fit an amount.
+pub fn book_funds(engine: &Engine) -> AppResult<(Amount, Amount)> {
+    let (mut available, mut held) = (Amount::zero(), Amount::zero());
+    for (_, acc) in engine.accounts_iter() {
+        available = available
+            .checked_add(acc.available)
+            .ok_or(AppErrors::Overflow)?;
+        held = held.checked_add(acc.held).ok_or(AppErrors::Overflow)?;
+    }
+    Ok((available, held))
+}
+
+impl ConservationReport {
+    /// Checks that an engine conserved funds over a run, from the book's
+    /// opening funds and the flows the engine tallied (see
+    /// [`Engine::flows`]).
+    ///
+    /// # Arguments
+    /// * `opening` - The sum of every account's total funds before the run.
+    /// * `engine` - The engine at the end of the run.
+    ///
+    /// # Returns
+    /// * `AppResult<Self>` - The check, or `AppErrors::Overflow` if a sum does
+    ///   not fit an amount.
+    pub fn check(opening: Amount, engine: &Engine) -> AppResult<Self> {
+        let flows = engine.flows();
+        let expected = opening
+            .checked_add(flows.deposited)
+            .and_then(|a| a.checked_sub(flows.withdrawn))
+            .and_then(|a| a.checked_sub(flows.charged_back))
+            .ok_or(AppErrors::Overflow)?;
+        let (available, held) = book_funds(engine)?;
+        let closing = available.checked_add(held).ok_or(AppErrors::Overflow)?;
+        let discrepancy = closing.checked_sub(expected).ok_or(AppErrors::Overflow)?;
+        Ok(Self {
+            opening: opening.to_string(),
+            deposited: flows.deposited.to_string(),
+            withdrawn: flows.withdrawn.to_string(),
+            charged_back: flows.charged_back.to_string(),
+            expected: expected.to_string(),
+            available: available.to_string(),
+            held: held.to_string(),
+            closing: closing.to_string(),
+            discrepancy: discrepancy.to_string(),
+            balanced: discrepancy == Amount::zero(),
+        })
+    }
+
+    /// Writes the check as pretty-printed JSON to a file, or as text to stderr.
+    ///
+    /// # Arguments
+    /// * `path` - The file, or `None` for stderr.
+    ///
+    /// # Returns
+    /// * `AppResult<()>` - `Ok(())` once written, or an `AppErrors::Io` on failure.
+    pub fn write(&self, path: Option<&str>) -> AppResult<()> {
+        let Some(path) = path else {
+            eprint!("{self}");
+            return Ok(());
+        };
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| AppErrors::Io(format!("encode {path}: {e}")))?;
+        fs::write(path, json + "\n").map_err(|e| AppErrors::Io(format!("write {path}: {e}")))
+    }
+}
+
+impl Display for ConservationReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "opening funds:   {}", self.opening)?;
+        writeln!(f, "+ deposited:     {}", self.deposited)?;
+        writeln!(f, "- withdrawn:     {}", self.withdrawn)?;
+        writeln!(f, "- charged back:  {}", self.charged_back)?;
+        writeln!(f, "= expected:      {}", self.expected)?;
+        writeln!(
+            f,
+            "closing funds:   {} (available {}, held {})",
+            self.closing, self.available, self.held
+        )?;
+        if self.balanced {
+            writeln!(f, "funds conserved")
+        } else {
+            writeln!(f, "*** DISCREPANCY: {} ***", self.discrepancy)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::tx_command::{
+        ChargebackCommand, DepositCommand, DisputeCommand, Transaction, WithdrawalCommand,
+    };
+
+    #[test]
+    fn balances_opening_funds_and_flows_against_the_book() {
+        let mut engine = Engine::default();
+        engine.acct_mut(9).available = Amount(30_000);
+        let (opening, _) = book_funds(&engine).unwrap();
+        for tx in [
+            Transaction::Deposit(DepositCommand {
+                client: 1,
+                tx: 1,
+                amount: Amount(50_000),
+            }),
+            Transaction::Deposit(DepositCommand {
+                client: 1,
+                tx: 2,
+                amount: Amount(20_000),
+            }),
+            Transaction::Withdrawal(WithdrawalCommand {
+                client: 1,
+                tx: 3,
+                amount: Amount(10_000),
+            }),
+            Transaction::Dispute(DisputeCommand { client: 1, tx: 2 }),
+            Transaction::Chargeback(ChargebackCommand { client: 1, tx: 2 }),
+        ] {
+            engine.apply(tx).unwrap();
+        }
+
+        let report = ConservationReport::check(opening, &engine).unwrap();
+        assert_eq!(report.charged_back, "2.0000");
+        assert_eq!(report.expected, "7.0000");
+        assert!(report.balanced);
+        assert!(report.to_string().ends_with("funds conserved\n"));
+
+        // Money appearing outside any command is flagged.
+        engine.acct_mut(9).held = Amount(5);
+        let report = ConservationReport::check(opening, &engine).unwrap();
+        assert!(!report.balanced);
+        assert!(report.to_string().contains("DISCREPANCY: 0.0005"));
+    }
+
+    #[test]
+    fn empty_engine_is_balanced_at_zero() {
+        let report = ConservationReport::check(Amount::zero(), &Engine::default()).unwrap();
+        assert_eq!(report.expected, "0.0000");
+        assert_eq!(report.closing, "0.0000");
+        assert!(report.balanced);
+    }
+
+    #[test]
+    fn commands_on_a_locked_account_move_no_funds() {
+        let mut engine = Engine::default();
+        engine.acct_mut(1).available = Amount(40_000);
+        engine.acct_mut(1).locked = true;
+        let (opening, _) = book_funds(&engine).unwrap();
+        for tx in [
+            Transaction::Deposit(DepositCommand {
+                client: 1,
+                tx: 1,
+                amount: Amount(50_000),
+            }),
+            Transaction::Withdrawal(WithdrawalCommand {
+                client: 1,
+                tx: 2,
+                amount: Amount(10_000),
+            }),
+        ] {
+            engine.apply(tx).unwrap();
+        }
+
+        let report = ConservationReport::check(opening, &engine).unwrap();
+        assert_eq!(
+            (report.deposited.as_str(), report.withdrawn.as_str()),
+            ("0.0000", "0.0000")
+        );
+        assert_eq!(report.closing, "4.0000");
+        assert!(report.balanced);
+    }
+}
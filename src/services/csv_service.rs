@@ -1,68 +1,842 @@
+use crate::consts::STDIN_PATH;
 use crate::errors::{AppErrors, AppResult};
 use crate::models::amount::Amount;
-use crate::models::csv_models::transaction::{CsvTxType, InputRow};
+use crate::models::csv_models::transaction::{CsvDialect, CsvTxType, InputRow, POSITIONAL_HEADERS};
+use crate::models::domain_state::Account;
+use crate::models::identifiers::{ClientId, TxId};
+use crate::models::policy::{ErrorPolicy, RowError};
 use crate::models::tx_command::{
-    ChargebackCommand, DepositCommand, DisputeCommand, ResolveCommand, WithdrawalCommand,
+    ChargebackCommand, DepositCommand, DisputeCommand, ResolveCommand, Transaction,
+    WithdrawalCommand,
 };
-use crate::services::commands::traits::tx_command_trait::TxCommandTrait;
-use crate::state::AppState;
-use csv::ReaderBuilder;
-use log::error;
-use std::fs::File;
-use std::io::BufReader;
+use crate::services::text_decoding::decode_text;
+use crate::state::{AppState, Outcome, Released};
+use csv::{Reader, ReaderBuilder, StringRecord};
+use flate2::bufread::MultiGzDecoder;
+use log::{error, warn};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
 use std::str::FromStr;
+use std::time::Instant;
+#[cfg(feature = "tokio")]
+use tokio::io::{AsyncBufReadExt, AsyncRead, Lines};
+
+/// One pass of two-pass processing (see [`AppState::pass`]): the fund
+/// movements of every input are applied before any dispute, so a dispute
+/// always finds the transaction it references however the input is ordered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pass {
+    /// Deposits and withdrawals.
+    FundMovements,
+    /// Disputes, resolves and chargebacks.
+    Disputes,
+}
+
+impl Pass {
+    /// Returns whether rows of the given type are applied in this pass.
+    ///
+    /// # Arguments
+    /// * `t` - The row's transaction type.
+    pub fn admits(self, t: CsvTxType) -> bool {
+        let moves_funds = matches!(t, CsvTxType::Deposit | CsvTxType::Withdrawal);
+        moves_funds == (self == Pass::FundMovements)
+    }
+}
+
+/// The first bytes of every gzip stream.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// The first bytes of every zstd frame.
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
 
 /// Processes transactions from a CSV file and updates the application state.
 ///
+/// A thin wrapper over [`run_from_reader`] that opens the file. Row and command
+/// errors are handled by the engine's [`ErrorPolicy`] (see
+/// [`Engine::config`](crate::state::Engine::config)), as in every function of
+/// this module.
+///
+/// # Arguments
+/// * `path` - The file path to the CSV file containing transaction data, or
+///   [`STDIN_PATH`] (`-`) to read from stdin.
+/// * `app_state` - A mutable reference to the application state.
+///
+/// # Returns
+/// * `AppResult<Vec<RowError>>` - The errors gathered under [`ErrorPolicy::Collect`]
+///   (empty otherwise), or an `AppErrors` variant if processing was aborted.
+pub fn run_from_csv_path(path: &str, app_state: &mut AppState) -> AppResult<Vec<RowError>> {
+    run_from_reader(open_text_input(path)?, app_state)
+}
+
+/// Processes transactions from a CSV file like [`run_from_csv_path`], calling
+/// `on_applied` with every applied row (see [`run_from_reader_observed`]).
+///
 /// # Arguments
 /// * `path` - The file path to the CSV file containing transaction data.
 /// * `app_state` - A mutable reference to the application state.
+/// * `on_applied` - Called with each executed row's line number, the row, its
+///   outcome, and the resulting state, in file order.
+///
+/// # Returns
+/// * `AppResult<Vec<RowError>>` - The errors gathered under [`ErrorPolicy::Collect`]
+///   (empty otherwise), or an `AppErrors` variant if processing was aborted
+///   (including any error returned by `on_applied`).
+pub fn run_from_csv_path_observed(
+    path: &str,
+    app_state: &mut AppState,
+    on_applied: impl FnMut(u64, &InputRow, Outcome, &AppState) -> AppResult<()>,
+) -> AppResult<Vec<RowError>> {
+    run_from_reader_observed(open_text_input(path)?, app_state, on_applied)
+}
+
+/// Processes transactions from any CSV source (an in-memory buffer, a socket,
+/// a decompressing stream, ...) and updates the application state.
+///
+/// # Arguments
+/// * `input` - The CSV data, including the header row.
+/// * `app_state` - A mutable reference to the application state.
+///
+/// # Returns
+/// * `AppResult<Vec<RowError>>` - The errors gathered under [`ErrorPolicy::Collect`]
+///   (empty otherwise), or an `AppErrors` variant if processing was aborted.
+pub fn run_from_reader<R: Read>(input: R, app_state: &mut AppState) -> AppResult<Vec<RowError>> {
+    run_from_reader_observed(input, app_state, |_, _, _, _| Ok(()))
+}
+
+/// Processes transactions from any CSV source like [`run_from_reader`], calling
+/// `on_applied` with every row whose command executed without error, along with
+/// its line number, its [`Outcome`] and the state after applying it (e.g., to
+/// stream applied events to a replica or evaluate alert rules). Rows the engine
+/// ignored are passed too, as [`Outcome::Ignored`], so observers that only
+/// count state changes must check the outcome.
+///
+/// # Arguments
+/// * `input` - The CSV data, including the header row.
+/// * `app_state` - A mutable reference to the application state.
+/// * `on_applied` - Called with each executed row's line number, the row, its
+///   outcome, and the resulting state, in input order.
+///
+/// # Returns
+/// * `AppResult<Vec<RowError>>` - The errors gathered under [`ErrorPolicy::Collect`]
+///   (empty otherwise), or an `AppErrors` variant if processing was aborted
+///   (including any error returned by `on_applied`).
+pub fn run_from_reader_observed<R: Read>(
+    input: R,
+    app_state: &mut AppState,
+    on_applied: impl FnMut(u64, &InputRow, Outcome, &AppState) -> AppResult<()>,
+) -> AppResult<Vec<RowError>> {
+    let mut rdr = csv_reader(input, &app_state.dialect);
+    let rows = read_rows(&mut rdr, &app_state.dialect)?.map(Ok);
+    run_rows_observed(rows, app_state, on_applied)
+}
+
+/// Processes already-decoded rows from any input format, calling `on_applied`
+/// with every applied row like [`run_from_reader_observed`].
+///
+/// # Arguments
+/// * `rows` - `(line, row)` pairs in input order, where `row` is the decoded row
+///   or the error for that record. An outer error (e.g., a truncated file) aborts
+///   processing regardless of the policy.
+/// * `app_state` - A mutable reference to the application state.
+/// * `on_applied` - Called with each executed row's line number, the row, its
+///   outcome, and the resulting state, in input order.
+///
+/// # Returns
+/// * `AppResult<Vec<RowError>>` - The errors gathered under [`ErrorPolicy::Collect`]
+///   (empty otherwise), or an `AppErrors` variant if processing was aborted.
+pub fn run_rows_observed(
+    rows: impl IntoIterator<Item = AppResult<(u64, AppResult<InputRow>)>>,
+    app_state: &mut AppState,
+    mut on_applied: impl FnMut(u64, &InputRow, Outcome, &AppState) -> AppResult<()>,
+) -> AppResult<Vec<RowError>> {
+    let policy = app_state.engine.error_policy();
+    let mut errors = Vec::new();
+    for rec in rows {
+        let (line, rec) = rec?;
+        match rec {
+            Ok(row) => apply_row(line, row, app_state, policy, &mut errors, &mut on_applied)?,
+            Err(e) => handle_malformed(line, e, app_state, policy, &mut errors)?,
+        }
+    }
+    Ok(errors)
+}
+
+/// Processes transactions from a CSV file sorted by client, emitting each account
+/// as soon as the reader moves past that client.
+///
+/// When the client id changes, the previous client's account and transaction
+/// records are evicted from the engine and the account is handed to
+/// `on_client_done`, so accounts and tx records are held only for the active
+/// client. The evicted tx ids are kept for duplicate detection, as one bit per
+/// id over the dense id range (see
+/// [`TxStore::retire`](crate::models::tx_store::TxStore::retire)), so what
+/// grows with the file is that bitmap rather than the records. A client that
+/// reappears after being emitted is logged and processed as a new account.
+///
+/// # Arguments
+/// * `path` - The file path to the CSV file containing transaction data.
+/// * `app_state` - A mutable reference to the application state.
+/// * `on_client_done` - Called with each finished client and its final account.
+///
+/// # Returns
+/// * `AppResult<Vec<RowError>>` - The errors gathered under [`ErrorPolicy::Collect`]
+///   (empty otherwise), or an `AppErrors` variant if processing was aborted
+///   (including any error returned by `on_client_done`).
+pub fn run_from_csv_path_sorted_by_client(
+    path: &str,
+    app_state: &mut AppState,
+    mut on_client_done: impl FnMut(ClientId, Account) -> AppResult<()>,
+) -> AppResult<Vec<RowError>> {
+    let policy = app_state.engine.error_policy();
+    let mut rdr = open_csv_reader(path, &app_state.dialect)?;
+    let mut errors = Vec::new();
+    let mut current: Option<ClientId> = None;
+    let mut current_txs: Vec<TxId> = Vec::new();
+    // One flag per possible client id, so tracking emitted clients takes a
+    // fixed 64 KiB however many there are.
+    let mut finished = vec![false; usize::from(ClientId::MAX) + 1];
+
+    for (line, rec) in read_rows(&mut rdr, &app_state.dialect)? {
+        let row = match rec {
+            Ok(row) => row,
+            Err(e) => {
+                handle_malformed(line, e, app_state, policy, &mut errors)?;
+                continue;
+            }
+        };
+
+        if current != Some(row.client) {
+            if let Some(done) = current.take() {
+                if let Some(acc) = app_state.engine.evict_client(done, &current_txs) {
+                    on_client_done(done, acc)?;
+                }
+                finished[usize::from(done)] = true;
+                current_txs.clear();
+            }
+            if finished[usize::from(row.client)] {
+                warn!(
+                    "client {} reappeared after being emitted; input is not sorted by client",
+                    row.client
+                );
+            }
+            current = Some(row.client);
+        }
+
+        if matches!(row.t, CsvTxType::Deposit | CsvTxType::Withdrawal) {
+            current_txs.push(row.tx);
+        }
+        apply_row(
+            line,
+            row,
+            app_state,
+            policy,
+            &mut errors,
+            &mut |_, _, _, _| Ok(()),
+        )?;
+    }
+
+    if let Some(done) = current
+        && let Some(acc) = app_state.engine.evict_client(done, &current_txs)
+    {
+        on_client_done(done, acc)?;
+    }
+    Ok(errors)
+}
+
+/// Processes transactions from an async CSV source (e.g., a network socket or an
+/// async file) without blocking the runtime while waiting for input.
+///
+/// Rows are read line by line, so quoted fields must not span lines (the
+/// engine's input format never needs them).
+///
+/// # Arguments
+/// * `input` - The CSV data, including the header row.
+/// * `app_state` - A mutable reference to the application state.
+///
+/// # Returns
+/// * `AppResult<Vec<RowError>>` - The errors gathered under [`ErrorPolicy::Collect`]
+///   (empty otherwise), or an `AppErrors` variant if processing was aborted.
+#[cfg(feature = "tokio")]
+pub async fn run_from_async_reader<R: AsyncRead + Unpin>(
+    input: R,
+    app_state: &mut AppState,
+) -> AppResult<Vec<RowError>> {
+    let policy = app_state.engine.error_policy();
+    let mut rows = AsyncRows::open(input, app_state.dialect.clone()).await?;
+    let mut errors = Vec::new();
+    while let Some((line, rec)) = rows.next().await? {
+        match rec {
+            Ok(row) => apply_row(
+                line,
+                row,
+                app_state,
+                policy,
+                &mut errors,
+                &mut |_, _, _, _| Ok(()),
+            )?,
+            Err(e) => handle_malformed(line, e, app_state, policy, &mut errors)?,
+        }
+    }
+    Ok(errors)
+}
+
+/// Reads CSV rows from an async source, one line at a time.
+#[cfg(feature = "tokio")]
+pub(crate) struct AsyncRows<R> {
+    /// The remaining input lines.
+    lines: Lines<tokio::io::BufReader<R>>,
+    /// The header row.
+    headers: StringRecord,
+    /// The input layout.
+    dialect: CsvDialect,
+    /// The 1-based line number of the last line read.
+    line: u64,
+}
+
+#[cfg(feature = "tokio")]
+impl<R: AsyncRead + Unpin> AsyncRows<R> {
+    /// Reads the header row, unless the input has none. A UTF-8 byte order mark
+    /// at the start is skipped.
+    ///
+    /// # Arguments
+    /// * `input` - The CSV data.
+    /// * `dialect` - The input layout.
+    ///
+    /// # Returns
+    /// * `AppResult<AsyncRows<R>>` - The row reader, or an `AppErrors::Io` if the
+    ///   header cannot be read.
+    pub(crate) async fn open(input: R, dialect: CsvDialect) -> AppResult<Self> {
+        let mut lines = tokio::io::BufReader::new(input).lines();
+        if dialect.no_headers {
+            return Ok(Self {
+                lines,
+                headers: StringRecord::from(POSITIONAL_HEADERS.to_vec()),
+                dialect,
+                line: 0,
+            });
+        }
+        let header = lines
+            .next_line()
+            .await
+            .map_err(|e| AppErrors::Io(format!("read csv headers: {e}")))?
+            .unwrap_or_default();
+        let headers = parse_record(header.trim_start_matches('\u{feff}'), &dialect)
+            .map_err(|e| AppErrors::Io(format!("read csv headers: {e}")))?;
+        let headers = dialect.columns.rename(&headers)?;
+        Ok(Self {
+            lines,
+            headers,
+            dialect,
+            line: 1,
+        })
+    }
+
+    /// Returns the next non-empty row with its line number, or `None` at the end
+    /// of the input.
+    ///
+    /// # Returns
+    /// * `AppResult<Option<(u64, AppResult<InputRow>)>>` - The next row (or the
+    ///   `AppErrors::Csv` for a malformed one, see [`malformed_record`]), or an
+    ///   `AppErrors::Io` if reading from the source fails.
+    pub(crate) async fn next(&mut self) -> AppResult<Option<(u64, AppResult<InputRow>)>> {
+        loop {
+            let Some(text) = self
+                .lines
+                .next_line()
+                .await
+                .map_err(|e| AppErrors::Io(format!("read csv: {e}")))?
+            else {
+                return Ok(None);
+            };
+            self.line += 1;
+            // Headerless input may start with a byte order mark.
+            let text = if self.line == 1 {
+                text.trim_start_matches('\u{feff}')
+            } else {
+                &text
+            };
+            if text.trim().is_empty() {
+                continue;
+            }
+            let row = match parse_record(text, &self.dialect) {
+                Ok(rec) => rec
+                    .deserialize(Some(&self.headers))
+                    .map_err(|e| malformed_record(e, &rec, &self.dialect)),
+                Err(e) => Err(AppErrors::Csv(e.to_string())),
+            };
+            return Ok(Some((self.line, row)));
+        }
+    }
+}
+
+/// Parses a single CSV line with the engine's input settings.
+#[cfg(feature = "tokio")]
+fn parse_record(text: &str, dialect: &CsvDialect) -> Result<StringRecord, csv::Error> {
+    let mut rec = StringRecord::new();
+    ReaderBuilder::new()
+        .has_headers(false)
+        .delimiter(dialect.delimiter.0)
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(text.as_bytes())
+        .read_record(&mut rec)?;
+    Ok(rec)
+}
+
+/// Calls `f` with the tx id of every deposit and withdrawal row in a CSV file,
+/// without applying anything. Malformed rows are skipped silently; they are
+/// reported by the processing pass.
+///
+/// # Arguments
+/// * `path` - The file path to the CSV file containing transaction data.
+/// * `dialect` - The input layout.
+/// * `f` - Called with each deposit/withdrawal tx id, in file order.
 ///
 /// # Returns
-/// * `AppResult<()>` - Returns `Ok(())` if successful, or an `AppErrors` variant if an error occurs.
-pub fn run_from_csv_path(path: &str, app_state: &mut AppState) -> AppResult<()> {
+/// * `AppResult<()>` - Returns `Ok(())` if the file was scanned, or an `AppErrors::Io`
+///   if it cannot be opened.
+pub fn scan_tx_ids(path: &str, dialect: &CsvDialect, mut f: impl FnMut(TxId)) -> AppResult<()> {
+    let mut rdr = open_csv_reader(path, dialect)?;
+    for row in read_rows(&mut rdr, dialect)?.filter_map(|(_, row)| row.ok()) {
+        if matches!(row.t, CsvTxType::Deposit | CsvTxType::Withdrawal) {
+            f(row.tx);
+        }
+    }
+    Ok(())
+}
+
+/// Discovers the `*.csv` (and compressed `*.csv.gz` / `*.csv.zst`) files under
+/// a directory, recursively, for batch runs over a drop folder.
+///
+/// Files are returned in lexicographic path order so every run over the same
+/// directory applies them in the same order. Hidden entries (names starting
+/// with `.`) are skipped, as are files with other extensions (compared
+/// case-insensitively).
+///
+/// # Arguments
+/// * `dir` - The directory to search.
+///
+/// # Returns
+/// * `AppResult<Vec<String>>` - The paths of the CSV files found, or an
+///   `AppErrors::Io` if a directory cannot be read.
+pub fn discover_csv_files(dir: &Path) -> AppResult<Vec<String>> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let entries = fs::read_dir(&dir)
+            .map_err(|e| AppErrors::Io(format!("read {}: {e}", dir.display())))?;
+        for entry in entries {
+            let path = entry
+                .map_err(|e| AppErrors::Io(format!("read {}: {e}", dir.display())))?
+                .path();
+            let name = path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_ascii_lowercase())
+                .unwrap_or_default();
+            if name.starts_with('.') {
+                continue;
+            }
+            if path.is_dir() {
+                pending.push(path);
+            } else if [".csv", ".csv.gz", ".csv.zst"]
+                .iter()
+                .any(|ext| name.ends_with(ext))
+            {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files.into_iter().map(|p| p.display().to_string()).collect())
+}
+
+/// Opens a CSV reader over the file at `path` with the engine's input settings
+/// (see [`csv_reader`]).
+///
+/// # Arguments
+/// * `path` - The file path to the CSV file.
+/// * `dialect` - The input layout.
+///
+/// # Returns
+/// * `AppResult<Reader<Box<dyn Read>>>` - The configured reader, or an `AppErrors::Io`
+///   if the file cannot be opened.
+fn open_csv_reader(path: &str, dialect: &CsvDialect) -> AppResult<Reader<Box<dyn Read>>> {
+    Ok(csv_reader(open_text_input(path)?, dialect))
+}
+
+/// Opens the input at `path` for buffered reading; [`STDIN_PATH`] reads stdin.
+/// Compressed input is decompressed transparently (see [`decompress`]).
+pub(crate) fn open_input(path: &str) -> AppResult<Box<dyn Read>> {
+    if path == STDIN_PATH {
+        return decompress(path, io::stdin().lock());
+    }
     let file = File::open(path).map_err(|e| AppErrors::Io(format!("open {path}: {e}")))?;
-    let mut rdr = ReaderBuilder::new()
+    decompress(path, BufReader::new(file))
+}
+
+/// Opens text input at `path` like [`open_input`], decoded to UTF-8 (see
+/// [`decode_text`]): byte order marks are stripped and UTF-16 is transcoded.
+pub(crate) fn open_text_input(path: &str) -> AppResult<Box<dyn Read>> {
+    decode_text(path, BufReader::new(open_input(path)?))
+}
+
+/// Wraps an input in a decoder if it starts with the gzip or zstd magic bytes,
+/// so `.gz` and `.zst` dumps (including concatenated members or frames) can be
+/// read directly. Other input is passed through unchanged.
+///
+/// # Arguments
+/// * `path` - The input path, for error messages.
+/// * `input` - The buffered input to sniff.
+///
+/// # Returns
+/// * `AppResult<Box<dyn Read>>` - The (possibly decoding) reader, an
+///   `AppErrors::Io` if the input cannot be read, or `AppErrors::InvalidInput`
+///   for zstd input when the `zstd` feature is disabled.
+fn decompress<R: BufRead + 'static>(path: &str, mut input: R) -> AppResult<Box<dyn Read>> {
+    let head = input
+        .fill_buf()
+        .map_err(|e| AppErrors::Io(format!("read {path}: {e}")))?;
+    if head.starts_with(&GZIP_MAGIC) {
+        return Ok(Box::new(MultiGzDecoder::new(input)));
+    }
+    if head.starts_with(&ZSTD_MAGIC) {
+        #[cfg(feature = "zstd")]
+        return Ok(Box::new(
+            zstd::stream::read::Decoder::with_buffer(input)
+                .map_err(|e| AppErrors::Io(format!("read {path}: {e}")))?,
+        ));
+        #[cfg(not(feature = "zstd"))]
+        return Err(AppErrors::InvalidInput(
+            "zstd-compressed input requires the `zstd` feature",
+        ));
+    }
+    Ok(Box::new(input))
+}
+
+/// Wraps a CSV source in a reader with the engine's input settings (the given
+/// dialect, all fields trimmed, flexible record lengths).
+pub(crate) fn csv_reader<R: Read>(input: R, dialect: &CsvDialect) -> Reader<R> {
+    ReaderBuilder::new()
+        .delimiter(dialect.delimiter.0)
+        .has_headers(!dialect.no_headers)
         .trim(csv::Trim::All)
         .flexible(true)
-        .from_reader(BufReader::new(file));
+        .from_reader(input)
+}
 
-    for rec in rdr.deserialize::<InputRow>() {
-        match rec {
-            Ok(row) => match row_to_command(row) {
-                Ok(cmd) => {
-                    if let Err(e) = cmd.execute(app_state) {
-                        error!("ignored command due to error: {e}");
-                    }
+/// Reads the header row, renamed per the dialect's column map (or, for
+/// headerless input, takes [`POSITIONAL_HEADERS`]) and returns an iterator over the remaining records, each paired with its
+/// 1-based line number in the input.
+///
+/// # Arguments
+/// * `rdr` - The CSV reader to consume, opened with `dialect`.
+/// * `dialect` - The input layout.
+///
+/// # Returns
+/// * `AppResult<impl Iterator>` - `(line, row)` pairs, where `row` is the deserialized
+///   record or the `AppErrors::Csv` for that record (naming its content, see
+///   [`malformed_record`]), or an `AppErrors::Io` if the header cannot be read.
+pub(crate) fn read_rows<'r, R: Read>(
+    rdr: &'r mut Reader<R>,
+    dialect: &CsvDialect,
+) -> AppResult<impl Iterator<Item = (u64, AppResult<InputRow>)> + 'r> {
+    let headers = if dialect.no_headers {
+        StringRecord::from(POSITIONAL_HEADERS.to_vec())
+    } else {
+        dialect.columns.rename(
+            rdr.headers()
+                .map_err(|e| AppErrors::Io(format!("read csv headers: {e}")))?,
+        )?
+    };
+    let dialect = dialect.clone();
+    Ok(rdr.records().map(move |rec| match rec {
+        Ok(record) => (
+            record.position().map_or(0, |p| p.line()),
+            record
+                .deserialize(Some(&headers))
+                .map_err(|e| malformed_record(e, &record, &dialect)),
+        ),
+        Err(e) => (
+            e.position().map_or(0, |p| p.line()),
+            Err(AppErrors::Csv(e.to_string())),
+        ),
+    }))
+}
+
+/// Describes a record that does not deserialize into an [`InputRow`], with its
+/// content, so a skipped row can be traced back to the input.
+///
+/// # Arguments
+/// * `e` - The deserialization error.
+/// * `record` - The record's (trimmed) fields.
+/// * `dialect` - The input layout, whose delimiter joins the fields.
+///
+/// # Returns
+/// * `AppErrors` - An `AppErrors::Csv` ending with `record: <fields>`.
+fn malformed_record(e: csv::Error, record: &StringRecord, dialect: &CsvDialect) -> AppErrors {
+    let delimiter = char::from(dialect.delimiter.0).to_string();
+    let fields: Vec<&str> = record.iter().collect();
+    AppErrors::Csv(format!("{e}; record: {}", fields.join(&delimiter)))
+}
+
+/// Handles a record that could not be deserialized: logs it under
+/// [`ErrorPolicy::Skip`]/[`ErrorPolicy::FailFast`] (as a warning under
+/// [`ErrorPolicy::Warn`]), gathers it under
+/// [`ErrorPolicy::Collect`], or returns it under [`ErrorPolicy::Strict`], and
+/// counts it in the [`AppState::summary`] and logs it in the
+/// [`AppState::rejects`]. In the [`Pass::Disputes`] pass, the record was
+/// already handled in the first pass and is skipped.
+///
+/// # Returns
+/// * `AppResult<()>` - `Ok(())` unless the policy is strict, in which case the
+///   error is returned tagged with the row's line number.
+pub(crate) fn handle_malformed(
+    line: u64,
+    e: AppErrors,
+    app_state: &mut AppState,
+    policy: ErrorPolicy,
+    errors: &mut Vec<RowError>,
+) -> AppResult<()> {
+    if app_state.pass == Some(Pass::Disputes) {
+        // Already handled in the fund movements pass.
+        return Ok(());
+    }
+    if let Some(summary) = app_state.summary.as_mut() {
+        summary.row();
+    }
+    if let Some(rejects) = app_state.rejects.as_mut() {
+        rejects.error(line, None, &e);
+    }
+    match policy {
+        ErrorPolicy::Collect => errors.push(RowError {
+            line,
+            client: None,
+            tx: None,
+            error: e,
+        }),
+        ErrorPolicy::Skip | ErrorPolicy::FailFast => error!("line {line}: skip malformed row: {e}"),
+        ErrorPolicy::Warn => warn!("line {line}: skip malformed row: {e}"),
+        ErrorPolicy::Strict => {
+            return Err(AppErrors::AtLine {
+                line,
+                source: Box::new(e),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Converts a parsed row into a command and executes it.
+///
+/// Rows of types the current [`AppState::pass`] does not admit, and rows of
+/// clients another instance owns under the [`AppState::shard`] router, are
+/// skipped silently. Rows that cannot be converted, or whose amount is
+/// finer than the [`AppState::currency`] allows, are skipped (logged or
+/// collected). Commands the [`AppState::exposure`] guard quarantines are not
+/// applied. With [`AppState::latency`] set, the time each command takes to
+/// apply is recorded, and with [`AppState::summary`] set, the row and the
+/// command's outcome are counted. With [`AppState::rejects`] set, every row
+/// skipped, quarantined, failed or ignored is logged there. Command errors are
+/// logged, collected, or, under [`ErrorPolicy::FailFast`], returned tagged with
+/// the row's line number; under [`ErrorPolicy::Strict`], so are rows that
+/// cannot be converted. Commands the orphan buffer releases because of the
+/// row are reported the same way, under the row's line number, after it; they
+/// are not passed to `on_applied`.
+///
+/// # Arguments
+/// * `line` - The line number of the row in the input.
+/// * `row` - A single row from the CSV file.
+/// * `app_state` - A mutable reference to the application state.
+/// * `policy` - How errors are handled.
+/// * `errors` - Where errors are gathered under [`ErrorPolicy::Collect`].
+/// * `on_applied` - Called with the row and its outcome if its command executed
+///   without error.
+///
+/// # Returns
+/// * `AppResult<()>` - `Ok(())` unless the policy is fail-fast and the command failed,
+///   the policy is strict and the row could not be processed, or `on_applied`
+///   returned an error.
+pub(crate) fn apply_row(
+    line: u64,
+    row: InputRow,
+    app_state: &mut AppState,
+    policy: ErrorPolicy,
+    errors: &mut Vec<RowError>,
+    on_applied: &mut dyn FnMut(u64, &InputRow, Outcome, &AppState) -> AppResult<()>,
+) -> AppResult<()> {
+    if let Some(pass) = app_state.pass
+        && !pass.admits(row.t)
+    {
+        return Ok(());
+    }
+    if let Some(summary) = app_state.summary.as_mut() {
+        summary.row();
+    }
+    if let Some(router) = app_state.shard.as_mut()
+        && !router.admit(row.client)
+    {
+        return Ok(());
+    }
+    let (client, tx) = (Some(row.client), Some(row.tx));
+    let checked = match &app_state.currency {
+        Some(currency) => currency.check(&row),
+        None => Ok(()),
+    };
+    let transaction = match checked.and_then(|_| row_to_transaction(&row)) {
+        Ok(transaction) => transaction,
+        Err(e) => {
+            if let Some(rejects) = app_state.rejects.as_mut() {
+                rejects.error(line, Some(&row), &e);
+            }
+            match policy {
+                ErrorPolicy::Collect => errors.push(RowError {
+                    line,
+                    client,
+                    tx,
+                    error: e,
+                }),
+                ErrorPolicy::Skip | ErrorPolicy::FailFast => {
+                    error!("line {line}: skip row `{row}`: {e}")
                 }
-                Err(e) => {
-                    error!("skip row: {e}");
+                ErrorPolicy::Warn => warn!("line {line}: skip row `{row}`: {e}"),
+                ErrorPolicy::Strict => {
+                    return Err(AppErrors::AtLine {
+                        line,
+                        source: Box::new(e),
+                    });
                 }
-            },
-            Err(e) => {
-                error!("skip malformed CSV row: {e}");
             }
+            return Ok(());
         }
+    };
+    if let Some(guard) = app_state.exposure.as_mut()
+        && !guard.admit(line, &row, &transaction, &app_state.engine)
+    {
+        if let Some(rejects) = app_state.rejects.as_mut() {
+            rejects.dropped(line, &row, "quarantined");
+        }
+        return Ok(());
+    }
+    let started = app_state.latency.is_some().then(Instant::now);
+    let mut released = Vec::new();
+    let applied = app_state.engine.apply_releasing(transaction, &mut released);
+    if let (Some(latency), Some(started)) = (app_state.latency.as_mut(), started) {
+        latency.record(row.t, started.elapsed());
+    }
+    let outcome = report_command(line, &row, applied, app_state, policy, errors)?;
+    for Released {
+        transaction,
+        result,
+    } in released
+    {
+        let released_row = transaction_to_row(&transaction);
+        report_command(line, &released_row, result, app_state, policy, errors)?;
+    }
+    match outcome {
+        Some(outcome) => on_applied(line, &row, outcome, app_state),
+        None => Ok(()),
     }
-    Ok(())
 }
 
-/// Converts a CSV row into a transaction command.
+/// Reports a command's result: counts it in the [`AppState::summary`], logs
+/// it in the [`AppState::rejects`] unless applied, and handles an error per
+/// the policy (logged, collected, or returned tagged with the line number).
+///
+/// # Arguments
+/// * `line` - The line number of the row that caused the command.
+/// * `row` - The command as a row.
+/// * `result` - The command's result.
+/// * `app_state` - A mutable reference to the application state.
+/// * `policy` - How errors are handled.
+/// * `errors` - Where errors are gathered under [`ErrorPolicy::Collect`].
+///
+/// # Returns
+/// * `AppResult<Option<Outcome>>` - The outcome if the command executed without
+///   error, or the error under [`ErrorPolicy::FailFast`] and
+///   [`ErrorPolicy::Strict`].
+fn report_command(
+    line: u64,
+    row: &InputRow,
+    result: AppResult<Outcome>,
+    app_state: &mut AppState,
+    policy: ErrorPolicy,
+    errors: &mut Vec<RowError>,
+) -> AppResult<Option<Outcome>> {
+    if let Some(summary) = app_state.summary.as_mut() {
+        summary.command(row.t, &result);
+    }
+    if let Some(rejects) = app_state.rejects.as_mut() {
+        match &result {
+            Ok(Outcome::Applied) => {}
+            Ok(Outcome::Ignored(reason)) => rejects.dropped(line, row, reason.as_str()),
+            Err(e) => rejects.error(line, Some(row), e),
+        }
+    }
+    let e = match result {
+        Ok(outcome) => return Ok(Some(outcome)),
+        Err(e) => e,
+    };
+    match policy {
+        ErrorPolicy::Skip => error!("line {line}: ignored command `{row}` due to error: {e}"),
+        ErrorPolicy::Warn => warn!("line {line}: ignored command `{row}` due to error: {e}"),
+        ErrorPolicy::Collect => errors.push(RowError {
+            line,
+            client: Some(row.client),
+            tx: Some(row.tx),
+            error: e,
+        }),
+        ErrorPolicy::FailFast | ErrorPolicy::Strict => {
+            return Err(AppErrors::AtLine {
+                line,
+                source: Box::new(e),
+            });
+        }
+    }
+    Ok(None)
+}
+
+/// Converts a [`Transaction`] back into a CSV row, e.g. to report a command
+/// the engine released from its orphan buffer.
+///
+/// # Arguments
+/// * `transaction` - The transaction.
+///
+/// # Returns
+/// * `InputRow` - The row that converts into the same transaction.
+pub fn transaction_to_row(transaction: &Transaction) -> InputRow {
+    let (t, amount) = match transaction {
+        Transaction::Deposit(cmd) => (CsvTxType::Deposit, Some(cmd.amount.to_string())),
+        Transaction::Withdrawal(cmd) => (CsvTxType::Withdrawal, Some(cmd.amount.to_string())),
+        Transaction::Dispute(_) => (CsvTxType::Dispute, None),
+        Transaction::Resolve(_) => (CsvTxType::Resolve, None),
+        Transaction::Chargeback(_) => (CsvTxType::Chargeback, None),
+    };
+    InputRow {
+        t,
+        client: transaction.client(),
+        tx: transaction.tx(),
+        amount,
+    }
+}
+
+/// Converts a CSV row into a [`Transaction`].
 ///
 /// # Arguments
 /// * `row` - A single row from the CSV file, parsed into an `InputRow` struct.
 ///
 /// # Returns
-/// * `AppResult<Box<dyn TxCommandTrait>>` - Returns a boxed transaction command if successful,
+/// * `AppResult<Transaction>` - Returns the transaction if successful,
 ///   or an `AppErrors` variant if an error occurs.
-fn row_to_command(row: InputRow) -> AppResult<Box<dyn TxCommandTrait>> {
+pub fn row_to_transaction(row: &InputRow) -> AppResult<Transaction> {
     match row.t {
         CsvTxType::Deposit => {
             let s = row
                 .amount
-                .ok_or(AppErrors::InvalidInput("deposit missing amount"))?;
-            let amount = Amount::from_str(&s).map_err(|_| AppErrors::InvalidInput("bad amount"))?;
-            Ok(Box::new(DepositCommand {
+                .as_deref()
+                .ok_or(AppErrors::MissingAmount("deposit"))?;
+            let amount = Amount::from_str(s)?;
+            Ok(Transaction::Deposit(DepositCommand {
                 client: row.client,
                 tx: row.tx,
                 amount,
@@ -71,25 +845,341 @@ fn row_to_command(row: InputRow) -> AppResult<Box<dyn TxCommandTrait>> {
         CsvTxType::Withdrawal => {
             let s = row
                 .amount
-                .ok_or(AppErrors::InvalidInput("withdrawal missing amount"))?;
-            let amount = Amount::from_str(&s).map_err(|_| AppErrors::InvalidInput("bad amount"))?;
-            Ok(Box::new(WithdrawalCommand {
+                .as_deref()
+                .ok_or(AppErrors::MissingAmount("withdrawal"))?;
+            let amount = Amount::from_str(s)?;
+            Ok(Transaction::Withdrawal(WithdrawalCommand {
                 client: row.client,
                 tx: row.tx,
                 amount,
             }))
         }
-        CsvTxType::Dispute => Ok(Box::new(DisputeCommand {
+        CsvTxType::Dispute => Ok(Transaction::Dispute(DisputeCommand {
             client: row.client,
             tx: row.tx,
         })),
-        CsvTxType::Resolve => Ok(Box::new(ResolveCommand {
+        CsvTxType::Resolve => Ok(Transaction::Resolve(ResolveCommand {
             client: row.client,
             tx: row.tx,
         })),
-        CsvTxType::Chargeback => Ok(Box::new(ChargebackCommand {
+        CsvTxType::Chargeback => Ok(Transaction::Chargeback(ChargebackCommand {
             client: row.client,
             tx: row.tx,
         })),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::csv_models::transaction::{ColumnMap, Delimiter};
+    use crate::models::policy::EngineConfig;
+    use crate::state::IgnoreReason;
+
+    #[test]
+    fn run_from_reader_processes_in_memory_input() {
+        let input = "type, client, tx, amount\n\
+                     deposit, 1, 1, 2.5\n\
+                     withdrawal, 1, 2, 1.0\n\
+                     withdrawal, 1, 3\n";
+        let mut state = AppState::default();
+        state.engine.set_error_policy(ErrorPolicy::Collect);
+
+        let errors = run_from_reader(input.as_bytes(), &mut state).unwrap();
+
+        assert_eq!(state.engine.acct(1).unwrap().available, Amount(15_000));
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, 4);
+        assert!(matches!(
+            errors[0].error,
+            AppErrors::MissingAmount("withdrawal")
+        ));
+    }
+
+    #[test]
+    fn strict_policy_stops_at_the_first_bad_row() {
+        for (bad_row, line) in [
+            ("deposit, x, 2, 1.0", 3),
+            ("withdrawal, 1, 2", 3),
+            ("deposit, 1, 2, 1.2.3", 3),
+        ] {
+            let input = format!(
+                "type, client, tx, amount\ndeposit, 1, 1, 2.5\n{bad_row}\ndeposit, 1, 3, 1.0\n"
+            );
+            let mut state = AppState::default();
+            state.engine.set_error_policy(ErrorPolicy::Strict);
+
+            let result = run_from_reader(input.as_bytes(), &mut state);
+
+            assert!(
+                matches!(result, Err(AppErrors::AtLine { line: l, .. }) if l == line),
+                "{bad_row}"
+            );
+            assert_eq!(state.engine.acct(1).unwrap().available, Amount(25_000));
+        }
+    }
+
+    #[test]
+    fn error_policy_comes_from_the_engine_config() {
+        let input = "type, client, tx, amount\n\
+                     deposit, 1, 1, 2.5\n\
+                     withdrawal, 1, 2\n\
+                     deposit, 1, 3, 1.0\n";
+        let mut state = AppState::default();
+        state.engine.set_config(EngineConfig {
+            error_policy: ErrorPolicy::Warn,
+            ..EngineConfig::default()
+        });
+
+        let errors = run_from_reader(input.as_bytes(), &mut state).unwrap();
+
+        assert!(errors.is_empty());
+        assert_eq!(state.engine.acct(1).unwrap().available, Amount(35_000));
+
+        let mut state = AppState::default();
+        state.engine.set_error_policy(ErrorPolicy::Collect);
+        let errors = run_from_reader(input.as_bytes(), &mut state).unwrap();
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn reads_semicolon_and_tab_delimited_input() {
+        for (delimiter, input) in [
+            (";", "type; client; tx; amount\ndeposit; 1; 1; 2.5\n"),
+            ("tab", "type\tclient\ttx\tamount\ndeposit\t1\t1\t2.5\n"),
+        ] {
+            let mut state = AppState {
+                dialect: CsvDialect {
+                    delimiter: delimiter.parse().unwrap(),
+                    ..CsvDialect::default()
+                },
+                ..AppState::default()
+            };
+            state.engine.set_error_policy(ErrorPolicy::Collect);
+
+            let errors = run_from_reader(input.as_bytes(), &mut state).unwrap();
+
+            assert!(errors.is_empty());
+            assert_eq!(state.engine.acct(1).unwrap().available, Amount(25_000));
+        }
+        assert!("\"".parse::<Delimiter>().is_err());
+        assert!(";;".parse::<Delimiter>().is_err());
+    }
+
+    #[test]
+    fn reads_headerless_input_by_position() {
+        let input = "deposit, 1, 1, 2.5\n\
+                     withdrawal, 1, 2\n\
+                     dispute, 1, 1\n";
+        let mut state = AppState {
+            dialect: CsvDialect {
+                no_headers: true,
+                ..CsvDialect::default()
+            },
+            ..AppState::default()
+        };
+        state.engine.set_error_policy(ErrorPolicy::Collect);
+
+        let errors = run_from_reader(input.as_bytes(), &mut state).unwrap();
+
+        // The first row is applied, not taken as a header.
+        assert_eq!(state.engine.acct(1).unwrap().held, Amount(25_000));
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, 2);
+    }
+
+    #[test]
+    fn two_passes_apply_disputes_after_every_fund_movement() {
+        let input = "type,client,tx,amount\n\
+                     dispute,1,1,\n\
+                     deposit,1,x,1.0\n\
+                     deposit,1,1,2.5\n\
+                     deposit,1,2,0.5\n";
+        let mut state = AppState::default();
+        state.engine.set_error_policy(ErrorPolicy::Collect);
+
+        let mut errors = Vec::new();
+        for pass in [Pass::FundMovements, Pass::Disputes] {
+            state.pass = Some(pass);
+            errors.extend(run_from_reader(input.as_bytes(), &mut state).unwrap());
+        }
+
+        // The malformed row is reported once, in the first pass.
+        assert_eq!(errors.len(), 1);
+        let acc = state.engine.acct(1).unwrap();
+        assert_eq!((acc.available, acc.held), (Amount(5_000), Amount(25_000)));
+    }
+
+    #[test]
+    fn reads_renamed_and_reordered_columns_through_a_map() {
+        // The file's own `type` column is shadowed by the mapped `kind`.
+        let input = "amount, customer_id, type, txn_id, kind\n\
+                     2.5, 7, x, 1, deposit\n\
+                     1.0, 7, x, 2, withdrawal\n";
+        let mut state = AppState {
+            dialect: CsvDialect {
+                columns: "type=kind, client=customer_id, tx=txn_id".parse().unwrap(),
+                ..CsvDialect::default()
+            },
+            ..AppState::default()
+        };
+        state.engine.set_error_policy(ErrorPolicy::Collect);
+
+        let errors = run_from_reader(input.as_bytes(), &mut state).unwrap();
+
+        assert!(errors.is_empty());
+        assert_eq!(state.engine.acct(7).unwrap().available, Amount(15_000));
+        state.dialect.columns = "tx=id".parse().unwrap();
+        assert!(run_from_reader(input.as_bytes(), &mut state).is_err());
+        assert!("kind=type".parse::<ColumnMap>().is_err());
+        assert!("tx=a,tx=b".parse::<ColumnMap>().is_err());
+    }
+
+    #[test]
+    fn gzip_input_is_decompressed_transparently() {
+        use flate2::Compression;
+        use flate2::write::GzEncoder;
+        use std::io::Write;
+
+        let path = std::env::temp_dir().join(format!("pe-{}-in.csv.gz", std::process::id()));
+        let mut gz = GzEncoder::new(File::create(&path).unwrap(), Compression::default());
+        gz.write_all(b"type,client,tx,amount\ndeposit,1,1,2.5\n")
+            .unwrap();
+        gz.finish().unwrap();
+        let mut state = AppState::default();
+
+        let errors = run_from_csv_path(&path.display().to_string(), &mut state);
+        fs::remove_file(&path).unwrap();
+
+        assert!(errors.unwrap().is_empty());
+        assert_eq!(state.engine.acct(1).unwrap().available, Amount(25_000));
+    }
+
+    #[test]
+    fn observers_are_told_which_rows_the_engine_ignored() {
+        let input = "type,client,tx,amount\n\
+                     deposit,1,1,1.00005\n\
+                     deposit,1,1,1.00005\n\
+                     withdrawal,1,2,9.0\n";
+        let mut state = AppState::default();
+        let mut seen = Vec::new();
+
+        let errors =
+            run_from_reader_observed(input.as_bytes(), &mut state, |line, _, outcome, _| {
+                seen.push((line, outcome));
+                Ok(())
+            })
+            .unwrap();
+
+        assert_eq!(
+            seen,
+            [
+                (2, Outcome::Applied),
+                (3, Outcome::Ignored(IgnoreReason::DuplicateTx)),
+                (4, Outcome::Ignored(IgnoreReason::InsufficientFunds)),
+            ]
+        );
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn sorted_run_keeps_evicted_tx_ids_seen() {
+        let path = std::env::temp_dir().join(format!("pe-{}-sorted.csv", std::process::id()));
+        fs::write(
+            &path,
+            "type,client,tx,amount\n\
+             deposit,1,1,2.5\n\
+             deposit,2,1,4.0\n\
+             deposit,2,2,1.0\n",
+        )
+        .unwrap();
+        let mut state = AppState::default();
+        let mut emitted = Vec::new();
+
+        let errors = run_from_csv_path_sorted_by_client(
+            &path.display().to_string(),
+            &mut state,
+            |c, acc| {
+                emitted.push((c, acc.available));
+                Ok(())
+            },
+        );
+        fs::remove_file(&path).unwrap();
+
+        assert!(errors.unwrap().is_empty());
+        // Client 2's reuse of tx 1 is a duplicate even after client 1 was evicted.
+        assert_eq!(emitted, [(1, Amount(25_000)), (2, Amount(10_000))]);
+        assert!(state.engine.acct(1).is_none());
+        assert!(state.engine.txs.contains_key(&1));
+        // No record slots are left behind; the ids are kept as retired bits.
+        assert_eq!(state.engine.txs.dense_slots(), 0);
+        assert_eq!(state.engine.txs.retired_len(), 2);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd_input_is_decompressed_transparently() {
+        let csv = b"type,client,tx,amount\ndeposit,1,1,2.5\n";
+        let compressed = zstd::encode_all(&csv[..], 0).unwrap();
+        let mut state = AppState::default();
+
+        let input = decompress("test", io::Cursor::new(compressed)).unwrap();
+        let errors = run_from_reader(input, &mut state).unwrap();
+
+        assert!(errors.is_empty());
+        assert_eq!(state.engine.acct(1).unwrap().available, Amount(25_000));
+    }
+
+    #[test]
+    fn discover_csv_files_walks_the_directory_in_order() {
+        let root = std::env::temp_dir().join(format!("pe-{}-dir", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("b")).unwrap();
+        fs::create_dir_all(root.join(".hidden")).unwrap();
+        for name in [
+            "c.csv",
+            "a.CSV",
+            "b/x.csv",
+            "b/y.csv.gz",
+            "notes.txt",
+            ".hidden/y.csv",
+        ] {
+            fs::write(root.join(name), "type,client,tx,amount\n").unwrap();
+        }
+
+        let files = discover_csv_files(&root).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        let names: Vec<_> = files
+            .iter()
+            .map(|f| {
+                Path::new(f)
+                    .strip_prefix(&root)
+                    .unwrap()
+                    .display()
+                    .to_string()
+            })
+            .collect();
+        assert_eq!(names, vec!["a.CSV", "b/x.csv", "b/y.csv.gz", "c.csv"]);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn run_from_async_reader_matches_sync_reader() {
+        let input = "type,client,tx,amount\n\
+                     deposit,1,1,2.5\n\
+                     \n\
+                     withdrawal,1,2,1.0\n\
+                     bogus,1,3,1.0\n";
+        let mut state = AppState::default();
+        state.engine.set_error_policy(ErrorPolicy::Collect);
+
+        let errors = run_from_async_reader(input.as_bytes(), &mut state)
+            .await
+            .unwrap();
+
+        assert_eq!(state.engine.acct(1).unwrap().available, Amount(15_000));
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, 5);
+    }
+}
@@ -0,0 +1,221 @@
+use crate::errors::{AppErrors, AppResult};
+use crate::models::csv_models::transaction::{CsvTxType, InputRow};
+use std::collections::HashMap;
+use std::fs;
+
+/// Active ISO 4217 currencies whose minor unit is not two decimal places.
+const NON_DECIMAL_2: &[(&str, u8)] = &[
+    ("BIF", 0),
+    ("CLP", 0),
+    ("DJF", 0),
+    ("GNF", 0),
+    ("ISK", 0),
+    ("JPY", 0),
+    ("KMF", 0),
+    ("KRW", 0),
+    ("PYG", 0),
+    ("RWF", 0),
+    ("UGX", 0),
+    ("UYI", 0),
+    ("VND", 0),
+    ("VUV", 0),
+    ("XAF", 0),
+    ("XOF", 0),
+    ("XPF", 0),
+    ("BHD", 3),
+    ("IQD", 3),
+    ("JOD", 3),
+    ("KWD", 3),
+    ("LYD", 3),
+    ("OMR", 3),
+    ("TND", 3),
+    ("CLF", 4),
+    ("UYW", 4),
+];
+
+/// Active ISO 4217 currencies with two decimal places.
+const DECIMAL_2: &str = "AED AFN ALL AMD ANG AOA ARS AUD AWG AZN BAM BBD BDT BGN BMD BND BOB BOV \
+    BRL BSD BTN BWP BYN BZD CAD CDF CHE CHF CHW CNY COP COU CRC CUP CVE CZK DKK DOP DZD EGP ERN \
+    ETB EUR FJD FKP GBP GEL GHS GIP GMD GTQ GYD HKD HNL HTG HUF IDR ILS INR IRR JMD KES KGS KHR \
+    KPW KYD KZT LAK LBP LKR LRD LSL MAD MDL MGA MKD MMK MNT MOP MRU MUR MVR MWK MXN MXV MYR MZN \
+    NAD NGN NIO NOK NPR NZD PAB PEN PGK PHP PKR PLN QAR RON RSD RUB SAR SBD SCR SDG SEK SGD SHP \
+    SLE SOS SRD SSP STN SVC SYP SZL THB TJS TMT TOP TRY TTD TWD TZS UAH USD USN UYU UZS VED VES \
+    WST XCD XCG YER ZAR ZMW ZWG";
+
+/// The number of decimal places (minor units) of each currency, from the bundled
+/// ISO 4217 table, optionally overridden by a JSON file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CurrencyTable {
+    /// Minor units by upper-case currency code.
+    minor_units: HashMap<String, u8>,
+}
+
+impl CurrencyTable {
+    /// Returns the bundled ISO 4217 table of active currencies.
+    pub fn bundled() -> Self {
+        let mut minor_units: HashMap<String, u8> = DECIMAL_2
+            .split_whitespace()
+            .map(|code| (code.to_string(), 2))
+            .collect();
+        minor_units.extend(NON_DECIMAL_2.iter().map(|(c, d)| (c.to_string(), *d)));
+        Self { minor_units }
+    }
+
+    /// Applies overrides from a JSON object of currency codes to minor units,
+    /// e.g. `{"ISK": 2, "XTS": 3}`, adding or replacing entries.
+    ///
+    /// # Arguments
+    /// * `path` - The JSON file.
+    ///
+    /// # Returns
+    /// * `AppResult<Self>` - The table with the overrides applied, or an
+    ///   `AppErrors` variant if the file cannot be read, is not such an object, or
+    ///   sets more than four decimal places (the engine's precision).
+    pub fn with_overrides(mut self, path: &str) -> AppResult<Self> {
+        let text =
+            fs::read_to_string(path).map_err(|e| AppErrors::Io(format!("read {path}: {e}")))?;
+        let overrides: HashMap<String, u8> = serde_json::from_str(&text)
+            .map_err(|e| AppErrors::Decode(format!("currency table {path}: {e}")))?;
+        for (code, digits) in overrides {
+            if digits > 4 {
+                return Err(AppErrors::InvalidInput(
+                    "currency minor units cannot exceed the engine's four decimal places",
+                ));
+            }
+            self.minor_units.insert(code.to_ascii_uppercase(), digits);
+        }
+        Ok(self)
+    }
+
+    /// Looks up a currency.
+    ///
+    /// # Arguments
+    /// * `code` - The currency code, in any case.
+    ///
+    /// # Returns
+    /// * `AppResult<Currency>` - The currency, or `AppErrors::InvalidInput` if the
+    ///   table does not list it.
+    pub fn currency(&self, code: &str) -> AppResult<Currency> {
+        let code = code.to_ascii_uppercase();
+        let minor_units = *self
+            .minor_units
+            .get(&code)
+            .ok_or(AppErrors::InvalidInput("unknown ISO 4217 currency code"))?;
+        Ok(Currency { code, minor_units })
+    }
+}
+
+/// The currency the input amounts are denominated in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Currency {
+    /// The upper-case ISO 4217 code.
+    pub code: String,
+    /// The number of decimal places an amount may have.
+    pub minor_units: u8,
+}
+
+impl Currency {
+    /// Checks that a deposit or withdrawal amount respects the currency's minor
+    /// units, e.g. rejects `100.5` in JPY. Trailing zeros do not count, so
+    /// `100.00` is a valid JPY amount; rows without an amount pass.
+    ///
+    /// # Arguments
+    /// * `row` - The input row.
+    ///
+    /// # Returns
+    /// * `AppResult<()>` - `Ok(())` if the amount is valid for the currency, or
+    ///   `AppErrors::InvalidPrecisionForCurrency` otherwise.
+    pub fn check(&self, row: &InputRow) -> AppResult<()> {
+        if !matches!(row.t, CsvTxType::Deposit | CsvTxType::Withdrawal) {
+            return Ok(());
+        }
+        let Some((_, frac)) = row.amount.as_deref().and_then(|a| a.trim().split_once('.')) else {
+            return Ok(());
+        };
+        if frac.trim_end_matches('0').len() > usize::from(self.minor_units) {
+            return Err(AppErrors::InvalidPrecisionForCurrency {
+                currency: self.code.clone(),
+                minor_units: self.minor_units,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+
+    fn deposit(amount: &str) -> InputRow {
+        InputRow {
+            t: CsvTxType::Deposit,
+            client: 1,
+            tx: 1,
+            amount: Some(amount.to_string()),
+        }
+    }
+
+    #[test]
+    fn checks_minor_units_and_applies_overrides() {
+        let table = CurrencyTable::bundled();
+        let jpy = table.currency("jpy").unwrap();
+        assert!(jpy.check(&deposit("100")).is_ok());
+        assert!(jpy.check(&deposit("100.00")).is_ok());
+        let err = jpy.check(&deposit("100.5")).unwrap_err();
+        assert_eq!(err.code(), "E1005");
+        assert!(err.to_string().contains("invalid_precision_for_currency"));
+        let bhd = table.currency("BHD").unwrap();
+        assert!(bhd.check(&deposit("1.125")).is_ok());
+        assert!(bhd.check(&deposit("1.1255")).is_err());
+        assert_eq!(table.currency("EUR").unwrap().minor_units, 2);
+        assert!(table.currency("XTS").is_err());
+
+        let path = temp_dir().join("payments_engine_currency_table.json");
+        fs::write(&path, r#"{"xts": 1, "JPY": 2}"#).unwrap();
+        let table = table.with_overrides(path.to_str().unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(table.currency("XTS").unwrap().minor_units, 1);
+        assert!(
+            table
+                .currency("JPY")
+                .unwrap()
+                .check(&deposit("1.25"))
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn rows_without_amounts_and_dispute_rows_pass() {
+        let jpy = CurrencyTable::bundled().currency("JPY").unwrap();
+        let mut row = deposit("1.5");
+        row.amount = None;
+        assert!(jpy.check(&row).is_ok());
+        row.t = CsvTxType::Dispute;
+        row.amount = Some("1.5".to_string());
+        assert!(jpy.check(&row).is_ok());
+        assert!(jpy.check(&deposit(" 7. ")).is_ok());
+    }
+
+    #[test]
+    fn malformed_or_out_of_range_overrides_are_rejected() {
+        let path = temp_dir().join(format!("pe-{}-currency-bad.json", std::process::id()));
+        let path_str = path.to_str().unwrap();
+        for (json, decode) in [
+            ("", true),
+            (r#"{"XTS": "two"}"#, true),
+            (r#"{"XTS": 5}"#, false),
+        ] {
+            fs::write(&path, json).unwrap();
+            let err = CurrencyTable::bundled()
+                .with_overrides(path_str)
+                .unwrap_err();
+            assert_eq!(matches!(err, AppErrors::Decode(_)), decode, "{json}: {err}");
+        }
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            CurrencyTable::bundled().with_overrides(path_str),
+            Err(AppErrors::Io(_))
+        ));
+    }
+}
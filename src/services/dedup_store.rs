@@ -0,0 +1,251 @@
+use crate::errors::{AppErrors, AppResult};
+use crate::models::csv_models::transaction::CsvDialect;
+use crate::models::identifiers::TxId;
+use crate::services::csv_service::scan_tx_ids;
+use crate::state::Engine;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+
+/// Magic header identifying a seen-tx-ids file (format version 1).
+const MAGIC: &[u8; 8] = b"PESEEN01";
+
+/// Loads a persisted set of seen tx ids into the engine, so that transactions
+/// replayed from a previous run are still detected as duplicates.
+///
+/// A missing file is treated as an empty set (first run).
+///
+/// # Arguments
+/// * `path` - The path to the seen-ids file.
+/// * `engine` - The engine to mark the ids as seen in.
+///
+/// # Returns
+/// * `AppResult<usize>` - The number of ids loaded, or an `AppErrors` variant if the
+///   file cannot be read or is malformed.
+pub fn load_seen_ids(path: &str, engine: &mut Engine) -> AppResult<usize> {
+    let file = match File::open(path) {
+        Ok(f) => f,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(AppErrors::Io(format!("open {path}: {e}"))),
+    };
+    let ids = decode_ids(BufReader::new(file))?;
+    for tx in &ids {
+        engine.txs.mark_seen(*tx);
+    }
+    Ok(ids.len())
+}
+
+/// Persists every tx id the engine has seen, replacing the file atomically
+/// (write to a temporary file, then rename).
+///
+/// # Arguments
+/// * `path` - The path to the seen-ids file.
+/// * `engine` - The engine whose seen ids are saved.
+///
+/// # Returns
+/// * `AppResult<usize>` - The number of ids written, or an `AppErrors::Io` on failure.
+pub fn save_seen_ids(path: &str, engine: &Engine) -> AppResult<usize> {
+    let mut ids = engine.txs.seen_ids();
+    ids.sort_unstable();
+    ids.dedup();
+
+    let tmp = format!("{path}.tmp");
+    let file = File::create(&tmp).map_err(|e| AppErrors::Io(format!("create {tmp}: {e}")))?;
+    let mut out = BufWriter::new(file);
+    encode_ids(&ids, &mut out).map_err(|e| AppErrors::Io(format!("write {tmp}: {e}")))?;
+    out.flush()
+        .map_err(|e| AppErrors::Io(format!("flush {tmp}: {e}")))?;
+    fs::rename(&tmp, path).map_err(|e| AppErrors::Io(format!("rename {tmp}: {e}")))?;
+    Ok(ids.len())
+}
+
+/// Summary of how much an input file overlaps tx ids seen by earlier runs.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct OverlapReport {
+    /// The number of deposit/withdrawal rows in the input.
+    pub total: usize,
+    /// The number of those rows whose tx id was already seen.
+    pub overlapping: usize,
+    /// The smallest overlapping tx id, if any.
+    pub first: Option<TxId>,
+    /// The largest overlapping tx id, if any.
+    pub last: Option<TxId>,
+}
+
+impl OverlapReport {
+    /// Records one input tx id, noting whether it was already seen.
+    fn record(&mut self, tx: TxId, seen: bool) {
+        self.total += 1;
+        if seen {
+            self.overlapping += 1;
+            self.first = Some(self.first.map_or(tx, |f| f.min(tx)));
+            self.last = Some(self.last.map_or(tx, |l| l.max(tx)));
+        }
+    }
+
+    /// Returns the fraction of rows that overlap, in `0.0..=1.0`.
+    pub fn ratio(&self) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            self.overlapping as f64 / self.total as f64
+        }
+    }
+}
+
+/// Scans an input file and reports how many of its tx ids the engine has
+/// already seen, before anything is applied.
+///
+/// # Arguments
+/// * `path` - The file path to the CSV input.
+/// * `dialect` - The layout of the input.
+/// * `engine` - The engine holding the previously seen ids.
+///
+/// # Returns
+/// * `AppResult<OverlapReport>` - The overlap summary, or an `AppErrors::Io` if the file
+///   cannot be read.
+pub fn check_overlap(
+    path: &str,
+    dialect: &CsvDialect,
+    engine: &Engine,
+) -> AppResult<OverlapReport> {
+    let mut report = OverlapReport::default();
+    scan_tx_ids(path, dialect, |tx| {
+        report.record(tx, engine.txs.contains_key(&tx))
+    })?;
+    Ok(report)
+}
+
+/// Encodes sorted ids as the magic header, a little-endian `u64` count, and the
+/// LEB128-encoded deltas between consecutive ids.
+fn encode_ids(sorted: &[TxId], out: &mut impl Write) -> std::io::Result<()> {
+    out.write_all(MAGIC)?;
+    out.write_all(&(sorted.len() as u64).to_le_bytes())?;
+    let mut prev: TxId = 0;
+    for &tx in sorted {
+        let mut delta = tx - prev;
+        prev = tx;
+        loop {
+            let byte = (delta & 0x7f) as u8;
+            delta >>= 7;
+            if delta == 0 {
+                out.write_all(&[byte])?;
+                break;
+            }
+            out.write_all(&[byte | 0x80])?;
+        }
+    }
+    Ok(())
+}
+
+/// Decodes ids written by [`encode_ids`].
+fn decode_ids(mut input: impl Read) -> AppResult<Vec<TxId>> {
+    let io_err = |e: std::io::Error| AppErrors::Io(format!("read seen ids: {e}"));
+
+    let mut magic = [0u8; 8];
+    input.read_exact(&mut magic).map_err(io_err)?;
+    if &magic != MAGIC {
+        return Err(AppErrors::InvalidInput("not a seen-ids file"));
+    }
+    let mut count = [0u8; 8];
+    input.read_exact(&mut count).map_err(io_err)?;
+    let count = u64::from_le_bytes(count) as usize;
+
+    let mut ids = Vec::with_capacity(count);
+    let mut prev: TxId = 0;
+    let mut byte = [0u8; 1];
+    for _ in 0..count {
+        let mut delta: u64 = 0;
+        let mut shift = 0;
+        loop {
+            input.read_exact(&mut byte).map_err(io_err)?;
+            delta |= u64::from(byte[0] & 0x7f) << shift;
+            if byte[0] & 0x80 == 0 {
+                break;
+            }
+            shift += 7;
+            if shift > 35 {
+                return Err(AppErrors::InvalidInput("malformed seen-ids delta"));
+            }
+        }
+        prev = TxId::try_from(u64::from(prev) + delta)
+            .map_err(|_| AppErrors::InvalidInput("seen-ids value out of range"))?;
+        ids.push(prev);
+    }
+    Ok(ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_decode_roundtrip() {
+        let ids: Vec<TxId> = vec![0, 1, 2, 130, 20_000, TxId::MAX];
+        let mut buf = Vec::new();
+        encode_ids(&ids, &mut buf).unwrap();
+
+        assert_eq!(decode_ids(buf.as_slice()).unwrap(), ids);
+    }
+
+    #[test]
+    fn dense_ids_encode_to_one_byte_each() {
+        let ids: Vec<TxId> = (1..=1_000).collect();
+        let mut buf = Vec::new();
+        encode_ids(&ids, &mut buf).unwrap();
+
+        assert_eq!(buf.len(), MAGIC.len() + 8 + ids.len());
+    }
+
+    #[test]
+    fn decode_rejects_bad_magic() {
+        let res = decode_ids(&b"NOTSEEN1\0\0\0\0\0\0\0\0"[..]);
+        assert!(matches!(res, Err(AppErrors::InvalidInput(_))));
+    }
+
+    #[test]
+    fn overlap_report_tracks_ratio_and_range() {
+        let mut report = OverlapReport::default();
+        report.record(5, true);
+        report.record(6, false);
+        report.record(2, true);
+        report.record(7, false);
+
+        assert_eq!(report.overlapping, 2);
+        assert_eq!(report.first, Some(2));
+        assert_eq!(report.last, Some(5));
+        assert_eq!(report.ratio(), 0.5);
+    }
+
+    #[test]
+    fn loaded_ids_are_treated_as_duplicates() {
+        let mut engine = Engine::default();
+        engine.txs.mark_seen(7);
+
+        assert!(engine.txs.contains_key(&7));
+        assert!(engine.txs.get(&7).is_none());
+        assert_eq!(engine.txs.seen_ids(), vec![7]);
+    }
+
+    #[test]
+    fn empty_or_missing_files_load_no_ids() {
+        let path = std::env::temp_dir().join(format!("pe-{}-seen-empty", std::process::id()));
+        let path = path.display().to_string();
+        let _ = fs::remove_file(&path);
+        let mut engine = Engine::default();
+        assert_eq!(load_seen_ids(&path, &mut engine).unwrap(), 0);
+
+        assert_eq!(save_seen_ids(&path, &engine).unwrap(), 0);
+        assert_eq!(load_seen_ids(&path, &mut engine).unwrap(), 0);
+        assert!(engine.txs.seen_ids().is_empty());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn decode_rejects_truncated_files() {
+        let mut buf = Vec::new();
+        encode_ids(&[1, 2, 300], &mut buf).unwrap();
+        for len in [0, 4, MAGIC.len() + 4, buf.len() - 1] {
+            assert!(decode_ids(&buf[..len]).is_err(), "{len}");
+        }
+    }
+}
@@ -0,0 +1,269 @@
+use crate::models::identifiers::{ClientId, TxId};
+use crate::state::{DisputeEvent, Engine};
+use log::warn;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// How a tracked dispute ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DisputeOutcome {
+    /// The dispute was resolved and the funds released.
+    Resolved,
+    /// The dispute ended in a chargeback.
+    ChargedBack,
+    /// The dispute was resolved automatically at the end of the dispute window.
+    Expired,
+    /// The dispute was still open at the end of the input.
+    Open,
+}
+
+/// A dispute that took longer than the SLA to close (or is still open past it).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SlaBreach {
+    /// The client owning the disputed transaction.
+    pub client: ClientId,
+    /// The disputed transaction.
+    pub tx: TxId,
+    /// The input line that opened the dispute.
+    pub opened_line: u64,
+    /// The input line that closed it, or the last line read if still open.
+    pub closed_line: u64,
+    /// The number of rows between opening and closing.
+    pub rows: u64,
+    /// How the dispute ended.
+    pub outcome: DisputeOutcome,
+}
+
+/// Dispute SLA statistics for a run.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SlaSummary {
+    /// The SLA, in rows between a dispute and its resolution or chargeback.
+    pub sla_rows: u64,
+    /// The number of disputes closed (resolved, charged back or expired).
+    pub closed: u64,
+    /// The number of disputes closed within the SLA.
+    pub within_sla: u64,
+    /// The number of disputes still open at the end of the input.
+    pub open: u64,
+    /// The percentage of disputes (closed, or open past the SLA) that met it.
+    pub compliance_pct: f64,
+    /// The disputes that breached the SLA, in the order they were detected.
+    pub breaches: Vec<SlaBreach>,
+}
+
+/// Tracks how many rows each dispute stays open, as rows are applied.
+///
+/// Disputes are tracked from the engine's [`DisputeEvent`]s, so a dispute is
+/// dated by the row whose command opened or closed it: the dispute row itself,
+/// the deposit that released a buffered dispute or funded a queued one, or the
+/// row during which the dispute window expired it.
+#[derive(Debug)]
+pub struct DisputeSlaTracker {
+    /// The SLA, in rows.
+    sla_rows: u64,
+    /// The open disputes: tx id to `(client, opening line)`.
+    open: HashMap<TxId, (ClientId, u64)>,
+    /// The number of disputes closed.
+    closed: u64,
+    /// The number of disputes closed within the SLA.
+    within_sla: u64,
+    /// The disputes closed past the SLA.
+    breaches: Vec<SlaBreach>,
+    /// The line of the last row observed.
+    last_line: u64,
+}
+
+impl DisputeSlaTracker {
+    /// Creates a tracker for the given SLA, in rows.
+    pub fn new(sla_rows: u64) -> Self {
+        Self {
+            sla_rows,
+            open: HashMap::new(),
+            closed: 0,
+            within_sla: 0,
+            breaches: Vec::new(),
+            last_line: 0,
+        }
+    }
+
+    /// Records the disputes an executed row opened or closed (see
+    /// [`Engine::dispute_events`]).
+    ///
+    /// # Arguments
+    /// * `line` - The input line of the row.
+    /// * `engine` - The engine after executing it.
+    pub fn observe(&mut self, line: u64, engine: &Engine) {
+        self.last_line = line;
+        for &event in engine.dispute_events() {
+            let (tx, outcome) = match event {
+                DisputeEvent::Opened { client, tx } => {
+                    self.open.entry(tx).or_insert((client, line));
+                    continue;
+                }
+                DisputeEvent::Resolved(tx) => (tx, DisputeOutcome::Resolved),
+                DisputeEvent::ChargedBack(tx) => (tx, DisputeOutcome::ChargedBack),
+                DisputeEvent::Expired(tx) => (tx, DisputeOutcome::Expired),
+            };
+            self.close(line, tx, outcome);
+        }
+    }
+
+    /// Records that a tracked dispute closed at a line.
+    fn close(&mut self, line: u64, tx: TxId, outcome: DisputeOutcome) {
+        let Some((client, opened_line)) = self.open.remove(&tx) else {
+            return;
+        };
+
+        self.closed += 1;
+        let rows = line.saturating_sub(opened_line);
+        if rows <= self.sla_rows {
+            self.within_sla += 1;
+        } else {
+            warn!(
+                "dispute of tx {tx} closed after {rows} row(s), past the {}-row SLA",
+                self.sla_rows
+            );
+            self.breaches.push(SlaBreach {
+                client,
+                tx,
+                opened_line,
+                closed_line: line,
+                rows,
+                outcome,
+            });
+        }
+    }
+
+    /// Finishes tracking and summarizes SLA compliance. Disputes still open past
+    /// the SLA at the last observed row count as breaches; those still within it
+    /// are not counted either way.
+    pub fn finish(mut self) -> SlaSummary {
+        let last_line = self.last_line;
+        let mut open: Vec<_> = self.open.iter().collect();
+        open.sort_by_key(|(tx, (_, opened))| (*opened, **tx));
+        for (&tx, &(client, opened_line)) in open {
+            let rows = last_line.saturating_sub(opened_line);
+            if rows > self.sla_rows {
+                self.breaches.push(SlaBreach {
+                    client,
+                    tx,
+                    opened_line,
+                    closed_line: last_line,
+                    rows,
+                    outcome: DisputeOutcome::Open,
+                });
+            }
+        }
+
+        let open_breaches = self.breaches.len() as u64 - (self.closed - self.within_sla);
+        let measured = self.closed + open_breaches;
+        SlaSummary {
+            sla_rows: self.sla_rows,
+            closed: self.closed,
+            within_sla: self.within_sla,
+            open: self.open.len() as u64,
+            compliance_pct: if measured == 0 {
+                100.0
+            } else {
+                self.within_sla as f64 * 100.0 / measured as f64
+            },
+            breaches: self.breaches,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::csv_models::transaction::{CsvTxType, InputRow};
+    use crate::services::csv_service::row_to_transaction;
+
+    fn apply(
+        engine: &mut Engine,
+        tracker: &mut DisputeSlaTracker,
+        line: u64,
+        t: CsvTxType,
+        tx: TxId,
+    ) {
+        let row = InputRow {
+            t,
+            client: 1,
+            tx,
+            amount: Some("1.0".into()),
+        };
+        engine.apply(row_to_transaction(&row).unwrap()).unwrap();
+        tracker.observe(line, engine);
+    }
+
+    #[test]
+    fn reports_breaches_and_compliance() {
+        let mut engine = Engine::default();
+        let mut tracker = DisputeSlaTracker::new(3);
+        for tx in 1..=3 {
+            apply(
+                &mut engine,
+                &mut tracker,
+                u64::from(tx) + 1,
+                CsvTxType::Deposit,
+                tx,
+            );
+        }
+        apply(&mut engine, &mut tracker, 5, CsvTxType::Dispute, 1);
+        apply(&mut engine, &mut tracker, 6, CsvTxType::Dispute, 2);
+        apply(&mut engine, &mut tracker, 7, CsvTxType::Dispute, 3);
+        apply(&mut engine, &mut tracker, 8, CsvTxType::Resolve, 1);
+        apply(&mut engine, &mut tracker, 12, CsvTxType::Chargeback, 2);
+        apply(&mut engine, &mut tracker, 20, CsvTxType::Deposit, 4);
+
+        let summary = tracker.finish();
+        assert_eq!(summary.closed, 2);
+        assert_eq!(summary.within_sla, 1);
+        assert_eq!(summary.open, 1);
+        let breached: Vec<_> = summary.breaches.iter().map(|b| (b.tx, b.outcome)).collect();
+        assert_eq!(
+            breached,
+            vec![(2, DisputeOutcome::ChargedBack), (3, DisputeOutcome::Open)]
+        );
+        assert!((summary.compliance_pct - 100.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn expired_disputes_close_at_the_row_that_expired_them() {
+        let mut engine = Engine::builder().dispute_window(2).build();
+        let mut tracker = DisputeSlaTracker::new(1);
+        apply(&mut engine, &mut tracker, 2, CsvTxType::Deposit, 1);
+        apply(&mut engine, &mut tracker, 3, CsvTxType::Dispute, 1);
+        apply(&mut engine, &mut tracker, 4, CsvTxType::Deposit, 2);
+        apply(&mut engine, &mut tracker, 5, CsvTxType::Deposit, 3);
+        // The fifth command expires the dispute opened at the second.
+        apply(&mut engine, &mut tracker, 6, CsvTxType::Deposit, 4);
+        apply(&mut engine, &mut tracker, 7, CsvTxType::Deposit, 5);
+
+        let summary = tracker.finish();
+        assert_eq!(
+            (summary.closed, summary.within_sla, summary.open),
+            (1, 0, 0)
+        );
+        let breach = &summary.breaches[0];
+        assert_eq!((breach.tx, breach.outcome), (1, DisputeOutcome::Expired));
+        assert_eq!((breach.opened_line, breach.closed_line), (3, 6));
+    }
+
+    #[test]
+    fn released_disputes_open_at_the_row_that_released_them() {
+        let mut engine = Engine::builder().orphan_buffer(10).build();
+        let mut tracker = DisputeSlaTracker::new(2);
+        apply(&mut engine, &mut tracker, 2, CsvTxType::Dispute, 1);
+        apply(&mut engine, &mut tracker, 3, CsvTxType::Deposit, 1);
+        apply(&mut engine, &mut tracker, 4, CsvTxType::Deposit, 2);
+        apply(&mut engine, &mut tracker, 5, CsvTxType::Resolve, 1);
+
+        let summary = tracker.finish();
+        assert_eq!(
+            (summary.closed, summary.within_sla, summary.open),
+            (1, 1, 0)
+        );
+        assert!(summary.breaches.is_empty());
+    }
+}
@@ -0,0 +1,263 @@
+use crate::errors::{AppErrors, AppResult};
+use crate::services::account_writer::{AccountWriter, OutputFormat};
+use crate::services::run_manifest::{RunManifest, TrialBalance, trial_balance};
+use crate::services::state_store::{StoreSpec, state_digest};
+use crate::state::Engine;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::Path;
+
+/// The frozen closing state of a day, inside its day directory.
+pub const CLOSING_SNAPSHOT: &str = "closing.snap";
+
+/// The closing balances of a day, in the account report layout; the next day's
+/// `--opening-balances` file.
+pub const CLOSING_BALANCES: &str = "balances.csv";
+
+/// The run manifest of a day's closing state; the next day's `--carry-forward`
+/// file.
+pub const CLOSING_MANIFEST: &str = "manifest.json";
+
+/// The summary of a day, pairing its opening and closing totals.
+pub const DAY_SUMMARY: &str = "day.json";
+
+/// The opening and closing totals of a closed accounting day.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DaySummary {
+    /// The business day, `YYYY-MM-DD`.
+    pub date: String,
+    /// The previous closed day, or `None` for the first day closed.
+    pub previous: Option<String>,
+    /// The opening totals: the previous day's closing totals, or `None` for the
+    /// first day closed.
+    pub opening: Option<TrialBalance>,
+    /// The closing totals.
+    pub closing: TrialBalance,
+    /// The closing state hash, as 16 hex digits.
+    pub state_hash: String,
+    /// When the day was closed, in seconds since the Unix epoch.
+    pub closed_at: u64,
+}
+
+/// Closes an accounting day of an engine, so a book kept in a state backend can
+/// run continuously across business days. The day's directory, `<dir>/<date>`,
+/// receives:
+/// - [`CLOSING_SNAPSHOT`]: the frozen closing state, read back and checked by
+///   state hash;
+/// - [`CLOSING_BALANCES`]: the closing balances sorted by client, which open
+///   the next day;
+/// - [`CLOSING_MANIFEST`]: the closing totals and state hash, which the next
+///   day's run checks it carries forward;
+/// - [`DAY_SUMMARY`]: the day's opening totals (the previous day's closing)
+///   paired with its closing totals.
+///
+/// The files are written to a temporary directory renamed into place once
+/// complete, so a day is closed in full or not at all. The engine is left as
+/// it is: it is the next day's opening state.
+///
+/// # Arguments
+/// * `engine` - The engine at the end of the day, fully loaded.
+/// * `dir` - The directory of closed days (created if missing).
+/// * `date` - The business day to close, `YYYY-MM-DD`.
+/// * `now` - The closing time, in seconds since the Unix epoch.
+///
+/// # Returns
+/// * `AppResult<DaySummary>` - The closed day, or `AppErrors::InvalidInput` if
+///   the date is malformed or not after the last closed day, or an
+///   `AppErrors::Io` on failure.
+pub fn close_day(engine: &Engine, dir: &Path, date: &str, now: u64) -> AppResult<DaySummary> {
+    if !is_date(date) {
+        return Err(AppErrors::InvalidInput("a business day must be YYYY-MM-DD"));
+    }
+    fs::create_dir_all(dir).map_err(|e| AppErrors::Io(format!("create {}: {e}", dir.display())))?;
+    let previous = closed_days(dir)?.pop();
+    if previous.as_deref().is_some_and(|last| *last >= *date) {
+        return Err(AppErrors::InvalidInput(
+            "the day is not after the last closed day",
+        ));
+    }
+    let opening = match &previous {
+        Some(day) => Some(read_summary(&dir.join(day))?.closing),
+        None => None,
+    };
+
+    let tmp = dir.join(format!(".{date}.tmp-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&tmp);
+    fs::create_dir(&tmp).map_err(|e| AppErrors::Io(format!("create {}: {e}", tmp.display())))?;
+    let written = write_day(engine, &tmp, date, now, previous, opening);
+    let summary = match written {
+        Ok(summary) => summary,
+        Err(e) => {
+            let _ = fs::remove_dir_all(&tmp);
+            return Err(e);
+        }
+    };
+    let day = dir.join(date);
+    fs::rename(&tmp, &day).map_err(|e| AppErrors::Io(format!("rename {}: {e}", tmp.display())))?;
+    Ok(summary)
+}
+
+/// Writes a day's files into a directory.
+fn write_day(
+    engine: &Engine,
+    day: &Path,
+    date: &str,
+    now: u64,
+    previous: Option<String>,
+    opening: Option<TrialBalance>,
+) -> AppResult<DaySummary> {
+    let snapshot = StoreSpec::Snapshot(day.join(CLOSING_SNAPSHOT).display().to_string());
+    snapshot.save(engine)?;
+    let digest = state_digest(engine);
+    if state_digest(&snapshot.load()?) != digest {
+        return Err(AppErrors::Internal(format!(
+            "closing snapshot of {date} does not match the state"
+        )));
+    }
+
+    let path = day.join(CLOSING_BALANCES);
+    let file = File::create(&path)
+        .map_err(|e| AppErrors::Io(format!("create {}: {e}", path.display())))?;
+    let mut wtr = AccountWriter::new(BufWriter::new(file), OutputFormat::Csv);
+    let mut accounts: Vec<_> = engine.accounts_iter().collect();
+    accounts.sort_unstable_by_key(|(client, _)| **client);
+    for (client, acc) in accounts {
+        wtr.write(*client, acc)?;
+    }
+    wtr.finish()?;
+
+    let manifest = RunManifest::closing(engine, now)?;
+    manifest.write(&day.join(CLOSING_MANIFEST).display().to_string())?;
+
+    let summary = DaySummary {
+        date: date.to_string(),
+        previous,
+        opening,
+        closing: trial_balance(engine)?,
+        state_hash: manifest.state_hash,
+        closed_at: now,
+    };
+    let path = day.join(DAY_SUMMARY);
+    let json = serde_json::to_string_pretty(&summary)
+        .map_err(|e| AppErrors::Io(format!("encode {}: {e}", path.display())))?;
+    fs::write(&path, json + "\n")
+        .map_err(|e| AppErrors::Io(format!("write {}: {e}", path.display())))?;
+    Ok(summary)
+}
+
+/// Lists the closed days in a directory, oldest first.
+///
+/// # Returns
+/// * `AppResult<Vec<String>>` - The day directories' dates, or an
+///   `AppErrors::Io` if the directory cannot be read.
+pub fn closed_days(dir: &Path) -> AppResult<Vec<String>> {
+    let entries =
+        fs::read_dir(dir).map_err(|e| AppErrors::Io(format!("read {}: {e}", dir.display())))?;
+    let mut days: Vec<String> = entries
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|name| is_date(name))
+        .collect();
+    days.sort_unstable();
+    Ok(days)
+}
+
+/// Reads the summary of a closed day's directory.
+fn read_summary(day: &Path) -> AppResult<DaySummary> {
+    let path = day.join(DAY_SUMMARY);
+    let json =
+        fs::read(&path).map_err(|e| AppErrors::Io(format!("read {}: {e}", path.display())))?;
+    serde_json::from_slice(&json)
+        .map_err(|e| AppErrors::Io(format!("parse {}: {e}", path.display())))
+}
+
+/// Returns whether a string is a `YYYY-MM-DD` date, so dates sort in calendar
+/// order.
+fn is_date(s: &str) -> bool {
+    let b = s.as_bytes();
+    b.len() == 10
+        && b.iter().enumerate().all(|(i, c)| {
+            if i == 4 || i == 7 {
+                *c == b'-'
+            } else {
+                c.is_ascii_digit()
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::amount::Amount;
+    use crate::services::opening_balances::load_opening_balances;
+
+    #[test]
+    fn pairs_each_days_opening_with_the_previous_close() {
+        let dir = std::env::temp_dir().join(format!("pe-{}-eod", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut engine = Engine::default();
+        engine.acct_mut(1).available = Amount(15_000);
+
+        let first = close_day(&engine, &dir, "2026-10-15", 1).unwrap();
+        assert_eq!(first.opening, None);
+        engine.acct_mut(2).held = Amount(5_000);
+        let second = close_day(&engine, &dir, "2026-10-16", 2).unwrap();
+        assert_eq!(second.previous.as_deref(), Some("2026-10-15"));
+        assert_eq!(second.opening, Some(first.closing));
+        assert_eq!(second.closing.total, "2.0000");
+
+        // A day closes once, and only after the last closed day.
+        assert!(close_day(&engine, &dir, "2026-10-16", 3).is_err());
+        assert!(close_day(&engine, &dir, "2026-10-14", 3).is_err());
+        assert!(close_day(&engine, &dir, "16/10/2026", 3).is_err());
+        assert_eq!(closed_days(&dir).unwrap(), ["2026-10-15", "2026-10-16"]);
+
+        // The closing balances open the next day with the same totals.
+        let day = dir.join("2026-10-16");
+        let mut opened = Engine::default();
+        load_opening_balances(
+            &day.join(CLOSING_BALANCES).display().to_string(),
+            &mut opened,
+        )
+        .unwrap();
+        assert_eq!(trial_balance(&opened).unwrap(), second.closing);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn empty_engine_closes_a_zero_day() {
+        let dir = std::env::temp_dir().join(format!("pe-{}-eod-empty", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        assert!(closed_days(&dir).is_err());
+
+        let day = close_day(&Engine::default(), &dir, "2026-10-15", 1).unwrap();
+        assert_eq!(day.closing.accounts, 0);
+        assert_eq!(day.closing.total, "0.0000");
+        // The empty balances file still opens the next day.
+        let balances = dir.join("2026-10-15").join(CLOSING_BALANCES);
+        let mut opened = Engine::default();
+        load_opening_balances(&balances.display().to_string(), &mut opened).unwrap();
+        assert_eq!(trial_balance(&opened).unwrap(), day.closing);
+        assert_eq!(closed_days(&dir).unwrap(), ["2026-10-15"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn locked_account_stays_locked_in_the_closing_snapshot() {
+        let dir = std::env::temp_dir().join(format!("pe-{}-eod-locked", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut engine = Engine::default();
+        engine.acct_mut(1).available = Amount(10_000);
+        engine.acct_mut(1).locked = true;
+
+        let day = close_day(&engine, &dir, "2026-10-15", 1).unwrap();
+        assert_eq!(day.closing.locked, 1);
+        let snapshot = dir.join("2026-10-15").join(CLOSING_SNAPSHOT);
+        let reloaded = StoreSpec::Snapshot(snapshot.display().to_string())
+            .load()
+            .unwrap();
+        assert!(reloaded.accounts_iter().all(|(_, acc)| acc.locked));
+        assert_eq!(state_digest(&reloaded), state_digest(&engine));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
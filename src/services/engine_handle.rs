@@ -0,0 +1,202 @@
+use crate::errors::{AppErrors, AppResult};
+use crate::models::domain_state::Account;
+use crate::models::identifiers::{ClientId, TxId};
+use crate::services::commands::traits::tx_command_trait::TxCommandTrait;
+use crate::state::{AppState, IgnoreReason, Outcome};
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::sync::{Mutex, MutexGuard};
+
+/// A thread-safe handle for applying commands from concurrent callers, e.g.
+/// request handlers in an async service.
+///
+/// Clients are partitioned across shards, each owning its own [`AppState`]
+/// behind a mutex. Commands for the same client always lock the same shard, so
+/// they never interleave; commands for clients in different shards proceed in
+/// parallel. Tx ids stay globally unique through a shared owner map that is
+/// only locked briefly around the id reservation, never while a command runs.
+///
+/// Locks are held only for the duration of a single command, so calling
+/// [`EngineHandle::execute`] from async code (e.g., via `spawn_blocking`) will
+/// not stall the runtime for long.
+pub struct EngineHandle {
+    /// The per-shard application states, indexed by `client % shards.len()`.
+    shards: Vec<Mutex<AppState>>,
+
+    /// Maps each tx id recorded (or being recorded) to the client that owns it.
+    tx_owners: Mutex<HashMap<TxId, ClientId>>,
+}
+
+impl EngineHandle {
+    /// Creates a handle with the given number of shards (at least one).
+    ///
+    /// # Arguments
+    /// * `shards` - The number of independently locked partitions.
+    pub fn new(shards: usize) -> Self {
+        Self {
+            shards: (0..shards.max(1))
+                .map(|_| Mutex::new(AppState::default()))
+                .collect(),
+            tx_owners: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Locks and returns the shard owning the given client.
+    fn shard(&self, client: ClientId) -> AppResult<MutexGuard<'_, AppState>> {
+        self.shards[client as usize % self.shards.len()]
+            .lock()
+            .map_err(|_| AppErrors::Internal("engine shard lock poisoned".into()))
+    }
+
+    /// Locks and returns the tx owner map.
+    fn owners(&self) -> AppResult<MutexGuard<'_, HashMap<TxId, ClientId>>> {
+        self.tx_owners
+            .lock()
+            .map_err(|_| AppErrors::Internal("tx owner lock poisoned".into()))
+    }
+
+    /// Executes a command against the shard owning its client.
+    ///
+    /// For commands that record a new transaction, the tx id is reserved in the
+    /// global owner map first; a tx id already owned by a different client is
+    /// ignored as a duplicate. The reservation is released if the command does
+    /// not end up recording the transaction.
+    ///
+    /// # Arguments
+    /// * `cmd` - The command to execute.
+    ///
+    /// # Returns
+    /// * `AppResult<Outcome>` - Whether the command was applied or ignored (and
+    ///   why), or an `AppErrors` variant if an error occurs.
+    pub fn execute(&self, cmd: &dyn TxCommandTrait) -> AppResult<Outcome> {
+        let client = cmd.client();
+        let mut shard = self.shard(client)?;
+
+        let Some(tx) = cmd.new_tx() else {
+            return cmd.execute(&mut shard);
+        };
+
+        let reserved = match self.owners()?.entry(tx) {
+            Entry::Occupied(e) if *e.get() != client => {
+                return Ok(Outcome::Ignored(IgnoreReason::DuplicateTx));
+            }
+            Entry::Occupied(_) => false,
+            Entry::Vacant(e) => {
+                e.insert(client);
+                true
+            }
+        };
+
+        let res = cmd.execute(&mut shard);
+        if reserved && !shard.engine.txs.contains_key(&tx) {
+            self.owners()?.remove(&tx);
+        }
+        res
+    }
+
+    /// Returns the version of a client's account (see
+    /// [`Engine::version`](crate::state::Engine::version)).
+    ///
+    /// Read it alongside the account to detect concurrent updates: a caller that
+    /// saw version `n` can tell whether anything was applied since.
+    pub fn version(&self, client: ClientId) -> AppResult<u64> {
+        Ok(self.shard(client)?.engine.version(client))
+    }
+
+    /// Returns a copy of every account across all shards.
+    ///
+    /// Each shard is locked in turn, so the result is consistent per client but
+    /// not a global point-in-time snapshot while writers are active.
+    pub fn accounts(&self) -> AppResult<Vec<(ClientId, Account)>> {
+        let mut out = Vec::new();
+        for shard in &self.shards {
+            let state = shard
+                .lock()
+                .map_err(|_| AppErrors::Internal("engine shard lock poisoned".into()))?;
+            out.extend(state.engine.accounts_iter().map(|(c, a)| (*c, a.clone())));
+        }
+        Ok(out)
+    }
+}
+
+impl Default for EngineHandle {
+    /// Creates a handle with one shard per available CPU.
+    fn default() -> Self {
+        Self::new(std::thread::available_parallelism().map_or(1, |n| n.get()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::amount::Amount;
+    use crate::models::tx_command::{DepositCommand, WithdrawalCommand};
+    use std::sync::Arc;
+    use std::thread;
+
+    fn deposit(client: ClientId, tx: TxId, amount: i64) -> DepositCommand {
+        DepositCommand {
+            client,
+            tx,
+            amount: Amount(amount),
+        }
+    }
+
+    fn balance(handle: &EngineHandle, client: ClientId) -> Amount {
+        handle
+            .accounts()
+            .unwrap()
+            .into_iter()
+            .find(|(c, _)| *c == client)
+            .map(|(_, a)| a.available)
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn concurrent_clients_are_applied_independently() {
+        let handle = Arc::new(EngineHandle::new(4));
+        let workers: Vec<_> = (0..8u16)
+            .map(|client| {
+                let handle = Arc::clone(&handle);
+                thread::spawn(move || {
+                    for i in 0..100u32 {
+                        let tx = u32::from(client) * 1_000 + i;
+                        handle.execute(&deposit(client, tx, 1)).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for w in workers {
+            w.join().unwrap();
+        }
+
+        for client in 0..8u16 {
+            assert_eq!(balance(&handle, client), Amount(100));
+        }
+    }
+
+    #[test]
+    fn tx_id_owned_by_another_client_is_ignored_across_shards() {
+        let handle = EngineHandle::new(2);
+        handle.execute(&deposit(1, 7, 10)).unwrap();
+        handle.execute(&deposit(2, 7, 10)).unwrap();
+
+        assert_eq!(balance(&handle, 1), Amount(10));
+        assert_eq!(balance(&handle, 2), Amount(0));
+    }
+
+    #[test]
+    fn reservation_is_released_when_tx_not_recorded() {
+        let handle = EngineHandle::new(2);
+        handle
+            .execute(&WithdrawalCommand {
+                client: 1,
+                tx: 5,
+                amount: Amount(10),
+            })
+            .unwrap();
+        handle.execute(&deposit(2, 5, 10)).unwrap();
+
+        assert_eq!(balance(&handle, 2), Amount(10));
+    }
+}
@@ -0,0 +1,156 @@
+use crate::errors::{AppErrors, AppResult};
+use crate::models::amount::Amount;
+use crate::models::domain_state::{DisputeState, TxKind};
+use crate::models::identifiers::{ClientId, TxId};
+use crate::state::Engine;
+use serde::Serialize;
+use std::fs::OpenOptions;
+use std::io::Write;
+
+/// The tombstone left in the erasure audit log when a client is erased.
+///
+/// It keeps only aggregates, so control totals over the log still reconcile
+/// after the client's records are gone: `deposited - withdrawn - charged_back`
+/// is the balance the account had, which erasure requires to be zero.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ErasureRecord {
+    /// The erased client.
+    pub client: ClientId,
+    /// When the client was erased, in seconds since the Unix epoch.
+    pub erased_at: u64,
+    /// The number of transaction records removed.
+    pub txs: u64,
+    /// The sum of the client's deposits.
+    pub deposited: String,
+    /// The sum of the client's withdrawals.
+    pub withdrawn: String,
+    /// The sum of the client's charged-back deposits.
+    pub charged_back: String,
+}
+
+/// Erases a closed client from the engine.
+///
+/// A client is closed once its account holds no funds: `available` and `held`
+/// are both zero, so no dispute is open. Its account and transaction records
+/// are removed; the tx ids stay seen so replayed transactions are still
+/// rejected as duplicates, but no longer record which client they belonged to.
+///
+/// # Arguments
+/// * `engine` - The engine to erase the client from.
+/// * `client` - The client to erase.
+/// * `now` - The erasure time, in seconds since the Unix epoch.
+///
+/// # Returns
+/// * `AppResult<ErasureRecord>` - The tombstone to record, or
+///   `AppErrors::InvalidInput` if the client has no account or is not closed.
+pub fn erase_client(engine: &mut Engine, client: ClientId, now: u64) -> AppResult<ErasureRecord> {
+    let acc = engine
+        .acct(client)
+        .ok_or(AppErrors::InvalidInput("client has no account to erase"))?;
+    if acc.available != Amount::default() || acc.held != Amount::default() {
+        return Err(AppErrors::InvalidInput(
+            "client still holds funds; only closed accounts can be erased",
+        ));
+    }
+
+    let mut txs: Vec<TxId> = Vec::new();
+    let (mut deposited, mut withdrawn, mut charged_back) =
+        (Amount::default(), Amount::default(), Amount::default());
+    for (tx, rec) in engine.txs.iter().filter(|(_, rec)| rec.client == client) {
+        txs.push(tx);
+        let sum = match rec.kind {
+            TxKind::Deposit => &mut deposited,
+            TxKind::Withdrawal => &mut withdrawn,
+        };
+        *sum = sum.checked_add(rec.amount).ok_or(AppErrors::Overflow)?;
+        if rec.state == DisputeState::ChargedBack {
+            charged_back = charged_back
+                .checked_add(rec.amount)
+                .ok_or(AppErrors::Overflow)?;
+        }
+    }
+
+    engine.evict_client(client, &txs);
+    Ok(ErasureRecord {
+        client,
+        erased_at: now,
+        txs: txs.len() as u64,
+        deposited: deposited.to_string(),
+        withdrawn: withdrawn.to_string(),
+        charged_back: charged_back.to_string(),
+    })
+}
+
+/// Appends a tombstone to the erasure audit log, one JSON object per line.
+///
+/// # Arguments
+/// * `path` - The audit log path; created if missing.
+/// * `record` - The tombstone to append.
+///
+/// # Returns
+/// * `AppResult<()>` - `Ok(())` once the line is written and synced, or an
+///   `AppErrors::Io` on failure.
+pub fn append_erasure_log(path: &str, record: &ErasureRecord) -> AppResult<()> {
+    let mut line =
+        serde_json::to_string(record).map_err(|e| AppErrors::Io(format!("encode {path}: {e}")))?;
+    line.push('\n');
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| AppErrors::Io(format!("open {path}: {e}")))?;
+    file.write_all(line.as_bytes())
+        .and_then(|()| file.sync_all())
+        .map_err(|e| AppErrors::Io(format!("write {path}: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::domain_state::TxRecord;
+
+    fn record(client: ClientId, kind: TxKind, amount: i64, state: DisputeState) -> TxRecord {
+        TxRecord {
+            client,
+            kind,
+            amount: Amount(amount),
+            state,
+        }
+    }
+
+    #[test]
+    fn erases_closed_client_and_keeps_tx_ids_seen() {
+        let mut engine = Engine::default();
+        engine.acct_mut(1).locked = true;
+        engine.acct_mut(2).available = Amount(5);
+        engine
+            .txs
+            .insert(1, record(1, TxKind::Deposit, 30_000, DisputeState::Normal));
+        engine.txs.insert(
+            2,
+            record(1, TxKind::Deposit, 10_000, DisputeState::ChargedBack),
+        );
+        engine.txs.insert(
+            3,
+            record(1, TxKind::Withdrawal, 30_000, DisputeState::Normal),
+        );
+        engine
+            .txs
+            .insert(4, record(2, TxKind::Deposit, 5, DisputeState::Normal));
+
+        let tombstone = erase_client(&mut engine, 1, 1_700_000_000).unwrap();
+
+        assert_eq!(tombstone.txs, 3);
+        assert_eq!(tombstone.deposited, "4.0000");
+        assert_eq!(tombstone.withdrawn, "3.0000");
+        assert_eq!(tombstone.charged_back, "1.0000");
+        assert!(engine.acct(1).is_none());
+        assert!(engine.txs.get(&1).is_none());
+        assert!(engine.txs.contains_key(&1));
+        assert!(engine.txs.get(&4).is_some());
+        engine.verify().unwrap();
+
+        assert!(erase_client(&mut engine, 2, 0).is_err());
+        assert!(erase_client(&mut engine, 1, 0).is_err());
+    }
+}
@@ -0,0 +1,272 @@
+use crate::errors::{AppErrors, AppResult};
+use crate::models::amount::Amount;
+use crate::models::csv_models::transaction::InputRow;
+use crate::models::tx_command::Transaction;
+use crate::services::alerts::Alert;
+use crate::state::Engine;
+use csv::WriterBuilder;
+use log::warn;
+
+/// Caps on the book-wide held and available totals, in the run's currency.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ExposureLimits {
+    /// The most the book may hold in open disputes.
+    pub max_held: Option<Amount>,
+    /// The most the book may have available.
+    pub max_available: Option<Amount>,
+}
+
+/// Keeps commands that would push the book past its [`ExposureLimits`] out of
+/// the engine, as a hard backstop against runaway files.
+///
+/// The book's held and available totals start from the loaded state and track
+/// every admitted command's effect, evaluated with [`Engine::probe`]. A command
+/// that would raise a total above its cap is quarantined (kept for review and
+/// replay instead of applied) and raises an alert. Commands that lower a total
+/// are always admitted, so a book loaded above its cap can still unwind.
+#[derive(Debug, Default, Clone)]
+pub struct ExposureGuard {
+    /// The caps.
+    limits: ExposureLimits,
+    /// The book's held total.
+    held: Amount,
+    /// The book's available total.
+    available: Amount,
+    /// The quarantined rows, in input order.
+    quarantined: Vec<InputRow>,
+    /// The alerts raised for quarantined rows, in order.
+    alerts: Vec<Alert>,
+}
+
+impl ExposureGuard {
+    /// Creates a guard over the book as loaded.
+    ///
+    /// # Arguments
+    /// * `limits` - The caps.
+    /// * `engine` - The engine before any input is applied.
+    ///
+    /// # Returns
+    /// * `AppResult<Self>` - The guard, or `AppErrors::Overflow` if a book total
+    ///   does not fit an amount.
+    pub fn new(limits: ExposureLimits, engine: &Engine) -> AppResult<Self> {
+        let (mut held, mut available) = (Amount::zero(), Amount::zero());
+        for (_, acc) in engine.accounts_iter() {
+            held = held.checked_add(acc.held).ok_or(AppErrors::Overflow)?;
+            available = available
+                .checked_add(acc.available)
+                .ok_or(AppErrors::Overflow)?;
+        }
+        Ok(Self {
+            limits,
+            held,
+            available,
+            ..Self::default()
+        })
+    }
+
+    /// Decides whether a command may be applied, quarantining it if it would
+    /// breach a cap. An admitted command's effect is counted in the book totals,
+    /// so it must then be applied.
+    ///
+    /// # Arguments
+    /// * `line` - The input line of the row.
+    /// * `row` - The row the command was built from.
+    /// * `tx` - The command.
+    /// * `engine` - The engine the command is about to be applied to.
+    ///
+    /// # Returns
+    /// * `bool` - `true` if the command may be applied; `false` if it was
+    ///   quarantined. A command the engine would reject with an error is admitted
+    ///   unchanged, so the error surfaces when it is applied.
+    pub fn admit(&mut self, line: u64, row: &InputRow, tx: &Transaction, engine: &Engine) -> bool {
+        let Ok(probe) = engine.probe(tx) else {
+            return true;
+        };
+        let before = engine.acct(row.client).cloned().unwrap_or_default();
+        let moved =
+            |total: Amount, now: Amount, was: Amount| total.checked_add(now)?.checked_sub(was);
+        let (Some(held), Some(available)) = (
+            moved(self.held, probe.account.held, before.held),
+            moved(self.available, probe.account.available, before.available),
+        ) else {
+            return true;
+        };
+        let breach = [
+            ("held", self.limits.max_held, held, self.held),
+            (
+                "available",
+                self.limits.max_available,
+                available,
+                self.available,
+            ),
+        ]
+        .into_iter()
+        .find(|(_, cap, next, now)| cap.is_some_and(|cap| next > now && *next > cap));
+        if let Some((field, cap, next, _)) = breach {
+            let rule = format!("book_{field}>{}", cap.unwrap_or_default());
+            warn!(
+                "alert: tx {} on line {line} would take the book to {rule} (to {next}); quarantined",
+                row.tx
+            );
+            self.alerts.push(Alert {
+                rule,
+                client: row.client,
+                tx: row.tx,
+                line,
+                value: next.to_string(),
+            });
+            self.quarantined.push(row.clone());
+            return false;
+        }
+        self.held = held;
+        self.available = available;
+        true
+    }
+
+    /// Returns the alerts raised for quarantined rows, in order.
+    pub fn alerts(&self) -> &[Alert] {
+        &self.alerts
+    }
+
+    /// Returns the number of quarantined rows.
+    pub fn quarantined(&self) -> usize {
+        self.quarantined.len()
+    }
+
+    /// Writes the quarantined rows as input CSV, in input order, so they can be
+    /// replayed once treasury has reviewed them.
+    ///
+    /// # Arguments
+    /// * `path` - The quarantine file path.
+    ///
+    /// # Returns
+    /// * `AppResult<()>` - `Ok(())` once written, or an `AppErrors::Io` on failure.
+    pub fn write_quarantine(&self, path: &str) -> AppResult<()> {
+        let mut wtr = WriterBuilder::new()
+            .from_path(path)
+            .map_err(|e| AppErrors::Io(format!("create {path}: {e}")))?;
+        for row in &self.quarantined {
+            wtr.serialize(row)
+                .map_err(|e| AppErrors::Io(format!("write {path}: {e}")))?;
+        }
+        wtr.flush()
+            .map_err(|e| AppErrors::Io(format!("flush {path}: {e}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::csv_models::transaction::CsvTxType;
+    use crate::services::csv_service::row_to_transaction;
+
+    fn row(t: CsvTxType, client: u16, tx: u32, amount: Option<&str>) -> InputRow {
+        InputRow {
+            t,
+            client,
+            tx,
+            amount: amount.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn quarantines_commands_breaching_a_book_cap() {
+        let mut engine = Engine::default();
+        engine.acct_mut(1).available = Amount(80_000);
+        let limits = ExposureLimits {
+            max_held: Some(Amount(30_000)),
+            max_available: Some(Amount(100_000)),
+        };
+        let mut guard = ExposureGuard::new(limits, &engine).unwrap();
+        let rows = [
+            row(CsvTxType::Deposit, 2, 1, Some("1.5")),
+            row(CsvTxType::Deposit, 2, 2, Some("5")),
+            row(CsvTxType::Dispute, 2, 2, None),
+            row(CsvTxType::Dispute, 2, 1, None),
+        ];
+
+        let admitted: Vec<bool> = rows
+            .iter()
+            .enumerate()
+            .map(|(i, row)| {
+                let tx = row_to_transaction(row).unwrap();
+                let admitted = guard.admit(i as u64 + 2, row, &tx, &engine);
+                if admitted {
+                    engine.apply(tx).unwrap();
+                }
+                admitted
+            })
+            .collect();
+
+        // 9.5 available stays under 10; a further 5 would not. Disputing tx 2 is
+        // ignored by the engine (unknown tx), so it moves nothing.
+        assert_eq!(admitted, [true, false, true, true]);
+        assert_eq!(guard.quarantined(), 1);
+        assert_eq!(guard.alerts()[0].rule, "book_available>10.0000");
+        assert_eq!(guard.alerts()[0].value, "14.5000");
+        assert_eq!(engine.acct(2).unwrap().held, Amount(15_000));
+        // Applied around the guard, as if carried in by --load-state.
+        let big = row(CsvTxType::Deposit, 3, 3, Some("20"));
+        engine.apply(row_to_transaction(&big).unwrap()).unwrap();
+        let dispute = row(CsvTxType::Dispute, 3, 3, None);
+        let tx = row_to_transaction(&dispute).unwrap();
+        assert!(!guard.admit(6, &dispute, &tx, &engine));
+        assert_eq!(guard.alerts()[1].rule, "book_held>3.0000");
+    }
+
+    #[test]
+    fn book_loaded_above_its_cap_can_still_unwind() {
+        let mut engine = Engine::default();
+        engine.acct_mut(1).available = Amount(200_000);
+        let limits = ExposureLimits {
+            max_available: Some(Amount(100_000)),
+            ..ExposureLimits::default()
+        };
+        let mut guard = ExposureGuard::new(limits, &engine).unwrap();
+
+        let withdrawal = row(CsvTxType::Withdrawal, 1, 1, Some("5"));
+        let tx = row_to_transaction(&withdrawal).unwrap();
+        assert!(guard.admit(2, &withdrawal, &tx, &engine));
+        engine.apply(tx).unwrap();
+        let deposit = row(CsvTxType::Deposit, 1, 2, Some("1"));
+        let tx = row_to_transaction(&deposit).unwrap();
+        assert!(!guard.admit(3, &deposit, &tx, &engine));
+        assert_eq!(guard.alerts()[0].value, "16.0000");
+    }
+
+    #[test]
+    fn commands_the_engine_ignores_are_admitted() {
+        let mut engine = Engine::default();
+        engine.acct_mut(1).locked = true;
+        let limits = ExposureLimits {
+            max_held: Some(Amount::zero()),
+            max_available: Some(Amount::zero()),
+        };
+        let mut guard = ExposureGuard::new(limits, &engine).unwrap();
+
+        // A deposit to a locked account and a dispute of an unknown tx move
+        // nothing, so they cannot breach a cap.
+        for (line, row) in [
+            row(CsvTxType::Deposit, 1, 1, Some("5")),
+            row(CsvTxType::Dispute, 2, 9, None),
+        ]
+        .iter()
+        .enumerate()
+        {
+            let tx = row_to_transaction(row).unwrap();
+            assert!(guard.admit(line as u64 + 2, row, &tx, &engine));
+        }
+        assert_eq!(guard.quarantined(), 0);
+    }
+
+    #[test]
+    fn empty_quarantine_writes_an_empty_file() {
+        let path = std::env::temp_dir().join(format!("pe-{}-quarantine.csv", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        ExposureGuard::default().write_quarantine(path).unwrap();
+
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "");
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,125 @@
+use crate::errors::AppResult;
+use crate::models::csv_models::transaction::{CsvTxType, InputRow};
+use crate::models::domain_state::Account;
+use crate::models::identifiers::ClientId;
+use crate::services::report_output::write_json_file;
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// Statistics and audit findings for one input file of a multi-file run, so
+/// anomalies can be attributed to the file (e.g., the hour) that caused them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct FileReport {
+    /// The input path.
+    pub input: String,
+    /// The number of rows applied (not counting those the engine ignored).
+    pub applied: u64,
+    /// Applied deposit rows.
+    pub deposits: u64,
+    /// Applied withdrawal rows.
+    pub withdrawals: u64,
+    /// Applied dispute rows.
+    pub disputes: u64,
+    /// Applied resolve rows.
+    pub resolves: u64,
+    /// Applied chargeback rows.
+    pub chargebacks: u64,
+    /// The number of distinct clients the applied rows touched.
+    pub clients: usize,
+    /// The row errors gathered from this file under `--on-error collect`.
+    pub row_errors: usize,
+    /// The alerts raised while applying this file.
+    pub alerts: usize,
+    /// The clients whose accounts were locked by a chargeback in this file.
+    pub locked: Vec<ClientId>,
+    /// The clients seen so far.
+    #[serde(skip)]
+    seen: HashSet<ClientId>,
+}
+
+impl FileReport {
+    /// Creates an empty report for an input.
+    pub fn new(input: &str) -> Self {
+        Self {
+            input: input.to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// Records an applied row.
+    ///
+    /// # Arguments
+    /// * `row` - The applied row.
+    /// * `acc` - The row's account after applying it, if it exists.
+    pub fn observe(&mut self, row: &InputRow, acc: Option<&Account>) {
+        self.applied += 1;
+        let count = match row.t {
+            CsvTxType::Deposit => &mut self.deposits,
+            CsvTxType::Withdrawal => &mut self.withdrawals,
+            CsvTxType::Dispute => &mut self.disputes,
+            CsvTxType::Resolve => &mut self.resolves,
+            CsvTxType::Chargeback => &mut self.chargebacks,
+        };
+        *count += 1;
+        if self.seen.insert(row.client) {
+            self.clients += 1;
+        }
+        if row.t == CsvTxType::Chargeback
+            && acc.is_some_and(|a| a.locked)
+            && !self.locked.contains(&row.client)
+        {
+            self.locked.push(row.client);
+        }
+    }
+}
+
+/// Writes per-file reports as a pretty-printed JSON array, in input order. The
+/// file is replaced only once the report is complete.
+///
+/// # Arguments
+/// * `path` - The report path.
+/// * `reports` - One report per input file.
+///
+/// # Returns
+/// * `AppResult<()>` - `Ok(())` once written, or an `AppErrors::Io` on failure.
+pub fn write_file_reports(path: &str, reports: &[FileReport]) -> AppResult<()> {
+    write_json_file(path, &reports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(t: CsvTxType, client: ClientId) -> InputRow {
+        InputRow {
+            t,
+            client,
+            tx: 1,
+            amount: None,
+        }
+    }
+
+    #[test]
+    fn counts_rows_by_type_and_records_locks() {
+        let locked = Account {
+            locked: true,
+            ..Account::default()
+        };
+        let mut report = FileReport::new("hour-01.csv");
+
+        report.observe(&row(CsvTxType::Deposit, 1), Some(&Account::default()));
+        report.observe(&row(CsvTxType::Deposit, 2), Some(&Account::default()));
+        report.observe(&row(CsvTxType::Dispute, 1), Some(&Account::default()));
+        report.observe(&row(CsvTxType::Chargeback, 1), Some(&locked));
+
+        assert_eq!(report.applied, 4);
+        assert_eq!(
+            (report.deposits, report.disputes, report.chargebacks),
+            (2, 1, 1)
+        );
+        assert_eq!(report.clients, 2);
+        assert_eq!(report.locked, vec![1]);
+        let json = serde_json::to_string(&report).unwrap();
+        assert!(json.starts_with(r#"{"input":"hour-01.csv","applied":4"#));
+    }
+}
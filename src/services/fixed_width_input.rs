@@ -0,0 +1,294 @@
+use crate::errors::{AppErrors, AppResult};
+use crate::models::csv_models::transaction::{CsvTxType, InputRow};
+use crate::services::csv_service::open_text_input;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader, Read};
+
+/// A field of a fixed-width record: `width` bytes starting at byte `start`
+/// (0-based).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FieldSpan {
+    /// The offset of the field's first byte in the record.
+    pub start: usize,
+    /// The field width in bytes.
+    pub width: usize,
+}
+
+/// The column layout of a fixed-width settlement file, read from a small JSON
+/// config such as:
+///
+/// ```json
+/// {
+///   "skip_lines": 1,
+///   "type": { "start": 0, "width": 3 },
+///   "client": { "start": 3, "width": 5 },
+///   "tx": { "start": 8, "width": 10 },
+///   "amount": { "start": 18, "width": 14 },
+///   "codes": { "DEP": "deposit", "WDL": "withdrawal" }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FixedWidthLayout {
+    /// Header lines to skip at the top of the file.
+    #[serde(default)]
+    pub skip_lines: usize,
+    /// The transaction type field.
+    #[serde(rename = "type")]
+    pub t: FieldSpan,
+    /// The client id field.
+    pub client: FieldSpan,
+    /// The tx id field.
+    pub tx: FieldSpan,
+    /// The amount field; without it, no row carries an amount.
+    #[serde(default)]
+    pub amount: Option<FieldSpan>,
+    /// Type codes used by the file in place of the CSV type names. Names not
+    /// listed here are parsed as CSV type names.
+    #[serde(default)]
+    pub codes: HashMap<String, CsvTxType>,
+}
+
+impl FixedWidthLayout {
+    /// Reads a layout config.
+    ///
+    /// # Arguments
+    /// * `path` - The JSON config path.
+    ///
+    /// # Returns
+    /// * `AppResult<Self>` - The layout, or an `AppErrors::Io` if it cannot be read
+    ///   or parsed, or `AppErrors::InvalidInput` if a field has zero width.
+    pub fn read(path: &str) -> AppResult<Self> {
+        let json = fs::read(path).map_err(|e| AppErrors::Io(format!("read {path}: {e}")))?;
+        let layout: Self = serde_json::from_slice(&json)
+            .map_err(|e| AppErrors::Io(format!("parse {path}: {e}")))?;
+        let spans = [
+            Some(layout.t),
+            Some(layout.client),
+            Some(layout.tx),
+            layout.amount,
+        ];
+        if spans.into_iter().flatten().any(|f| f.width == 0) {
+            return Err(AppErrors::InvalidInput(
+                "fixed-width layout fields must have a non-zero width",
+            ));
+        }
+        Ok(layout)
+    }
+
+    /// Cuts a record into a transaction row.
+    ///
+    /// Fields are trimmed of surrounding blanks, and an all-blank amount is
+    /// absent. A record may end early when its trailing fields are blank, as
+    /// editors and legacy exporters often strip trailing spaces.
+    ///
+    /// # Returns
+    /// * `AppResult<InputRow>` - The row, or an `AppErrors` variant if a field is
+    ///   not a valid value or splits a multi-byte character.
+    pub fn parse_record(&self, record: &str) -> AppResult<InputRow> {
+        let t = field(record, self.t)?;
+        Ok(InputRow {
+            t: match self.codes.get(t) {
+                Some(t) => *t,
+                None => t.parse::<CsvTxType>()?,
+            },
+            client: field(record, self.client)?
+                .parse()
+                .map_err(|_| AppErrors::InvalidInput("client id missing or out of range"))?,
+            tx: field(record, self.tx)?
+                .parse()
+                .map_err(|_| AppErrors::InvalidInput("tx id missing or out of range"))?,
+            amount: match self.amount {
+                Some(span) => Some(field(record, span)?)
+                    .filter(|a| !a.is_empty())
+                    .map(str::to_string),
+                None => None,
+            },
+        })
+    }
+}
+
+/// Returns a record's field, trimmed; empty if the record ends before it.
+fn field(record: &str, span: FieldSpan) -> AppResult<&str> {
+    let start = span.start.min(record.len());
+    let end = span.start.saturating_add(span.width).min(record.len());
+    record
+        .get(start..end)
+        .map(str::trim)
+        .ok_or(AppErrors::InvalidInput(
+            "fixed-width field splits a multi-byte character",
+        ))
+}
+
+/// Reads transaction rows from a fixed-width record file.
+///
+/// Records are numbered by their line, like CSV rows; blank lines are skipped.
+/// A record whose fields do not parse is yielded as a row error; a read failure
+/// is an outer error that aborts processing.
+pub struct FixedWidthRows<R: BufRead> {
+    /// The input, positioned at the next record.
+    input: R,
+    /// The column layout.
+    layout: FixedWidthLayout,
+    /// The buffer of the current line.
+    buf: String,
+    /// The number of the last line read.
+    line: u64,
+    /// Set once the input is exhausted or a read failed.
+    done: bool,
+}
+
+/// Opens a fixed-width record file.
+///
+/// # Arguments
+/// * `path` - The file path, or [`STDIN_PATH`](crate::consts::STDIN_PATH) (`-`)
+///   to read stdin. A gzip- or zstd-compressed file is decompressed first, and
+///   UTF-16 or a byte order mark is decoded (see `csv_service::open_text_input`).
+/// * `layout` - The column layout.
+///
+/// # Returns
+/// * `AppResult<FixedWidthRows<BufReader<Box<dyn Read>>>>` - The record reader,
+///   or an `AppErrors` variant if the file cannot be opened.
+pub fn open_fixed_width(
+    path: &str,
+    layout: &FixedWidthLayout,
+) -> AppResult<FixedWidthRows<BufReader<Box<dyn Read>>>> {
+    Ok(FixedWidthRows::new(
+        BufReader::new(open_text_input(path)?),
+        layout.clone(),
+    ))
+}
+
+impl<R: BufRead> FixedWidthRows<R> {
+    /// Creates a record reader over any line source.
+    pub fn new(input: R, layout: FixedWidthLayout) -> Self {
+        Self {
+            input,
+            layout,
+            buf: String::new(),
+            line: 0,
+            done: false,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for FixedWidthRows<R> {
+    type Item = AppResult<(u64, AppResult<InputRow>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            self.buf.clear();
+            match self.input.read_line(&mut self.buf) {
+                Ok(0) => self.done = true,
+                Ok(_) => {
+                    self.line += 1;
+                    let record = self.buf.trim_end_matches(['\n', '\r']);
+                    if self.line as usize <= self.layout.skip_lines || record.trim().is_empty() {
+                        continue;
+                    }
+                    return Some(Ok((self.line, self.layout.parse_record(record))));
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(AppErrors::Io(format!(
+                        "read line {}: {e}",
+                        self.line + 1
+                    ))));
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout() -> FixedWidthLayout {
+        serde_json::from_str(
+            r#"{
+                "skip_lines": 1,
+                "type": { "start": 0, "width": 3 },
+                "client": { "start": 3, "width": 5 },
+                "tx": { "start": 8, "width": 6 },
+                "amount": { "start": 14, "width": 10 },
+                "codes": { "DEP": "deposit" }
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn cuts_records_by_layout() {
+        let layout: FixedWidthLayout = serde_json::from_str(
+            r#"{
+                "skip_lines": 1,
+                "type": { "start": 0, "width": 3 },
+                "client": { "start": 3, "width": 5 },
+                "tx": { "start": 8, "width": 6 },
+                "amount": { "start": 14, "width": 10 },
+                "codes": { "DEP": "deposit", "DIS": "dispute" }
+            }"#,
+        )
+        .unwrap();
+        let file = "TYPCLINTTXID  AMOUNT\n\
+                    DEP    1     7    2.5000\n\
+                    \n\
+                    DIS    1     7\r\n\
+                    XXX    1     8       1.0\n";
+
+        let rows: Vec<_> = FixedWidthRows::new(file.as_bytes(), layout)
+            .map(Result::unwrap)
+            .collect();
+
+        assert_eq!(rows.len(), 3);
+        let (line, deposit) = &rows[0];
+        let deposit = deposit.as_ref().unwrap();
+        assert_eq!(*line, 2);
+        assert_eq!(
+            (deposit.t, deposit.client, deposit.tx),
+            (CsvTxType::Deposit, 1, 7)
+        );
+        assert_eq!(deposit.amount.as_deref(), Some("2.5000"));
+        let (line, dispute) = &rows[1];
+        let dispute = dispute.as_ref().unwrap();
+        assert_eq!(*line, 4);
+        assert_eq!(
+            (dispute.t, dispute.amount.is_none()),
+            (CsvTxType::Dispute, true)
+        );
+        assert!(rows[2].1.is_err());
+    }
+
+    #[test]
+    fn empty_file_and_header_only_file_yield_no_rows() {
+        assert_eq!(FixedWidthRows::new(&b""[..], layout()).count(), 0);
+        assert_eq!(
+            FixedWidthRows::new(&b"TYPCLINTTXID  AMOUNT\n\n"[..], layout()).count(),
+            0
+        );
+    }
+
+    #[test]
+    fn malformed_records_are_row_errors_and_reading_continues() {
+        let file = "TYPCLINTTXID  AMOUNT\n\
+                    DEP99999     7    2.5000\n\
+                    DEP    1\n\
+                    DEP    1    1é   1.0000\n\
+                    DEP    1    10    1.0000\n";
+
+        let rows: Vec<_> = FixedWidthRows::new(file.as_bytes(), layout())
+            .map(Result::unwrap)
+            .collect();
+
+        let lines: Vec<_> = rows.iter().map(|(line, _)| *line).collect();
+        assert_eq!(lines, [2, 3, 4, 5]);
+        // Client out of range, tx id cut off, a field splitting a character.
+        assert!(rows[..3].iter().all(|(_, row)| row.is_err()));
+        assert_eq!(rows[3].1.as_ref().unwrap().tx, 10);
+    }
+}
@@ -0,0 +1,336 @@
+use crate::errors::{AppErrors, AppResult};
+use crate::models::amount::Amount;
+use crate::models::csv_models::transaction::{CsvTxType, InputRow};
+use crate::models::identifiers::{ClientId, TxId};
+use csv::WriterBuilder;
+use std::fmt::{Display, Formatter};
+use std::io::Write;
+use std::str::FromStr;
+
+/// The Zipf exponent of the skewed client distribution: with `s = 1.1`, the
+/// busiest of 1000 clients sends about 13% of the rows.
+const ZIPF_EXPONENT: f64 = 1.1;
+
+/// The share of rows the hot client sends in [`Preset::HotClient`].
+const HOT_CLIENT_SHARE: f64 = 0.9;
+
+/// A named workload shape for synthetic input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    /// Clients picked uniformly; mostly deposits and withdrawals, few disputes.
+    Uniform,
+    /// Clients picked from a Zipf distribution, so a few clients dominate.
+    Zipfian,
+    /// Uniform clients, but most rows dispute, resolve or charge back earlier
+    /// deposits.
+    DisputeStorm,
+    /// One client sends nine in ten rows; the rest are uniform.
+    HotClient,
+}
+
+impl Preset {
+    /// The cumulative row-type mix, in per mille: deposit, withdrawal, dispute,
+    /// resolve, chargeback.
+    fn mix(self) -> [(u32, CsvTxType); 5] {
+        let [d, w, dp, r, _] = match self {
+            Preset::DisputeStorm => [300, 100, 400, 100, 100],
+            _ => [600, 300, 60, 20, 20],
+        };
+        [
+            (d, CsvTxType::Deposit),
+            (d + w, CsvTxType::Withdrawal),
+            (d + w + dp, CsvTxType::Dispute),
+            (d + w + dp + r, CsvTxType::Resolve),
+            (1000, CsvTxType::Chargeback),
+        ]
+    }
+}
+
+impl FromStr for Preset {
+    type Err = AppErrors;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "uniform" => Ok(Preset::Uniform),
+            "zipfian" => Ok(Preset::Zipfian),
+            "dispute-storm" => Ok(Preset::DisputeStorm),
+            "hot-client" => Ok(Preset::HotClient),
+            _ => Err(AppErrors::InvalidInput(
+                "preset must be uniform, zipfian, dispute-storm or hot-client",
+            )),
+        }
+    }
+}
+
+impl Display for Preset {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Preset::Uniform => "uniform",
+            Preset::Zipfian => "zipfian",
+            Preset::DisputeStorm => "dispute-storm",
+            Preset::HotClient => "hot-client",
+        })
+    }
+}
+
+/// One phase of a generation schedule: `rows` rows shaped by `preset`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Phase {
+    /// The workload shape.
+    pub preset: Preset,
+    /// The number of rows to generate.
+    pub rows: u64,
+}
+
+impl FromStr for Phase {
+    type Err = AppErrors;
+
+    /// Parses a `<preset>:<rows>` phase, e.g. `zipfian:100000`.
+    ///
+    /// # Errors
+    /// * `AppErrors::InvalidInput` if the preset is unknown or the row count is
+    ///   not a number.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (preset, rows) = s.split_once(':').ok_or(AppErrors::InvalidInput(
+            "phase must be written as <preset>:<rows>",
+        ))?;
+        Ok(Self {
+            preset: preset.trim().parse()?,
+            rows: rows
+                .trim()
+                .parse()
+                .map_err(|_| AppErrors::InvalidInput("phase row count must be a number"))?,
+        })
+    }
+}
+
+/// Generates synthetic transaction input for performance testing.
+///
+/// Phases run in order over a shared state, so a mixed schedule such as
+/// `uniform:50000,dispute-storm:5000` disputes deposits made in earlier phases.
+/// Tx ids are sequential from 1. Disputes reference earlier deposits of the
+/// same client; resolves and chargebacks reference open disputes. When there is
+/// nothing to dispute or settle yet, a deposit is generated instead. The same
+/// seed always yields the same rows.
+pub struct Generator {
+    /// The random source.
+    rng: SplitMix64,
+    /// The number of clients, numbered from 1.
+    clients: ClientId,
+    /// The cumulative Zipf distribution over clients, built on first use.
+    zipf: Vec<f64>,
+    /// The last tx id generated.
+    last_tx: TxId,
+    /// Deposits that can be disputed.
+    deposits: Vec<(ClientId, TxId)>,
+    /// Open disputes that can be resolved or charged back.
+    disputed: Vec<(ClientId, TxId)>,
+}
+
+impl Generator {
+    /// Creates a generator.
+    ///
+    /// # Arguments
+    /// * `clients` - The number of clients, at least 1.
+    /// * `seed` - The random seed.
+    pub fn new(clients: ClientId, seed: u64) -> Self {
+        Self {
+            rng: SplitMix64(seed),
+            clients: clients.max(1),
+            zipf: Vec::new(),
+            last_tx: 0,
+            deposits: Vec::new(),
+            disputed: Vec::new(),
+        }
+    }
+
+    /// Writes the rows of a schedule as input CSV, with a header.
+    ///
+    /// # Arguments
+    /// * `out` - The CSV sink.
+    /// * `schedule` - The phases, in order.
+    ///
+    /// # Returns
+    /// * `AppResult<u64>` - The number of rows written, or `AppErrors::Overflow` if
+    ///   the schedule needs more tx ids than fit, or an `AppErrors::Io` on write
+    ///   failure.
+    pub fn write(&mut self, out: impl Write, schedule: &[Phase]) -> AppResult<u64> {
+        let mut wtr = WriterBuilder::new().from_writer(out);
+        let mut written = 0;
+        for phase in schedule {
+            for _ in 0..phase.rows {
+                let row = self.row(phase.preset)?;
+                wtr.serialize(&row)
+                    .map_err(|e| AppErrors::Io(format!("write csv: {e}")))?;
+                written += 1;
+            }
+        }
+        wtr.flush()
+            .map_err(|e| AppErrors::Io(format!("flush csv: {e}")))?;
+        Ok(written)
+    }
+
+    /// Generates the next row of a preset.
+    fn row(&mut self, preset: Preset) -> AppResult<InputRow> {
+        let roll = (self.rng.next() % 1000) as u32;
+        let t = preset
+            .mix()
+            .into_iter()
+            .find(|(bound, _)| roll < *bound)
+            .map_or(CsvTxType::Chargeback, |(_, t)| t);
+        let settle = |list: &mut Vec<(ClientId, TxId)>, rng: &mut SplitMix64| {
+            (!list.is_empty()).then(|| list.swap_remove((rng.next() % list.len() as u64) as usize))
+        };
+        let referenced = match t {
+            CsvTxType::Dispute => settle(&mut self.deposits, &mut self.rng),
+            CsvTxType::Resolve | CsvTxType::Chargeback => settle(&mut self.disputed, &mut self.rng),
+            CsvTxType::Deposit | CsvTxType::Withdrawal => None,
+        };
+        if let Some((client, tx)) = referenced {
+            match t {
+                CsvTxType::Dispute => self.disputed.push((client, tx)),
+                // A resolved deposit can be disputed again.
+                CsvTxType::Resolve => self.deposits.push((client, tx)),
+                _ => {}
+            }
+            return Ok(InputRow {
+                t,
+                client,
+                tx,
+                amount: None,
+            });
+        }
+
+        let t = if t == CsvTxType::Withdrawal {
+            t
+        } else {
+            CsvTxType::Deposit
+        };
+        let client = self.client(preset);
+        self.last_tx = self.last_tx.checked_add(1).ok_or(AppErrors::Overflow)?;
+        if t == CsvTxType::Deposit {
+            self.deposits.push((client, self.last_tx));
+        }
+        // Amounts from 0.0001 to 1000.0000.
+        let amount = Amount(1 + (self.rng.next() % 10_000_000) as i64);
+        Ok(InputRow {
+            t,
+            client,
+            tx: self.last_tx,
+            amount: Some(amount.to_string()),
+        })
+    }
+
+    /// Picks the client of a new deposit or withdrawal.
+    fn client(&mut self, preset: Preset) -> ClientId {
+        match preset {
+            Preset::Zipfian => {
+                if self.zipf.is_empty() {
+                    let weights: Vec<f64> = (1..=self.clients)
+                        .map(|rank| 1.0 / f64::from(rank).powf(ZIPF_EXPONENT))
+                        .collect();
+                    let total: f64 = weights.iter().sum();
+                    let mut acc = 0.0;
+                    self.zipf = weights
+                        .iter()
+                        .map(|w| {
+                            acc += w / total;
+                            acc
+                        })
+                        .collect();
+                }
+                let u = self.rng.unit();
+                let rank = self.zipf.partition_point(|c| *c < u);
+                (rank as ClientId + 1).min(self.clients)
+            }
+            Preset::HotClient if self.rng.unit() < HOT_CLIENT_SHARE => 1,
+            _ => (self.rng.next() % u64::from(self.clients)) as ClientId + 1,
+        }
+    }
+}
+
+/// A small, fast, seedable random source (SplitMix64); no cryptographic quality
+/// is needed to shape test input.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    /// Returns the next 64 random bits.
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a uniform float in `[0, 1)`.
+    fn unit(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::policy::ErrorPolicy;
+    use crate::services::csv_service::run_from_reader;
+    use crate::state::AppState;
+
+    fn generate(schedule: &str, clients: ClientId, seed: u64) -> Vec<u8> {
+        let schedule: Vec<Phase> = schedule.split(',').map(|p| p.parse().unwrap()).collect();
+        let mut out = Vec::new();
+        Generator::new(clients, seed)
+            .write(&mut out, &schedule)
+            .unwrap();
+        out
+    }
+
+    #[test]
+    fn presets_shape_deterministic_replayable_input() {
+        let csv = generate("uniform:2000,dispute-storm:500,hot-client:1000", 50, 7);
+        assert_eq!(
+            csv,
+            generate("uniform:2000,dispute-storm:500,hot-client:1000", 50, 7)
+        );
+        let rows: Vec<InputRow> = csv::Reader::from_reader(csv.as_slice())
+            .deserialize()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(rows.len(), 3500);
+
+        let disputes = |rows: &[InputRow]| {
+            rows.iter().filter(|r| r.t == CsvTxType::Dispute).count() as f64 / rows.len() as f64
+        };
+        assert!(disputes(&rows[..2000]) < 0.1);
+        assert!(disputes(&rows[2000..2500]) > 0.2);
+        let hot = rows[2500..]
+            .iter()
+            .filter(|r| r.amount.is_some() && r.client == 1)
+            .count();
+        assert!(hot > 700, "{hot}");
+
+        let zipf = generate("zipfian:2000", 100, 1);
+        let rows: Vec<InputRow> = csv::Reader::from_reader(zipf.as_slice())
+            .deserialize()
+            .map(Result::unwrap)
+            .collect();
+        let top = rows.iter().filter(|r| r.client == 1).count();
+        let tail = rows.iter().filter(|r| r.client == 100).count();
+        assert!(top > 10 * tail.max(1), "{top} vs {tail}");
+
+        // Every generated row is valid engine input.
+        let mut state = AppState::default();
+        state.engine.set_error_policy(ErrorPolicy::Collect);
+        let errors = run_from_reader(csv.as_slice(), &mut state).unwrap();
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn parses_phases() {
+        let phase: Phase = "dispute-storm:250".parse().unwrap();
+        assert_eq!(phase.preset, Preset::DisputeStorm);
+        assert_eq!(phase.rows, 250);
+        assert!("storm:1".parse::<Phase>().is_err());
+        assert!("uniform".parse::<Phase>().is_err());
+    }
+}
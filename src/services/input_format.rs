@@ -0,0 +1,96 @@
+use crate::errors::{AppErrors, AppResult};
+use crate::models::csv_models::transaction::InputRow;
+use crate::models::policy::RowError;
+use crate::services::avro_input::open_avro;
+use crate::services::csv_service::{run_from_csv_path_observed, run_rows_observed};
+use crate::services::fixed_width_input::{FixedWidthLayout, open_fixed_width};
+use crate::services::nacha_input::open_nacha;
+use crate::state::{AppState, Outcome};
+use clap::ValueEnum;
+
+/// The formats transaction input can be read in.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InputFormat {
+    /// CSV with a `type,client,tx,amount` header row.
+    #[default]
+    Csv,
+    /// An Avro object container file whose records have the CSV row's fields
+    /// (see [`AvroRows`](crate::services::avro_input::AvroRows)).
+    Avro,
+    /// Fixed-width records cut by a column layout config (see
+    /// [`FixedWidthLayout`]).
+    FixedWidth,
+    /// A NACHA ACH file, whose entry detail records become deposits (credits)
+    /// and withdrawals (debits) (see
+    /// [`NachaRows`](crate::services::nacha_input::NachaRows)).
+    Nacha,
+    /// Length-delimited protobuf `Transaction` messages (see
+    /// `proto/transaction.proto`).
+    #[cfg(feature = "proto")]
+    Proto,
+    /// An Arrow IPC stream or file (Feather v2) with `type`, `client`, `tx` and
+    /// `amount` columns (see [`ArrowRows`](crate::services::arrow_input::ArrowRows)).
+    #[cfg(feature = "arrow")]
+    Arrow,
+    /// The first sheet of an `.xlsx` workbook, with `type`, `client`, `tx` and
+    /// `amount` header cells (see [`read_xlsx`](crate::services::xlsx_input::read_xlsx)).
+    #[cfg(feature = "xlsx")]
+    Xlsx,
+}
+
+/// Processes transactions from a file in the given format, calling `on_applied`
+/// with every applied row (see
+/// [`run_from_reader_observed`](crate::services::csv_service::run_from_reader_observed)).
+///
+/// # Arguments
+/// * `path` - The input path, or [`STDIN_PATH`](crate::consts::STDIN_PATH) (`-`).
+/// * `format` - The input format.
+/// * `fixed_width` - The column layout, required by [`InputFormat::FixedWidth`].
+/// * `app_state` - A mutable reference to the application state.
+/// * `on_applied` - Called with each executed row's line (or record) number,
+///   the row, its outcome, and the resulting state, in input order.
+///
+/// # Returns
+/// * `AppResult<Vec<RowError>>` - The errors gathered under
+///   [`ErrorPolicy::Collect`](crate::models::policy::ErrorPolicy::Collect)
+///   (empty otherwise), or an `AppErrors` variant if processing was aborted or
+///   fixed-width input has no layout.
+pub fn run_from_path_observed(
+    path: &str,
+    format: InputFormat,
+    fixed_width: Option<&FixedWidthLayout>,
+    app_state: &mut AppState,
+    on_applied: impl FnMut(u64, &InputRow, Outcome, &AppState) -> AppResult<()>,
+) -> AppResult<Vec<RowError>> {
+    match format {
+        InputFormat::Csv => run_from_csv_path_observed(path, app_state, on_applied),
+        InputFormat::Avro => run_rows_observed(open_avro(path)?, app_state, on_applied),
+        InputFormat::FixedWidth => {
+            let layout = fixed_width.ok_or(AppErrors::InvalidInput(
+                "fixed-width input requires a column layout",
+            ))?;
+            run_rows_observed(open_fixed_width(path, layout)?, app_state, on_applied)
+        }
+        InputFormat::Nacha => run_rows_observed(open_nacha(path)?, app_state, on_applied),
+        #[cfg(feature = "proto")]
+        InputFormat::Proto => run_rows_observed(
+            crate::services::proto_input::open_proto(path)?,
+            app_state,
+            on_applied,
+        ),
+        #[cfg(feature = "arrow")]
+        InputFormat::Arrow => run_rows_observed(
+            crate::services::arrow_input::open_arrow(path)?,
+            app_state,
+            on_applied,
+        ),
+        #[cfg(feature = "xlsx")]
+        InputFormat::Xlsx => run_rows_observed(
+            crate::services::xlsx_input::read_xlsx(path)?
+                .into_iter()
+                .map(Ok),
+            app_state,
+            on_applied,
+        ),
+    }
+}
@@ -0,0 +1,175 @@
+use crate::errors::{AppErrors, AppResult};
+use crate::models::csv_models::transaction::CsvTxType;
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD;
+use hdrhistogram::Histogram;
+use hdrhistogram::serialization::{Serializer, V2DeflateSerializer};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// The significant decimal digits the histograms keep: values are recorded to
+/// within 0.1%.
+const SIGNIFICANT_DIGITS: u8 = 3;
+
+/// The highest latency the histograms track, in nanoseconds (one minute);
+/// slower commands are recorded as this value.
+const MAX_TRACKABLE_NANOS: u64 = 60_000_000_000;
+
+/// Records how long the engine takes to apply each command, in HDR histograms
+/// per command type, so soak and benchmark runs over different storage
+/// backends can be compared percentile by percentile.
+#[derive(Debug, Clone)]
+pub struct LatencyRecorder {
+    /// One histogram of nanoseconds per command type, by name.
+    histograms: BTreeMap<&'static str, Histogram<u64>>,
+}
+
+impl Default for LatencyRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LatencyRecorder {
+    /// Creates an empty recorder.
+    pub fn new() -> Self {
+        Self {
+            histograms: BTreeMap::new(),
+        }
+    }
+
+    /// Records the time one command took to apply.
+    ///
+    /// # Arguments
+    /// * `kind` - The command type.
+    /// * `elapsed` - How long applying it took.
+    pub fn record(&mut self, kind: CsvTxType, elapsed: Duration) {
+        let nanos = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+        self.histograms
+            .entry(kind.as_str())
+            .or_insert_with(new_histogram)
+            .saturating_record(nanos);
+    }
+
+    /// Summarizes the recorded latencies per command type and over all
+    /// commands (as `all`).
+    ///
+    /// # Arguments
+    /// * `raw` - Whether to embed each histogram, V2-deflate serialized and
+    ///   base64 encoded, so it can be merged or re-analyzed with HdrHistogram
+    ///   tooling.
+    ///
+    /// # Returns
+    /// * `AppResult<BTreeMap<String, LatencySummary>>` - The summaries by command
+    ///   type, or an `AppErrors::Internal` if a histogram cannot be serialized.
+    pub fn summary(&self, raw: bool) -> AppResult<BTreeMap<String, LatencySummary>> {
+        let mut all = new_histogram();
+        let mut summaries = BTreeMap::new();
+        for (kind, histogram) in &self.histograms {
+            all.add(histogram)
+                .map_err(|e| AppErrors::Internal(format!("merge latency histograms: {e:?}")))?;
+            summaries.insert(kind.to_string(), LatencySummary::new(histogram, raw)?);
+        }
+        if !all.is_empty() {
+            summaries.insert("all".to_string(), LatencySummary::new(&all, raw)?);
+        }
+        Ok(summaries)
+    }
+}
+
+/// The percentile summary of one latency histogram, in nanoseconds.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LatencySummary {
+    /// The number of commands recorded.
+    pub count: u64,
+    /// The fastest command.
+    pub min: u64,
+    /// The mean, rounded down.
+    pub mean: u64,
+    /// The median.
+    pub p50: u64,
+    /// The 90th percentile.
+    pub p90: u64,
+    /// The 99th percentile.
+    pub p99: u64,
+    /// The 99.9th percentile.
+    pub p999: u64,
+    /// The slowest command.
+    pub max: u64,
+    /// The histogram, V2-deflate serialized and base64 encoded, if requested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub histogram: Option<String>,
+}
+
+impl LatencySummary {
+    /// Summarizes a histogram, optionally embedding it.
+    fn new(histogram: &Histogram<u64>, raw: bool) -> AppResult<Self> {
+        let histogram_b64 = if raw {
+            let mut bytes = Vec::new();
+            V2DeflateSerializer::new()
+                .serialize(histogram, &mut bytes)
+                .map_err(|e| AppErrors::Internal(format!("serialize latency histogram: {e:?}")))?;
+            Some(STANDARD.encode(bytes))
+        } else {
+            None
+        };
+        Ok(Self {
+            count: histogram.len(),
+            min: histogram.min(),
+            mean: histogram.mean() as u64,
+            p50: histogram.value_at_quantile(0.5),
+            p90: histogram.value_at_quantile(0.9),
+            p99: histogram.value_at_quantile(0.99),
+            p999: histogram.value_at_quantile(0.999),
+            max: histogram.max(),
+            histogram: histogram_b64,
+        })
+    }
+}
+
+/// Creates an empty histogram tracking 1 ns to [`MAX_TRACKABLE_NANOS`].
+fn new_histogram() -> Histogram<u64> {
+    Histogram::new_with_bounds(1, MAX_TRACKABLE_NANOS, SIGNIFICANT_DIGITS)
+        .expect("the latency bounds are valid")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hdrhistogram::serialization::Deserializer;
+
+    #[test]
+    fn summarizes_latencies_per_command_type() {
+        let mut recorder = LatencyRecorder::new();
+        for micros in 1..=100 {
+            recorder.record(CsvTxType::Deposit, Duration::from_micros(micros));
+        }
+        recorder.record(CsvTxType::Dispute, Duration::from_millis(5));
+
+        let summary = recorder.summary(true).unwrap();
+
+        assert_eq!(
+            summary.keys().collect::<Vec<_>>(),
+            ["all", "deposit", "dispute"]
+        );
+        let deposits = &summary["deposit"];
+        assert_eq!(deposits.count, 100);
+        // Within the histogram's 0.1% precision.
+        assert!(deposits.p50.abs_diff(50_000) <= 50, "{}", deposits.p50);
+        assert!(deposits.p99.abs_diff(99_000) <= 99, "{}", deposits.p99);
+        assert_eq!(summary["all"].count, 101);
+        assert!(summary["all"].max >= 5_000_000);
+        let raw = STANDARD
+            .decode(summary["dispute"].histogram.as_ref().unwrap())
+            .unwrap();
+        let decoded: Histogram<u64> = Deserializer::new().deserialize(&mut &raw[..]).unwrap();
+        assert_eq!(decoded.len(), 1);
+        assert!(recorder.summary(false).unwrap()["all"].histogram.is_none());
+    }
+
+    #[test]
+    fn empty_recorder_has_no_summaries() {
+        assert!(LatencyRecorder::new().summary(true).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,342 @@
+use crate::errors::{AppErrors, AppResult};
+use crate::models::amount::Amount;
+use crate::models::domain_state::{DisputeState, TxKind};
+use crate::models::identifiers::{ClientId, TxId};
+use crate::services::backup::utc_date;
+use crate::services::currency::Currency;
+use crate::state::Engine;
+use clap::ValueEnum;
+use std::io::Write;
+
+/// The cash the engine holds for its clients.
+const CASH: &str = "Assets:Cash";
+
+/// Where balances carried in from earlier runs are booked from.
+const OPENING: &str = "Equity:Opening-Balances";
+
+/// The plain-text accounting syntax of a [`write_ledger`] export.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LedgerSyntax {
+    /// Beancount, with `open` directives and the tx id as `tx` metadata.
+    #[default]
+    Beancount,
+    /// ledger-cli, with the tx id as the transaction code.
+    Ledger,
+}
+
+/// Writes the tx history as double-entry plain-text accounting, for beancount or
+/// ledger-cli users.
+///
+/// The engine owes each client its funds, so client `N` has a liability account
+/// `Liabilities:Clients:CN` for available funds and `Liabilities:Clients:CN:Held`
+/// for funds held by disputes, against the cash in `Assets:Cash`:
+/// - A deposit debits cash and credits the client; a withdrawal the reverse.
+/// - An open dispute moves the deposit from the client's available to its held
+///   account; a chargeback moves it there and reverses it out of cash.
+/// - Balances the tx log does not explain (carried in from opening balances or
+///   compacted history) are booked first, from `Equity:Opening-Balances`.
+///
+/// Clients are sorted by id and their transactions by tx id. The engine records
+/// no tx timestamps, so every entry is dated on the export date.
+///
+/// # Arguments
+/// * `out` - The text sink.
+/// * `engine` - The state to export.
+/// * `syntax` - Beancount or ledger-cli.
+/// * `currency` - The commodity the amounts are in.
+/// * `created_at` - The export date, in seconds since the Unix epoch.
+///
+/// # Returns
+/// * `AppResult<usize>` - The number of entries written, or `AppErrors::Overflow`
+///   if a balance does not fit an amount, or an `AppErrors::Io` on write failure.
+pub fn write_ledger(
+    mut out: impl Write,
+    engine: &Engine,
+    syntax: LedgerSyntax,
+    currency: &Currency,
+    created_at: u64,
+) -> AppResult<usize> {
+    let mut accounts: Vec<_> = engine.accounts_iter().collect();
+    accounts.sort_by_key(|(c, _)| **c);
+    let mut history = engine.tx_history();
+    let mut book = Book {
+        text: String::new(),
+        syntax,
+        date: utc_date(created_at),
+        currency: &currency.code,
+        entries: 0,
+    };
+
+    if syntax == LedgerSyntax::Beancount {
+        let mut opens = vec![CASH.to_string(), OPENING.to_string()];
+        for (client, _) in &accounts {
+            opens.push(available(**client));
+            opens.push(held(**client));
+        }
+        for account in opens {
+            book.text
+                .push_str(&format!("{} open {account} {}\n", book.date, book.currency));
+        }
+        book.text.push('\n');
+    }
+
+    for (client, acc) in &accounts {
+        let (available_acct, held_acct) = (available(**client), held(**client));
+        let txs = history.remove(client).unwrap_or_default();
+        // What the log explains of each balance; the rest was carried in.
+        let (mut logged_available, mut logged_held) = (Amount::zero(), Amount::zero());
+        for (_, rec) in &txs {
+            let (next_available, next_held) = match (rec.kind, rec.state) {
+                (TxKind::Withdrawal, _) => {
+                    (logged_available.checked_sub(rec.amount), Some(logged_held))
+                }
+                (TxKind::Deposit, DisputeState::Disputed) => {
+                    (Some(logged_available), logged_held.checked_add(rec.amount))
+                }
+                (TxKind::Deposit, DisputeState::ChargedBack) => {
+                    (Some(logged_available), Some(logged_held))
+                }
+                (TxKind::Deposit, _) => {
+                    (logged_available.checked_add(rec.amount), Some(logged_held))
+                }
+            };
+            logged_available = next_available.ok_or(AppErrors::Overflow)?;
+            logged_held = next_held.ok_or(AppErrors::Overflow)?;
+        }
+        let opening = [
+            (&available_acct, acc.available.checked_sub(logged_available)),
+            (&held_acct, acc.held.checked_sub(logged_held)),
+        ];
+        for (account, carried) in opening {
+            let carried = carried.ok_or(AppErrors::Overflow)?;
+            if carried != Amount::zero() {
+                book.entry(
+                    None,
+                    "opening balance",
+                    &[(OPENING, carried), (account, neg(carried))],
+                );
+            }
+        }
+
+        for (tx, rec) in txs {
+            let a = rec.amount;
+            match rec.kind {
+                TxKind::Deposit => {
+                    book.entry(Some(tx), "deposit", &[(CASH, a), (&available_acct, neg(a))])
+                }
+                TxKind::Withdrawal => book.entry(
+                    Some(tx),
+                    "withdrawal",
+                    &[(&available_acct, a), (CASH, neg(a))],
+                ),
+            }
+            if matches!(
+                rec.state,
+                DisputeState::Disputed | DisputeState::ChargedBack
+            ) {
+                book.entry(
+                    Some(tx),
+                    "dispute",
+                    &[(&available_acct, a), (&held_acct, neg(a))],
+                );
+            }
+            if rec.state == DisputeState::ChargedBack {
+                book.entry(Some(tx), "chargeback", &[(&held_acct, a), (CASH, neg(a))]);
+            }
+        }
+    }
+
+    out.write_all(book.text.as_bytes())
+        .and_then(|_| out.flush())
+        .map_err(|e| AppErrors::Io(format!("write ledger: {e}")))?;
+    Ok(book.entries)
+}
+
+/// The export being written.
+struct Book<'a> {
+    /// The text so far.
+    text: String,
+    /// The syntax to write.
+    syntax: LedgerSyntax,
+    /// The date of every entry, `YYYY-MM-DD`.
+    date: String,
+    /// The commodity.
+    currency: &'a str,
+    /// The number of entries written.
+    entries: usize,
+}
+
+impl Book<'_> {
+    /// Appends a balanced entry.
+    fn entry(&mut self, tx: Option<TxId>, narration: &str, postings: &[(&str, Amount)]) {
+        match (self.syntax, tx) {
+            (LedgerSyntax::Beancount, Some(tx)) => self.text.push_str(&format!(
+                "{} * \"{narration}\"\n  tx: \"{tx}\"\n",
+                self.date
+            )),
+            (LedgerSyntax::Beancount, None) => self
+                .text
+                .push_str(&format!("{} * \"{narration}\"\n", self.date)),
+            (LedgerSyntax::Ledger, Some(tx)) => self
+                .text
+                .push_str(&format!("{} * ({tx}) {narration}\n", self.date)),
+            (LedgerSyntax::Ledger, None) => self
+                .text
+                .push_str(&format!("{} * {narration}\n", self.date)),
+        }
+        for (account, amount) in postings {
+            self.text.push_str(&format!(
+                "  {account:<32} {:>14} {}\n",
+                amount.to_string(),
+                self.currency
+            ));
+        }
+        self.text.push('\n');
+        self.entries += 1;
+    }
+}
+
+/// The liability account for a client's available funds.
+fn available(client: ClientId) -> String {
+    format!("Liabilities:Clients:C{client}")
+}
+
+/// The liability account for a client's funds held by disputes.
+fn held(client: ClientId) -> String {
+    format!("Liabilities:Clients:C{client}:Held")
+}
+
+/// Negates an amount; posting amounts are bounded by stored balances, so this
+/// cannot overflow.
+fn neg(amount: Amount) -> Amount {
+    Amount(-amount.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::domain_state::TxRecord;
+    use crate::services::currency::CurrencyTable;
+
+    #[test]
+    fn books_balanced_entries_with_opening_balances() {
+        let mut engine = Engine::default();
+        // Carried in 10, deposited 5 (disputed) and 2 (charged back), withdrew 1.
+        engine.acct_mut(4).available = Amount(90_000);
+        engine.acct_mut(4).held = Amount(50_000);
+        for (tx, kind, amount, state) in [
+            (1, TxKind::Deposit, 50_000, DisputeState::Disputed),
+            (2, TxKind::Deposit, 20_000, DisputeState::ChargedBack),
+            (3, TxKind::Withdrawal, 10_000, DisputeState::Normal),
+        ] {
+            engine.txs.insert(
+                tx,
+                TxRecord {
+                    client: 4,
+                    kind,
+                    amount: Amount(amount),
+                    state,
+                },
+            );
+        }
+        let eur = CurrencyTable::bundled().currency("EUR").unwrap();
+        let mut beancount = Vec::new();
+        let mut ledger = Vec::new();
+
+        let entries = write_ledger(
+            &mut beancount,
+            &engine,
+            LedgerSyntax::Beancount,
+            &eur,
+            1_792_203_016,
+        )
+        .unwrap();
+        write_ledger(
+            &mut ledger,
+            &engine,
+            LedgerSyntax::Ledger,
+            &eur,
+            1_792_203_016,
+        )
+        .unwrap();
+
+        let beancount = String::from_utf8(beancount).unwrap();
+        let ledger = String::from_utf8(ledger).unwrap();
+        // Opening, three movements, two dispute holds, one chargeback.
+        assert_eq!(entries, 7);
+        assert!(beancount.contains("2026-10-17 open Liabilities:Clients:C4:Held EUR\n"));
+        assert!(beancount.contains(&format!(
+            "2026-10-17 * \"opening balance\"\n  {OPENING:<32} {:>14} EUR\n",
+            "10.0000"
+        )));
+        assert!(beancount.contains("2026-10-17 * \"chargeback\"\n  tx: \"2\"\n"));
+        assert!(ledger.contains("2026-10-17 * (1) dispute\n"));
+        assert!(!ledger.contains(" open "));
+        // Every entry balances, so the postings of the whole export sum to zero.
+        let sum: i64 = ledger
+            .lines()
+            .filter_map(|l| l.strip_suffix(" EUR"))
+            .map(|l| {
+                Amount::parse_4dp(l.split_whitespace().last().unwrap())
+                    .unwrap()
+                    .0
+            })
+            .sum();
+        assert_eq!(sum, 0);
+    }
+
+    #[test]
+    fn empty_engine_opens_only_the_book_accounts() {
+        let eur = CurrencyTable::bundled().currency("EUR").unwrap();
+        let mut beancount = Vec::new();
+        let mut ledger = Vec::new();
+
+        let entries = write_ledger(
+            &mut beancount,
+            &Engine::default(),
+            LedgerSyntax::Beancount,
+            &eur,
+            0,
+        )
+        .unwrap();
+        write_ledger(
+            &mut ledger,
+            &Engine::default(),
+            LedgerSyntax::Ledger,
+            &eur,
+            0,
+        )
+        .unwrap();
+
+        assert_eq!(entries, 0);
+        assert_eq!(
+            String::from_utf8(beancount).unwrap(),
+            format!("1970-01-01 open {CASH} EUR\n1970-01-01 open {OPENING} EUR\n\n")
+        );
+        assert!(ledger.is_empty());
+    }
+
+    #[test]
+    fn locked_account_without_history_is_booked_from_opening_balances() {
+        let mut engine = Engine::default();
+        let acc = engine.acct_mut(2);
+        acc.available = Amount(-30_000);
+        acc.held = Amount(10_000);
+        acc.locked = true;
+        let eur = CurrencyTable::bundled().currency("EUR").unwrap();
+        let mut ledger = Vec::new();
+
+        let entries = write_ledger(&mut ledger, &engine, LedgerSyntax::Ledger, &eur, 0).unwrap();
+
+        let ledger = String::from_utf8(ledger).unwrap();
+        assert_eq!(entries, 2);
+        assert!(ledger.contains(&format!(
+            "  {:<32} {:>14} EUR\n",
+            "Liabilities:Clients:C2", "3.0000"
+        )));
+        assert!(ledger.contains(&format!(
+            "  {:<32} {:>14} EUR\n",
+            "Liabilities:Clients:C2:Held", "-1.0000"
+        )));
+    }
+}
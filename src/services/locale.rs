@@ -0,0 +1,134 @@
+use crate::errors::AppErrors;
+use crate::models::amount::Amount;
+use std::fmt;
+use std::str::FromStr;
+
+/// Number formatting conventions for human-facing reports (tables, statements),
+/// selected by a locale tag such as `en-US`, `de-DE` or `fr-FR`.
+///
+/// Only the thousands separator and the decimal mark vary; amounts keep their
+/// four decimal places so no precision is hidden from the reader. Machine
+/// formats (CSV, JSON, Parquet, camt.053) never use a locale.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Locale {
+    /// The tag as given, normalized to `ll-RR` form.
+    tag: String,
+    /// The thousands separator, if digits are grouped.
+    group: Option<char>,
+    /// The decimal mark.
+    decimal: char,
+}
+
+impl Locale {
+    /// Formats an amount with this locale's separators, e.g. `1234567.5` as
+    /// `1,234,567.5000` in `en-US` or `1.234.567,5000` in `de-DE`.
+    ///
+    /// # Arguments
+    /// * `amount` - The amount to format.
+    ///
+    /// # Returns
+    /// * `String` - The formatted amount, with a leading `-` if negative.
+    pub fn format_amount(&self, amount: Amount) -> String {
+        let plain = amount.to_string();
+        let (sign, unsigned) = match plain.strip_prefix('-') {
+            Some(rest) => ("-", rest),
+            None => ("", plain.as_str()),
+        };
+        let (int, frac) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+        let mut out = String::from(sign);
+        for (i, digit) in int.chars().enumerate() {
+            if let Some(group) = self.group
+                && i > 0
+                && (int.len() - i) % 3 == 0
+            {
+                out.push(group);
+            }
+            out.push(digit);
+        }
+        if !frac.is_empty() {
+            out.push(self.decimal);
+            out.push_str(frac);
+        }
+        out
+    }
+}
+
+impl Default for Locale {
+    /// No grouping and a decimal point, as in the machine CSV.
+    fn default() -> Self {
+        Self {
+            tag: "C".to_string(),
+            group: None,
+            decimal: '.',
+        }
+    }
+}
+
+impl FromStr for Locale {
+    type Err = AppErrors;
+
+    /// Parses a locale tag such as `en-US`, `de_DE` or `fr`. The language picks
+    /// the conventions; Swiss regions (`-CH`) group with apostrophes.
+    ///
+    /// # Errors
+    /// * `AppErrors::InvalidInput` if the language is not supported.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tag = s.trim().replace('_', "-");
+        let (lang, region) = tag.split_once('-').unwrap_or((&tag, ""));
+        let (lang, region) = (lang.to_ascii_lowercase(), region.to_ascii_uppercase());
+        if lang == "c" || lang == "posix" {
+            return Ok(Self::default());
+        }
+        let (group, decimal) = match (lang.as_str(), region.as_str()) {
+            (_, "CH") => ('\'', '.'),
+            ("en" | "ja" | "zh" | "ko" | "he" | "th", _) => (',', '.'),
+            ("de" | "nl" | "es" | "it" | "pt" | "da" | "id" | "tr" | "el", _) => ('.', ','),
+            ("fr" | "pl" | "cs" | "sk" | "sv" | "fi" | "nb" | "no" | "ru" | "uk", _) => {
+                ('\u{a0}', ',')
+            }
+            _ => {
+                return Err(AppErrors::InvalidInput(
+                    "unsupported locale; use a tag such as en-US, de-DE or fr-FR",
+                ));
+            }
+        };
+        Ok(Self {
+            tag: if region.is_empty() {
+                lang
+            } else {
+                format!("{lang}-{region}")
+            },
+            group: Some(group),
+            decimal,
+        })
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.tag)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_amounts_per_locale() {
+        let amount = Amount(-12_345_675_000);
+        let format = |tag: &str| tag.parse::<Locale>().unwrap().format_amount(amount);
+
+        assert_eq!(format("en-US"), "-1,234,567.5000");
+        assert_eq!(format("de_DE"), "-1.234.567,5000");
+        assert_eq!(format("fr-FR"), "-1\u{a0}234\u{a0}567,5000");
+        assert_eq!(format("de-CH"), "-1'234'567.5000");
+        assert_eq!(format("C"), "-1234567.5000");
+        assert_eq!(
+            "en".parse::<Locale>().unwrap().format_amount(Amount(1_000)),
+            "0.1000"
+        );
+        assert_eq!("de_de".parse::<Locale>().unwrap().to_string(), "de-DE");
+        assert!("xx-YY".parse::<Locale>().is_err());
+    }
+}
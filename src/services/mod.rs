@@ -1,2 +1,55 @@
+pub mod account_writer;
+pub mod alerts;
+pub mod analysis;
+pub mod archive;
+#[cfg(feature = "arrow")]
+pub mod arrow_input;
+#[cfg(feature = "tokio")]
+pub mod async_engine;
+pub mod avro_input;
+pub mod backup;
+pub mod camt053;
+pub mod cdc;
+pub mod client_export;
 pub mod commands;
+pub mod conservation;
 pub mod csv_service;
+pub mod currency;
+pub mod dedup_store;
+pub mod dispute_sla;
+pub mod end_of_day;
+pub mod engine_handle;
+pub mod erasure;
+pub mod exposure;
+pub mod file_report;
+pub mod fixed_width_input;
+pub mod generator;
+pub mod input_format;
+pub mod latency;
+pub mod ledger_export;
+pub mod locale;
+pub mod mt940;
+pub mod nacha_input;
+pub mod ofx;
+pub mod opening_balances;
+pub mod paged_store;
+pub mod priority_lanes;
+#[cfg(feature = "proto")]
+pub mod proto_input;
+pub mod reconcile;
+pub mod rejects;
+pub mod replication;
+pub mod report_output;
+pub mod rounding;
+pub mod run_history;
+pub mod run_manifest;
+pub mod run_report;
+pub mod run_summary;
+pub mod server;
+pub mod shard_map;
+pub mod state_store;
+pub mod statement;
+pub mod text_decoding;
+pub mod validation;
+#[cfg(feature = "xlsx")]
+pub mod xlsx_input;
@@ -0,0 +1,169 @@
+use crate::errors::{AppErrors, AppResult};
+use crate::models::amount::Amount;
+use crate::models::domain_state::{DisputeState, TxKind};
+use crate::services::backup::utc_date;
+use crate::services::currency::Currency;
+use crate::state::Engine;
+use std::io::Write;
+
+/// Writes one SWIFT MT940 customer statement per client, sorted by client, for
+/// interop with treasury systems. Each statement is the message text block
+/// (`:20:` to `:64:`), lines ending in CRLF, closed by a `-` line:
+///
+/// - `:20:` the reference `PE<YYMMDD>-<client>`, `:25:` the client id as the
+///   account, `:28C:` statement number `1`.
+/// - `:60F:` the opening balance: the closing total less every movement in
+///   the tx log, so balances carried in from opening balances or compacted
+///   history are part of it.
+/// - `:61:` one statement line per tx record, by tx id: deposits are credits
+///   (`C`), withdrawals debits (`D`), with the tx id as reference and `:86:`
+///   naming the type; a disputed deposit is marked `disputed`, and a
+///   charged-back one is followed by a reversal of the credit (`RC`).
+/// - `:62F:` the closing booked balance (the total) and `:64:` the closing
+///   available balance.
+///
+/// The engine records no tx timestamps, so every line is value-dated on the
+/// statement date. Amounts use a decimal comma and keep at least the currency's
+/// minor units, e.g. `1,50` in EUR or `100,` in JPY.
+///
+/// # Arguments
+/// * `out` - The statement sink.
+/// * `engine` - The state to report on.
+/// * `currency` - The currency the amounts are in.
+/// * `created_at` - The statement date, in seconds since the Unix epoch.
+///
+/// # Returns
+/// * `AppResult<usize>` - The number of statements written, or
+///   `AppErrors::Overflow` if a balance does not fit an amount, or an
+///   `AppErrors::Io` on write failure.
+pub fn write_mt940(
+    mut out: impl Write,
+    engine: &Engine,
+    currency: &Currency,
+    created_at: u64,
+) -> AppResult<usize> {
+    let mut accounts: Vec<_> = engine.accounts_iter().collect();
+    accounts.sort_by_key(|(c, _)| **c);
+    let mut history = engine.tx_history();
+    let date = utc_date(created_at);
+    let yymmdd = format!("{}{}{}", &date[2..4], &date[5..7], &date[8..10]);
+    let fmt = |amount: Amount| mt940_amount(amount, currency.minor_units);
+
+    let mut text = String::new();
+    for (client, acc) in &accounts {
+        let txs = history.remove(client).unwrap_or_default();
+        let mut lines = String::new();
+        let mut opening = acc.total();
+        for (tx, rec) in txs {
+            let (mark, before) = match rec.kind {
+                TxKind::Deposit => ("C", opening.checked_sub(rec.amount)),
+                TxKind::Withdrawal => ("D", opening.checked_add(rec.amount)),
+            };
+            opening = before.ok_or(AppErrors::Overflow)?;
+            let info = match (rec.kind, rec.state) {
+                (TxKind::Deposit, DisputeState::Disputed) => "deposit, disputed",
+                (TxKind::Deposit, _) => "deposit",
+                (TxKind::Withdrawal, _) => "withdrawal",
+            };
+            lines.push_str(&format!(
+                ":61:{yymmdd}{mark}{}NMSC{tx}\r\n:86:{info}\r\n",
+                fmt(rec.amount)
+            ));
+            if rec.state == DisputeState::ChargedBack {
+                opening = opening.checked_add(rec.amount).ok_or(AppErrors::Overflow)?;
+                lines.push_str(&format!(
+                    ":61:{yymmdd}RC{}NMSC{tx}\r\n:86:chargeback\r\n",
+                    fmt(rec.amount)
+                ));
+            }
+        }
+        let balance = |tag: &str, amount: Amount| {
+            let mark = if amount.is_negative() { "D" } else { "C" };
+            format!(":{tag}:{mark}{yymmdd}{}{}\r\n", currency.code, fmt(amount))
+        };
+        text.push_str(&format!(
+            ":20:PE{yymmdd}-{client}\r\n:25:{client}\r\n:28C:1\r\n"
+        ));
+        text.push_str(&balance("60F", opening));
+        text.push_str(&lines);
+        text.push_str(&balance("62F", acc.total()));
+        text.push_str(&balance("64", acc.available));
+        text.push_str("-\r\n");
+    }
+    out.write_all(text.as_bytes())
+        .and_then(|_| out.flush())
+        .map_err(|e| AppErrors::Io(format!("write statement: {e}")))?;
+    Ok(accounts.len())
+}
+
+/// Formats an amount's magnitude with a decimal comma, trimming trailing zeros
+/// down to the currency's minor units, e.g. `1.5000` as `1,50` with two.
+fn mt940_amount(amount: Amount, minor_units: u8) -> String {
+    let plain = Amount(amount.0.abs()).to_string();
+    let (int, frac) = plain.split_once('.').unwrap_or((&plain, ""));
+    let keep = frac
+        .trim_end_matches('0')
+        .len()
+        .max(usize::from(minor_units));
+    format!("{int},{}", &frac[..keep.min(frac.len())])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::domain_state::TxRecord;
+    use crate::services::currency::CurrencyTable;
+
+    #[test]
+    fn derives_opening_balance_and_lines_from_the_tx_log() {
+        let mut engine = Engine::default();
+        // Carried in 10.00, deposited 5.00 and 2.50 (charged back), withdrew 1.25.
+        engine.acct_mut(3).available = Amount(137_500);
+        for (tx, kind, amount, state) in [
+            (4, TxKind::Withdrawal, 12_500, DisputeState::Normal),
+            (1, TxKind::Deposit, 50_000, DisputeState::Normal),
+            (2, TxKind::Deposit, 25_000, DisputeState::ChargedBack),
+        ] {
+            engine.txs.insert(
+                tx,
+                TxRecord {
+                    client: 3,
+                    kind,
+                    amount: Amount(amount),
+                    state,
+                },
+            );
+        }
+        let eur = CurrencyTable::bundled().currency("EUR").unwrap();
+        let mut out = Vec::new();
+
+        let written = write_mt940(&mut out, &engine, &eur, 1_792_203_016).unwrap();
+
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<_> = text.split("\r\n").collect();
+        assert_eq!(written, 1);
+        assert_eq!(
+            lines,
+            [
+                ":20:PE261017-3",
+                ":25:3",
+                ":28C:1",
+                ":60F:C261017EUR10,00",
+                ":61:261017C5,00NMSC1",
+                ":86:deposit",
+                ":61:261017C2,50NMSC2",
+                ":86:deposit",
+                ":61:261017RC2,50NMSC2",
+                ":86:chargeback",
+                ":61:261017D1,25NMSC4",
+                ":86:withdrawal",
+                ":62F:C261017EUR13,75",
+                ":64:C261017EUR13,75",
+                "-",
+                "",
+            ]
+        );
+        assert_eq!(mt940_amount(Amount(1_000_000), 0), "100,");
+        assert_eq!(mt940_amount(Amount(-12_345), 2), "1,2345");
+    }
+}
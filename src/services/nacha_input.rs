@@ -0,0 +1,258 @@
+use crate::errors::{AppErrors, AppResult};
+use crate::models::csv_models::transaction::{CsvTxType, InputRow};
+use crate::services::csv_service::open_text_input;
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read};
+
+/// The length of every NACHA record.
+const RECORD_LEN: usize = 94;
+
+/// The record type code of an entry detail record.
+const ENTRY_DETAIL: u8 = b'6';
+
+/// Reads a NACHA ACH file's entry detail records as transaction rows.
+///
+/// Only entry detail records (type `6`) carry money movement; file, batch and
+/// addenda records and the `9`-filled blocking padding are skipped. Each entry
+/// becomes one row:
+///
+/// | Positions | Field                 | Becomes                                  |
+/// |-----------|-----------------------|------------------------------------------|
+/// | 2-3       | transaction code      | `22`/`32` credit: deposit; `27`/`37` debit: withdrawal |
+/// | 30-39     | amount, in cents      | the amount, e.g. `0000012345` is `123.45` |
+/// | 40-54     | individual id number  | the client id                            |
+/// | 88-94     | trace sequence number | the tx id                                |
+///
+/// Prenotes and zero-dollar entries (codes `23`, `24`, `28`, `29`, `33`, `34`,
+/// `38`, `39`) move no money and are skipped. Trace sequence numbers are unique
+/// within an originator's file; inputs combining several originators must keep
+/// them unique, or later entries are ignored as duplicate tx ids.
+///
+/// Records are numbered from 1 in file order, in place of CSV line numbers, and
+/// may be newline-separated or blocked without separators. An entry that does
+/// not parse is yielded as a row error; a read failure is an outer error that
+/// aborts processing.
+pub struct NachaRows<R: BufRead> {
+    /// The input, positioned at the next line.
+    input: R,
+    /// The buffer of the current line.
+    buf: String,
+    /// Records of the current line not yet read.
+    pending: VecDeque<String>,
+    /// The number of the last record read.
+    record: u64,
+    /// Set once the input is exhausted or a read failed.
+    done: bool,
+}
+
+/// Opens a NACHA ACH file.
+///
+/// # Arguments
+/// * `path` - The file path, or [`STDIN_PATH`](crate::consts::STDIN_PATH) (`-`)
+///   to read stdin. A gzip- or zstd-compressed file is decompressed first, and
+///   UTF-16 or a byte order mark is decoded (see `csv_service::open_text_input`).
+///
+/// # Returns
+/// * `AppResult<NachaRows<BufReader<Box<dyn Read>>>>` - The entry reader, or an
+///   `AppErrors` variant if the file cannot be opened.
+pub fn open_nacha(path: &str) -> AppResult<NachaRows<BufReader<Box<dyn Read>>>> {
+    Ok(NachaRows::new(BufReader::new(open_text_input(path)?)))
+}
+
+impl<R: BufRead> NachaRows<R> {
+    /// Creates an entry reader over any line source.
+    pub fn new(input: R) -> Self {
+        Self {
+            input,
+            buf: String::new(),
+            pending: VecDeque::new(),
+            record: 0,
+            done: false,
+        }
+    }
+
+    /// Reads the next line and splits it into records; a line longer than one
+    /// record is a run of blocked records.
+    ///
+    /// # Returns
+    /// * `AppResult<bool>` - `false` at the end of the input, or an `AppErrors::Io`
+    ///   if reading failed.
+    fn fill(&mut self) -> AppResult<bool> {
+        self.buf.clear();
+        let read = self
+            .input
+            .read_line(&mut self.buf)
+            .map_err(|e| AppErrors::Io(format!("read record {}: {e}", self.record + 1)))?;
+        if read == 0 {
+            return Ok(false);
+        }
+        let line = self.buf.trim_end_matches(['\n', '\r']);
+        if line.len() > RECORD_LEN && line.is_ascii() {
+            self.pending.extend(
+                line.as_bytes()
+                    .chunks(RECORD_LEN)
+                    .map(|r| String::from_utf8_lossy(r).into_owned()),
+            );
+        } else {
+            self.pending.push_back(line.to_string());
+        }
+        Ok(true)
+    }
+}
+
+impl<R: BufRead> Iterator for NachaRows<R> {
+    type Item = AppResult<(u64, AppResult<InputRow>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let Some(record) = self.pending.pop_front() else {
+                if self.done {
+                    return None;
+                }
+                match self.fill() {
+                    Ok(true) => continue,
+                    Ok(false) => {
+                        self.done = true;
+                        return None;
+                    }
+                    Err(e) => {
+                        self.done = true;
+                        return Some(Err(e));
+                    }
+                }
+            };
+            if record.trim().is_empty() {
+                continue;
+            }
+            self.record += 1;
+            if record.as_bytes()[0] != ENTRY_DETAIL {
+                continue;
+            }
+            match entry_to_row(&record) {
+                Ok(Some(row)) => return Some(Ok((self.record, Ok(row)))),
+                Ok(None) => continue,
+                Err(e) => return Some(Ok((self.record, Err(e)))),
+            }
+        }
+    }
+}
+
+/// Converts an entry detail record into a transaction row.
+///
+/// # Returns
+/// * `AppResult<Option<InputRow>>` - The row, `None` for a prenote or zero-dollar
+///   entry, or an `AppErrors` variant if the record is malformed or its
+///   transaction code is not a checking or savings credit or debit.
+fn entry_to_row(record: &str) -> AppResult<Option<InputRow>> {
+    if record.len() != RECORD_LEN || !record.is_ascii() {
+        return Err(AppErrors::InvalidInput(
+            "NACHA entry is not a 94-character record",
+        ));
+    }
+    let t = match &record[1..3] {
+        "22" | "32" => CsvTxType::Deposit,
+        "27" | "37" => CsvTxType::Withdrawal,
+        "23" | "24" | "28" | "29" | "33" | "34" | "38" | "39" => return Ok(None),
+        _ => {
+            return Err(AppErrors::InvalidInput(
+                "unsupported NACHA transaction code",
+            ));
+        }
+    };
+    let cents: u64 =
+        digits(&record[29..39]).ok_or(AppErrors::InvalidInput("NACHA amount is not numeric"))?;
+    Ok(Some(InputRow {
+        t,
+        client: record[39..54]
+            .trim()
+            .parse()
+            .map_err(|_| AppErrors::InvalidInput("client id missing or out of range"))?,
+        tx: digits(&record[87..94])
+            .and_then(|n| n.try_into().ok())
+            .ok_or(AppErrors::InvalidInput("NACHA trace number is not numeric"))?,
+        amount: Some(format!("{}.{:02}", cents / 100, cents % 100)),
+    }))
+}
+
+/// Parses a zero-padded, all-digit field.
+fn digits(field: &str) -> Option<u64> {
+    if field.bytes().all(|b| b.is_ascii_digit()) {
+        field.parse().ok()
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds an entry detail record.
+    fn entry(code: &str, cents: u64, client: &str, seq: u32) -> String {
+        format!(
+            "6{code}09100001312345678901234567{cents:010}{client:<15}JANE DOE                009100001{seq:07}"
+        )
+    }
+
+    #[test]
+    fn maps_credits_and_debits_and_skips_other_records() {
+        let header = format!("101 091000013 0910000132401011200A094101{:<54}", "");
+        let batch = format!("5200ACME{:<86}", "");
+        let blocked = entry("22", 12_345, "7", 1) + &entry("27", 2_000, "7", 2);
+        let file = [
+            header,
+            batch,
+            blocked,
+            entry("23", 0, "7", 3),
+            entry("99", 100, "7", 4),
+            "9".repeat(RECORD_LEN),
+        ]
+        .join("\n");
+        assert!(file.lines().all(|l| l.len() % RECORD_LEN == 0));
+
+        let rows: Vec<_> = NachaRows::new(file.as_bytes())
+            .map(Result::unwrap)
+            .collect();
+
+        assert_eq!(rows.len(), 3);
+        let (record, deposit) = &rows[0];
+        let deposit = deposit.as_ref().unwrap();
+        assert_eq!(*record, 3);
+        assert_eq!(
+            (deposit.t, deposit.client, deposit.tx),
+            (CsvTxType::Deposit, 7, 1)
+        );
+        assert_eq!(deposit.amount.as_deref(), Some("123.45"));
+        let withdrawal = rows[1].1.as_ref().unwrap();
+        assert_eq!(
+            (withdrawal.t, withdrawal.amount.as_deref()),
+            (CsvTxType::Withdrawal, Some("20.00"))
+        );
+        assert_eq!(rows[2].0, 6);
+        assert!(rows[2].1.is_err());
+    }
+
+    #[test]
+    fn empty_file_yields_no_rows() {
+        assert_eq!(NachaRows::new(&b""[..]).count(), 0);
+        assert_eq!(NachaRows::new(&b"\n\n"[..]).count(), 0);
+    }
+
+    #[test]
+    fn malformed_entries_are_row_errors_and_reading_continues() {
+        let short = entry("22", 100, "7", 1)[..60].to_string();
+        let mut bad_amount = entry("22", 100, "7", 2);
+        bad_amount.replace_range(29..39, "00000001X0");
+        let bad_client = entry("22", 100, "client", 3);
+        let file = [short, bad_amount, bad_client, entry("22", 100, "7", 4)].join("\n");
+
+        let rows: Vec<_> = NachaRows::new(file.as_bytes())
+            .map(Result::unwrap)
+            .collect();
+
+        assert_eq!(rows.len(), 4);
+        assert!(rows[..3].iter().all(|(_, row)| row.is_err()));
+        let (record, row) = &rows[3];
+        assert_eq!((*record, row.as_ref().unwrap().tx), (4, 4));
+    }
+}
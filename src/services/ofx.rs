@@ -0,0 +1,168 @@
+use crate::errors::{AppErrors, AppResult};
+use crate::models::amount::Amount;
+use crate::models::domain_state::{DisputeState, TxKind};
+use crate::models::identifiers::ClientId;
+use crate::services::backup::backup_id;
+use crate::services::currency::Currency;
+use crate::state::Engine;
+use std::io::Write;
+
+/// Writes accounts and their tx history as an OFX 2.2 bank statement download,
+/// so clients can import their processed history into personal-finance tools.
+///
+/// Each client gets a statement (`STMTRS`), sorted by client, with the client id
+/// as account id:
+/// - One `STMTTRN` per tx record, by tx id, with the tx id as `FITID`: deposits
+///   are `CREDIT`s, withdrawals `DEBIT`s with a negative amount. A disputed
+///   deposit's `MEMO` says its amount is held; a charged-back one is followed by
+///   a `DEBIT` reversing it, with `FITID` `<tx>-chargeback`.
+/// - `LEDGERBAL` is the total and `AVAILBAL` the available funds.
+///
+/// The engine records no tx timestamps, so every entry is posted at the
+/// statement time.
+///
+/// # Arguments
+/// * `out` - The OFX sink.
+/// * `engine` - The state to export.
+/// * `client` - Only this client's statement, or `None` for every client.
+/// * `currency` - The currency the amounts are in.
+/// * `created_at` - The statement time, in seconds since the Unix epoch.
+///
+/// # Returns
+/// * `AppResult<usize>` - The number of statements written, or an `AppErrors::Io`
+///   on write failure.
+pub fn write_ofx(
+    mut out: impl Write,
+    engine: &Engine,
+    client: Option<ClientId>,
+    currency: &Currency,
+    created_at: u64,
+) -> AppResult<usize> {
+    let mut accounts: Vec<_> = engine
+        .accounts_iter()
+        .filter(|(c, _)| client.is_none_or(|only| **c == only))
+        .collect();
+    accounts.sort_by_key(|(c, _)| **c);
+    let mut history = engine.tx_history();
+    let now = ofx_datetime(created_at);
+
+    let mut ofx = String::new();
+    ofx.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>\n");
+    ofx.push_str(
+        "<?OFX OFXHEADER=\"200\" VERSION=\"220\" SECURITY=\"NONE\" OLDFILEUID=\"NONE\" NEWFILEUID=\"NONE\"?>\n",
+    );
+    ofx.push_str("<OFX>\n");
+    ofx.push_str(&format!(
+        "  <SIGNONMSGSRSV1><SONRS><STATUS><CODE>0</CODE><SEVERITY>INFO</SEVERITY></STATUS><DTSERVER>{now}</DTSERVER><LANGUAGE>ENG</LANGUAGE></SONRS></SIGNONMSGSRSV1>\n"
+    ));
+    ofx.push_str("  <BANKMSGSRSV1>\n");
+    for (client, acc) in &accounts {
+        ofx.push_str(&format!(
+            "    <STMTTRNRS><TRNUID>{client}</TRNUID><STATUS><CODE>0</CODE><SEVERITY>INFO</SEVERITY></STATUS>\n"
+        ));
+        ofx.push_str(&format!(
+            "      <STMTRS><CURDEF>{}</CURDEF><BANKACCTFROM><BANKID>0</BANKID><ACCTID>{client}</ACCTID><ACCTTYPE>CHECKING</ACCTTYPE></BANKACCTFROM>\n",
+            currency.code
+        ));
+        ofx.push_str(&format!(
+            "        <BANKTRANLIST><DTSTART>{now}</DTSTART><DTEND>{now}</DTEND>\n"
+        ));
+        for (tx, rec) in history.remove(client).unwrap_or_default() {
+            let (kind, amount, name) = match rec.kind {
+                TxKind::Deposit => ("CREDIT", rec.amount, "deposit"),
+                TxKind::Withdrawal => ("DEBIT", Amount(-rec.amount.0), "withdrawal"),
+            };
+            let memo = match rec.state {
+                DisputeState::Disputed => format!("<MEMO>disputed, {} held</MEMO>", rec.amount),
+                DisputeState::ChargedBack => "<MEMO>charged back</MEMO>".to_string(),
+                _ => String::new(),
+            };
+            ofx.push_str(&entry(kind, &now, amount, &tx.to_string(), name, &memo));
+            if rec.state == DisputeState::ChargedBack {
+                ofx.push_str(&entry(
+                    "DEBIT",
+                    &now,
+                    Amount(-rec.amount.0),
+                    &format!("{tx}-chargeback"),
+                    "chargeback",
+                    &format!("<MEMO>reverses tx {tx}</MEMO>"),
+                ));
+            }
+        }
+        ofx.push_str("        </BANKTRANLIST>\n");
+        ofx.push_str(&format!(
+            "        <LEDGERBAL><BALAMT>{}</BALAMT><DTASOF>{now}</DTASOF></LEDGERBAL>\n",
+            acc.total()
+        ));
+        ofx.push_str(&format!(
+            "        <AVAILBAL><BALAMT>{}</BALAMT><DTASOF>{now}</DTASOF></AVAILBAL>\n",
+            acc.available
+        ));
+        ofx.push_str("      </STMTRS>\n    </STMTTRNRS>\n");
+    }
+    ofx.push_str("  </BANKMSGSRSV1>\n</OFX>\n");
+    out.write_all(ofx.as_bytes())
+        .and_then(|_| out.flush())
+        .map_err(|e| AppErrors::Io(format!("write statement: {e}")))?;
+    Ok(accounts.len())
+}
+
+/// Formats a Unix timestamp as an OFX date-time, `YYYYMMDDHHMMSS` (UTC).
+fn ofx_datetime(secs: u64) -> String {
+    backup_id(secs).replace(['T', 'Z'], "")
+}
+
+/// Formats a statement transaction.
+fn entry(kind: &str, posted: &str, amount: Amount, fitid: &str, name: &str, memo: &str) -> String {
+    format!(
+        "          <STMTTRN><TRNTYPE>{kind}</TRNTYPE><DTPOSTED>{posted}</DTPOSTED><TRNAMT>{amount}</TRNAMT><FITID>{fitid}</FITID><NAME>{name}</NAME>{memo}</STMTTRN>\n"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::domain_state::TxRecord;
+    use crate::services::currency::CurrencyTable;
+
+    #[test]
+    fn writes_entries_with_dispute_annotations() {
+        let mut engine = Engine::default();
+        engine.acct_mut(1).available = Amount(10_000);
+        engine.acct_mut(1).held = Amount(20_000);
+        engine.acct_mut(2).available = Amount(5_000);
+        for (tx, kind, state) in [
+            (3, TxKind::Deposit, DisputeState::ChargedBack),
+            (2, TxKind::Withdrawal, DisputeState::Normal),
+            (1, TxKind::Deposit, DisputeState::Disputed),
+        ] {
+            engine.txs.insert(
+                tx,
+                TxRecord {
+                    client: 1,
+                    kind,
+                    amount: Amount(20_000),
+                    state,
+                },
+            );
+        }
+        let usd = CurrencyTable::bundled().currency("USD").unwrap();
+        let mut out = Vec::new();
+
+        let written = write_ofx(&mut out, &engine, Some(1), &usd, 1_792_203_016).unwrap();
+
+        let ofx = String::from_utf8(out).unwrap();
+        assert_eq!(written, 1);
+        assert!(!ofx.contains("<ACCTID>2</ACCTID>"));
+        assert!(ofx.contains("<DTSERVER>20261017021016</DTSERVER>"));
+        let fitids: Vec<_> = ofx
+            .match_indices("<FITID>")
+            .map(|(i, _)| &ofx[i + 7..i + 7 + ofx[i + 7..].find('<').unwrap()])
+            .collect();
+        assert_eq!(fitids, ["1", "2", "3", "3-chargeback"]);
+        assert!(ofx.contains("<TRNAMT>2.0000</TRNAMT><FITID>1</FITID><NAME>deposit</NAME><MEMO>disputed, 2.0000 held</MEMO>"));
+        assert!(ofx.contains("<TRNTYPE>DEBIT</TRNTYPE><DTPOSTED>20261017021016</DTPOSTED><TRNAMT>-2.0000</TRNAMT><FITID>2</FITID>"));
+        assert!(ofx.contains("<LEDGERBAL><BALAMT>3.0000</BALAMT>"));
+        assert!(ofx.contains("<AVAILBAL><BALAMT>1.0000</BALAMT>"));
+    }
+}
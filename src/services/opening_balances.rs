@@ -0,0 +1,120 @@
+use crate::errors::{AppErrors, AppResult};
+use crate::models::amount::Amount;
+use crate::models::identifiers::ClientId;
+use crate::state::Engine;
+use csv::ReaderBuilder;
+use log::warn;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::str::FromStr;
+
+/// A row of an opening balances file: the layout of the account report.
+#[derive(Debug, Deserialize)]
+struct OpeningRow {
+    client: ClientId,
+    available: String,
+    held: String,
+    locked: bool,
+}
+
+/// Loads opening balances from a CSV file into the engine, so a run can start
+/// from a previous statement instead of a binary snapshot.
+///
+/// See [`load_opening_balances_from_reader`] for the file layout.
+///
+/// # Arguments
+/// * `path` - The opening balances file.
+/// * `engine` - The engine to seed; expected to be empty.
+///
+/// # Returns
+/// * `AppResult<usize>` - The number of accounts loaded, or an `AppErrors`
+///   variant if the file cannot be read or a row is invalid.
+pub fn load_opening_balances(path: &str, engine: &mut Engine) -> AppResult<usize> {
+    let file = File::open(path).map_err(|e| AppErrors::Io(format!("open {path}: {e}")))?;
+    load_opening_balances_from_reader(BufReader::new(file), engine)
+}
+
+/// Loads opening balances from any CSV source into the engine.
+///
+/// The layout is `client,available,held,locked`, as in the account report; a
+/// `total` column, if present, is ignored. Opening balances carry no tx
+/// records, so held funds are not tied to a dispute: no resolve or chargeback
+/// can release them, and `Engine::verify` reports them.
+///
+/// # Arguments
+/// * `input` - The CSV source.
+/// * `engine` - The engine to seed; expected to be empty.
+///
+/// # Returns
+/// * `AppResult<usize>` - The number of accounts loaded, or an `AppErrors::AtLine`
+///   naming the first invalid row (bad amount, negative balance, or duplicate
+///   client).
+pub fn load_opening_balances_from_reader<R: Read>(
+    input: R,
+    engine: &mut Engine,
+) -> AppResult<usize> {
+    let mut rdr = ReaderBuilder::new().trim(csv::Trim::All).from_reader(input);
+    let mut seen = HashSet::new();
+    let mut with_held = 0;
+    for (i, rec) in rdr.deserialize::<OpeningRow>().enumerate() {
+        let line = i as u64 + 2;
+        let at_line = |source| AppErrors::AtLine {
+            line,
+            source: Box::new(source),
+        };
+        let row = rec.map_err(|e| at_line(AppErrors::Csv(e.to_string())))?;
+        let available = Amount::from_str(&row.available).map_err(|e| at_line(e.into()))?;
+        let held = Amount::from_str(&row.held).map_err(|e| at_line(e.into()))?;
+        if available.is_negative() || held.is_negative() {
+            return Err(at_line(AppErrors::InvalidInput(
+                "opening balances must not be negative",
+            )));
+        }
+        if !seen.insert(row.client) {
+            return Err(at_line(AppErrors::InvalidInput(
+                "duplicate client in opening balances",
+            )));
+        }
+        if held != Amount::zero() {
+            with_held += 1;
+        }
+
+        let acc = engine.acct_mut(row.client);
+        acc.available = available;
+        acc.held = held;
+        acc.locked = row.locked;
+    }
+    if with_held > 0 {
+        warn!(
+            "{with_held} opening account(s) have held funds; they are not tied to a dispute and cannot be released"
+        );
+    }
+    Ok(seen.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loads_the_account_report_layout() {
+        let input = "client,available,held,total,locked\n\
+                     1,1.5,0,1.5,false\n\
+                     2, 0.0000 , 2.0 ,2.0,true\n";
+        let mut engine = Engine::default();
+
+        assert_eq!(
+            load_opening_balances_from_reader(input.as_bytes(), &mut engine).unwrap(),
+            2
+        );
+        assert_eq!(engine.acct(1).unwrap().available, Amount(15_000));
+        assert!(engine.acct(2).unwrap().locked);
+        assert_eq!(engine.acct(2).unwrap().held, Amount(20_000));
+
+        let dup = "client,available,held,locked\n1,1,0,false\n1,2,0,false\n";
+        let err = load_opening_balances_from_reader(dup.as_bytes(), &mut Engine::default());
+        assert!(matches!(err, Err(AppErrors::AtLine { line: 3, .. })));
+    }
+}
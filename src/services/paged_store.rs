@@ -0,0 +1,542 @@
+use crate::errors::{AppErrors, AppResult};
+use crate::models::amount::Amount;
+use crate::models::domain_state::{Account, TxRecord};
+use crate::models::identifiers::{ClientId, TxId};
+use crate::services::state_store::{
+    ByteReader, fnv1a, kind_code, kind_from_code, state_code, state_from_code,
+};
+use crate::state::Engine;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Magic header identifying a paged state file.
+const PAGED_MAGIC: &[u8; 8] = b"PEPAGE01";
+
+/// The encoded size of a page index entry: client, offset, length and record
+/// count.
+const INDEX_ENTRY_LEN: usize = 2 + 8 + 4 + 4;
+
+/// One client's page: its account and the tx records it owns.
+#[derive(Debug, Clone)]
+pub(crate) struct Page {
+    /// The client.
+    pub(crate) client: ClientId,
+    /// The client's account, or `None` for records whose owner has no account.
+    pub(crate) account: Option<Account>,
+    /// The client's tx records, by tx id.
+    pub(crate) records: Vec<(TxId, TxRecord)>,
+}
+
+/// A client's account and tx records, gathered for encoding.
+type PageParts<'a> = (Option<&'a Account>, Vec<(TxId, &'a TxRecord)>);
+
+/// Where a page lives in the file.
+#[derive(Debug, Clone, Copy)]
+struct PageRef {
+    /// The byte offset of the page.
+    offset: u64,
+    /// The encoded length of the page, checksum included.
+    len: u32,
+    /// The number of tx records on the page.
+    records: u32,
+}
+
+/// Loads the pages of a paged state file on first touch.
+///
+/// Opening a paged file reads only its index (where each client's page lives,
+/// which client owns each live tx id, and the retired ids), so opening does not
+/// decode the book. A client's page is read, checked and installed the first
+/// time a command touches the client or one of its transactions (see
+/// [`Engine::page_in`]).
+#[derive(Clone)]
+pub struct Pager {
+    /// The path of the paged file, for error messages.
+    path: String,
+    /// The open paged file.
+    file: Arc<Mutex<File>>,
+    /// The pages not loaded yet, by client.
+    unloaded: HashMap<ClientId, PageRef>,
+    /// The owner of every live tx record in the file, sorted by tx id.
+    owners: Vec<(TxId, ClientId)>,
+    /// Pages read ahead by the background prefetch, if one was started.
+    prefetched: Option<Arc<Mutex<Receiver<AppResult<Page>>>>>,
+}
+
+impl Pager {
+    /// Returns the number of pages not loaded yet.
+    pub fn pending(&self) -> usize {
+        self.unloaded.len()
+    }
+
+    /// Returns the client owning a tx id in the file, if it is on a page not
+    /// loaded yet.
+    pub(crate) fn unloaded_owner(&self, tx: TxId) -> Option<ClientId> {
+        let i = self.owners.binary_search_by_key(&tx, |(id, _)| *id).ok()?;
+        let owner = self.owners[i].1;
+        self.unloaded.contains_key(&owner).then_some(owner)
+    }
+
+    /// Reads a client's page if it is not loaded yet, and marks it loaded.
+    ///
+    /// # Returns
+    /// * `AppResult<Option<Page>>` - The page, `None` if the client has no page
+    ///   or it was already loaded, or an `AppErrors` variant if it cannot be read
+    ///   or fails its checksum.
+    pub(crate) fn take(&mut self, client: ClientId) -> AppResult<Option<Page>> {
+        let Some(page_ref) = self.unloaded.remove(&client) else {
+            return Ok(None);
+        };
+        let mut file = self
+            .file
+            .lock()
+            .map_err(|_| AppErrors::Internal("paged file lock poisoned".to_string()))?;
+        read_page(&mut *file, &self.path, client, page_ref).map(Some)
+    }
+
+    /// Takes the pages the background prefetch has read so far that are still
+    /// not loaded.
+    ///
+    /// # Returns
+    /// * `AppResult<Vec<Page>>` - The prefetched pages, or the `AppErrors` variant
+    ///   the prefetch failed with.
+    pub(crate) fn take_prefetched(&mut self) -> AppResult<Vec<Page>> {
+        let Some(rx) = &self.prefetched else {
+            return Ok(Vec::new());
+        };
+        let mut pages = Vec::new();
+        let rx = rx
+            .lock()
+            .map_err(|_| AppErrors::Internal("prefetch lock poisoned".to_string()))?;
+        loop {
+            match rx.try_recv() {
+                Ok(page) => {
+                    let page = page?;
+                    if self.unloaded.remove(&page.client).is_some() {
+                        pages.push(page);
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    drop(rx);
+                    self.prefetched = None;
+                    break;
+                }
+            }
+        }
+        Ok(pages)
+    }
+
+    /// Reads every page not loaded yet, in file order.
+    ///
+    /// # Returns
+    /// * `AppResult<Vec<Page>>` - The pages, or an `AppErrors` variant if one
+    ///   cannot be read or fails its checksum.
+    pub(crate) fn take_all(&mut self) -> AppResult<Vec<Page>> {
+        let mut pages = self.take_prefetched()?;
+        let mut rest: Vec<_> = self.unloaded.keys().copied().collect();
+        rest.sort_by_key(|c| self.unloaded[c].offset);
+        for client in rest {
+            pages.extend(self.take(client)?);
+        }
+        Ok(pages)
+    }
+}
+
+/// Opens a paged state file for lazy loading.
+///
+/// # Arguments
+/// * `path` - The paged file.
+/// * `prefetch_hot` - The number of hottest clients (those with the most tx
+///   records) to read ahead on a background thread, so their first commands do
+///   not wait on the disk; `0` for none.
+///
+/// # Returns
+/// * `AppResult<Engine>` - An engine holding the retired ids, whose pages load
+///   on first touch, or an `AppErrors` variant if the file cannot be opened or
+///   its index is corrupt.
+pub fn open_paged(path: &str, prefetch_hot: usize) -> AppResult<Engine> {
+    let file = File::open(path).map_err(|e| AppErrors::Io(format!("open {path}: {e}")))?;
+    let mut rdr = BufReader::new(file);
+    let mut magic = [0u8; 8];
+    rdr.read_exact(&mut magic)
+        .map_err(|e| AppErrors::Io(format!("read {path}: {e}")))?;
+    if &magic != PAGED_MAGIC {
+        return Err(AppErrors::InvalidInput("not a paged state file"));
+    }
+
+    let mut index = Vec::new();
+    let mut section = |len: usize, index: &mut Vec<u8>| -> AppResult<u64> {
+        let start = index.len();
+        index.resize(start + 8, 0);
+        rdr.read_exact(&mut index[start..])
+            .map_err(|e| AppErrors::Io(format!("read {path}: {e}")))?;
+        let count = u64::from_le_bytes(index[start..].try_into().expect("8 bytes"));
+        let body = usize::try_from(count)
+            .ok()
+            .and_then(|n| n.checked_mul(len))
+            .ok_or(AppErrors::InvalidInput("corrupt paged index"))?;
+        let start = index.len();
+        index.resize(start + body, 0);
+        rdr.read_exact(&mut index[start..])
+            .map_err(|e| AppErrors::Io(format!("read {path}: {e}")))?;
+        Ok(count)
+    };
+    section(INDEX_ENTRY_LEN, &mut index)?;
+    section(4 + 2, &mut index)?;
+    section(4, &mut index)?;
+    let mut checksum = [0u8; 8];
+    rdr.read_exact(&mut checksum)
+        .map_err(|e| AppErrors::Io(format!("read {path}: {e}")))?;
+    if fnv1a(&index).to_le_bytes() != checksum {
+        return Err(AppErrors::InvalidInput("paged index checksum mismatch"));
+    }
+
+    let mut r = ByteReader(&index);
+    let mut engine = Engine::default();
+    let mut unloaded = HashMap::new();
+    let mut hot = Vec::new();
+    for _ in 0..r.u64()? {
+        let client = ClientId::from_le_bytes(r.take()?);
+        let page_ref = PageRef {
+            offset: r.u64()?,
+            len: u32::from_le_bytes(r.take()?),
+            records: u32::from_le_bytes(r.take()?),
+        };
+        hot.push((client, page_ref));
+        unloaded.insert(client, page_ref);
+    }
+    let mut owners = Vec::new();
+    for _ in 0..r.u64()? {
+        owners.push((
+            TxId::from_le_bytes(r.take()?),
+            ClientId::from_le_bytes(r.take()?),
+        ));
+    }
+    for _ in 0..r.u64()? {
+        engine.txs.mark_seen(TxId::from_le_bytes(r.take()?));
+    }
+
+    let file = rdr.into_inner();
+    let prefetched = if prefetch_hot > 0 && !hot.is_empty() {
+        hot.sort_by_key(|(client, page_ref)| (std::cmp::Reverse(page_ref.records), *client));
+        hot.truncate(prefetch_hot);
+        // Read in file order, so the prefetch streams through the file.
+        hot.sort_by_key(|(_, page_ref)| page_ref.offset);
+        let mut ahead = file
+            .try_clone()
+            .map_err(|e| AppErrors::Io(format!("open {path}: {e}")))?;
+        let (tx, rx) = mpsc::channel();
+        let path = path.to_string();
+        thread::spawn(move || {
+            for (client, page_ref) in hot {
+                let page = read_page(&mut ahead, &path, client, page_ref);
+                let failed = page.is_err();
+                if tx.send(page).is_err() || failed {
+                    break;
+                }
+            }
+        });
+        Some(Arc::new(Mutex::new(rx)))
+    } else {
+        None
+    };
+    engine.set_pager(Pager {
+        path: path.to_string(),
+        file: Arc::new(Mutex::new(file)),
+        unloaded,
+        owners,
+        prefetched,
+    });
+    Ok(engine)
+}
+
+/// Reads and decodes one page, checking its checksum.
+fn read_page(
+    file: &mut (impl Read + Seek),
+    path: &str,
+    client: ClientId,
+    page_ref: PageRef,
+) -> AppResult<Page> {
+    let mut bytes = vec![0u8; page_ref.len as usize];
+    file.seek(SeekFrom::Start(page_ref.offset))
+        .and_then(|_| file.read_exact(&mut bytes))
+        .map_err(|e| AppErrors::Io(format!("read {path}: {e}")))?;
+    let split = bytes
+        .len()
+        .checked_sub(8)
+        .ok_or(AppErrors::InvalidInput("corrupt page"))?;
+    let (body, checksum) = bytes.split_at(split);
+    if fnv1a(body).to_le_bytes() != checksum {
+        return Err(AppErrors::InvalidInput("page checksum mismatch"));
+    }
+
+    let mut r = ByteReader(body);
+    let account = match r.take::<1>()?[0] {
+        0 => None,
+        _ => Some(Account {
+            available: Amount(i64::from_le_bytes(r.take()?)),
+            held: Amount(i64::from_le_bytes(r.take()?)),
+            locked: r.take::<1>()?[0] != 0,
+            version: r.u64()?,
+        }),
+    };
+    let mut records = Vec::with_capacity(page_ref.records as usize);
+    for _ in 0..page_ref.records {
+        let tx = TxId::from_le_bytes(r.take()?);
+        let kind = kind_from_code(r.take::<1>()?[0])?;
+        let amount = Amount(i64::from_le_bytes(r.take()?));
+        let state = state_from_code(r.take::<1>()?[0])?;
+        records.push((
+            tx,
+            TxRecord {
+                client,
+                kind,
+                amount,
+                state,
+            },
+        ));
+    }
+    if !r.0.is_empty() {
+        return Err(AppErrors::InvalidInput("corrupt page"));
+    }
+    Ok(Page {
+        client,
+        account,
+        records,
+    })
+}
+
+/// Encodes an engine as a paged state file: the magic header; the index of
+/// pages, tx owners and retired ids, with its checksum; then one checksummed
+/// page per client, sorted by client.
+///
+/// # Arguments
+/// * `engine` - The engine to encode, fully loaded.
+///
+/// # Returns
+/// * `AppResult<Vec<u8>>` - The file contents, or `AppErrors::Overflow` if a
+///   page does not fit the index.
+pub(crate) fn encode_paged(engine: &Engine) -> AppResult<Vec<u8>> {
+    let mut clients: BTreeMap<ClientId, PageParts<'_>> = BTreeMap::new();
+    for (client, acc) in engine.accounts_iter() {
+        clients.entry(*client).or_default().0 = Some(acc);
+    }
+    let mut owners = Vec::new();
+    for (tx, rec) in engine.txs.iter() {
+        clients.entry(rec.client).or_default().1.push((tx, rec));
+        owners.push((tx, rec.client));
+    }
+    owners.sort_unstable();
+    let mut retired: Vec<TxId> = engine.txs.retired_iter().collect();
+    retired.sort_unstable();
+
+    let mut pages = Vec::new();
+    let mut entries = Vec::new();
+    for (client, (acc, mut records)) in clients {
+        records.sort_by_key(|(tx, _)| *tx);
+        let mut page = Vec::new();
+        match acc {
+            Some(acc) => {
+                page.push(1);
+                page.extend_from_slice(&acc.available.0.to_le_bytes());
+                page.extend_from_slice(&acc.held.0.to_le_bytes());
+                page.push(u8::from(acc.locked));
+                page.extend_from_slice(&acc.version.to_le_bytes());
+            }
+            None => page.push(0),
+        }
+        for (tx, rec) in &records {
+            page.extend_from_slice(&tx.to_le_bytes());
+            page.push(kind_code(rec.kind));
+            page.extend_from_slice(&rec.amount.0.to_le_bytes());
+            page.push(state_code(rec.state));
+        }
+        let checksum = fnv1a(&page);
+        page.extend_from_slice(&checksum.to_le_bytes());
+        let len = u32::try_from(page.len()).map_err(|_| AppErrors::Overflow)?;
+        let count = u32::try_from(records.len()).map_err(|_| AppErrors::Overflow)?;
+        entries.push((client, pages.len() as u64, len, count));
+        pages.extend_from_slice(&page);
+    }
+
+    // Pages start after the magic header, the index and its checksum.
+    let index_len =
+        8 + entries.len() * INDEX_ENTRY_LEN + 8 + owners.len() * 6 + 8 + retired.len() * 4;
+    let base = (PAGED_MAGIC.len() + index_len + 8) as u64;
+    let mut index = Vec::with_capacity(index_len);
+    index.extend_from_slice(&(entries.len() as u64).to_le_bytes());
+    for (client, offset, len, count) in entries {
+        index.extend_from_slice(&client.to_le_bytes());
+        index.extend_from_slice(&(base + offset).to_le_bytes());
+        index.extend_from_slice(&len.to_le_bytes());
+        index.extend_from_slice(&count.to_le_bytes());
+    }
+    index.extend_from_slice(&(owners.len() as u64).to_le_bytes());
+    for (tx, client) in owners {
+        index.extend_from_slice(&tx.to_le_bytes());
+        index.extend_from_slice(&client.to_le_bytes());
+    }
+    index.extend_from_slice(&(retired.len() as u64).to_le_bytes());
+    for tx in retired {
+        index.extend_from_slice(&tx.to_le_bytes());
+    }
+
+    let mut out = Vec::with_capacity(PAGED_MAGIC.len() + index_len + 8 + pages.len());
+    out.extend_from_slice(PAGED_MAGIC);
+    out.extend_from_slice(&index);
+    out.extend_from_slice(&fnv1a(&index).to_le_bytes());
+    out.extend_from_slice(&pages);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::domain_state::{DisputeState, TxKind};
+    use crate::models::tx_command::{DepositCommand, DisputeCommand, Transaction};
+    use crate::services::state_store::state_digest;
+    use crate::state::{IgnoreReason, Outcome};
+
+    #[test]
+    fn loads_pages_on_first_touch() {
+        let mut engine = Engine::default();
+        for (tx, client) in [(1, 1), (2, 2), (3, 2), (4, 3)] {
+            engine
+                .apply(Transaction::Deposit(DepositCommand {
+                    client,
+                    tx,
+                    amount: Amount(10_000),
+                }))
+                .unwrap();
+        }
+        engine.txs.mark_seen(9);
+        // A record whose owner has no account survives the round trip as-is.
+        engine.txs.insert(
+            7,
+            TxRecord {
+                client: 5,
+                kind: TxKind::Deposit,
+                amount: Amount(1),
+                state: DisputeState::Normal,
+            },
+        );
+        let path = std::env::temp_dir().join(format!("pe-{}-paged", std::process::id()));
+        std::fs::write(&path, encode_paged(&engine).unwrap()).unwrap();
+        let path = path.display().to_string();
+
+        let mut lazy = open_paged(&path, 1).unwrap();
+        assert_eq!(lazy.pending_pages(), 4);
+        assert!(lazy.acct(1).is_none());
+        // Disputing client 2's tx 3 loads client 2's page (and whatever the
+        // prefetch of the hottest client, also 2, has read).
+        lazy.apply(Transaction::Dispute(DisputeCommand { client: 2, tx: 3 }))
+            .unwrap();
+        assert_eq!(lazy.acct(2).unwrap().held, Amount(10_000));
+        assert!(lazy.acct(1).is_none());
+        // A deposit reusing client 1's tx id is caught as a duplicate.
+        lazy.apply(Transaction::Deposit(DepositCommand {
+            client: 4,
+            tx: 1,
+            amount: Amount(5),
+        }))
+        .unwrap();
+        assert!(
+            lazy.acct(4)
+                .is_none_or(|acc| acc.available == Amount::zero())
+        );
+        assert!(lazy.acct(1).is_some());
+        assert!(lazy.txs.contains_key(&9));
+
+        lazy.page_in_all().unwrap();
+        engine
+            .apply(Transaction::Dispute(DisputeCommand { client: 2, tx: 3 }))
+            .unwrap();
+        engine
+            .apply(Transaction::Deposit(DepositCommand {
+                client: 4,
+                tx: 1,
+                amount: Amount(5),
+            }))
+            .unwrap();
+        assert_eq!(lazy.pending_pages(), 0);
+        assert_eq!(state_digest(&lazy), state_digest(&engine));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    /// Writes an encoded engine to a temporary paged file and returns its path.
+    fn write_paged(engine: &Engine, name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("pe-{}-{name}", std::process::id()));
+        std::fs::write(&path, encode_paged(engine).unwrap()).unwrap();
+        path.display().to_string()
+    }
+
+    #[test]
+    fn empty_engine_round_trips_with_no_pages() {
+        let path = write_paged(&Engine::default(), "paged-empty");
+
+        let mut lazy = open_paged(&path, 4).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(lazy.pending_pages(), 0);
+        lazy.page_in_all().unwrap();
+        assert_eq!(state_digest(&lazy), state_digest(&Engine::default()));
+    }
+
+    #[test]
+    fn corrupt_files_are_rejected() {
+        let mut engine = Engine::default();
+        engine.acct_mut(1).available = Amount(10_000);
+        let bytes = encode_paged(&engine).unwrap();
+        let path = std::env::temp_dir().join(format!("pe-{}-paged-corrupt", std::process::id()));
+        let open = |bytes: &[u8]| {
+            std::fs::write(&path, bytes).unwrap();
+            open_paged(&path.display().to_string(), 0)
+        };
+
+        assert!(matches!(
+            open(b"not paged"),
+            Err(AppErrors::InvalidInput(_))
+        ));
+        assert!(matches!(
+            open(&bytes[..bytes.len() / 2]),
+            Err(AppErrors::Io(_) | AppErrors::InvalidInput(_))
+        ));
+        // A flipped byte in the page is caught when the page is loaded.
+        let mut flipped = bytes.clone();
+        flipped[bytes.len() - 9] ^= 0xff;
+        let mut lazy = open(&flipped).unwrap();
+        assert!(matches!(
+            lazy.page_in_all(),
+            Err(AppErrors::InvalidInput("page checksum mismatch"))
+        ));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn locked_account_stays_locked_when_paged_in() {
+        let mut engine = Engine::default();
+        let acc = engine.acct_mut(1);
+        acc.available = Amount(10_000);
+        acc.locked = true;
+        let path = write_paged(&engine, "paged-locked");
+
+        let mut lazy = open_paged(&path, 0).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let outcome = lazy
+            .apply(Transaction::Deposit(DepositCommand {
+                client: 1,
+                tx: 1,
+                amount: Amount(5),
+            }))
+            .unwrap();
+        assert_eq!(outcome, Outcome::Ignored(IgnoreReason::AccountLocked));
+        let acc = lazy.acct(1).unwrap();
+        assert!(acc.locked);
+        assert_eq!(acc.available, Amount(10_000));
+    }
+}
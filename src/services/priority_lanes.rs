@@ -0,0 +1,183 @@
+use crate::models::identifiers::ClientId;
+use crate::models::tx_command::Transaction;
+use std::collections::{HashMap, VecDeque};
+
+/// The lane a command is scheduled in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lane {
+    /// Deposits and withdrawals: the bulk of the traffic.
+    Bulk,
+    /// Disputes, resolves and chargebacks: served ahead of the bulk backlog.
+    Priority,
+}
+
+impl Lane {
+    /// Returns the lane a command belongs to.
+    pub fn of(tx: &Transaction) -> Self {
+        match tx {
+            Transaction::Deposit(_) | Transaction::Withdrawal(_) => Lane::Bulk,
+            Transaction::Dispute(_) | Transaction::Resolve(_) | Transaction::Chargeback(_) => {
+                Lane::Priority
+            }
+        }
+    }
+}
+
+/// A client's queued commands, in arrival order.
+#[derive(Debug, Default)]
+struct ClientQueue {
+    /// The commands with their arrival sequence numbers.
+    commands: VecDeque<(u64, Transaction)>,
+    /// How many of them are priority commands.
+    priority: usize,
+}
+
+/// A two-lane command scheduler for feeding an engine from a busy stream.
+///
+/// Priority commands (see [`Lane`]) are served before the bulk backlog, so a
+/// dispute is not stuck behind millions of deposits. Order per client is still
+/// preserved: a dispute must not overtake the deposit it disputes, so when a
+/// client has priority work, its earlier bulk commands are served first, ahead
+/// of other clients' bulk commands. Across clients, bulk commands keep their
+/// arrival order.
+#[derive(Debug, Default)]
+pub struct LaneScheduler {
+    /// Every client's queued commands.
+    clients: HashMap<ClientId, ClientQueue>,
+    /// Clients with queued priority commands, in the order they became urgent.
+    urgent: VecDeque<ClientId>,
+    /// Bulk commands in arrival order, as `(sequence, client)` tickets. A ticket
+    /// whose command was already served ahead of time is skipped.
+    bulk: VecDeque<(u64, ClientId)>,
+    /// The next arrival sequence number.
+    next_seq: u64,
+    /// The number of queued commands.
+    len: usize,
+}
+
+impl LaneScheduler {
+    /// Creates an empty scheduler.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues a command.
+    pub fn push(&mut self, tx: Transaction) {
+        let client = tx.client();
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        let queue = self.clients.entry(client).or_default();
+        match Lane::of(&tx) {
+            Lane::Bulk => self.bulk.push_back((seq, client)),
+            Lane::Priority => {
+                if queue.priority == 0 {
+                    self.urgent.push_back(client);
+                }
+                queue.priority += 1;
+            }
+        }
+        queue.commands.push_back((seq, tx));
+        self.len += 1;
+    }
+
+    /// Returns the next command to apply, or `None` if nothing is queued.
+    pub fn pop(&mut self) -> Option<Transaction> {
+        if let Some(&client) = self.urgent.front() {
+            return Some(self.pop_client(client));
+        }
+        while let Some((seq, client)) = self.bulk.pop_front() {
+            let is_next = self
+                .clients
+                .get(&client)
+                .and_then(|q| q.commands.front())
+                .is_some_and(|(front, _)| *front == seq);
+            if is_next {
+                return Some(self.pop_client(client));
+            }
+        }
+        None
+    }
+
+    /// Returns the number of queued commands.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if no commands are queued.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Pops a client's oldest command, which must exist.
+    fn pop_client(&mut self, client: ClientId) -> Transaction {
+        let queue = self
+            .clients
+            .get_mut(&client)
+            .expect("client has queued commands");
+        let (_, tx) = queue
+            .commands
+            .pop_front()
+            .expect("client has queued commands");
+        if Lane::of(&tx) == Lane::Priority {
+            queue.priority -= 1;
+            if queue.priority == 0 {
+                self.urgent.retain(|c| *c != client);
+            }
+        }
+        if queue.commands.is_empty() {
+            self.clients.remove(&client);
+        }
+        self.len -= 1;
+        tx
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::amount::Amount;
+    use crate::models::tx_command::{DepositCommand, DisputeCommand};
+
+    fn deposit(client: ClientId, tx: u32) -> Transaction {
+        DepositCommand {
+            client,
+            tx,
+            amount: Amount(10_000),
+        }
+        .into()
+    }
+
+    fn dispute(client: ClientId, tx: u32) -> Transaction {
+        DisputeCommand { client, tx }.into()
+    }
+
+    #[test]
+    fn priority_jumps_the_backlog_but_not_its_own_client() {
+        let mut lanes = LaneScheduler::new();
+        for tx in 1..=3 {
+            lanes.push(deposit(1, tx));
+        }
+        lanes.push(deposit(2, 4));
+        lanes.push(deposit(2, 5));
+        lanes.push(dispute(2, 4));
+        lanes.push(deposit(1, 6));
+
+        let order: Vec<_> = std::iter::from_fn(|| lanes.pop())
+            .map(|t| (t.client(), t.tx(), Lane::of(&t)))
+            .collect();
+
+        assert_eq!(
+            order,
+            vec![
+                (2, 4, Lane::Bulk),
+                (2, 5, Lane::Bulk),
+                (2, 4, Lane::Priority),
+                (1, 1, Lane::Bulk),
+                (1, 2, Lane::Bulk),
+                (1, 3, Lane::Bulk),
+                (1, 6, Lane::Bulk),
+            ]
+        );
+        assert!(lanes.is_empty());
+    }
+}
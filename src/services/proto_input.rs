@@ -0,0 +1,225 @@
+use crate::errors::{AppErrors, AppResult};
+use crate::models::csv_models::transaction::{CsvTxType, InputRow};
+use crate::services::csv_service::open_input;
+use prost::Message;
+use std::io::{self, Read};
+
+/// The largest message the reader accepts; a longer length prefix means the
+/// framing is corrupt.
+const MAX_MESSAGE_LEN: u64 = 1 << 20;
+
+/// The transaction types of `proto/transaction.proto`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, prost::Enumeration)]
+#[repr(i32)]
+pub enum ProtoTxType {
+    Unspecified = 0,
+    Deposit = 1,
+    Withdrawal = 2,
+    Dispute = 3,
+    Resolve = 4,
+    Chargeback = 5,
+}
+
+/// A transaction message, as defined in `proto/transaction.proto`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ProtoTransaction {
+    /// The transaction type.
+    #[prost(enumeration = "ProtoTxType", tag = "1")]
+    pub r#type: i32,
+    /// The client id; must fit 16 bits.
+    #[prost(uint32, tag = "2")]
+    pub client: u32,
+    /// The transaction id.
+    #[prost(uint32, tag = "3")]
+    pub tx: u32,
+    /// The amount, for deposits and withdrawals.
+    #[prost(string, optional, tag = "4")]
+    pub amount: Option<String>,
+}
+
+impl ProtoTransaction {
+    /// Converts the message into a transaction row.
+    ///
+    /// # Returns
+    /// * `AppResult<InputRow>` - The row, or `AppErrors::InvalidInput` if the type
+    ///   is unspecified or unknown, or the client id does not fit 16 bits.
+    pub fn to_input_row(&self) -> AppResult<InputRow> {
+        let t = match ProtoTxType::try_from(self.r#type) {
+            Ok(ProtoTxType::Deposit) => CsvTxType::Deposit,
+            Ok(ProtoTxType::Withdrawal) => CsvTxType::Withdrawal,
+            Ok(ProtoTxType::Dispute) => CsvTxType::Dispute,
+            Ok(ProtoTxType::Resolve) => CsvTxType::Resolve,
+            Ok(ProtoTxType::Chargeback) => CsvTxType::Chargeback,
+            Ok(ProtoTxType::Unspecified) | Err(_) => {
+                return Err(AppErrors::InvalidInput("unknown transaction type"));
+            }
+        };
+        Ok(InputRow {
+            t,
+            client: self
+                .client
+                .try_into()
+                .map_err(|_| AppErrors::InvalidInput("client id out of range"))?,
+            tx: self.tx,
+            amount: self.amount.clone(),
+        })
+    }
+}
+
+/// Reads length-delimited `Transaction` messages (see `proto/transaction.proto`).
+///
+/// Messages are numbered from 1 in file order, in place of CSV line numbers. A
+/// message that does not decode, or whose values do not fit the engine's types,
+/// is yielded as a row error; a corrupt length prefix or a truncated file is an
+/// outer error that aborts processing.
+pub struct ProtoRows<R: Read> {
+    /// The input, positioned at the next length prefix.
+    input: R,
+    /// The buffer of the current message.
+    buf: Vec<u8>,
+    /// The number of the last message read.
+    record: u64,
+    /// Set once the input is exhausted or an outer error was returned.
+    done: bool,
+}
+
+/// Opens a file of length-delimited transaction messages.
+///
+/// # Arguments
+/// * `path` - The file path, or [`STDIN_PATH`](crate::consts::STDIN_PATH) (`-`)
+///   to read stdin. A gzip- or zstd-compressed file is decompressed first.
+///
+/// # Returns
+/// * `AppResult<ProtoRows<Box<dyn Read>>>` - The row reader, or an `AppErrors::Io`
+///   if the file cannot be opened.
+pub fn open_proto(path: &str) -> AppResult<ProtoRows<Box<dyn Read>>> {
+    Ok(ProtoRows::new(open_input(path)?))
+}
+
+impl<R: Read> ProtoRows<R> {
+    /// Creates a reader over length-delimited messages.
+    pub fn new(input: R) -> Self {
+        Self {
+            input,
+            buf: Vec::new(),
+            record: 0,
+            done: false,
+        }
+    }
+
+    /// Reads the next message into the buffer, or returns `false` at the end of
+    /// the input.
+    fn read_message(&mut self) -> io::Result<bool> {
+        let Some(len) = read_len(&mut self.input)? else {
+            return Ok(false);
+        };
+        if len > MAX_MESSAGE_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("message length {len} exceeds {MAX_MESSAGE_LEN}"),
+            ));
+        }
+        self.buf.clear();
+        (&mut self.input).take(len).read_to_end(&mut self.buf)?;
+        if self.buf.len() as u64 != len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(true)
+    }
+}
+
+impl<R: Read> Iterator for ProtoRows<R> {
+    type Item = AppResult<(u64, AppResult<InputRow>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.read_message() {
+            Ok(true) => {
+                self.record += 1;
+                let row = ProtoTransaction::decode(self.buf.as_slice())
+                    .map_err(|e| AppErrors::Decode(format!("protobuf: {e}")))
+                    .and_then(|msg| msg.to_input_row());
+                Some(Ok((self.record, row)))
+            }
+            Ok(false) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(AppErrors::Decode(format!(
+                    "protobuf: after message {}: {e}",
+                    self.record
+                ))))
+            }
+        }
+    }
+}
+
+/// Reads a varint length prefix, or `None` at a clean end of input.
+fn read_len(input: &mut impl Read) -> io::Result<Option<u64>> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0; 1];
+        if input.read(&mut byte)? == 0 {
+            return if shift == 0 {
+                Ok(None)
+            } else {
+                Err(io::ErrorKind::UnexpectedEof.into())
+            };
+        }
+        value |= u64::from(byte[0] & 0x7f) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(Some(value));
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "varint too long",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(t: ProtoTxType, client: u32, tx: u32, amount: Option<&str>) -> ProtoTransaction {
+        ProtoTransaction {
+            r#type: t as i32,
+            client,
+            tx,
+            amount: amount.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn reads_length_delimited_messages() {
+        let mut file = Vec::new();
+        for msg in [
+            message(ProtoTxType::Deposit, 1, 1, Some("2.5")),
+            message(ProtoTxType::Unspecified, 1, 2, None),
+            message(ProtoTxType::Dispute, 1, 1, None),
+        ] {
+            msg.encode_length_delimited(&mut file).unwrap();
+        }
+
+        let rows: Vec<_> = ProtoRows::new(file.as_slice())
+            .map(Result::unwrap)
+            .collect();
+
+        assert_eq!(rows.len(), 3);
+        let deposit = rows[0].1.as_ref().unwrap();
+        assert_eq!(
+            (deposit.t, deposit.amount.as_deref()),
+            (CsvTxType::Deposit, Some("2.5"))
+        );
+        assert!(rows[1].1.is_err());
+        assert_eq!(rows[2].0, 3);
+
+        file.pop();
+        let last = ProtoRows::new(file.as_slice()).last().unwrap();
+        assert!(matches!(last, Err(AppErrors::Decode(_))));
+    }
+}
@@ -0,0 +1,221 @@
+use crate::errors::{AppErrors, AppResult};
+use crate::models::amount::Amount;
+use crate::models::identifiers::ClientId;
+use csv::ReaderBuilder;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::str::FromStr;
+
+/// A row of an account report; other columns (`total`, `--extended`
+/// activity) are ignored.
+#[derive(Debug, Deserialize)]
+struct ReportRow {
+    client: ClientId,
+    available: String,
+    held: String,
+    locked: bool,
+}
+
+/// The balances of one client in an account report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReportedAccount {
+    /// The available funds.
+    pub available: Amount,
+    /// The held funds.
+    pub held: Amount,
+    /// Whether the account is locked.
+    pub locked: bool,
+}
+
+/// How a client differs between two account reports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientDiff {
+    /// The client.
+    pub client: ClientId,
+    /// The client's balances in the first report, if it is in it.
+    pub a: Option<ReportedAccount>,
+    /// The client's balances in the second report, if it is in it.
+    pub b: Option<ReportedAccount>,
+}
+
+/// Reads an account report written as CSV (`client,available,held,total,locked`).
+///
+/// # Arguments
+/// * `path` - The report file.
+///
+/// # Returns
+/// * `AppResult<BTreeMap<ClientId, ReportedAccount>>` - The balances by client,
+///   or an `AppErrors` variant if the file cannot be read or a row is invalid.
+pub fn read_account_report(path: &str) -> AppResult<BTreeMap<ClientId, ReportedAccount>> {
+    let file = File::open(path).map_err(|e| AppErrors::Io(format!("open {path}: {e}")))?;
+    read_account_report_from_reader(BufReader::new(file))
+}
+
+/// Reads an account report from any CSV source.
+///
+/// # Arguments
+/// * `input` - The CSV source.
+///
+/// # Returns
+/// * `AppResult<BTreeMap<ClientId, ReportedAccount>>` - The balances by client,
+///   or an `AppErrors::AtLine` naming the first invalid row (bad amount or
+///   duplicate client).
+pub fn read_account_report_from_reader<R: Read>(
+    input: R,
+) -> AppResult<BTreeMap<ClientId, ReportedAccount>> {
+    let mut rdr = ReaderBuilder::new().trim(csv::Trim::All).from_reader(input);
+    let mut accounts = BTreeMap::new();
+    for (i, rec) in rdr.deserialize::<ReportRow>().enumerate() {
+        let at_line = |source| AppErrors::AtLine {
+            line: i as u64 + 2,
+            source: Box::new(source),
+        };
+        let row = rec.map_err(|e| at_line(AppErrors::Csv(e.to_string())))?;
+        let account = ReportedAccount {
+            available: Amount::from_str(&row.available).map_err(|e| at_line(e.into()))?,
+            held: Amount::from_str(&row.held).map_err(|e| at_line(e.into()))?,
+            locked: row.locked,
+        };
+        if accounts.insert(row.client, account).is_some() {
+            return Err(at_line(AppErrors::InvalidInput(
+                "duplicate client in account report",
+            )));
+        }
+    }
+    Ok(accounts)
+}
+
+/// Compares two account reports client by client, e.g. those of two runs of a
+/// batch, on available and held funds and the locked flag.
+///
+/// # Arguments
+/// * `a` - The first report's balances.
+/// * `b` - The second report's balances.
+///
+/// # Returns
+/// * `Vec<ClientDiff>` - The clients that differ or are in only one report,
+///   sorted by client.
+pub fn reconcile(
+    a: &BTreeMap<ClientId, ReportedAccount>,
+    b: &BTreeMap<ClientId, ReportedAccount>,
+) -> Vec<ClientDiff> {
+    let mut clients: Vec<ClientId> = a.keys().chain(b.keys()).copied().collect();
+    clients.sort_unstable();
+    clients.dedup();
+    clients
+        .into_iter()
+        .map(|client| ClientDiff {
+            client,
+            a: a.get(&client).copied(),
+            b: b.get(&client).copied(),
+        })
+        .filter(|diff| diff.a != diff.b)
+        .collect()
+}
+
+impl Display for ClientDiff {
+    /// Writes the differences, e.g.
+    /// `client 3: available 1.0000 -> 1.5000 (+0.5000), locked false -> true`.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let client = self.client;
+        let (a, b) = match (self.a, self.b) {
+            (Some(a), Some(b)) => (a, b),
+            (Some(_), None) => return write!(f, "client {client}: only in the first report"),
+            (None, _) => return write!(f, "client {client}: only in the second report"),
+        };
+        let mut fields = Vec::new();
+        for (name, from, to) in [
+            ("available", a.available, b.available),
+            ("held", a.held, b.held),
+        ] {
+            if from != to {
+                let delta = match to.checked_sub(from) {
+                    Some(d) if d.is_negative() => d.to_string(),
+                    Some(d) => format!("+{d}"),
+                    None => "overflow".to_string(),
+                };
+                fields.push(format!("{name} {from} -> {to} ({delta})"));
+            }
+        }
+        if a.locked != b.locked {
+            fields.push(format!("locked {} -> {}", a.locked, b.locked));
+        }
+        write!(f, "client {client}: {}", fields.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_differing_and_missing_clients() {
+        let a = "client,available,held,total,locked\n\
+                 1,1.5000,0.0000,1.5000,false\n\
+                 2,2.0000,1.0000,3.0000,false\n\
+                 3,1.0000,0.0000,1.0000,false\n";
+        // Trimmed amounts and extra columns compare equal.
+        let b = "client,available,held,total,locked,deposited\n\
+                 1,1.5,0,1.5,false,1.5\n\
+                 2,2.5,0,2.5,true,3\n\
+                 4,0,0,0,false,0\n";
+        let a = read_account_report_from_reader(a.as_bytes()).unwrap();
+        let b = read_account_report_from_reader(b.as_bytes()).unwrap();
+
+        let lines: Vec<String> = reconcile(&a, &b).iter().map(|d| d.to_string()).collect();
+        assert_eq!(
+            lines,
+            [
+                "client 2: available 2.0000 -> 2.5000 (+0.5000), held 1.0000 -> 0.0000 (-1.0000), locked false -> true",
+                "client 3: only in the first report",
+                "client 4: only in the second report",
+            ]
+        );
+        let dup = "client,available,held,locked\n1,1,0,false\n1,1,0,false\n";
+        assert!(matches!(
+            read_account_report_from_reader(dup.as_bytes()),
+            Err(AppErrors::AtLine { line: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn empty_or_header_only_reports_reconcile_cleanly() {
+        let empty = read_account_report_from_reader("".as_bytes()).unwrap();
+        let header = "client,available,held,total,locked\n";
+        let header = read_account_report_from_reader(header.as_bytes()).unwrap();
+        assert!(empty.is_empty() && header.is_empty());
+        assert!(reconcile(&empty, &header).is_empty());
+    }
+
+    #[test]
+    fn malformed_rows_name_their_line() {
+        for report in [
+            "client,available,held,total,locked\n1,1.0,0,1.0,false\n2,abc,0,0,false\n",
+            "client,available,held,total,locked\n1,1.0,0,1.0,false\n2,1.0,0,1.0,maybe\n",
+            "client,available,held,total,locked\n1,1.0,0,1.0,false\nx,1.0,0,1.0,false\n",
+        ] {
+            assert!(
+                matches!(
+                    read_account_report_from_reader(report.as_bytes()),
+                    Err(AppErrors::AtLine { line: 3, .. })
+                ),
+                "{report}"
+            );
+        }
+    }
+
+    #[test]
+    fn locked_flag_alone_is_a_difference() {
+        let a = "client,available,held,total,locked\n5,1.0,0,1.0,false\n";
+        let b = "client,available,held,total,locked\n5,1.0,0,1.0,true\n";
+        let a = read_account_report_from_reader(a.as_bytes()).unwrap();
+        let b = read_account_report_from_reader(b.as_bytes()).unwrap();
+
+        let diffs = reconcile(&a, &b);
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].to_string(), "client 5: locked false -> true");
+    }
+}
@@ -0,0 +1,229 @@
+use crate::errors::{AppErrors, AppResult};
+use crate::models::csv_models::transaction::{CsvTxType, InputRow};
+use crate::models::identifiers::{ClientId, TxId};
+use csv::WriterBuilder;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// A row the engine skipped or ignored, as written to the `--rejects` file.
+#[derive(Debug, Clone, Serialize)]
+pub struct Reject {
+    /// The line number of the row in the input.
+    pub line: u64,
+    /// Why the row was dropped, e.g. `malformed_record` or `insufficient_funds`.
+    pub reason: &'static str,
+    /// The stable error code (see [`AppErrors::code`]), if an error dropped the
+    /// row.
+    pub code: Option<&'static str>,
+    /// The row's type, if it was parsed.
+    #[serde(rename = "type")]
+    pub t: Option<CsvTxType>,
+    /// The row's client, if it was parsed.
+    pub client: Option<ClientId>,
+    /// The row's tx id, if it was parsed.
+    pub tx: Option<TxId>,
+    /// The row's amount, as given.
+    pub amount: Option<String>,
+    /// The error message, if an error dropped the row; for a malformed record,
+    /// it ends with the record's content.
+    pub detail: Option<String>,
+}
+
+/// Gathers the rows a run skipped or ignored, so what the engine silently
+/// dropped can be investigated: malformed records, rows that do not convert to
+/// a command, commands the engine failed or ignored as no-ops, and commands
+/// quarantined by the exposure guard.
+#[derive(Debug, Clone, Default)]
+pub struct RejectLog {
+    /// The rejected rows, in input order.
+    rejects: Vec<Reject>,
+}
+
+impl RejectLog {
+    /// Records a row dropped by an error.
+    ///
+    /// # Arguments
+    /// * `line` - The line number of the row in the input.
+    /// * `row` - The row, or `None` if it could not be parsed.
+    /// * `error` - The error that dropped it.
+    pub fn error(&mut self, line: u64, row: Option<&InputRow>, error: &AppErrors) {
+        self.push(line, row, reason_of(error), Some(error));
+    }
+
+    /// Records a row dropped without an error.
+    ///
+    /// # Arguments
+    /// * `line` - The line number of the row in the input.
+    /// * `row` - The row.
+    /// * `reason` - Why it was dropped, e.g. `quarantined` or an ignore reason
+    ///   (see [`IgnoreReason::as_str`](crate::state::IgnoreReason::as_str)).
+    pub fn dropped(&mut self, line: u64, row: &InputRow, reason: &'static str) {
+        self.push(line, Some(row), reason, None);
+    }
+
+    /// Appends a rejected row.
+    fn push(
+        &mut self,
+        line: u64,
+        row: Option<&InputRow>,
+        reason: &'static str,
+        error: Option<&AppErrors>,
+    ) {
+        self.rejects.push(Reject {
+            line,
+            reason,
+            code: error.map(AppErrors::code),
+            t: row.map(|r| r.t),
+            client: row.map(|r| r.client),
+            tx: row.map(|r| r.tx),
+            amount: row.and_then(|r| r.amount.clone()),
+            detail: error.map(ToString::to_string),
+        });
+    }
+
+    /// Returns the number of rejected rows.
+    pub fn len(&self) -> usize {
+        self.rejects.len()
+    }
+
+    /// Returns `true` if no row was rejected.
+    pub fn is_empty(&self) -> bool {
+        self.rejects.is_empty()
+    }
+
+    /// Returns the number of rejected rows by reason code.
+    pub fn by_reason(&self) -> BTreeMap<&'static str, usize> {
+        let mut counts = BTreeMap::new();
+        for reject in &self.rejects {
+            *counts.entry(reject.reason).or_default() += 1;
+        }
+        counts
+    }
+
+    /// Writes the rejected rows as CSV, one
+    /// `line,reason,code,type,client,tx,amount,detail` row each.
+    ///
+    /// # Arguments
+    /// * `path` - The rejects file path.
+    ///
+    /// # Returns
+    /// * `AppResult<()>` - `Ok(())` once written, or an `AppErrors::Io` on failure.
+    pub fn write(&self, path: &str) -> AppResult<()> {
+        let mut wtr = WriterBuilder::new()
+            .from_path(path)
+            .map_err(|e| AppErrors::Io(format!("create {path}: {e}")))?;
+        for reject in &self.rejects {
+            wtr.serialize(reject)
+                .map_err(|e| AppErrors::Io(format!("write {path}: {e}")))?;
+        }
+        wtr.flush()
+            .map_err(|e| AppErrors::Io(format!("flush {path}: {e}")))
+    }
+}
+
+/// The reason code of a row dropped by an error.
+fn reason_of(error: &AppErrors) -> &'static str {
+    match error {
+        AppErrors::Csv(_) | AppErrors::Decode(_) => "malformed_record",
+        AppErrors::MissingAmount(_) => "missing_amount",
+        AppErrors::AmountParseError(_) => "invalid_amount",
+        AppErrors::InvalidPrecisionForCurrency { .. } => "invalid_precision",
+        AppErrors::Overflow => "overflow",
+        AppErrors::AtLine { source, .. } => reason_of(source),
+        _ => "failed",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::csv_service::run_from_reader;
+    use crate::state::AppState;
+
+    #[test]
+    fn logs_every_dropped_row_with_a_reason() {
+        let input = "type,client,tx,amount\n\
+                     deposit,1,1,5.0\n\
+                     deposit,x,2,1.0\n\
+                     withdrawal,1,3,\n\
+                     deposit,1,4,1.2.3\n\
+                     dispute,1,99,\n\
+                     withdrawal,1,5,10.0\n";
+        let mut app_state = AppState {
+            rejects: Some(RejectLog::default()),
+            ..AppState::default()
+        };
+        run_from_reader(input.as_bytes(), &mut app_state).unwrap();
+
+        let rejects = &app_state.rejects.unwrap().rejects;
+        let reasons: Vec<_> = rejects.iter().map(|r| (r.line, r.reason)).collect();
+        assert_eq!(
+            reasons,
+            [
+                (3, "malformed_record"),
+                (4, "missing_amount"),
+                (5, "invalid_amount"),
+                (6, "unknown_tx"),
+                (7, "insufficient_funds"),
+            ]
+        );
+        assert!(
+            rejects[0]
+                .detail
+                .as_deref()
+                .unwrap()
+                .ends_with("; record: deposit,x,2,1.0")
+        );
+        assert_eq!(rejects[1].code, Some("E1002"));
+        assert_eq!(
+            (rejects[3].t, rejects[3].client, rejects[3].tx),
+            (Some(CsvTxType::Dispute), Some(1), Some(99))
+        );
+        assert_eq!(rejects[4].amount.as_deref(), Some("10.0"));
+    }
+
+    #[test]
+    fn logs_released_orphans_at_the_releasing_line() {
+        use crate::state::Engine;
+
+        let input = "type,client,tx,amount\n\
+                     dispute,1,5,\n\
+                     resolve,1,9,\n\
+                     dispute,1,7,\n\
+                     deposit,1,5,1.0\n\
+                     deposit,2,9,1.0\n\
+                     withdrawal,1,7,10.0\n";
+        let mut app_state = AppState {
+            engine: Engine::builder().orphan_buffer(4).build(),
+            rejects: Some(RejectLog::default()),
+            ..AppState::default()
+        };
+        run_from_reader(input.as_bytes(), &mut app_state).unwrap();
+
+        let rejects = &app_state.rejects.unwrap().rejects;
+        let reasons: Vec<_> = rejects
+            .iter()
+            .map(|r| (r.line, r.reason, r.t, r.tx))
+            .collect();
+        assert_eq!(
+            reasons,
+            [
+                (2, "buffered", Some(CsvTxType::Dispute), Some(5)),
+                (3, "buffered", Some(CsvTxType::Resolve), Some(9)),
+                (4, "buffered", Some(CsvTxType::Dispute), Some(7)),
+                // Released for client 2's tx: it belongs to another client.
+                (6, "client_mismatch", Some(CsvTxType::Resolve), Some(9)),
+                // The withdrawal was refused, so its waiter is dropped.
+                (
+                    7,
+                    "insufficient_funds",
+                    Some(CsvTxType::Withdrawal),
+                    Some(7)
+                ),
+                (7, "unknown_tx", Some(CsvTxType::Dispute), Some(7)),
+            ]
+        );
+        assert_eq!(app_state.engine.buffered_count(), 0);
+        assert_eq!(app_state.engine.acct(1).unwrap().held.to_string(), "1.0000");
+    }
+}
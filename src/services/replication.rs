@@ -0,0 +1,275 @@
+use crate::errors::{AppErrors, AppResult};
+use crate::models::csv_models::transaction::{CsvTxType, InputRow};
+use crate::models::identifiers::{ClientId, TxId};
+use crate::models::policy::EngineConfig;
+use crate::services::csv_service::row_to_transaction;
+use crate::state::AppState;
+use csv::{ReaderBuilder, Writer, WriterBuilder};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How many events the follower applies between lag reports.
+const LAG_REPORT_EVERY: u64 = 10_000;
+
+/// One applied row as sent from the leader to a follower.
+///
+/// The stream starts with the leader's [`EngineConfig`] as one JSON line,
+/// followed by CSV with a header line: the sequence number and send time, the
+/// original row's columns, and the client's account version after the row.
+#[derive(Debug, Serialize, Deserialize)]
+struct ReplicaEvent {
+    /// 1-based position of the event in the leader's stream.
+    seq: u64,
+    /// The leader's wall-clock time when the event was sent, in ms since the Unix epoch.
+    sent_ms: u64,
+    /// The type of the transaction.
+    #[serde(rename = "type")]
+    t: CsvTxType,
+    /// The client the transaction belongs to.
+    client: ClientId,
+    /// The transaction id.
+    tx: TxId,
+    /// The amount as written in the input, if any.
+    amount: Option<String>,
+    /// The leader's version of the client's account after applying the row.
+    version: u64,
+}
+
+/// Streams applied rows to a follower over TCP.
+pub struct ReplicationSink {
+    /// The CSV writer over the connection.
+    wtr: Writer<BufWriter<TcpStream>>,
+    /// The sequence number of the last event sent.
+    seq: u64,
+}
+
+impl ReplicationSink {
+    /// Connects to a follower listening at `addr` and sends it the leader's
+    /// engine config, which the follower applies the stream under.
+    ///
+    /// # Arguments
+    /// * `addr` - The follower's address, e.g. `replica-host:7000`.
+    /// * `config` - The leader's engine config.
+    ///
+    /// # Returns
+    /// * `AppResult<ReplicationSink>` - The connected sink, or an `AppErrors::Io` if
+    ///   the connection fails.
+    pub fn connect(
+        addr: impl ToSocketAddrs + std::fmt::Display,
+        config: &EngineConfig,
+    ) -> AppResult<Self> {
+        let stream =
+            TcpStream::connect(&addr).map_err(|e| AppErrors::Io(format!("connect {addr}: {e}")))?;
+        let mut out = BufWriter::new(stream);
+        serde_json::to_writer(&mut out, config)
+            .map_err(|e| AppErrors::Io(format!("replicate: {e}")))?;
+        out.write_all(b"\n")
+            .map_err(|e| AppErrors::Io(format!("replicate: {e}")))?;
+        Ok(Self {
+            wtr: WriterBuilder::new().has_headers(true).from_writer(out),
+            seq: 0,
+        })
+    }
+
+    /// Sends one applied row. Events are buffered; call [`ReplicationSink::finish`]
+    /// to flush the stream.
+    ///
+    /// # Arguments
+    /// * `row` - The row whose command was applied.
+    /// * `version` - The client's account version after applying it (see
+    ///   [`Engine::version`](crate::state::Engine::version)).
+    ///
+    /// # Returns
+    /// * `AppResult<()>` - Returns `Ok(())` if the event was written, or an
+    ///   `AppErrors::Io` if the connection failed.
+    pub fn send(&mut self, row: &InputRow, version: u64) -> AppResult<()> {
+        self.seq += 1;
+        self.wtr
+            .serialize(ReplicaEvent {
+                seq: self.seq,
+                sent_ms: now_ms(),
+                t: row.t,
+                client: row.client,
+                tx: row.tx,
+                amount: row.amount.clone(),
+                version,
+            })
+            .map_err(|e| AppErrors::Io(format!("replicate: {e}")))
+    }
+
+    /// Flushes the stream and closes the connection.
+    ///
+    /// # Returns
+    /// * `AppResult<u64>` - The number of events sent, or an `AppErrors::Io` if the
+    ///   final flush failed.
+    pub fn finish(mut self) -> AppResult<u64> {
+        self.wtr
+            .flush()
+            .map_err(|e| AppErrors::Io(format!("replicate: {e}")))?;
+        Ok(self.seq)
+    }
+}
+
+/// Replication lag metrics gathered by a follower.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ReplicaStats {
+    /// The number of events applied.
+    pub applied: u64,
+    /// The sequence number of the last event applied.
+    pub last_seq: u64,
+    /// The number of events missing from the sequence (gaps).
+    pub gaps: u64,
+    /// The number of events after which the replica's account version differed
+    /// from the leader's: the replica missed, or diverged on, an update.
+    pub version_mismatches: u64,
+    /// The largest observed delay between the leader sending an event and the
+    /// follower applying it.
+    pub max_lag: Duration,
+}
+
+/// Accepts one leader connection and applies its events to `app_state` until the
+/// leader closes the stream, logging lag metrics as it goes.
+///
+/// The replica's engine takes the leader's config from the head of the stream
+/// and applies the same rows in the same order, so a replica starting from an
+/// empty engine ends in the same state as a leader that also started empty
+/// (the leader refuses to replicate a run that loads an opening state). Rows the
+/// leader quarantined, skipped or failed are never sent, and the engine does
+/// not count failed commands towards its dispute window, so those rows do not
+/// make the two diverge. Events that fail to apply are logged and skipped; they
+/// failed identically on the leader and were therefore never sent. After each
+/// event, the client's account version is compared with the leader's, so a
+/// replica that diverged is reported even when no sequence number is missing.
+///
+/// # Arguments
+/// * `listener` - The socket to accept the leader on.
+/// * `app_state` - The replica's application state.
+///
+/// # Returns
+/// * `AppResult<ReplicaStats>` - The lag metrics for the stream, or an `AppErrors::Io`
+///   if the connection or stream fails.
+pub fn follow(listener: &TcpListener, app_state: &mut AppState) -> AppResult<ReplicaStats> {
+    let (stream, peer) = listener
+        .accept()
+        .map_err(|e| AppErrors::Io(format!("accept leader: {e}")))?;
+    info!("Leader connected from {peer}");
+
+    let mut input = BufReader::new(stream);
+    let mut header = String::new();
+    input
+        .read_line(&mut header)
+        .map_err(|e| AppErrors::Io(format!("replication stream: {e}")))?;
+    let config: EngineConfig = serde_json::from_str(&header)
+        .map_err(|e| AppErrors::Io(format!("replication stream config: {e}")))?;
+    app_state.engine.set_config(config);
+
+    let mut rdr = ReaderBuilder::new().from_reader(input);
+    let mut stats = ReplicaStats::default();
+    for rec in rdr.deserialize::<ReplicaEvent>() {
+        let ev = rec.map_err(|e| AppErrors::Io(format!("replication stream: {e}")))?;
+        if ev.seq != stats.last_seq + 1 {
+            warn!(
+                "replication gap: expected seq {}, got {}",
+                stats.last_seq + 1,
+                ev.seq
+            );
+            stats.gaps += ev.seq.saturating_sub(stats.last_seq + 1);
+        }
+        stats.last_seq = ev.seq;
+
+        let row = InputRow {
+            t: ev.t,
+            client: ev.client,
+            tx: ev.tx,
+            amount: ev.amount,
+        };
+        if let Err(e) = row_to_transaction(&row).and_then(|t| app_state.engine.apply(t)) {
+            warn!("replica failed to apply seq {}: {e}", ev.seq);
+        }
+        let version = app_state.engine.version(ev.client);
+        if version != ev.version {
+            warn!(
+                "replica diverged at seq {}: client {} is at version {version}, leader at {}",
+                ev.seq, ev.client, ev.version
+            );
+            stats.version_mismatches += 1;
+        }
+        stats.applied += 1;
+
+        let lag = Duration::from_millis(now_ms().saturating_sub(ev.sent_ms));
+        stats.max_lag = stats.max_lag.max(lag);
+        if stats.applied % LAG_REPORT_EVERY == 0 {
+            info!(
+                "Replica at seq {} (lag {} ms, max {} ms)",
+                ev.seq,
+                lag.as_millis(),
+                stats.max_lag.as_millis()
+            );
+        }
+    }
+    Ok(stats)
+}
+
+/// Returns the current wall-clock time in milliseconds since the Unix epoch.
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::amount::Amount;
+    use crate::models::policy::UnfundedDisputePolicy;
+    use std::thread;
+
+    fn row(t: CsvTxType, client: ClientId, tx: TxId, amount: Option<&str>) -> InputRow {
+        InputRow {
+            t,
+            client,
+            tx,
+            amount: amount.map(String::from),
+        }
+    }
+
+    #[test]
+    fn follower_replays_leader_stream() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let follower = thread::spawn(move || {
+            let mut replica = AppState::default();
+            let stats = follow(&listener, &mut replica).unwrap();
+            (replica, stats)
+        });
+
+        let config = EngineConfig {
+            unfunded_disputes: UnfundedDisputePolicy::Negative,
+            ..EngineConfig::default()
+        };
+        let mut sink = ReplicationSink::connect(addr, &config).unwrap();
+        sink.send(&row(CsvTxType::Deposit, 1, 1, Some("5.0")), 1)
+            .unwrap();
+        sink.send(&row(CsvTxType::Deposit, 1, 2, Some("2.0")), 2)
+            .unwrap();
+        sink.send(&row(CsvTxType::Withdrawal, 1, 3, Some("6.0")), 3)
+            .unwrap();
+        // A leader that applied an update the replica never saw. The dispute is
+        // unfunded, so it only applies under the leader's policy.
+        sink.send(&row(CsvTxType::Dispute, 1, 2, None), 5).unwrap();
+        assert_eq!(sink.finish().unwrap(), 4);
+
+        let (replica, stats) = follower.join().unwrap();
+        assert_eq!(replica.engine.config(), config);
+        assert_eq!(stats.applied, 4);
+        assert_eq!(stats.last_seq, 4);
+        assert_eq!(stats.gaps, 0);
+        assert_eq!(stats.version_mismatches, 1);
+        let acc = replica.engine.acct(1).unwrap();
+        assert_eq!(acc.available, Amount(-10_000));
+        assert_eq!(acc.held, Amount(20_000));
+    }
+}
@@ -0,0 +1,203 @@
+use crate::errors::{AppErrors, AppResult};
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, StdoutLock, Write};
+use std::path::{Path, PathBuf};
+
+/// A file written in full or not at all: data goes to a temporary file next to
+/// the target, which replaces the target only on [`AtomicFile::commit`]. A run
+/// that fails or crashes mid-write leaves any earlier file at the path intact,
+/// never a truncated one; an uncommitted temporary file is removed on drop.
+pub struct AtomicFile {
+    /// The temporary file being written.
+    file: BufWriter<File>,
+    /// The temporary file's path.
+    tmp: PathBuf,
+    /// The target path.
+    path: PathBuf,
+    /// Whether the file has been renamed into place.
+    committed: bool,
+}
+
+impl AtomicFile {
+    /// Creates the temporary file of a target path, in the same directory so
+    /// the final rename cannot cross file systems.
+    ///
+    /// # Arguments
+    /// * `path` - The target path.
+    ///
+    /// # Returns
+    /// * `AppResult<Self>` - The file, or an `AppErrors::Io` if the temporary
+    ///   file cannot be created.
+    pub fn create(path: &str) -> AppResult<Self> {
+        let path = PathBuf::from(path);
+        let name = path
+            .file_name()
+            .ok_or_else(|| AppErrors::Io(format!("create {}: not a file path", path.display())))?;
+        let tmp = path.with_file_name(format!(
+            ".{}.tmp-{}",
+            name.to_string_lossy(),
+            std::process::id()
+        ));
+        let file = File::create(&tmp)
+            .map_err(|e| AppErrors::Io(format!("create {}: {e}", tmp.display())))?;
+        Ok(Self {
+            file: BufWriter::new(file),
+            tmp,
+            path,
+            committed: false,
+        })
+    }
+
+    /// Flushes the data to disk and renames the file into place.
+    ///
+    /// # Returns
+    /// * `AppResult<()>` - `Ok(())` once the target holds the data, or an
+    ///   `AppErrors::Io` on failure (the target is then left untouched).
+    pub fn commit(mut self) -> AppResult<()> {
+        let io_err = |e: io::Error| AppErrors::Io(format!("write {}: {e}", self.path.display()));
+        self.file.flush().map_err(io_err)?;
+        self.file.get_ref().sync_all().map_err(io_err)?;
+        fs::rename(&self.tmp, &self.path).map_err(io_err)?;
+        self.committed = true;
+        // Persist the rename itself where the platform allows syncing a directory.
+        if let Some(dir) = self.path.parent().map(|d| match d.as_os_str().is_empty() {
+            true => Path::new("."),
+            false => d,
+        }) && let Ok(dir) = File::open(dir)
+        {
+            let _ = dir.sync_all();
+        }
+        Ok(())
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = fs::remove_file(&self.tmp);
+        }
+    }
+}
+
+/// Writes a value as pretty-printed JSON to a report file, atomically (see
+/// [`AtomicFile`]), so a failed write never leaves a truncated report.
+///
+/// # Arguments
+/// * `path` - The report path.
+/// * `value` - The value to write.
+///
+/// # Returns
+/// * `AppResult<()>` - `Ok(())` once the file holds the report, or an
+///   `AppErrors::Io` on failure.
+pub fn write_json_file(path: &str, value: &impl Serialize) -> AppResult<()> {
+    let mut file = AtomicFile::create(path)?;
+    serde_json::to_writer_pretty(&mut file, value)
+        .map_err(|e| AppErrors::Io(format!("write {path}: {e}")))?;
+    file.commit()
+}
+
+/// Where the account report goes: stdout, or a file written atomically.
+pub enum ReportOutput {
+    /// Standard output.
+    Stdout(StdoutLock<'static>),
+    /// A file, replaced only once the report is complete.
+    File(AtomicFile),
+}
+
+impl ReportOutput {
+    /// Opens the report output.
+    ///
+    /// # Arguments
+    /// * `path` - The report path, or `None` for stdout.
+    ///
+    /// # Returns
+    /// * `AppResult<Self>` - The output, or an `AppErrors::Io` if the file
+    ///   cannot be created.
+    pub fn open(path: Option<&str>) -> AppResult<Self> {
+        Ok(match path {
+            Some(path) => Self::File(AtomicFile::create(path)?),
+            None => Self::Stdout(io::stdout().lock()),
+        })
+    }
+
+    /// Completes the report: commits a file into place; stdout needs nothing.
+    ///
+    /// # Returns
+    /// * `AppResult<()>` - `Ok(())` once complete, or an `AppErrors::Io` on
+    ///   failure.
+    pub fn commit(self) -> AppResult<()> {
+        match self {
+            Self::Stdout(_) => Ok(()),
+            Self::File(file) => file.commit(),
+        }
+    }
+}
+
+impl Write for ReportOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Stdout(out) => out.write(buf),
+            Self::File(file) => file.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Stdout(out) => out.flush(),
+            Self::File(file) => file.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_the_target_only_on_commit() {
+        let dir = std::env::temp_dir().join(format!("pe-{}-atomic", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("accounts.csv").display().to_string();
+        fs::write(&path, "old\n").unwrap();
+
+        let mut abandoned = AtomicFile::create(&path).unwrap();
+        abandoned.write_all(b"partial").unwrap();
+        abandoned.flush().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old\n");
+        drop(abandoned);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old\n");
+
+        let mut file = AtomicFile::create(&path).unwrap();
+        file.write_all(b"new\n").unwrap();
+        file.commit().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new\n");
+        // Only the target is left behind.
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn json_report_is_complete_once_written() {
+        let path = std::env::temp_dir()
+            .join(format!("pe-{}-report.json", std::process::id()))
+            .display()
+            .to_string();
+
+        write_json_file(&path, &[1, 2, 3]).unwrap();
+
+        let written: Vec<u32> = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(written, [1, 2, 3]);
+        fs::remove_file(&path).unwrap();
+    }
+}
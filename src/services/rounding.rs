@@ -0,0 +1,126 @@
+use crate::models::csv_models::transaction::{CsvTxType, InputRow};
+use std::fmt::{Display, Formatter};
+
+/// Decimal places tracked beyond the engine's four, so residuals are exact to
+/// 10^-12 of a unit. Digits past that are not tracked.
+const RESIDUAL_DIGITS: usize = 8;
+
+/// `10^RESIDUAL_DIGITS`: one minor unit (10^-4) expressed in residual units.
+const RESIDUAL_PER_MINOR: i64 = 100_000_000;
+
+/// Money created (positive) or destroyed (negative) by rounding, in units of
+/// 10^-12.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Residual(pub i64);
+
+impl Display for Residual {
+    /// Formats the residual with all twelve decimal places and an explicit sign,
+    /// e.g. `+0.000000500000`.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let sign = if self.0 < 0 { '-' } else { '+' };
+        let abs = self.0.unsigned_abs();
+        let per_unit = 10_000 * RESIDUAL_PER_MINOR as u64;
+        write!(f, "{sign}{}.{:012}", abs / per_unit, abs % per_unit)
+    }
+}
+
+/// Accumulates the rounding residual of applied deposits and withdrawals.
+///
+/// Amounts with more than four decimal places are rounded half-up when parsed,
+/// so the engine credits or debits slightly more or less than the input says.
+/// The tracker records that difference instead of letting it vanish: a
+/// deposit of `0.00005` is credited as `0.0001`, creating `+0.00005`.
+#[derive(Debug, Default)]
+pub struct RoundingTracker {
+    /// The net money created by rounding so far.
+    residual: Residual,
+    /// The number of applied amounts that were rounded.
+    rounded: u64,
+}
+
+impl RoundingTracker {
+    /// Records the rounding residual of an applied row, if any.
+    ///
+    /// # Arguments
+    /// * `row` - The applied row.
+    pub fn observe(&mut self, row: &InputRow) {
+        let sign = match row.t {
+            CsvTxType::Deposit => 1,
+            CsvTxType::Withdrawal => -1,
+            _ => return,
+        };
+        let Some(dropped) = row.amount.as_deref().and_then(dropped_by_rounding) else {
+            return;
+        };
+        if dropped != 0 {
+            self.rounded += 1;
+            self.residual.0 -= sign * dropped;
+        }
+    }
+
+    /// Returns the net money created (positive) or destroyed (negative) by rounding.
+    pub fn residual(&self) -> Residual {
+        self.residual
+    }
+
+    /// Returns the number of applied amounts that were rounded.
+    pub fn rounded(&self) -> u64 {
+        self.rounded
+    }
+}
+
+/// Returns `exact - rounded` for an amount string, in residual units, or `None`
+/// if the amount has four or fewer decimal places (nothing was rounded).
+fn dropped_by_rounding(s: &str) -> Option<i64> {
+    let s = s.trim();
+    let neg = s.starts_with('-');
+    let (_, frac) = s.split_once('.')?;
+    let rest = frac.get(4..).filter(|r| !r.is_empty())?;
+
+    let mut digits: String = rest.chars().take(RESIDUAL_DIGITS).collect();
+    while digits.len() < RESIDUAL_DIGITS {
+        digits.push('0');
+    }
+    let mut dropped: i64 = digits.parse().ok()?;
+    if rest.as_bytes()[0] >= b'5' {
+        dropped -= RESIDUAL_PER_MINOR;
+    }
+    Some(if neg { -dropped } else { dropped })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(t: CsvTxType, amount: &str) -> InputRow {
+        InputRow {
+            t,
+            client: 1,
+            tx: 1,
+            amount: Some(amount.to_string()),
+        }
+    }
+
+    #[test]
+    fn dropped_digits_are_measured_against_half_up_rounding() {
+        assert_eq!(dropped_by_rounding("1.0000"), None);
+        assert_eq!(dropped_by_rounding("1"), None);
+        assert_eq!(dropped_by_rounding("1.00004"), Some(40_000_000));
+        assert_eq!(dropped_by_rounding("1.00005"), Some(-50_000_000));
+        assert_eq!(dropped_by_rounding("-1.00005"), Some(50_000_000));
+    }
+
+    #[test]
+    fn residual_nets_deposits_against_withdrawals() {
+        let mut tracker = RoundingTracker::default();
+        tracker.observe(&row(CsvTxType::Deposit, "0.00005")); // credited 0.0001
+        tracker.observe(&row(CsvTxType::Deposit, "0.00012")); // credited 0.0001
+        tracker.observe(&row(CsvTxType::Withdrawal, "0.00006")); // debited 0.0001
+        tracker.observe(&row(CsvTxType::Deposit, "1.5"));
+
+        assert_eq!(tracker.rounded(), 3);
+        // +0.00005 - 0.00002 - 0.00004
+        assert_eq!(tracker.residual(), Residual(-10_000_000));
+        assert_eq!(tracker.residual().to_string(), "-0.000010000000");
+    }
+}
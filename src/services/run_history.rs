@@ -0,0 +1,99 @@
+use crate::errors::{AppErrors, AppResult};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+
+/// Key performance indicators of one processing run, appended as a row to a
+/// run history file so engine health can be trended across batches.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunKpis {
+    /// The UTC date the run started, `YYYY-MM-DD`.
+    pub date: String,
+    /// When the run started, in seconds since the Unix epoch.
+    pub started_at: u64,
+    /// The number of input files processed.
+    pub inputs: usize,
+    /// The number of rows applied (not counting those the engine ignored).
+    pub rows: u64,
+    /// The number of dispute rows applied.
+    pub disputes: u64,
+    /// Disputes per deposit applied, as a fraction.
+    pub dispute_rate: f64,
+    /// The number of rejected rows gathered under `--on-error collect`.
+    pub rejects: u64,
+    /// The wall-clock duration of the run, in milliseconds.
+    pub duration_ms: u64,
+    /// The peak resident set size of the process in KiB, where available.
+    pub peak_rss_kb: Option<u64>,
+}
+
+/// Appends a run's KPIs to a CSV history file, writing the header first if the
+/// file is new or empty.
+///
+/// # Arguments
+/// * `path` - The history file; created if missing.
+/// * `kpis` - The run's KPIs.
+///
+/// # Returns
+/// * `AppResult<()>` - `Ok(())` once the row is written, or an `AppErrors::Io`
+///   on failure.
+pub fn append_run_kpis(path: &str, kpis: &RunKpis) -> AppResult<()> {
+    let is_new = fs::metadata(path).map_or(true, |m| m.len() == 0);
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| AppErrors::Io(format!("open {path}: {e}")))?;
+    let mut wtr = csv::WriterBuilder::new()
+        .has_headers(is_new)
+        .from_writer(file);
+    wtr.serialize(kpis)
+        .and_then(|()| Ok(wtr.flush()?))
+        .map_err(|e| AppErrors::Io(format!("write {path}: {e}")))
+}
+
+/// Returns the peak resident set size of this process in KiB, read from
+/// `/proc/self/status` (`VmHWM`). `None` on platforms without procfs.
+pub fn peak_rss_kb() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    status
+        .lines()
+        .find_map(|l| l.strip_prefix("VmHWM:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn appends_rows_under_a_single_header() {
+        let path = std::env::temp_dir().join(format!("pe-{}-history.csv", std::process::id()));
+        let path = path.display().to_string();
+        let _ = fs::remove_file(&path);
+        let kpis = RunKpis {
+            date: "2024-06-01".into(),
+            started_at: 1_717_200_000,
+            inputs: 1,
+            rows: 10,
+            disputes: 1,
+            dispute_rate: 0.25,
+            rejects: 2,
+            duration_ms: 15,
+            peak_rss_kb: None,
+        };
+
+        append_run_kpis(&path, &kpis).unwrap();
+        append_run_kpis(&path, &kpis).unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("date,started_at,inputs,rows"));
+        assert_eq!(lines[1], "2024-06-01,1717200000,1,10,1,0.25,2,15,");
+    }
+}
@@ -0,0 +1,304 @@
+use crate::errors::{AppErrors, AppResult};
+use crate::models::amount::Amount;
+use crate::services::latency::LatencySummary;
+use crate::services::state_store::{fnv1a, state_digest};
+use crate::state::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+
+/// Version of the run manifest layout, bumped on incompatible changes.
+pub const MANIFEST_FORMAT_VERSION: u32 = 1;
+
+/// Version of the engine's command semantics, bumped whenever the same input
+/// and policy can produce different balances, so runs can tell whether an
+/// earlier run's results are reproducible with this binary.
+pub const BEHAVIOR_VERSION: u32 = 1;
+
+/// What produced a run: enough to tell whether replaying its input with another
+/// binary or other settings would reproduce its results.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Provenance {
+    /// The crate version of the binary.
+    pub engine_version: String,
+    /// The binary's [`BEHAVIOR_VERSION`].
+    pub behavior_version: u32,
+    /// The FNV-1a hash of the behavior-affecting settings, as 16 hex digits.
+    pub policy_hash: String,
+    /// The behavior-affecting settings, as `name=value`, sorted by name.
+    pub policy: Vec<String>,
+    /// The cargo features the binary was built with.
+    pub features: Vec<String>,
+}
+
+impl Provenance {
+    /// Describes this binary running with the given settings.
+    ///
+    /// # Arguments
+    /// * `policy` - The behavior-affecting settings as `(name, value)` pairs, in
+    ///   any order.
+    pub fn current(policy: &[(&str, String)]) -> Self {
+        let mut policy: Vec<String> = policy
+            .iter()
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect();
+        policy.sort();
+        let features = [
+            ("arrow", cfg!(feature = "arrow")),
+            ("parquet", cfg!(feature = "parquet")),
+            ("profiling", cfg!(feature = "profiling")),
+            ("proto", cfg!(feature = "proto")),
+            ("tokio", cfg!(feature = "tokio")),
+            ("xlsx", cfg!(feature = "xlsx")),
+            ("zstd", cfg!(feature = "zstd")),
+        ];
+        Self {
+            engine_version: env!("CARGO_PKG_VERSION").to_string(),
+            behavior_version: BEHAVIOR_VERSION,
+            policy_hash: format!("{:016x}", fnv1a(policy.join("\n").as_bytes())),
+            policy,
+            features: features
+                .into_iter()
+                .filter(|(_, on)| *on)
+                .map(|(name, _)| name.to_string())
+                .collect(),
+        }
+    }
+}
+
+/// The ledger totals of an engine: what the next run must open with.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrialBalance {
+    /// The number of accounts.
+    pub accounts: usize,
+    /// The number of locked accounts.
+    pub locked: usize,
+    /// The sum of available funds.
+    pub available: String,
+    /// The sum of held funds.
+    pub held: String,
+    /// The sum of total funds.
+    pub total: String,
+}
+
+/// The record a run leaves behind so the next run can check it carries the
+/// closing state forward intact.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunManifest {
+    /// The layout version of this document ([`MANIFEST_FORMAT_VERSION`]).
+    pub format_version: u32,
+    /// When the run closed, in seconds since the Unix epoch.
+    pub closed_at: u64,
+    /// The closing totals.
+    pub closing: TrialBalance,
+    /// The closing state hash ([`state_digest`]), as 16 hex digits.
+    pub state_hash: String,
+    /// The binary and settings that produced the run; absent in manifests
+    /// written before provenance was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
+    /// Per-command latency percentiles by command type, if the run measured
+    /// them (see [`LatencyRecorder`](crate::services::latency::LatencyRecorder)).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency: Option<BTreeMap<String, LatencySummary>>,
+}
+
+impl RunManifest {
+    /// Builds the manifest of an engine's closing state.
+    ///
+    /// # Arguments
+    /// * `engine` - The engine at the end of the run.
+    /// * `now` - The closing time, in seconds since the Unix epoch.
+    ///
+    /// # Returns
+    /// * `AppResult<Self>` - The manifest, or `AppErrors::Overflow` if a total
+    ///   does not fit an amount.
+    pub fn closing(engine: &Engine, now: u64) -> AppResult<Self> {
+        Ok(Self {
+            format_version: MANIFEST_FORMAT_VERSION,
+            closed_at: now,
+            closing: trial_balance(engine)?,
+            state_hash: format!("{:016x}", state_digest(engine)),
+            provenance: None,
+            latency: None,
+        })
+    }
+
+    /// Writes the manifest as pretty-printed JSON.
+    ///
+    /// # Returns
+    /// * `AppResult<()>` - `Ok(())` once written, or an `AppErrors::Io` on failure.
+    pub fn write(&self, path: &str) -> AppResult<()> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| AppErrors::Io(format!("encode {path}: {e}")))?;
+        fs::write(path, json + "\n").map_err(|e| AppErrors::Io(format!("write {path}: {e}")))
+    }
+
+    /// Reads a manifest written by [`RunManifest::write`].
+    ///
+    /// # Returns
+    /// * `AppResult<Self>` - The manifest, or an `AppErrors::Io` if it cannot be
+    ///   read or parsed.
+    pub fn read(path: &str) -> AppResult<Self> {
+        let json = fs::read(path).map_err(|e| AppErrors::Io(format!("read {path}: {e}")))?;
+        serde_json::from_slice(&json).map_err(|e| AppErrors::Io(format!("parse {path}: {e}")))
+    }
+}
+
+/// Compares the provenance of an earlier run with this run's.
+///
+/// # Arguments
+/// * `previous` - The earlier run's manifest.
+/// * `current` - This run's provenance.
+///
+/// # Returns
+/// * `Option<String>` - Why the earlier run may not be reproducible with this
+///   run, or `None` if the behavior version and policy hash match.
+pub fn provenance_mismatch(previous: &RunManifest, current: &Provenance) -> Option<String> {
+    let Some(previous) = &previous.provenance else {
+        return Some("the manifest records no engine version".to_string());
+    };
+    if previous.behavior_version != current.behavior_version {
+        return Some(format!(
+            "it was written by engine {} (behavior version {}), this is engine {} (behavior version {})",
+            previous.engine_version,
+            previous.behavior_version,
+            current.engine_version,
+            current.behavior_version
+        ));
+    }
+    (previous.policy_hash != current.policy_hash).then(|| {
+        format!(
+            "its policy ({}) differs from this run's ({})",
+            previous.policy.join(", "),
+            current.policy.join(", ")
+        )
+    })
+}
+
+/// Sums the engine's accounts.
+///
+/// # Returns
+/// * `AppResult<TrialBalance>` - The totals, or `AppErrors::Overflow` if a sum
+///   does not fit an amount.
+pub fn trial_balance(engine: &Engine) -> AppResult<TrialBalance> {
+    let (mut accounts, mut locked) = (0, 0);
+    let (mut available, mut held) = (Amount::zero(), Amount::zero());
+    for (_, acc) in engine.accounts_iter() {
+        accounts += 1;
+        locked += usize::from(acc.locked);
+        available = available
+            .checked_add(acc.available)
+            .ok_or(AppErrors::Overflow)?;
+        held = held.checked_add(acc.held).ok_or(AppErrors::Overflow)?;
+    }
+    let total = available.checked_add(held).ok_or(AppErrors::Overflow)?;
+    Ok(TrialBalance {
+        accounts,
+        locked,
+        available: available.to_string(),
+        held: held.to_string(),
+        total: total.to_string(),
+    })
+}
+
+/// Checks that an engine opens with the state the previous run closed with.
+///
+/// The totals are always compared. The state hash is compared only when
+/// `full_state` is set (the engine was loaded from a snapshot of the previous
+/// run): opening balances carry no tx records, so their hash never matches.
+///
+/// # Arguments
+/// * `previous` - The previous run's manifest.
+/// * `engine` - The engine as loaded, before any input is applied.
+/// * `full_state` - Whether the engine holds the previous run's full state.
+///
+/// # Returns
+/// * `AppResult<()>` - `Ok(())` if the chain holds, or
+///   `AppErrors::CarryForwardMismatch` naming the first total that differs.
+pub fn verify_carry_forward(
+    previous: &RunManifest,
+    engine: &Engine,
+    full_state: bool,
+) -> AppResult<()> {
+    let opening = trial_balance(engine)?;
+    let closing = &previous.closing;
+    let checks = [
+        (
+            "account count",
+            closing.accounts.to_string(),
+            opening.accounts.to_string(),
+        ),
+        (
+            "locked count",
+            closing.locked.to_string(),
+            opening.locked.to_string(),
+        ),
+        (
+            "available total",
+            closing.available.clone(),
+            opening.available,
+        ),
+        ("held total", closing.held.clone(), opening.held),
+        ("total", closing.total.clone(), opening.total),
+    ];
+    for (what, expected, found) in checks {
+        if expected != found {
+            return Err(AppErrors::CarryForwardMismatch {
+                what,
+                expected,
+                found,
+            });
+        }
+    }
+    let hash = format!("{:016x}", state_digest(engine));
+    if full_state && hash != previous.state_hash {
+        return Err(AppErrors::CarryForwardMismatch {
+            what: "state hash",
+            expected: previous.state_hash.clone(),
+            found: hash,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn carry_forward_catches_changed_totals() {
+        let mut engine = Engine::default();
+        engine.acct_mut(1).available = Amount(15_000);
+        engine.acct_mut(2).held = Amount(5_000);
+        engine.acct_mut(2).locked = true;
+        let manifest = RunManifest::closing(&engine, 1_700_000_000).unwrap();
+        assert_eq!(manifest.closing.total, "2.0000");
+        assert_eq!(manifest.closing.locked, 1);
+
+        verify_carry_forward(&manifest, &engine, true).unwrap();
+
+        let current = Provenance::current(&[("on_error", "skip".to_string())]);
+        assert_eq!(
+            provenance_mismatch(&manifest, &current).unwrap(),
+            "the manifest records no engine version"
+        );
+        let pinned = RunManifest {
+            provenance: Some(current.clone()),
+            ..manifest.clone()
+        };
+        assert_eq!(provenance_mismatch(&pinned, &current), None);
+        let stricter = Provenance::current(&[("on_error", "fail-fast".to_string())]);
+        assert!(provenance_mismatch(&pinned, &stricter).is_some());
+
+        engine.acct_mut(1).available = Amount(15_001);
+        let err = verify_carry_forward(&manifest, &engine, false).unwrap_err();
+        assert!(matches!(
+            err,
+            AppErrors::CarryForwardMismatch {
+                what: "available total",
+                ..
+            }
+        ));
+    }
+}
@@ -0,0 +1,121 @@
+use crate::errors::{AppErrors, AppResult};
+use crate::services::rejects::RejectLog;
+use crate::services::run_summary::{CommandCounts, SummaryRecorder};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::time::Duration;
+
+/// The machine-readable run report written by `--report`, for ingestion into
+/// batch monitoring.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RunReport {
+    /// When the run started, in seconds since the Unix epoch.
+    pub started_at: u64,
+    /// The wall-clock duration of the run, in milliseconds.
+    pub duration_ms: u64,
+    /// Every data row read, including malformed ones.
+    pub rows: u64,
+    /// Rows read per second of the run.
+    pub rows_per_sec: f64,
+    /// The command counts by command type name; types not seen are absent.
+    pub commands: BTreeMap<&'static str, CommandCounts>,
+    /// The number of rows skipped, quarantined, failed or ignored.
+    pub rejected: usize,
+    /// The rejected rows by reason code (see [`RejectLog`]).
+    pub rejects: BTreeMap<&'static str, usize>,
+}
+
+impl RunReport {
+    /// Builds the report of a finished run.
+    ///
+    /// # Arguments
+    /// * `started_at` - When the run started, in seconds since the Unix epoch.
+    /// * `elapsed` - The wall-clock duration of the run.
+    /// * `recorder` - The run's row and command tallies.
+    /// * `rejects` - The run's rejected rows.
+    ///
+    /// # Returns
+    /// * `RunReport` - The report.
+    pub fn new(
+        started_at: u64,
+        elapsed: Duration,
+        recorder: &SummaryRecorder,
+        rejects: &RejectLog,
+    ) -> Self {
+        let rows = recorder.rows();
+        let secs = elapsed.as_secs_f64();
+        Self {
+            started_at,
+            duration_ms: elapsed.as_millis() as u64,
+            rows,
+            rows_per_sec: if secs > 0.0 { rows as f64 / secs } else { 0.0 },
+            commands: recorder.commands().clone(),
+            rejected: rejects.len(),
+            rejects: rejects.by_reason(),
+        }
+    }
+
+    /// Writes the report as pretty-printed JSON.
+    ///
+    /// # Arguments
+    /// * `path` - The report file.
+    ///
+    /// # Returns
+    /// * `AppResult<()>` - `Ok(())` once written, or an `AppErrors::Io` on failure.
+    pub fn write(&self, path: &str) -> AppResult<()> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| AppErrors::Io(format!("encode {path}: {e}")))?;
+        fs::write(path, json + "\n").map_err(|e| AppErrors::Io(format!("write {path}: {e}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::csv_service::run_from_reader;
+    use crate::state::AppState;
+
+    #[test]
+    fn reports_counts_rejects_and_throughput() {
+        let input = "type,client,tx,amount\n\
+                     deposit,1,1,5.0\n\
+                     deposit,x,2,1.0\n\
+                     withdrawal,1,3,9.0\n\
+                     withdrawal,1,4,1.0\n\
+                     dispute,1,99,\n";
+        let mut app_state = AppState {
+            summary: Some(SummaryRecorder::default()),
+            rejects: Some(RejectLog::default()),
+            ..AppState::default()
+        };
+        run_from_reader(input.as_bytes(), &mut app_state).unwrap();
+
+        let report = RunReport::new(
+            7,
+            Duration::from_millis(500),
+            app_state.summary.as_ref().unwrap(),
+            app_state.rejects.as_ref().unwrap(),
+        );
+
+        assert_eq!((report.rows, report.duration_ms), (5, 500));
+        assert_eq!(report.rows_per_sec, 10.0);
+        assert_eq!(
+            report.commands["withdrawal"],
+            CommandCounts {
+                applied: 1,
+                ignored: 1,
+                rejected: 0,
+            }
+        );
+        assert_eq!(report.rejected, 3);
+        assert_eq!(
+            report.rejects,
+            BTreeMap::from([
+                ("insufficient_funds", 1),
+                ("malformed_record", 1),
+                ("unknown_tx", 1),
+            ])
+        );
+    }
+}
@@ -0,0 +1,208 @@
+use crate::errors::{AppErrors, AppResult};
+use crate::models::csv_models::transaction::CsvTxType;
+use crate::services::run_manifest::trial_balance;
+use crate::state::{Engine, Outcome};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::fs;
+
+/// What happened to the commands of one type during a run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct CommandCounts {
+    /// Commands that changed the engine's state.
+    pub applied: u64,
+    /// Commands that were no-ops (see [`Outcome::Ignored`]).
+    pub ignored: u64,
+    /// Commands the engine rejected with an error.
+    pub rejected: u64,
+}
+
+/// Tallies a run's input as it is processed, for batch monitoring: how many
+/// rows were read, and what happened to the commands of each type.
+#[derive(Debug, Clone, Default)]
+pub struct SummaryRecorder {
+    /// Every data row read, including malformed ones.
+    rows: u64,
+    /// The command counts by command type name.
+    commands: BTreeMap<&'static str, CommandCounts>,
+}
+
+impl SummaryRecorder {
+    /// Counts a data row read.
+    pub fn row(&mut self) {
+        self.rows += 1;
+    }
+
+    /// Counts a command the engine evaluated.
+    ///
+    /// # Arguments
+    /// * `kind` - The command type.
+    /// * `result` - What applying it returned.
+    pub fn command(&mut self, kind: CsvTxType, result: &AppResult<Outcome>) {
+        let counts = self.commands.entry(kind.as_str()).or_default();
+        match result {
+            Ok(Outcome::Applied) => counts.applied += 1,
+            Ok(Outcome::Ignored(_)) => counts.ignored += 1,
+            Err(_) => counts.rejected += 1,
+        }
+    }
+
+    /// Returns the number of data rows read, including malformed ones.
+    pub fn rows(&self) -> u64 {
+        self.rows
+    }
+
+    /// Returns the command counts by command type name.
+    pub fn commands(&self) -> &BTreeMap<&'static str, CommandCounts> {
+        &self.commands
+    }
+
+    /// Summarizes the run: the tallies, and the totals of the engine it left.
+    ///
+    /// # Arguments
+    /// * `engine` - The engine at the end of the run.
+    ///
+    /// # Returns
+    /// * `AppResult<RunSummary>` - The summary, or `AppErrors::Overflow` if a
+    ///   total does not fit an amount.
+    pub fn summary(&self, engine: &Engine) -> AppResult<RunSummary> {
+        let evaluated: u64 = self
+            .commands
+            .values()
+            .map(|c| c.applied + c.ignored + c.rejected)
+            .sum();
+        let totals = trial_balance(engine)?;
+        Ok(RunSummary {
+            rows: self.rows,
+            skipped: self.rows - evaluated,
+            commands: self.commands.clone(),
+            accounts: totals.accounts,
+            locked: totals.locked,
+            held: totals.held,
+        })
+    }
+}
+
+/// The end-of-run summary written by `--summary`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RunSummary {
+    /// Every data row read, including malformed ones.
+    pub rows: u64,
+    /// Rows that never reached the engine: malformed or invalid rows,
+    /// quarantined commands, and rows another shard instance owns.
+    pub skipped: u64,
+    /// The command counts by command type name; types not seen are absent.
+    pub commands: BTreeMap<&'static str, CommandCounts>,
+    /// The number of accounts.
+    pub accounts: usize,
+    /// The number of locked accounts.
+    pub locked: usize,
+    /// The sum of held funds.
+    pub held: String,
+}
+
+impl RunSummary {
+    /// Writes the summary as pretty-printed JSON to a file, or as text to
+    /// stderr.
+    ///
+    /// # Arguments
+    /// * `path` - The file, or `None` for stderr.
+    ///
+    /// # Returns
+    /// * `AppResult<()>` - `Ok(())` once written, or an `AppErrors::Io` on failure.
+    pub fn write(&self, path: Option<&str>) -> AppResult<()> {
+        let Some(path) = path else {
+            eprint!("{self}");
+            return Ok(());
+        };
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| AppErrors::Io(format!("encode {path}: {e}")))?;
+        fs::write(path, json + "\n").map_err(|e| AppErrors::Io(format!("write {path}: {e}")))
+    }
+}
+
+impl Display for RunSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "rows read:    {}", self.rows)?;
+        writeln!(f, "rows skipped: {}", self.skipped)?;
+        for (kind, c) in &self.commands {
+            writeln!(
+                f,
+                "{kind:<12}  applied {}, ignored {}, rejected {}",
+                c.applied, c.ignored, c.rejected
+            )?;
+        }
+        writeln!(
+            f,
+            "accounts:     {} ({} locked)",
+            self.accounts, self.locked
+        )?;
+        writeln!(f, "funds held:   {}", self.held)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::amount::Amount;
+    use crate::state::IgnoreReason;
+
+    #[test]
+    fn counts_rows_and_outcomes_per_type() {
+        let mut recorder = SummaryRecorder::default();
+        for _ in 0..5 {
+            recorder.row();
+        }
+        recorder.command(CsvTxType::Deposit, &Ok(Outcome::Applied));
+        recorder.command(
+            CsvTxType::Deposit,
+            &Ok(Outcome::Ignored(IgnoreReason::DuplicateTx)),
+        );
+        recorder.command(CsvTxType::Withdrawal, &Err(AppErrors::Overflow));
+        let mut engine = Engine::default();
+        engine.acct_mut(1).held = Amount(25_000);
+        engine.acct_mut(2).locked = true;
+
+        let summary = recorder.summary(&engine).unwrap();
+
+        assert_eq!(summary.skipped, 2);
+        assert_eq!(
+            summary.commands["deposit"],
+            CommandCounts {
+                applied: 1,
+                ignored: 1,
+                rejected: 0,
+            }
+        );
+        assert_eq!(summary.commands["withdrawal"].rejected, 1);
+        assert_eq!((summary.accounts, summary.locked), (2, 1));
+        assert!(summary.to_string().contains("funds held:   2.5000"));
+    }
+
+    #[test]
+    fn empty_run_summarizes_to_zero() {
+        let summary = SummaryRecorder::default()
+            .summary(&Engine::default())
+            .unwrap();
+        assert_eq!((summary.rows, summary.skipped), (0, 0));
+        assert!(summary.commands.is_empty());
+        assert_eq!((summary.accounts, summary.locked), (0, 0));
+        assert_eq!(summary.held, "0.0000");
+    }
+
+    #[test]
+    fn malformed_rows_are_skipped_and_locked_account_commands_ignored() {
+        let mut recorder = SummaryRecorder::default();
+        recorder.row();
+        recorder.row();
+        recorder.command(
+            CsvTxType::Deposit,
+            &Ok(Outcome::Ignored(IgnoreReason::AccountLocked)),
+        );
+
+        let summary = recorder.summary(&Engine::default()).unwrap();
+        assert_eq!(summary.skipped, 1);
+        assert_eq!(summary.commands["deposit"].ignored, 1);
+    }
+}
@@ -0,0 +1,127 @@
+use crate::errors::{AppErrors, AppResult};
+use crate::services::csv_service::run_from_reader_observed;
+use crate::state::{AppState, Outcome};
+use log::info;
+use std::io::Write;
+use std::net::{Shutdown, SocketAddr, TcpListener};
+
+/// What one connection's input did to the served engine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServedBatch {
+    /// The client's address.
+    pub peer: SocketAddr,
+    /// The number of rows whose command was applied.
+    pub applied: u64,
+    /// The number of row errors gathered under `collect`.
+    pub row_errors: usize,
+    /// The error the batch stopped at under `fail-fast` or `strict`, if any.
+    pub failure: Option<String>,
+}
+
+/// Accepts one client connection, applies the transaction CSV it streams
+/// (with a header row, in `app_state`'s dialect) until the client shuts down
+/// its sending half, and answers with one line: `ok <applied> <row errors>`,
+/// or `error <message>` if the engine's error policy stopped the batch.
+///
+/// Rows are applied under the engine's error policy like any other run, so a
+/// batch stopped by an error keeps the rows applied before it. Rows the engine
+/// ignored (e.g., duplicates) are not counted as applied.
+///
+/// # Arguments
+/// * `listener` - The socket to accept the client on.
+/// * `app_state` - The served application state.
+///
+/// # Returns
+/// * `AppResult<ServedBatch>` - What the batch did, or an `AppErrors::Io` if the
+///   connection fails.
+pub fn serve_connection(
+    listener: &TcpListener,
+    app_state: &mut AppState,
+) -> AppResult<ServedBatch> {
+    let (stream, peer) = listener
+        .accept()
+        .map_err(|e| AppErrors::Io(format!("accept client: {e}")))?;
+    info!("Client connected from {peer}");
+
+    let mut applied = 0;
+    let result = run_from_reader_observed(&stream, app_state, |_, _, outcome, _| {
+        if outcome == Outcome::Applied {
+            applied += 1;
+        }
+        Ok(())
+    });
+    let (reply, batch) = match result {
+        Ok(errors) => (
+            format!("ok {applied} {}\n", errors.len()),
+            ServedBatch {
+                peer,
+                applied,
+                row_errors: errors.len(),
+                failure: None,
+            },
+        ),
+        Err(e) => (
+            format!("error {e}\n"),
+            ServedBatch {
+                peer,
+                applied,
+                row_errors: 0,
+                failure: Some(e.to_string()),
+            },
+        ),
+    };
+    (&stream)
+        .write_all(reply.as_bytes())
+        .and_then(|_| stream.shutdown(Shutdown::Write))
+        .map_err(|e| AppErrors::Io(format!("reply to {peer}: {e}")))?;
+    Ok(batch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::amount::Amount;
+    use crate::models::policy::ErrorPolicy;
+    use std::io::Read;
+    use std::net::TcpStream;
+    use std::thread;
+
+    fn send(addr: SocketAddr, input: &'static str) -> thread::JoinHandle<String> {
+        thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write_all(input.as_bytes()).unwrap();
+            stream.shutdown(Shutdown::Write).unwrap();
+            let mut reply = String::new();
+            stream.read_to_string(&mut reply).unwrap();
+            reply
+        })
+    }
+
+    #[test]
+    fn applies_each_connection_to_the_same_engine() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut app_state = AppState::default();
+
+        // The duplicate is ignored, not counted as applied.
+        let client = send(
+            addr,
+            "type,client,tx,amount\ndeposit,1,1,5.0\ndeposit,1,1,5.0\n",
+        );
+        let first = serve_connection(&listener, &mut app_state).unwrap();
+        assert_eq!(client.join().unwrap(), "ok 1 0\n");
+        assert_eq!((first.applied, first.failure), (1, None));
+
+        app_state.engine.set_error_policy(ErrorPolicy::Strict);
+        let client = send(
+            addr,
+            "type,client,tx,amount\nwithdrawal,1,2,1.5\ndeposit,x,3,1.0\ndeposit,1,4,9.0\n",
+        );
+        let second = serve_connection(&listener, &mut app_state).unwrap();
+        assert!(client.join().unwrap().starts_with("error line 3:"));
+        assert_eq!(second.applied, 1);
+        assert!(second.failure.is_some());
+
+        assert_eq!(app_state.engine.acct(1).unwrap().available, Amount(35_000));
+    }
+}
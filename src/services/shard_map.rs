@@ -0,0 +1,262 @@
+use crate::errors::{AppErrors, AppResult};
+use crate::models::identifiers::ClientId;
+use crate::services::state_store::fnv1a;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+
+/// Version of the shard map layout, bumped on incompatible changes.
+pub const SHARD_MAP_FORMAT_VERSION: u32 = 1;
+
+/// The hash every ring point is computed with, recorded in the map so routers
+/// in other languages know what to implement (see [`ring_hash`]).
+const SHARD_HASH: &str = "fnv1a64-fmix64";
+
+/// One point on the hash ring: the clients hashing to it, or past the previous
+/// point, belong to `instance`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RingPoint {
+    /// The position on the ring.
+    pub point: u64,
+    /// The owning instance.
+    pub instance: String,
+}
+
+/// Assigns clients to engine instances by consistent hashing, so a dataset can
+/// be split across processes with deterministic ownership, and adding or
+/// removing an instance only moves the clients it gains or loses.
+///
+/// Each instance owns `vnodes` points on a 64-bit ring, at
+/// `ring_hash("<instance>#<vnode>")`. A client hashes to `ring_hash("<client>")`
+/// (its decimal id) and belongs to the instance of the first point at or after
+/// its hash, wrapping around. The exported map lists the ring, so routers only
+/// need the client hash to look up owners.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShardMap {
+    /// The layout version of this document ([`SHARD_MAP_FORMAT_VERSION`]).
+    pub format_version: u32,
+    /// The hash of ring points and clients, `fnv1a64-fmix64`.
+    pub hash: String,
+    /// The number of ring points per instance.
+    pub vnodes: u32,
+    /// The instances, in the order given.
+    pub instances: Vec<String>,
+    /// The ring, sorted by point.
+    pub ring: Vec<RingPoint>,
+}
+
+impl ShardMap {
+    /// Builds the shard map of a set of instances.
+    ///
+    /// # Arguments
+    /// * `instances` - The instance names, unique and non-empty.
+    /// * `vnodes` - The number of ring points per instance, at least 1; more
+    ///   points spread clients more evenly.
+    ///
+    /// # Returns
+    /// * `AppResult<Self>` - The map, or `AppErrors::InvalidInput` if there are no
+    ///   instances, a name is empty or repeated, or `vnodes` is 0.
+    pub fn new(instances: &[String], vnodes: u32) -> AppResult<Self> {
+        if instances.is_empty() {
+            return Err(AppErrors::InvalidInput("a shard map needs an instance"));
+        }
+        if vnodes == 0 {
+            return Err(AppErrors::InvalidInput("vnodes must be at least 1"));
+        }
+        let mut seen = HashSet::new();
+        if instances
+            .iter()
+            .any(|name| name.is_empty() || !seen.insert(name))
+        {
+            return Err(AppErrors::InvalidInput(
+                "shard instance names must be non-empty and unique",
+            ));
+        }
+        let mut ring: Vec<RingPoint> = instances
+            .iter()
+            .flat_map(|name| {
+                (0..vnodes).map(move |vnode| RingPoint {
+                    point: ring_hash(format!("{name}#{vnode}").as_bytes()),
+                    instance: name.clone(),
+                })
+            })
+            .collect();
+        // Ties are broken by name so the ring does not depend on input order.
+        ring.sort_by(|a, b| (a.point, &a.instance).cmp(&(b.point, &b.instance)));
+        Ok(Self {
+            format_version: SHARD_MAP_FORMAT_VERSION,
+            hash: SHARD_HASH.to_string(),
+            vnodes,
+            instances: instances.to_vec(),
+            ring,
+        })
+    }
+
+    /// Returns the instance owning a client.
+    pub fn owner(&self, client: ClientId) -> &str {
+        let hash = ring_hash(client.to_string().as_bytes());
+        let i = self.ring.partition_point(|p| p.point < hash);
+        &self.ring[i % self.ring.len()].instance
+    }
+
+    /// Writes the map as pretty-printed JSON.
+    ///
+    /// # Returns
+    /// * `AppResult<()>` - `Ok(())` once written, or an `AppErrors::Io` on failure.
+    pub fn write(&self, path: &str) -> AppResult<()> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| AppErrors::Io(format!("encode {path}: {e}")))?;
+        fs::write(path, json + "\n").map_err(|e| AppErrors::Io(format!("write {path}: {e}")))
+    }
+
+    /// Reads a map written by [`ShardMap::write`], checking that its ring is the
+    /// one its instances and vnodes produce, so every process routes alike.
+    ///
+    /// # Returns
+    /// * `AppResult<Self>` - The map, or an `AppErrors::Io` if it cannot be read
+    ///   or parsed, or `AppErrors::InvalidInput` if its version, hash or ring does
+    ///   not match.
+    pub fn read(path: &str) -> AppResult<Self> {
+        let json = fs::read(path).map_err(|e| AppErrors::Io(format!("read {path}: {e}")))?;
+        let map: Self = serde_json::from_slice(&json)
+            .map_err(|e| AppErrors::Io(format!("parse {path}: {e}")))?;
+        if map.format_version != SHARD_MAP_FORMAT_VERSION || map.hash != SHARD_HASH {
+            return Err(AppErrors::InvalidInput(
+                "unsupported shard map version or hash",
+            ));
+        }
+        if Self::new(&map.instances, map.vnodes)? != map {
+            return Err(AppErrors::InvalidInput(
+                "shard map ring does not match its instances",
+            ));
+        }
+        Ok(map)
+    }
+}
+
+/// Keeps an instance to the clients a [`ShardMap`] assigns it, so each process
+/// of a split run applies only its own share of a shared input.
+#[derive(Debug, Clone)]
+pub struct ShardRouter {
+    /// The map.
+    map: ShardMap,
+    /// This instance's name.
+    instance: String,
+    /// The number of rows routed away, to other instances.
+    foreign: u64,
+}
+
+impl ShardRouter {
+    /// Creates the router of one instance.
+    ///
+    /// # Arguments
+    /// * `map` - The shard map.
+    /// * `instance` - This instance's name.
+    ///
+    /// # Returns
+    /// * `AppResult<Self>` - The router, or `AppErrors::InvalidInput` if the map
+    ///   has no such instance.
+    pub fn new(map: ShardMap, instance: &str) -> AppResult<Self> {
+        if !map.instances.iter().any(|name| name == instance) {
+            return Err(AppErrors::InvalidInput("instance is not in the shard map"));
+        }
+        Ok(Self {
+            map,
+            instance: instance.to_string(),
+            foreign: 0,
+        })
+    }
+
+    /// Decides whether this instance owns a client's rows, counting those it
+    /// does not.
+    pub fn admit(&mut self, client: ClientId) -> bool {
+        let own = self.map.owner(client) == self.instance;
+        self.foreign += u64::from(!own);
+        own
+    }
+
+    /// Returns the number of rows routed away, to other instances.
+    pub fn foreign(&self) -> u64 {
+        self.foreign
+    }
+}
+
+/// Hashes a ring key: FNV-1a, then the MurmurHash3 64-bit finalizer, since
+/// FNV-1a alone spreads short, similar keys such as sequential client ids
+/// unevenly over the ring.
+fn ring_hash(key: &[u8]) -> u64 {
+    let mut h = fnv1a(key);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    h ^ (h >> 33)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn assigns_clients_deterministically_and_moves_few_on_growth() {
+        let three = ShardMap::new(&names(&["a", "b", "c"]), 128).unwrap();
+        let reordered = ShardMap::new(&names(&["c", "a", "b"]), 128).unwrap();
+        let four = ShardMap::new(&names(&["a", "b", "c", "d"]), 128).unwrap();
+
+        let mut counts = [0usize; 3];
+        let mut moved = 0;
+        for client in 0..=ClientId::MAX {
+            let owner = three.owner(client);
+            assert_eq!(owner, reordered.owner(client));
+            counts[usize::from(owner.as_bytes()[0] - b'a')] += 1;
+            let grown = four.owner(client);
+            if grown != owner {
+                assert_eq!(grown, "d", "clients only move to the new instance");
+                moved += 1;
+            }
+        }
+        // Roughly a third each, and roughly a quarter moves to the newcomer.
+        for count in counts {
+            assert!((15_000..30_000).contains(&count), "{counts:?}");
+        }
+        assert!((10_000..25_000).contains(&moved), "{moved}");
+
+        let mut router = ShardRouter::new(three.clone(), "b").unwrap();
+        let admitted = (0..100).filter(|c| router.admit(*c)).count() as u64;
+        assert_eq!(admitted + router.foreign(), 100);
+        assert!(ShardRouter::new(three, "z").is_err());
+        assert!(ShardMap::new(&names(&["a", "a"]), 8).is_err());
+    }
+
+    #[test]
+    fn empty_or_degenerate_maps_are_rejected() {
+        assert!(ShardMap::new(&[], 8).is_err());
+        assert!(ShardMap::new(&names(&["a"]), 0).is_err());
+        let single = ShardMap::new(&names(&["a"]), 1).unwrap();
+        assert!((0..1_000).all(|c| single.owner(c) == "a"));
+    }
+
+    #[test]
+    fn read_round_trips_and_rejects_malformed_or_tampered_maps() {
+        let path = std::env::temp_dir().join(format!("pe-{}-shards.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        let map = ShardMap::new(&names(&["a", "b"]), 4).unwrap();
+        map.write(path).unwrap();
+        assert_eq!(ShardMap::read(path).unwrap(), map);
+
+        fs::write(path, "{").unwrap();
+        assert!(matches!(ShardMap::read(path), Err(AppErrors::Io(_))));
+        let json = serde_json::to_string(&map).unwrap();
+        // Another instance name with the same ring routes differently.
+        fs::write(path, json.replacen("\"b\"", "\"c\"", 1)).unwrap();
+        assert!(matches!(
+            ShardMap::read(path),
+            Err(AppErrors::InvalidInput(_))
+        ));
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,794 @@
+use crate::errors::{AppErrors, AppResult};
+use crate::models::amount::Amount;
+use crate::models::domain_state::{
+    Account, Annotation, DisputeState, NoteTarget, TxKind, TxRecord,
+};
+use crate::models::identifiers::{ClientId, TxId};
+use crate::services::paged_store::{encode_paged, open_paged};
+use crate::state::Engine;
+use sha2::{Digest, Sha256};
+use std::fmt::{Display, Formatter};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::str::FromStr;
+
+/// Magic header identifying a binary engine snapshot (format version 3, which
+/// appends account versions and operator annotations to the body).
+const SNAPSHOT_MAGIC: &[u8; 8] = b"PESNAP03";
+
+/// Magic header of format version 2 snapshots, which carry no annotations.
+const SNAPSHOT_MAGIC_V2: &[u8; 8] = b"PESNAP02";
+
+/// Magic header of format version 1 snapshots, which carry no account versions;
+/// they still load, with every account at version 0.
+const SNAPSHOT_MAGIC_V1: &[u8; 8] = b"PESNAP01";
+
+/// A storage backend location, written as `<kind>:<path>` on the command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StoreSpec {
+    /// A compact binary snapshot file (`snapshot:state.bin`).
+    Snapshot(String),
+    /// A human-readable JSON file (`json:state.json`).
+    Json(String),
+    /// A binary file of per-client pages that can be loaded lazily
+    /// (`paged:state.pages`; see [`open_paged`]).
+    Paged(String),
+}
+
+impl FromStr for StoreSpec {
+    type Err = AppErrors;
+
+    /// Parses a `<kind>:<path>` backend specification.
+    ///
+    /// # Errors
+    /// * `AppErrors::InvalidInput` if the kind is unknown or the path is missing.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some((_, "")) | None => Err(AppErrors::InvalidInput(
+                "backend must be written as <kind>:<path>",
+            )),
+            Some(("snapshot", path)) => Ok(StoreSpec::Snapshot(path.to_string())),
+            Some(("json", path)) => Ok(StoreSpec::Json(path.to_string())),
+            Some(("paged", path)) => Ok(StoreSpec::Paged(path.to_string())),
+            Some(_) => Err(AppErrors::InvalidInput(
+                "unsupported backend (expected snapshot, json or paged)",
+            )),
+        }
+    }
+}
+
+impl Display for StoreSpec {
+    /// Formats the backend back into its `<kind>:<path>` form.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StoreSpec::Snapshot(path) => write!(f, "snapshot:{path}"),
+            StoreSpec::Json(path) => write!(f, "json:{path}"),
+            StoreSpec::Paged(path) => write!(f, "paged:{path}"),
+        }
+    }
+}
+
+impl StoreSpec {
+    /// Loads an engine from this backend.
+    ///
+    /// # Returns
+    /// * `AppResult<Engine>` - The loaded engine, or an `AppErrors` variant if the
+    ///   backend cannot be read or fails its integrity check.
+    pub fn load(&self) -> AppResult<Engine> {
+        match self {
+            StoreSpec::Snapshot(path) => {
+                let file =
+                    File::open(path).map_err(|e| AppErrors::Io(format!("open {path}: {e}")))?;
+                let mut buf = Vec::new();
+                BufReader::new(file)
+                    .read_to_end(&mut buf)
+                    .map_err(|e| AppErrors::Io(format!("read {path}: {e}")))?;
+                decode_snapshot(&buf)
+            }
+            StoreSpec::Json(path) => {
+                let file =
+                    File::open(path).map_err(|e| AppErrors::Io(format!("open {path}: {e}")))?;
+                let doc: JsonState = serde_json::from_reader(BufReader::new(file))
+                    .map_err(|e| AppErrors::Io(format!("parse {path}: {e}")))?;
+                doc.into_engine()
+            }
+            StoreSpec::Paged(path) => {
+                let mut engine = open_paged(path, 0)?;
+                engine.page_in_all()?;
+                Ok(engine)
+            }
+        }
+    }
+
+    /// Opens an engine from this backend for processing. A paged backend is
+    /// opened lazily: only its index is read up front, and each client's page
+    /// is loaded when a command first touches it (see [`Engine::page_in`]).
+    /// Other backends are loaded in full, like [`StoreSpec::load`].
+    ///
+    /// # Arguments
+    /// * `prefetch_hot` - For a paged backend, the number of hottest clients to
+    ///   read ahead in the background; `0` for none.
+    ///
+    /// # Returns
+    /// * `AppResult<Engine>` - The opened engine, or an `AppErrors` variant if the
+    ///   backend cannot be read or fails its integrity check.
+    pub fn open(&self, prefetch_hot: usize) -> AppResult<Engine> {
+        match self {
+            StoreSpec::Paged(path) => open_paged(path, prefetch_hot),
+            _ => self.load(),
+        }
+    }
+
+    /// Saves an engine to this backend, replacing any existing state atomically
+    /// (write to a temporary file, then rename).
+    ///
+    /// # Arguments
+    /// * `engine` - The engine to save.
+    ///
+    /// # Returns
+    /// * `AppResult<()>` - Returns `Ok(())` if saved, or an `AppErrors::Io` on failure,
+    ///   or `AppErrors::InvalidInput` if the engine has pages not loaded yet
+    ///   (see [`Engine::page_in_all`]), or it has annotations and the backend is
+    ///   paged, whose per-client pages have no place for them.
+    pub fn save(&self, engine: &Engine) -> AppResult<()> {
+        if engine.pending_pages() > 0 {
+            return Err(AppErrors::InvalidInput(
+                "cannot save an engine with pages not loaded",
+            ));
+        }
+        let (path, bytes) = match self {
+            StoreSpec::Snapshot(path) => (path, encode_snapshot(engine)),
+            StoreSpec::Paged(_) if !engine.annotations().is_empty() => {
+                return Err(AppErrors::InvalidInput(
+                    "the paged backend cannot store annotations; use snapshot: or json:",
+                ));
+            }
+            StoreSpec::Paged(path) => (path, encode_paged(engine)?),
+            StoreSpec::Json(path) => {
+                let doc = JsonState::from_engine(engine);
+                let bytes = serde_json::to_vec_pretty(&doc)
+                    .map_err(|e| AppErrors::Internal(format!("serialize state: {e}")))?;
+                (path, bytes)
+            }
+        };
+        let tmp = format!("{path}.tmp");
+        let file = File::create(&tmp).map_err(|e| AppErrors::Io(format!("create {tmp}: {e}")))?;
+        let mut out = BufWriter::new(file);
+        out.write_all(&bytes)
+            .and_then(|_| out.flush())
+            .map_err(|e| AppErrors::Io(format!("write {tmp}: {e}")))?;
+        fs::rename(&tmp, path).map_err(|e| AppErrors::Io(format!("rename {tmp}: {e}")))
+    }
+}
+
+/// Summary of a completed migration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationReport {
+    /// The number of accounts copied.
+    pub accounts: usize,
+    /// The number of transaction records copied.
+    pub txs: usize,
+    /// The verification hash of the migrated state (identical at source and target).
+    pub digest: u64,
+}
+
+/// Copies all accounts and transaction records from one backend to another and
+/// verifies the copy by re-reading the target and comparing state hashes.
+///
+/// # Arguments
+/// * `from` - The source backend.
+/// * `to` - The target backend.
+///
+/// # Returns
+/// * `AppResult<MigrationReport>` - What was copied, or an `AppErrors` variant if
+///   loading, saving, or verification fails.
+pub fn migrate(from: &StoreSpec, to: &StoreSpec) -> AppResult<MigrationReport> {
+    let engine = from.load()?;
+    let digest = state_digest(&engine);
+    to.save(&engine)?;
+
+    let copied = state_digest(&to.load()?);
+    if copied != digest {
+        return Err(AppErrors::Internal(format!(
+            "migration verification failed: source {digest:016x} != target {copied:016x}"
+        )));
+    }
+    Ok(MigrationReport {
+        accounts: engine.accounts_iter().count(),
+        txs: engine.txs.len(),
+        digest,
+    })
+}
+
+/// Computes a deterministic hash of the engine state (accounts, tx records, and
+/// retired tx ids), independent of map iteration order.
+///
+/// The hash is FNV-1a over the canonical snapshot encoding, so it is stable
+/// across runs, platforms, and backends. Account versions and annotations are
+/// bookkeeping, not ledger state, and are left out.
+pub fn state_digest(engine: &Engine) -> u64 {
+    fnv1a(&encode_body(engine))
+}
+
+/// Computes a SHA-256 fingerprint of the engine's final account balances, so
+/// two independent runs of the same input can be shown identical without
+/// comparing their full outputs.
+///
+/// Unlike [`state_digest`], only the balances count, not tx records, and the
+/// hash is over a documented text layout that can be recomputed outside the
+/// engine: one `client,available,held,locked` line per account, sorted by
+/// client, amounts with four decimal places, each line ending in `\n`.
+///
+/// # Returns
+/// * `String` - The fingerprint, as 64 hex digits.
+pub fn account_fingerprint(engine: &Engine) -> String {
+    let mut accounts: Vec<_> = engine.accounts_iter().collect();
+    accounts.sort_by_key(|(c, _)| **c);
+    let mut hasher = Sha256::new();
+    for (client, acc) in accounts {
+        hasher.update(format!(
+            "{client},{},{},{}\n",
+            acc.available, acc.held, acc.locked
+        ));
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// 64-bit FNV-1a hash.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |h, b| {
+        (h ^ u64::from(*b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Encodes the engine as a binary snapshot: magic header, canonical body,
+/// account versions, annotations, and a trailing FNV-1a checksum of all three.
+fn encode_snapshot(engine: &Engine) -> Vec<u8> {
+    let mut body = encode_body(engine);
+    body.extend_from_slice(&encode_versions(engine));
+    body.extend_from_slice(&encode_notes(engine));
+    let mut out = Vec::with_capacity(SNAPSHOT_MAGIC.len() + body.len() + 8);
+    out.extend_from_slice(SNAPSHOT_MAGIC);
+    out.extend_from_slice(&body);
+    out.extend_from_slice(&fnv1a(&body).to_le_bytes());
+    out
+}
+
+/// Encodes accounts, tx records, and retired ids, each sorted by id, as
+/// little-endian fixed-width fields.
+fn encode_body(engine: &Engine) -> Vec<u8> {
+    let mut accounts: Vec<_> = engine.accounts_iter().collect();
+    accounts.sort_by_key(|(c, _)| **c);
+    let mut txs: Vec<_> = engine.txs.iter().collect();
+    txs.sort_by_key(|(tx, _)| *tx);
+    let mut retired: Vec<TxId> = engine.txs.retired_iter().collect();
+    retired.sort_unstable();
+
+    let mut out = Vec::new();
+    out.extend_from_slice(&(accounts.len() as u64).to_le_bytes());
+    for (client, acc) in accounts {
+        out.extend_from_slice(&client.to_le_bytes());
+        out.extend_from_slice(&acc.available.0.to_le_bytes());
+        out.extend_from_slice(&acc.held.0.to_le_bytes());
+        out.push(u8::from(acc.locked));
+    }
+    out.extend_from_slice(&(txs.len() as u64).to_le_bytes());
+    for (tx, rec) in txs {
+        out.extend_from_slice(&tx.to_le_bytes());
+        out.extend_from_slice(&rec.client.to_le_bytes());
+        out.push(kind_code(rec.kind));
+        out.extend_from_slice(&rec.amount.0.to_le_bytes());
+        out.push(state_code(rec.state));
+    }
+    out.extend_from_slice(&(retired.len() as u64).to_le_bytes());
+    for tx in retired {
+        out.extend_from_slice(&tx.to_le_bytes());
+    }
+    out
+}
+
+/// Encodes the account versions, sorted by client, as `(client, version)` pairs.
+fn encode_versions(engine: &Engine) -> Vec<u8> {
+    let mut accounts: Vec<_> = engine.accounts_iter().collect();
+    accounts.sort_by_key(|(c, _)| **c);
+    let mut out = Vec::new();
+    out.extend_from_slice(&(accounts.len() as u64).to_le_bytes());
+    for (client, acc) in accounts {
+        out.extend_from_slice(&client.to_le_bytes());
+        out.extend_from_slice(&acc.version.to_le_bytes());
+    }
+    out
+}
+
+/// Encodes the annotations, in the order added: a target tag (0 for an
+/// account, 1 for a tx) and id, the time, then the length-prefixed author and
+/// text.
+fn encode_notes(engine: &Engine) -> Vec<u8> {
+    let notes = engine.annotations();
+    let mut out = Vec::new();
+    out.extend_from_slice(&(notes.len() as u64).to_le_bytes());
+    for note in notes {
+        match note.target {
+            NoteTarget::Account(client) => {
+                out.push(0);
+                out.extend_from_slice(&client.to_le_bytes());
+            }
+            NoteTarget::Tx(tx) => {
+                out.push(1);
+                out.extend_from_slice(&tx.to_le_bytes());
+            }
+        }
+        out.extend_from_slice(&note.at.to_le_bytes());
+        for text in [&note.author, &note.text] {
+            out.extend_from_slice(&(text.len() as u64).to_le_bytes());
+            out.extend_from_slice(text.as_bytes());
+        }
+    }
+    out
+}
+
+/// Decodes a binary snapshot written by [`encode_snapshot`] (or a version 1 or
+/// 2 snapshot), checking the magic header and checksum.
+fn decode_snapshot(bytes: &[u8]) -> AppResult<Engine> {
+    let corrupt = || AppErrors::InvalidInput("corrupt snapshot");
+    let (body, version) = [
+        (SNAPSHOT_MAGIC, 3),
+        (SNAPSHOT_MAGIC_V2, 2),
+        (SNAPSHOT_MAGIC_V1, 1),
+    ]
+    .into_iter()
+    .find_map(|(magic, version)| Some((bytes.strip_prefix(magic.as_slice())?, version)))
+    .ok_or(AppErrors::InvalidInput("not a snapshot file"))?;
+    let split = body.len().checked_sub(8).ok_or_else(corrupt)?;
+    let (body, checksum) = body.split_at(split);
+    if fnv1a(body).to_le_bytes() != checksum {
+        return Err(AppErrors::InvalidInput("snapshot checksum mismatch"));
+    }
+
+    let mut r = ByteReader(body);
+    let mut engine = Engine::default();
+    for _ in 0..r.u64()? {
+        let client = ClientId::from_le_bytes(r.take()?);
+        let acc = engine.acct_mut(client);
+        acc.available = Amount(i64::from_le_bytes(r.take()?));
+        acc.held = Amount(i64::from_le_bytes(r.take()?));
+        acc.locked = r.take::<1>()?[0] != 0;
+    }
+    for _ in 0..r.u64()? {
+        let tx = TxId::from_le_bytes(r.take()?);
+        let client = ClientId::from_le_bytes(r.take()?);
+        let kind = kind_from_code(r.take::<1>()?[0])?;
+        let amount = Amount(i64::from_le_bytes(r.take()?));
+        let state = state_from_code(r.take::<1>()?[0])?;
+        engine.txs.insert(
+            tx,
+            TxRecord {
+                client,
+                kind,
+                amount,
+                state,
+            },
+        );
+    }
+    for _ in 0..r.u64()? {
+        engine.txs.mark_seen(TxId::from_le_bytes(r.take()?));
+    }
+    if version >= 2 {
+        for _ in 0..r.u64()? {
+            let client = ClientId::from_le_bytes(r.take()?);
+            let acc = engine.acct_mut_if_exists(&client).ok_or_else(corrupt)?;
+            acc.version = r.u64()?;
+        }
+    }
+    if version >= 3 {
+        for _ in 0..r.u64()? {
+            let target = match r.take::<1>()?[0] {
+                0 => NoteTarget::Account(ClientId::from_le_bytes(r.take()?)),
+                1 => NoteTarget::Tx(TxId::from_le_bytes(r.take()?)),
+                _ => return Err(corrupt()),
+            };
+            let at = r.u64()?;
+            let author = r.string()?;
+            let text = r.string()?;
+            engine.annotate(Annotation {
+                target,
+                author,
+                at,
+                text,
+            })?;
+        }
+    }
+    if !r.0.is_empty() {
+        return Err(corrupt());
+    }
+    Ok(engine)
+}
+
+/// A cursor over a byte slice for decoding fixed-width fields.
+pub(crate) struct ByteReader<'a>(pub(crate) &'a [u8]);
+
+impl ByteReader<'_> {
+    /// Takes the next `N` bytes.
+    pub(crate) fn take<const N: usize>(&mut self) -> AppResult<[u8; N]> {
+        if self.0.len() < N {
+            return Err(AppErrors::InvalidInput("truncated snapshot"));
+        }
+        let (head, rest) = self.0.split_at(N);
+        self.0 = rest;
+        Ok(head.try_into().expect("split_at returns N bytes"))
+    }
+
+    /// Takes the next little-endian `u64`.
+    pub(crate) fn u64(&mut self) -> AppResult<u64> {
+        Ok(u64::from_le_bytes(self.take()?))
+    }
+
+    /// Takes the next UTF-8 string, prefixed by its byte length as a `u64`.
+    pub(crate) fn string(&mut self) -> AppResult<String> {
+        let len = usize::try_from(self.u64()?)
+            .ok()
+            .filter(|len| *len <= self.0.len())
+            .ok_or(AppErrors::InvalidInput("truncated snapshot"))?;
+        let (head, rest) = self.0.split_at(len);
+        self.0 = rest;
+        String::from_utf8(head.to_vec()).map_err(|_| AppErrors::InvalidInput("corrupt snapshot"))
+    }
+}
+
+/// Returns the snapshot code for a transaction kind.
+pub(crate) fn kind_code(kind: TxKind) -> u8 {
+    match kind {
+        TxKind::Deposit => 0,
+        TxKind::Withdrawal => 1,
+    }
+}
+
+/// Decodes a transaction kind from its snapshot code.
+pub(crate) fn kind_from_code(code: u8) -> AppResult<TxKind> {
+    match code {
+        0 => Ok(TxKind::Deposit),
+        1 => Ok(TxKind::Withdrawal),
+        _ => Err(AppErrors::InvalidInput("unknown tx kind in snapshot")),
+    }
+}
+
+/// Returns the snapshot code for a dispute state.
+pub(crate) fn state_code(state: DisputeState) -> u8 {
+    match state {
+        DisputeState::Normal => 0,
+        DisputeState::Disputed => 1,
+        DisputeState::ChargedBack => 2,
+        DisputeState::Resolved => 3,
+    }
+}
+
+/// Decodes a dispute state from its snapshot code.
+pub(crate) fn state_from_code(code: u8) -> AppResult<DisputeState> {
+    match code {
+        0 => Ok(DisputeState::Normal),
+        1 => Ok(DisputeState::Disputed),
+        2 => Ok(DisputeState::ChargedBack),
+        3 => Ok(DisputeState::Resolved),
+        _ => Err(AppErrors::InvalidInput("unknown dispute state in snapshot")),
+    }
+}
+
+/// The JSON backend document.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct JsonState {
+    /// All accounts, sorted by client.
+    accounts: Vec<JsonAccount>,
+    /// All transaction records, sorted by tx id.
+    txs: Vec<JsonTx>,
+    /// Tx ids seen without a live record, sorted.
+    retired: Vec<TxId>,
+    /// Operator annotations, oldest first; absent in documents written before
+    /// annotations existed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    notes: Vec<JsonNote>,
+    /// The state hash at write time, checked on load.
+    digest: String,
+}
+
+/// An account in the JSON backend. Amounts are raw fixed-point units.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct JsonAccount {
+    client: ClientId,
+    available: i64,
+    held: i64,
+    locked: bool,
+    /// The account version; absent in documents written before versions existed.
+    #[serde(default)]
+    version: u64,
+}
+
+/// A transaction record in the JSON backend. Amounts are raw fixed-point units.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct JsonTx {
+    tx: TxId,
+    client: ClientId,
+    kind: u8,
+    amount: i64,
+    state: u8,
+}
+
+/// An operator annotation in the JSON backend, on either `client`'s account or
+/// transaction `tx`.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct JsonNote {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    client: Option<ClientId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tx: Option<TxId>,
+    author: String,
+    at: u64,
+    note: String,
+}
+
+impl JsonState {
+    /// Builds the document from an engine.
+    fn from_engine(engine: &Engine) -> Self {
+        let mut accounts: Vec<JsonAccount> = engine
+            .accounts_iter()
+            .map(|(c, a): (&ClientId, &Account)| JsonAccount {
+                client: *c,
+                available: a.available.0,
+                held: a.held.0,
+                locked: a.locked,
+                version: a.version,
+            })
+            .collect();
+        accounts.sort_by_key(|a| a.client);
+        let mut txs: Vec<JsonTx> = engine
+            .txs
+            .iter()
+            .map(|(tx, r)| JsonTx {
+                tx,
+                client: r.client,
+                kind: kind_code(r.kind),
+                amount: r.amount.0,
+                state: state_code(r.state),
+            })
+            .collect();
+        txs.sort_by_key(|t| t.tx);
+        let mut retired: Vec<TxId> = engine.txs.retired_iter().collect();
+        retired.sort_unstable();
+        let notes = engine
+            .annotations()
+            .iter()
+            .map(|n| {
+                let (client, tx) = match n.target {
+                    NoteTarget::Account(client) => (Some(client), None),
+                    NoteTarget::Tx(tx) => (None, Some(tx)),
+                };
+                JsonNote {
+                    client,
+                    tx,
+                    author: n.author.clone(),
+                    at: n.at,
+                    note: n.text.clone(),
+                }
+            })
+            .collect();
+        Self {
+            accounts,
+            txs,
+            retired,
+            notes,
+            digest: format!("{:016x}", state_digest(engine)),
+        }
+    }
+
+    /// Rebuilds an engine from the document and checks its digest.
+    fn into_engine(self) -> AppResult<Engine> {
+        let mut engine = Engine::default();
+        for a in self.accounts {
+            let acc = engine.acct_mut(a.client);
+            acc.available = Amount(a.available);
+            acc.held = Amount(a.held);
+            acc.locked = a.locked;
+            acc.version = a.version;
+        }
+        for t in self.txs {
+            engine.txs.insert(
+                t.tx,
+                TxRecord {
+                    client: t.client,
+                    kind: kind_from_code(t.kind)?,
+                    amount: Amount(t.amount),
+                    state: state_from_code(t.state)?,
+                },
+            );
+        }
+        for tx in self.retired {
+            engine.txs.mark_seen(tx);
+        }
+        for n in self.notes {
+            let target = match (n.client, n.tx) {
+                (Some(client), None) => NoteTarget::Account(client),
+                (None, Some(tx)) => NoteTarget::Tx(tx),
+                _ => {
+                    return Err(AppErrors::InvalidInput(
+                        "a json note needs exactly one of client and tx",
+                    ));
+                }
+            };
+            engine.annotate(Annotation {
+                target,
+                author: n.author,
+                at: n.at,
+                text: n.note,
+            })?;
+        }
+        if format!("{:016x}", state_digest(&engine)) != self.digest {
+            return Err(AppErrors::InvalidInput("json state digest mismatch"));
+        }
+        Ok(engine)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_engine() -> Engine {
+        let mut engine = Engine::default();
+        let acc = engine.acct_mut(1);
+        acc.available = Amount(5_000);
+        acc.held = Amount(2_000);
+        acc.version = 4;
+        engine.acct_mut(2).locked = true;
+        engine.txs.insert(
+            10,
+            TxRecord {
+                client: 1,
+                kind: TxKind::Deposit,
+                amount: Amount(2_000),
+                state: DisputeState::Disputed,
+            },
+        );
+        engine.txs.mark_seen(3);
+        engine
+    }
+
+    #[test]
+    fn snapshot_roundtrip_preserves_digest() {
+        let engine = sample_engine();
+        let decoded = decode_snapshot(&encode_snapshot(&engine)).unwrap();
+
+        assert_eq!(state_digest(&decoded), state_digest(&engine));
+        assert!(decoded.txs.contains_key(&3));
+        assert_eq!(decoded.acct(1).unwrap().held, Amount(2_000));
+        assert_eq!(decoded.version(1), 4);
+    }
+
+    #[test]
+    fn fingerprints_only_the_sorted_balances() {
+        let mut engine = sample_engine();
+        // SHA-256 of "1,0.5000,0.2000,false\n2,0.0000,0.0000,true\n".
+        let expected = "b3e33148c7a8b54920faded4b46ef5121dd28cdc78607f3c2bf10e8412de6442";
+        assert_eq!(account_fingerprint(&engine), expected);
+
+        engine.txs.remove(&10);
+        assert_eq!(account_fingerprint(&engine), expected);
+        engine.acct_mut(2).available = Amount(1);
+        assert_ne!(account_fingerprint(&engine), expected);
+    }
+
+    #[test]
+    fn empty_engine_fingerprints_the_empty_input() {
+        // SHA-256 of "".
+        assert_eq!(
+            account_fingerprint(&Engine::default()),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn snapshot_rejects_tampering() {
+        let mut bytes = encode_snapshot(&sample_engine());
+        let mid = bytes.len() / 2;
+        bytes[mid] ^= 0xff;
+
+        assert!(decode_snapshot(&bytes).is_err());
+    }
+
+    #[test]
+    fn json_roundtrip_preserves_digest() {
+        let engine = sample_engine();
+        let doc = JsonState::from_engine(&engine);
+        let text = serde_json::to_string(&doc).unwrap();
+        let decoded = serde_json::from_str::<JsonState>(&text)
+            .unwrap()
+            .into_engine()
+            .unwrap();
+
+        assert_eq!(state_digest(&decoded), state_digest(&engine));
+    }
+
+    #[test]
+    fn annotations_roundtrip_outside_the_digest() {
+        let mut engine = sample_engine();
+        let digest = state_digest(&engine);
+        for (target, text) in [
+            (NoteTarget::Account(1), "KYC refresh requested"),
+            (NoteTarget::Tx(10), "chargeback expected, ticket #77"),
+            (NoteTarget::Tx(3), "retired; duplicate of 10"),
+        ] {
+            engine
+                .annotate(Annotation {
+                    target,
+                    author: "ops".into(),
+                    at: 1_700_000_000,
+                    text: text.into(),
+                })
+                .unwrap();
+        }
+        assert_eq!(state_digest(&engine), digest);
+        assert!(
+            engine
+                .annotate(Annotation {
+                    target: NoteTarget::Tx(99),
+                    author: "ops".into(),
+                    at: 0,
+                    text: "unknown".into(),
+                })
+                .is_err()
+        );
+
+        let snapshot = decode_snapshot(&encode_snapshot(&engine)).unwrap();
+        let text = serde_json::to_string(&JsonState::from_engine(&engine)).unwrap();
+        let json = serde_json::from_str::<JsonState>(&text)
+            .unwrap()
+            .into_engine()
+            .unwrap();
+        for decoded in [snapshot, json] {
+            assert_eq!(decoded.annotations(), engine.annotations());
+            assert_eq!(decoded.client_annotations(1).count(), 2);
+        }
+        let paged = StoreSpec::Paged("unused.pages".into());
+        assert!(paged.save(&engine).is_err());
+    }
+
+    #[test]
+    fn store_spec_parses_known_backends() {
+        assert_eq!(
+            "snapshot:a.bin".parse::<StoreSpec>().unwrap(),
+            StoreSpec::Snapshot("a.bin".into())
+        );
+        assert_eq!(
+            "json:/tmp/a.json".parse::<StoreSpec>().unwrap(),
+            StoreSpec::Json("/tmp/a.json".into())
+        );
+        assert_eq!(
+            "paged:a.pages".parse::<StoreSpec>().unwrap(),
+            StoreSpec::Paged("a.pages".into())
+        );
+        assert!("rocksdb:/data".parse::<StoreSpec>().is_err());
+        assert!("state.bin".parse::<StoreSpec>().is_err());
+    }
+
+    #[test]
+    fn migrate_copies_between_backends() {
+        let dir = std::env::temp_dir();
+        let pid = std::process::id();
+        let from = StoreSpec::Snapshot(dir.join(format!("pe-{pid}-a.bin")).display().to_string());
+        let to = StoreSpec::Json(dir.join(format!("pe-{pid}-b.json")).display().to_string());
+        let engine = sample_engine();
+        from.save(&engine).unwrap();
+
+        let report = migrate(&from, &to).unwrap();
+
+        assert_eq!(report.accounts, 2);
+        assert_eq!(report.txs, 1);
+        assert_eq!(report.digest, state_digest(&engine));
+        for spec in [from, to] {
+            let (StoreSpec::Snapshot(p) | StoreSpec::Json(p) | StoreSpec::Paged(p)) = spec;
+            let _ = fs::remove_file(p);
+        }
+    }
+}
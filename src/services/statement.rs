@@ -0,0 +1,262 @@
+use crate::errors::{AppErrors, AppResult};
+use crate::models::amount::Amount;
+use crate::models::domain_state::{DisputeState, NoteTarget, TxKind};
+use crate::models::identifiers::{ClientId, TxId};
+use crate::services::account_writer::table_report;
+use crate::services::backup::utc_datetime;
+use crate::services::locale::Locale;
+use crate::state::Engine;
+use std::io::Write;
+
+/// The column names of a statement.
+const STATEMENT_HEADER: [&str; 5] = ["tx", "amount", "available", "held", "entry"];
+
+/// One movement of a statement: how an entry changed the available and held
+/// funds.
+struct Movement {
+    /// The tx the entry belongs to.
+    tx: TxId,
+    /// What happened, e.g. `deposit` or `chargeback`.
+    entry: &'static str,
+    /// The tx amount.
+    amount: Amount,
+    /// The change to the available funds.
+    available: Amount,
+    /// The change to the held funds.
+    held: Amount,
+}
+
+/// Writes a client's statement as a text table: every entry of its tx history
+/// with the running available and held balance after it, between an opening
+/// and a closing balance line, followed by the operator notes on the account
+/// and its transactions.
+///
+/// The history is replayed from the tx log, by tx id: a deposit credits the
+/// available funds and a withdrawal debits them. A deposit still disputed is
+/// followed by a `dispute` entry moving its amount to held; a charged-back one
+/// by a `dispute` and a `chargeback` entry removing it. The engine keeps each
+/// deposit's final dispute state rather than the time of the dispute, and
+/// resolved disputes leave no trace, so dispute entries follow their deposit.
+/// The opening balance is the closing balance less every movement, so balances
+/// carried in from opening balances or compacted history are part of it.
+///
+/// # Arguments
+/// * `out` - The statement sink.
+/// * `engine` - The state to report on.
+/// * `client` - The client whose statement to write.
+/// * `locale` - The number formatting of the amounts.
+///
+/// # Returns
+/// * `AppResult<usize>` - The number of entries written, or
+///   `AppErrors::InvalidInput` if the client has no account, or
+///   `AppErrors::Overflow` if a balance does not fit an amount, or an
+///   `AppErrors::Io` on write failure.
+pub fn write_statement(
+    mut out: impl Write,
+    engine: &Engine,
+    client: ClientId,
+    locale: &Locale,
+) -> AppResult<usize> {
+    let acc = engine
+        .acct(client)
+        .ok_or(AppErrors::InvalidInput("no such client account"))?;
+    let movements = movements(engine, client);
+
+    let (mut available, mut held) = (acc.available, acc.held);
+    for m in &movements {
+        available = available
+            .checked_sub(m.available)
+            .ok_or(AppErrors::Overflow)?;
+        held = held.checked_sub(m.held).ok_or(AppErrors::Overflow)?;
+    }
+    let fmt = |amount: Amount| locale.format_amount(amount);
+    let mut rows = vec![balance_row(&fmt, available, held, "opening balance")];
+    for m in &movements {
+        available = available
+            .checked_add(m.available)
+            .ok_or(AppErrors::Overflow)?;
+        held = held.checked_add(m.held).ok_or(AppErrors::Overflow)?;
+        rows.push([
+            m.tx.to_string(),
+            fmt(m.amount),
+            fmt(available),
+            fmt(held),
+            m.entry.to_string(),
+        ]);
+    }
+    let closing = if acc.locked {
+        "closing balance, locked"
+    } else {
+        "closing balance"
+    };
+    rows.push(balance_row(&fmt, available, held, closing));
+
+    let mut text = format!("Statement of client {client}\n\n");
+    text.push_str(&table_report(STATEMENT_HEADER, &rows));
+    let notes: Vec<_> = engine.client_annotations(client).collect();
+    if !notes.is_empty() {
+        text.push_str("\nNotes:\n");
+    }
+    for note in notes {
+        let about = match note.target {
+            NoteTarget::Account(_) => "account".to_string(),
+            NoteTarget::Tx(tx) => format!("tx {tx}"),
+        };
+        text.push_str(&format!(
+            "{} {} on {about}: {}\n",
+            utc_datetime(note.at),
+            note.author,
+            note.text
+        ));
+    }
+    out.write_all(text.as_bytes())
+        .and_then(|_| out.flush())
+        .map_err(|e| AppErrors::Io(format!("write statement: {e}")))?;
+    Ok(movements.len())
+}
+
+/// Replays a client's tx log, by tx id, as statement movements.
+fn movements(engine: &Engine, client: ClientId) -> Vec<Movement> {
+    let txs = engine.tx_history().remove(&client).unwrap_or_default();
+    let mut movements = Vec::with_capacity(txs.len());
+    for (tx, rec) in txs {
+        let (entry, available) = match rec.kind {
+            TxKind::Deposit => ("deposit", rec.amount),
+            TxKind::Withdrawal => ("withdrawal", Amount(-rec.amount.0)),
+        };
+        let movement = |entry, available: i64, held: i64| Movement {
+            tx,
+            entry,
+            amount: rec.amount,
+            available: Amount(available),
+            held: Amount(held),
+        };
+        movements.push(movement(entry, available.0, 0));
+        if rec.kind == TxKind::Deposit
+            && matches!(
+                rec.state,
+                DisputeState::Disputed | DisputeState::ChargedBack
+            )
+        {
+            movements.push(movement("dispute", -rec.amount.0, rec.amount.0));
+        }
+        if rec.state == DisputeState::ChargedBack {
+            movements.push(movement("chargeback", 0, -rec.amount.0));
+        }
+    }
+    movements
+}
+
+/// A statement line showing a balance without a movement.
+fn balance_row(
+    fmt: &impl Fn(Amount) -> String,
+    available: Amount,
+    held: Amount,
+    entry: &str,
+) -> [String; 5] {
+    [
+        String::new(),
+        String::new(),
+        fmt(available),
+        fmt(held),
+        entry.to_string(),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::domain_state::{Annotation, TxRecord};
+
+    #[test]
+    fn replays_the_tx_log_with_running_balances() {
+        let mut engine = Engine::default();
+        for (tx, kind, amount, state) in [
+            (1, TxKind::Deposit, 50_000, DisputeState::Normal),
+            (2, TxKind::Deposit, 20_000, DisputeState::Disputed),
+            (3, TxKind::Withdrawal, 10_000, DisputeState::Normal),
+        ] {
+            engine.txs.insert(
+                tx,
+                TxRecord {
+                    client: 7,
+                    kind,
+                    amount: Amount(amount),
+                    state,
+                },
+            );
+        }
+        // 1.0000 carried in from before the log.
+        let acc = engine.acct_mut(7);
+        acc.available = Amount(50_000);
+        acc.held = Amount(20_000);
+        engine
+            .annotate(Annotation {
+                target: NoteTarget::Tx(2),
+                author: "ops".to_string(),
+                at: 0,
+                text: "chasing the bank".to_string(),
+            })
+            .unwrap();
+
+        let mut buf = Vec::new();
+        let entries = write_statement(&mut buf, &engine, 7, &Locale::default()).unwrap();
+
+        assert_eq!(entries, 4);
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "Statement of client 7\n\
+             \n\
+             tx  amount  available    held  entry\n\
+             --  ------  ---------  ------  ---------------\n\
+             \x20              1.0000  0.0000  opening balance\n\
+             \x201  5.0000     6.0000  0.0000  deposit\n\
+             \x202  2.0000     8.0000  0.0000  deposit\n\
+             \x202  2.0000     6.0000  2.0000  dispute\n\
+             \x203  1.0000     5.0000  2.0000  withdrawal\n\
+             \x20              5.0000  2.0000  closing balance\n\
+             \n\
+             Notes:\n\
+             1970-01-01T00:00:00Z ops on tx 2: chasing the bank\n"
+        );
+        assert!(write_statement(Vec::new(), &engine, 8, &Locale::default()).is_err());
+    }
+
+    #[test]
+    fn account_without_history_has_only_balance_lines() {
+        let mut engine = Engine::default();
+        engine.acct_mut(3);
+
+        let mut buf = Vec::new();
+        let entries = write_statement(&mut buf, &engine, 3, &Locale::default()).unwrap();
+
+        assert_eq!(entries, 0);
+        let text = String::from_utf8(buf).unwrap();
+        assert!(text.contains("0.0000  0.0000  opening balance\n"));
+        assert!(text.ends_with("0.0000  0.0000  closing balance\n"));
+        assert!(!text.contains("Notes:"));
+    }
+
+    #[test]
+    fn charged_back_deposit_leaves_the_account_locked() {
+        let mut engine = Engine::default();
+        engine.txs.insert(
+            1,
+            TxRecord {
+                client: 4,
+                kind: TxKind::Deposit,
+                amount: Amount(30_000),
+                state: DisputeState::ChargedBack,
+            },
+        );
+        engine.acct_mut(4).locked = true;
+
+        let mut buf = Vec::new();
+        let entries = write_statement(&mut buf, &engine, 4, &Locale::default()).unwrap();
+
+        assert_eq!(entries, 3);
+        let text = String::from_utf8(buf).unwrap();
+        assert!(text.contains("1  3.0000     0.0000  0.0000  chargeback\n"));
+        assert!(text.ends_with("0.0000  0.0000  closing balance, locked\n"));
+    }
+}
@@ -0,0 +1,177 @@
+use crate::errors::{AppErrors, AppResult};
+use std::io::{self, BufRead, Read};
+
+/// The UTF-8 byte order mark.
+const UTF8_BOM: [u8; 3] = [0xef, 0xbb, 0xbf];
+
+/// The UTF-16 little-endian byte order mark.
+const UTF16LE_BOM: [u8; 2] = [0xff, 0xfe];
+
+/// The UTF-16 big-endian byte order mark.
+const UTF16BE_BOM: [u8; 2] = [0xfe, 0xff];
+
+/// Detects the encoding of text input and wraps it so it reads as plain UTF-8,
+/// since spreadsheet tools on Windows export CSV with byte order marks or as
+/// UTF-16, which would otherwise garble the first header name.
+///
+/// - A UTF-8 byte order mark is stripped.
+/// - UTF-16 with a byte order mark is transcoded in its byte order.
+/// - UTF-16 without one is recognised by an ASCII first character next to a
+///   zero byte (e.g. `t\0` of a little-endian `type` header) and transcoded.
+///
+/// Other input is passed through unchanged.
+///
+/// # Arguments
+/// * `path` - The input path, for error messages.
+/// * `input` - The buffered input to sniff.
+///
+/// # Returns
+/// * `AppResult<Box<dyn Read>>` - The UTF-8 reader, or an `AppErrors::Io` if the
+///   input cannot be read.
+pub(crate) fn decode_text<R: BufRead + 'static>(
+    path: &str,
+    mut input: R,
+) -> AppResult<Box<dyn Read>> {
+    let head = input
+        .fill_buf()
+        .map_err(|e| AppErrors::Io(format!("read {path}: {e}")))?;
+    let (bom, big_endian) = match head {
+        [a, b, c, ..] if [*a, *b, *c] == UTF8_BOM => {
+            input.consume(UTF8_BOM.len());
+            return Ok(Box::new(input));
+        }
+        [a, b, ..] if [*a, *b] == UTF16LE_BOM => (UTF16LE_BOM.len(), false),
+        [a, b, ..] if [*a, *b] == UTF16BE_BOM => (UTF16BE_BOM.len(), true),
+        [a, 0, ..] if *a != 0 && a.is_ascii() => (0, false),
+        [0, b, ..] if *b != 0 && b.is_ascii() => (0, true),
+        _ => return Ok(Box::new(input)),
+    };
+    input.consume(bom);
+    Ok(Box::new(Utf16Decoder {
+        input,
+        big_endian,
+        raw: Vec::new(),
+        decoded: Vec::new(),
+        pos: 0,
+    }))
+}
+
+/// Transcodes UTF-16 input to UTF-8 as it is read.
+struct Utf16Decoder<R> {
+    /// The UTF-16 source.
+    input: R,
+    /// Whether code units are big-endian.
+    big_endian: bool,
+    /// Source bytes not yet decoded: an odd trailing byte, or a high surrogate
+    /// waiting for its pair.
+    raw: Vec<u8>,
+    /// Decoded UTF-8 bytes.
+    decoded: Vec<u8>,
+    /// How many of the decoded bytes have been returned.
+    pos: usize,
+}
+
+impl<R: BufRead> Utf16Decoder<R> {
+    /// Decodes the next chunk of the source into `decoded`.
+    ///
+    /// # Returns
+    /// * `io::Result<bool>` - `false` at the end of the source, or an
+    ///   `InvalidData` error if it is not valid UTF-16.
+    fn fill(&mut self) -> io::Result<bool> {
+        let chunk = self.input.fill_buf()?;
+        if chunk.is_empty() {
+            return if self.raw.is_empty() {
+                Ok(false)
+            } else {
+                Err(invalid_utf16())
+            };
+        }
+        let len = chunk.len();
+        self.raw.extend_from_slice(chunk);
+        self.input.consume(len);
+
+        let mut units: Vec<u16> = self
+            .raw
+            .chunks_exact(2)
+            .map(|pair| {
+                let pair = [pair[0], pair[1]];
+                if self.big_endian {
+                    u16::from_be_bytes(pair)
+                } else {
+                    u16::from_le_bytes(pair)
+                }
+            })
+            .collect();
+        let mut keep = self.raw.len() % 2;
+        if matches!(units.last(), Some(0xd800..=0xdbff)) {
+            units.pop();
+            keep += 2;
+        }
+        self.raw.drain(..self.raw.len() - keep);
+
+        self.decoded.clear();
+        self.pos = 0;
+        for c in char::decode_utf16(units) {
+            let c = c.map_err(|_| invalid_utf16())?;
+            self.decoded
+                .extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+        }
+        Ok(true)
+    }
+}
+
+impl<R: BufRead> Read for Utf16Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.decoded.len() {
+            if !self.fill()? {
+                return Ok(0);
+            }
+        }
+        let n = buf.len().min(self.decoded.len() - self.pos);
+        buf[..n].copy_from_slice(&self.decoded[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// The error of input that is not valid UTF-16.
+fn invalid_utf16() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "invalid UTF-16 input")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufReader, Cursor};
+
+    fn decode(bytes: Vec<u8>) -> io::Result<String> {
+        // A tiny buffer splits code units and surrogate pairs across chunks.
+        let input = BufReader::with_capacity(3, Cursor::new(bytes));
+        let mut text = String::new();
+        decode_text("test", input)
+            .unwrap()
+            .read_to_string(&mut text)?;
+        Ok(text)
+    }
+
+    #[test]
+    fn decodes_boms_and_utf16() {
+        let text = "type,client,tx,amount\ndeposit,1,1,2.5 💶\n";
+        let le: Vec<u8> = text.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let be: Vec<u8> = text.encode_utf16().flat_map(u16::to_be_bytes).collect();
+
+        for bytes in [
+            [&UTF8_BOM[..], text.as_bytes()].concat(),
+            [&UTF16LE_BOM[..], &le].concat(),
+            [&UTF16BE_BOM[..], &be].concat(),
+            le.clone(),
+            be,
+            text.as_bytes().to_vec(),
+        ] {
+            assert_eq!(decode(bytes).unwrap(), text);
+        }
+        // A lone high surrogate at the end is rejected.
+        let truncated = [&UTF16LE_BOM[..], &le[..le.len() - 4]].concat();
+        assert!(decode(truncated).is_err());
+    }
+}
@@ -0,0 +1,125 @@
+use crate::errors::AppResult;
+use crate::models::csv_models::transaction::CsvDialect;
+use crate::models::policy::RowError;
+use crate::services::csv_service::{csv_reader, open_text_input, read_rows, row_to_transaction};
+use crate::services::currency::Currency;
+use std::io::Read;
+
+/// What validating an input found, for the `validate` subcommand.
+#[derive(Debug, Default)]
+pub struct ValidationReport {
+    /// Every data row read, including malformed ones.
+    pub rows: u64,
+    /// The rows that would not reach the engine, in input order.
+    pub problems: Vec<RowError>,
+}
+
+/// Validates a CSV file without applying it (see [`validate_reader`]).
+///
+/// # Arguments
+/// * `path` - The CSV file, or [`STDIN_PATH`](crate::consts::STDIN_PATH) (`-`).
+/// * `dialect` - The input layout.
+/// * `currency` - The currency amounts are denominated in, if set.
+///
+/// # Returns
+/// * `AppResult<ValidationReport>` - The rows read and the problems found, or
+///   an `AppErrors` variant if the file cannot be read.
+pub fn validate_csv_path(
+    path: &str,
+    dialect: &CsvDialect,
+    currency: Option<&Currency>,
+) -> AppResult<ValidationReport> {
+    validate_reader(open_text_input(path)?, dialect, currency)
+}
+
+/// Parses and type-checks every row of a CSV source the way a run would
+/// before applying it, without touching any engine state: the record must
+/// decode (a known tx type, client and tx ids in range), deposits and
+/// withdrawals must carry a well-formed amount, and with a currency set the
+/// amount must not be finer than its minor units.
+///
+/// # Arguments
+/// * `input` - The CSV data, including the header row unless the dialect has
+///   none.
+/// * `dialect` - The input layout.
+/// * `currency` - The currency amounts are denominated in, if set.
+///
+/// # Returns
+/// * `AppResult<ValidationReport>` - The rows read and the problems found, or
+///   an `AppErrors::Io` if the header cannot be read.
+pub fn validate_reader<R: Read>(
+    input: R,
+    dialect: &CsvDialect,
+    currency: Option<&Currency>,
+) -> AppResult<ValidationReport> {
+    let mut rdr = csv_reader(input, dialect);
+    let mut report = ValidationReport::default();
+    for (line, rec) in read_rows(&mut rdr, dialect)? {
+        report.rows += 1;
+        let row = match rec {
+            Ok(row) => row,
+            Err(error) => {
+                report.problems.push(RowError {
+                    line,
+                    client: None,
+                    tx: None,
+                    error,
+                });
+                continue;
+            }
+        };
+        let checked = match currency {
+            Some(currency) => currency.check(&row),
+            None => Ok(()),
+        };
+        if let Err(error) = checked.and_then(|_| row_to_transaction(&row).map(|_| ())) {
+            report.problems.push(RowError {
+                line,
+                client: Some(row.client),
+                tx: Some(row.tx),
+                error,
+            });
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_every_row_a_run_would_reject() {
+        let input = "type,client,tx,amount\n\
+                     deposit,1,1,5.0\n\
+                     refund,1,2,1.0\n\
+                     deposit,70000,3,1.0\n\
+                     withdrawal,1,4,\n\
+                     deposit,1,5,abc\n\
+                     dispute,1,1,\n\
+                     deposit,1,6,0.001\n";
+        let jpy = Currency {
+            code: "JPY".to_string(),
+            minor_units: 0,
+        };
+
+        let report = validate_reader(input.as_bytes(), &CsvDialect::default(), Some(&jpy)).unwrap();
+
+        assert_eq!(report.rows, 7);
+        let codes: Vec<_> = report
+            .problems
+            .iter()
+            .map(|p| (p.line, p.error.code()))
+            .collect();
+        assert_eq!(
+            codes,
+            [
+                (3, "E1003"),
+                (4, "E1003"),
+                (5, "E1002"),
+                (6, "E1001"),
+                (8, "E1005"),
+            ]
+        );
+    }
+}
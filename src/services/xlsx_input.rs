@@ -0,0 +1,151 @@
+use crate::errors::{AppErrors, AppResult};
+use crate::models::csv_models::transaction::{CsvTxType, InputRow};
+use crate::services::csv_service::open_input;
+use calamine::{Data, Range, Reader, Xlsx};
+use std::io::{Cursor, Read};
+
+/// The columns of the transaction row, in [`InputRow`] order.
+const COLUMNS: [&str; 4] = ["type", "client", "tx", "amount"];
+
+/// Reads transaction rows from the first sheet of an `.xlsx` workbook.
+///
+/// The first non-empty row is the header; columns are found by name
+/// (`type`, `client`, `tx` and optionally `amount`, case-insensitive), so their
+/// order does not matter and extra columns are ignored. Ids may be text or
+/// whole numbers; amounts may be text or numbers. Blank rows are skipped.
+///
+/// Rows are numbered by their spreadsheet row, like CSV line numbers. A row
+/// whose cells do not fit the engine's types is returned as a row error.
+///
+/// # Arguments
+/// * `path` - The workbook path, or [`STDIN_PATH`](crate::consts::STDIN_PATH)
+///   (`-`) to read stdin. The workbook is read into memory.
+///
+/// # Returns
+/// * `AppResult<Vec<(u64, AppResult<InputRow>)>>` - The rows, or an
+///   `AppErrors::Decode` if the workbook cannot be read or lacks a column.
+pub fn read_xlsx(path: &str) -> AppResult<Vec<(u64, AppResult<InputRow>)>> {
+    let decode_err = |e: &dyn std::fmt::Display| AppErrors::Decode(format!("{path}: {e}"));
+    let mut bytes = Vec::new();
+    open_input(path)?
+        .read_to_end(&mut bytes)
+        .map_err(|e| AppErrors::Io(format!("read {path}: {e}")))?;
+    let mut workbook = Xlsx::new(Cursor::new(bytes)).map_err(|e| decode_err(&e))?;
+    let sheet = workbook
+        .worksheet_range_at(0)
+        .ok_or_else(|| decode_err(&"workbook has no sheets"))?
+        .map_err(|e| decode_err(&e))?;
+    sheet_rows(&sheet).map_err(|e| match e {
+        AppErrors::Decode(msg) => decode_err(&msg),
+        e => e,
+    })
+}
+
+/// Converts a sheet into transaction rows (see [`read_xlsx`]).
+fn sheet_rows(sheet: &Range<Data>) -> AppResult<Vec<(u64, AppResult<InputRow>)>> {
+    let first_row = u64::from(sheet.start().map_or(0, |(row, _)| row)) + 1;
+    let mut rows = sheet
+        .rows()
+        .zip(first_row..)
+        .filter(|(cells, _)| cells.iter().any(|c| *c != Data::Empty));
+
+    let Some((header, _)) = rows.next() else {
+        return Ok(Vec::new());
+    };
+    let find = |name: &str| {
+        header
+            .iter()
+            .position(|c| c.to_string().trim().eq_ignore_ascii_case(name))
+    };
+    let mut index = [None; COLUMNS.len()];
+    for (i, name) in COLUMNS.iter().enumerate() {
+        index[i] = find(name);
+        if index[i].is_none() && *name != "amount" {
+            return Err(AppErrors::Decode(format!("missing `{name}` column")));
+        }
+    }
+
+    Ok(rows
+        .map(|(cells, line)| {
+            let cell = |i: usize| {
+                index[i]
+                    .and_then(|col| cells.get(col))
+                    .unwrap_or(&Data::Empty)
+            };
+            (line, to_input_row(cell(0), cell(1), cell(2), cell(3)))
+        })
+        .collect())
+}
+
+/// Converts the four cells of a row into a transaction row.
+fn to_input_row(t: &Data, client: &Data, tx: &Data, amount: &Data) -> AppResult<InputRow> {
+    Ok(InputRow {
+        t: t.to_string().trim().parse::<CsvTxType>()?,
+        client: whole_number(client)
+            .and_then(|n| n.try_into().ok())
+            .ok_or(AppErrors::InvalidInput("client id missing or out of range"))?,
+        tx: whole_number(tx)
+            .and_then(|n| n.try_into().ok())
+            .ok_or(AppErrors::InvalidInput("tx id missing or out of range"))?,
+        amount: match amount {
+            Data::Empty => None,
+            // `f64`'s shortest round-trip formatting keeps `2.5` as `2.5`; amounts
+            // beyond four decimals are then rejected by the amount parser.
+            other => Some(other.to_string().trim().to_string()).filter(|s| !s.is_empty()),
+        },
+    })
+}
+
+/// Reads a cell holding a whole number, as a number or as text.
+fn whole_number(cell: &Data) -> Option<i64> {
+    match cell {
+        Data::Int(n) => Some(*n),
+        Data::Float(f) if f.fract() == 0.0 && f.abs() < 2f64.powi(53) => Some(*f as i64),
+        Data::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_xlsxwriter::Workbook;
+
+    #[test]
+    fn reads_the_first_sheet_by_header_name() {
+        let mut workbook = Workbook::new();
+        let sheet = workbook.add_worksheet();
+        for (col, name) in ["Client", "Type", "TX", "Amount", "Note"]
+            .iter()
+            .enumerate()
+        {
+            sheet.write_string(0, col as u16, *name).unwrap();
+        }
+        sheet.write_number(1, 0, 1).unwrap();
+        sheet.write_string(1, 1, "deposit").unwrap();
+        sheet.write_number(1, 2, 7).unwrap();
+        sheet.write_number(1, 3, 2.5).unwrap();
+        sheet.write_number(3, 0, 1).unwrap();
+        sheet.write_string(3, 1, "dispute").unwrap();
+        sheet.write_string(3, 2, "7").unwrap();
+        sheet.write_number(4, 0, 1.5).unwrap();
+        sheet.write_string(4, 1, "deposit").unwrap();
+        let path = std::env::temp_dir().join(format!("pe-{}-in.xlsx", std::process::id()));
+        workbook.save(&path).unwrap();
+
+        let rows = read_xlsx(&path.display().to_string());
+        std::fs::remove_file(&path).unwrap();
+        let rows = rows.unwrap();
+
+        assert_eq!(rows.len(), 3);
+        let (line, deposit) = &rows[0];
+        let deposit = deposit.as_ref().unwrap();
+        assert_eq!(*line, 2);
+        assert_eq!((deposit.client, deposit.tx), (1, 7));
+        assert_eq!(deposit.amount.as_deref(), Some("2.5"));
+        let (line, dispute) = &rows[1];
+        assert_eq!(*line, 4);
+        assert_eq!(dispute.as_ref().unwrap().t, CsvTxType::Dispute);
+        assert!(rows[2].1.is_err());
+    }
+}
@@ -1,12 +1,56 @@
-use crate::models::domain_state::{Account, TxRecord};
+use crate::errors::{AppErrors, AppResult};
+use crate::models::amount::Amount;
+use crate::models::csv_models::transaction::CsvDialect;
+use crate::models::domain_state::{
+    Account, Annotation, ClientActivity, DisputeState, FundFlows, NoteTarget, TxKind, TxRecord,
+};
 use crate::models::identifiers::{ClientId, TxId};
-use std::collections::HashMap;
+use crate::models::policy::{
+    EngineConfig, ErrorPolicy, LockedDepositPolicy, RedisputePolicy, UnfundedDisputePolicy,
+};
+use crate::models::tx_command::{DisputeCommand, ResolveCommand, Transaction};
+use crate::models::tx_store::TxStore;
+use crate::services::commands::chargeback_command::process_chargeback_command;
+use crate::services::commands::deposit_command::process_deposit_command;
+use crate::services::commands::dispute_command::process_dispute_command;
+use crate::services::commands::resolve_command::process_resolve_command;
+use crate::services::commands::withdrawal_command::process_withdrawal_command;
+use crate::services::csv_service::Pass;
+use crate::services::currency::Currency;
+use crate::services::exposure::ExposureGuard;
+use crate::services::latency::LatencyRecorder;
+use crate::services::paged_store::{Page, Pager};
+use crate::services::rejects::RejectLog;
+use crate::services::run_summary::SummaryRecorder;
+use crate::services::shard_map::ShardRouter;
+use std::collections::{HashMap, VecDeque};
+use std::mem::size_of;
 /// Represents the application state, which contains the engine responsible
 /// for managing accounts and transactions.
 #[derive(Clone, Default)]
 pub struct AppState {
     /// The engine that handles accounts and transaction records.
     pub engine: Engine,
+    /// The currency input amounts are denominated in, if set; amounts finer
+    /// than its minor units are rejected before reaching the engine.
+    pub currency: Option<Currency>,
+    /// The book-wide exposure caps, if set; commands that would breach them are
+    /// quarantined instead of applied.
+    pub exposure: Option<ExposureGuard>,
+    /// The layout of CSV input.
+    pub dialect: CsvDialect,
+    /// The per-command latency recorder, if latency is being measured.
+    pub latency: Option<LatencyRecorder>,
+    /// The shard router, if this instance processes one share of a split
+    /// dataset; rows of clients other instances own are skipped.
+    pub shard: Option<ShardRouter>,
+    /// The pass of two-pass processing under way, if any; rows of other types
+    /// are skipped.
+    pub pass: Option<Pass>,
+    /// The run summary recorder, if an end-of-run summary was requested.
+    pub summary: Option<SummaryRecorder>,
+    /// The log of rows skipped or ignored, if a rejects file was requested.
+    pub rejects: Option<RejectLog>,
 }
 
 /// Represents the core engine of the application, responsible for managing
@@ -16,11 +60,768 @@ pub struct Engine {
     /// A mapping of client IDs to their respective accounts.
     accounts: HashMap<ClientId, Account>,
 
-    /// A mapping of transaction IDs to their respective transaction records.
-    pub txs: HashMap<TxId, TxRecord>,
+    /// A mapping of transaction IDs to their respective transaction records,
+    /// kept in a hybrid dense/overflow store.
+    pub txs: TxStore,
+
+    /// The pages of a lazily loaded state not loaded yet, if any (see
+    /// [`Engine::page_in`]).
+    pager: Option<Pager>,
+
+    /// The error handling and command policies (see [`Engine::config`]).
+    config: EngineConfig,
+
+    /// Operator annotations, in the order they were added.
+    notes: Vec<Annotation>,
+
+    /// The disputes waiting for funds under [`UnfundedDisputePolicy::Queue`],
+    /// by client, in the order they were queued (see
+    /// [`Engine::pending_disputes`]).
+    pending_disputes: HashMap<ClientId, Vec<TxId>>,
+
+    /// The commands waiting for a tx id not seen yet, by that tx id, in
+    /// arrival order (see [`EngineConfig::orphan_buffer`]).
+    orphans: HashMap<TxId, Vec<Transaction>>,
+
+    /// The number of commands in `orphans`.
+    buffered: usize,
+
+    /// The number of commands applied during this run, which dates the
+    /// opening of disputes (see [`EngineConfig::dispute_window`]).
+    commands: u64,
+
+    /// The disputes opened during this run under a dispute window, as the
+    /// command count they opened at and their tx id, oldest first.
+    open_disputes: VecDeque<(u64, TxId)>,
+
+    /// The command count each tracked dispute last opened at, so a stale
+    /// entry of a resolved and re-disputed transaction does not expire the
+    /// newer dispute.
+    dispute_opened: HashMap<TxId, u64>,
+
+    /// The number of disputes resolved automatically at the end of their
+    /// window (see [`Engine::expired_disputes`]).
+    expired: u64,
+
+    /// The disputes the last command opened or closed, with those of failed
+    /// commands before it (see [`Engine::dispute_events`]).
+    dispute_events: Vec<DisputeEvent>,
+
+    /// Whether the last command failed, so its dispute events are kept.
+    failed: bool,
+
+    /// What each client did during this run (see [`Engine::activity`]).
+    activity: HashMap<ClientId, ClientActivity>,
+
+    /// The money that moved into and out of the book during this run (see
+    /// [`Engine::flows`]).
+    flows: FundFlows,
+}
+
+/// Builds an [`Engine`] with a given config (see [`Engine::builder`]).
+///
+/// ```
+/// use payments_engine::prelude::*;
+///
+/// let engine = Engine::builder()
+///     .allow_withdrawal_disputes(true)
+///     .strict_duplicates(true)
+///     .build();
+/// assert!(engine.config().withdrawal_disputes);
+/// ```
+#[derive(Debug, Default, Clone, Copy)]
+pub struct EngineBuilder {
+    /// The config the engine is built with.
+    config: EngineConfig,
+}
+
+impl EngineBuilder {
+    /// Sets how the row pipeline handles row and command errors.
+    pub fn error_policy(mut self, policy: ErrorPolicy) -> Self {
+        self.config.error_policy = policy;
+        self
+    }
+
+    /// Sets what happens to deposits for locked accounts.
+    pub fn locked_deposits(mut self, policy: LockedDepositPolicy) -> Self {
+        self.config.locked_deposits = policy;
+        self
+    }
+
+    /// Sets whether withdrawals can be disputed (see
+    /// [`EngineConfig::withdrawal_disputes`]).
+    pub fn allow_withdrawal_disputes(mut self, allow: bool) -> Self {
+        self.config.withdrawal_disputes = allow;
+        self
+    }
+
+    /// Sets whether reused tx ids fail instead of being ignored (see
+    /// [`EngineConfig::strict_duplicates`]).
+    pub fn strict_duplicates(mut self, strict: bool) -> Self {
+        self.config.strict_duplicates = strict;
+        self
+    }
+
+    /// Sets the most commands held back for tx ids not seen yet (see
+    /// [`EngineConfig::orphan_buffer`]).
+    pub fn orphan_buffer(mut self, cap: usize) -> Self {
+        self.config.orphan_buffer = cap;
+        self
+    }
+
+    /// Sets what happens to disputes the available funds do not cover.
+    pub fn unfunded_disputes(mut self, policy: UnfundedDisputePolicy) -> Self {
+        self.config.unfunded_disputes = policy;
+        self
+    }
+
+    /// Sets whether resolved transactions can be disputed again.
+    pub fn redisputes(mut self, policy: RedisputePolicy) -> Self {
+        self.config.redisputes = policy;
+        self
+    }
+
+    /// Sets the number of commands after which open disputes are resolved
+    /// automatically (see [`EngineConfig::dispute_window`]).
+    pub fn dispute_window(mut self, commands: u64) -> Self {
+        self.config.dispute_window = Some(commands);
+        self
+    }
+
+    /// Builds an empty engine with the config set.
+    pub fn build(self) -> Engine {
+        Engine {
+            config: self.config,
+            ..Engine::default()
+        }
+    }
+}
+
+/// Statistics returned by [`Engine::compact`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CompactionStats {
+    /// The number of transaction records dropped because they can no longer change.
+    pub retired_records: usize,
+    /// The estimated number of heap bytes released.
+    pub freed_bytes: usize,
+}
+
+/// The result of applying a transaction with [`Engine::apply`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The transaction changed the engine's state.
+    Applied,
+    /// The transaction was a no-op, for the given reason.
+    Ignored(IgnoreReason),
+}
+
+/// Why the engine ignored a transaction as a no-op.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IgnoreReason {
+    /// A deposit or withdrawal reused a tx id already recorded.
+    DuplicateTx,
+    /// The client's account is locked.
+    AccountLocked,
+    /// The account's available funds do not cover the withdrawal or dispute.
+    InsufficientFunds,
+    /// The referenced transaction is not recorded (or was compacted away).
+    UnknownTx,
+    /// The referenced transaction belongs to another client.
+    ClientMismatch,
+    /// The referenced transaction is a withdrawal, which cannot be disputed
+    /// unless the engine allows withdrawal disputes.
+    NotDisputable,
+    /// The referenced transaction is already disputed or charged back.
+    AlreadyDisputed,
+    /// The referenced transaction is not under dispute.
+    NotDisputed,
+    /// The referenced transaction's dispute was resolved, and the engine
+    /// forbids re-disputes.
+    AlreadyResolved,
+    /// The available funds do not cover the dispute, which was queued to be
+    /// retried after the client's next deposits.
+    DisputeQueued,
+    /// The referenced transaction has not been seen yet; the command was
+    /// buffered and is applied once it arrives.
+    Buffered,
+}
+
+impl IgnoreReason {
+    /// Returns the snake_case reason code used in reports, e.g. `account_locked`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            IgnoreReason::DuplicateTx => "duplicate_tx",
+            IgnoreReason::AccountLocked => "account_locked",
+            IgnoreReason::InsufficientFunds => "insufficient_funds",
+            IgnoreReason::UnknownTx => "unknown_tx",
+            IgnoreReason::ClientMismatch => "client_mismatch",
+            IgnoreReason::NotDisputable => "not_disputable",
+            IgnoreReason::AlreadyDisputed => "already_disputed",
+            IgnoreReason::NotDisputed => "not_disputed",
+            IgnoreReason::AlreadyResolved => "already_resolved",
+            IgnoreReason::DisputeQueued => "dispute_queued",
+            IgnoreReason::Buffered => "buffered",
+        }
+    }
+}
+
+/// A command the orphan buffer held back, released by the deposit or
+/// withdrawal it waited for (see [`Engine::apply_releasing`]).
+#[derive(Debug)]
+pub struct Released {
+    /// The released command.
+    pub transaction: Transaction,
+    /// Its result: applied or ignored, or the error it failed with.
+    pub result: AppResult<Outcome>,
+}
+
+/// A dispute opened or closed while applying a command (see
+/// [`Engine::dispute_events`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisputeEvent {
+    /// A dispute of the client's transaction was opened.
+    Opened {
+        /// The client owning the disputed transaction.
+        client: ClientId,
+        /// The disputed transaction.
+        tx: TxId,
+    },
+    /// The dispute of the transaction was resolved.
+    Resolved(TxId),
+    /// The dispute of the transaction ended in a chargeback.
+    ChargedBack(TxId),
+    /// The dispute of the transaction was resolved automatically at the end of
+    /// the dispute window.
+    Expired(TxId),
+}
+
+/// The result of evaluating a transaction with [`Engine::probe`].
+#[derive(Debug, Clone)]
+pub struct Probe {
+    /// Whether the transaction would be applied or ignored.
+    pub outcome: Outcome,
+    /// The client's account as it would be after the transaction.
+    pub account: Account,
 }
 
 impl Engine {
+    /// Returns a builder for an engine with a non-default config.
+    pub fn builder() -> EngineBuilder {
+        EngineBuilder::default()
+    }
+
+    /// Applies a transaction to the engine.
+    ///
+    /// This is the typed, statically dispatched entry point for library users;
+    /// it behaves exactly like executing the corresponding command. Commands
+    /// the orphan buffer releases because of it are applied too, but their
+    /// results are only reported by [`Engine::apply_releasing`].
+    ///
+    /// # Arguments
+    /// * `tx` - The transaction to apply.
+    ///
+    /// # Returns
+    /// * `AppResult<Outcome>` - Whether the transaction was applied or ignored,
+    ///   or an `AppErrors` variant if an error occurs (the engine is left unchanged,
+    ///   apart from pages loaded for the transaction).
+    pub fn apply(&mut self, tx: Transaction) -> AppResult<Outcome> {
+        self.apply_releasing(tx, &mut Vec::new())
+    }
+
+    /// Applies a transaction like [`Engine::apply`], gathering the commands
+    /// the orphan buffer held for its tx id into `released`, with their
+    /// results: applied once a deposit or withdrawal records the id, or
+    /// ignored as `unknown_tx` if it was not recorded after all (say, the
+    /// deposit was refused). Released commands count as part of the command
+    /// that released them towards the dispute window.
+    ///
+    /// # Arguments
+    /// * `tx` - The transaction to apply.
+    /// * `released` - Where released commands and their results are gathered.
+    ///
+    /// # Returns
+    /// * `AppResult<Outcome>` - Whether the transaction was applied or ignored,
+    ///   or an `AppErrors` variant if expiring disputes or executing it fails
+    ///   (nothing is released then).
+    pub fn apply_releasing(
+        &mut self,
+        tx: Transaction,
+        released: &mut Vec<Released>,
+    ) -> AppResult<Outcome> {
+        if !self.failed {
+            self.dispute_events.clear();
+        }
+        let result = self.apply_command(tx, released);
+        self.failed = result.is_err();
+        result
+    }
+
+    /// Returns the disputes the last command applied opened or closed, in
+    /// order: those it expired, its own, and those of the commands it
+    /// released. The disputes of a command that fails are kept and returned
+    /// with those of the next one.
+    pub fn dispute_events(&self) -> &[DisputeEvent] {
+        &self.dispute_events
+    }
+
+    /// Applies a transaction for [`Engine::apply_releasing`]: expires the
+    /// disputes past the window, executes it and releases the commands
+    /// waiting for its tx id.
+    fn apply_command(
+        &mut self,
+        tx: Transaction,
+        released: &mut Vec<Released>,
+    ) -> AppResult<Outcome> {
+        self.page_in(&tx)?;
+        let count = self.commands + 1;
+        self.expire_disputes(count)?;
+        // Disputes the command opens are stamped with its count. Failed
+        // commands do not count towards the dispute window, so a replica that
+        // never sees them stays in step.
+        self.commands = count;
+        let outcome = match self.execute(&tx) {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                self.commands -= 1;
+                return Err(e);
+            }
+        };
+        if matches!(tx, Transaction::Deposit(_) | Transaction::Withdrawal(_)) {
+            self.release_orphans(tx.tx(), released)?;
+        }
+        Ok(outcome)
+    }
+
+    /// Executes a transaction's command and tallies it, buffering it if it
+    /// references a tx id never seen and retrying the client's queued
+    /// disputes after a deposit.
+    fn execute(&mut self, tx: &Transaction) -> AppResult<Outcome> {
+        let outcome = match tx {
+            Transaction::Deposit(cmd) => process_deposit_command(self, cmd),
+            Transaction::Withdrawal(cmd) => process_withdrawal_command(self, cmd),
+            Transaction::Dispute(cmd) => process_dispute_command(self, cmd),
+            Transaction::Resolve(cmd) => process_resolve_command(self, cmd),
+            Transaction::Chargeback(cmd) => process_chargeback_command(self, cmd),
+        }?;
+        if outcome == Outcome::Ignored(IgnoreReason::UnknownTx) && self.buffer_orphan(tx) {
+            return Ok(Outcome::Ignored(IgnoreReason::Buffered));
+        }
+        if outcome == Outcome::Applied {
+            let activity = self.activity.entry(tx.client()).or_default();
+            let flows = &mut self.flows;
+            match tx {
+                Transaction::Deposit(cmd) => {
+                    activity.deposited = Amount(activity.deposited.0.saturating_add(cmd.amount.0));
+                    flows.deposited = Amount(flows.deposited.0.saturating_add(cmd.amount.0));
+                }
+                Transaction::Withdrawal(cmd) => {
+                    activity.withdrawn = Amount(activity.withdrawn.0.saturating_add(cmd.amount.0));
+                    flows.withdrawn = Amount(flows.withdrawn.0.saturating_add(cmd.amount.0));
+                }
+                Transaction::Dispute(cmd) => {
+                    activity.disputes += 1;
+                    self.track_dispute(cmd.client, cmd.tx);
+                }
+                Transaction::Resolve(cmd) => {
+                    self.dispute_events.push(DisputeEvent::Resolved(cmd.tx));
+                }
+                Transaction::Chargeback(cmd) => {
+                    activity.chargebacks += 1;
+                    self.dispute_events.push(DisputeEvent::ChargedBack(cmd.tx));
+                    match self.txs.get(&cmd.tx) {
+                        // A charged back withdrawal returns its amount to the book.
+                        Some(rec) if rec.kind == TxKind::Withdrawal => {
+                            flows.withdrawn =
+                                Amount(flows.withdrawn.0.saturating_sub(rec.amount.0));
+                        }
+                        rec => {
+                            let amount = rec.map_or(0, |rec| rec.amount.0);
+                            flows.charged_back =
+                                Amount(flows.charged_back.0.saturating_add(amount));
+                        }
+                    }
+                }
+            }
+        }
+        if outcome == Outcome::Applied && matches!(tx, Transaction::Deposit(_)) {
+            self.retry_pending_disputes(tx.client())?;
+        }
+        Ok(outcome)
+    }
+
+    /// Holds back a dispute, resolve or chargeback whose tx id has never been
+    /// seen, if the orphan buffer has room. Returns `false` if it was not
+    /// buffered: the buffer is disabled or full, or the tx id was seen and its
+    /// record compacted away.
+    fn buffer_orphan(&mut self, tx: &Transaction) -> bool {
+        if self.buffered >= self.config.orphan_buffer || self.txs.contains_key(&tx.tx()) {
+            return false;
+        }
+        self.orphans.entry(tx.tx()).or_default().push(tx.clone());
+        self.buffered += 1;
+        true
+    }
+
+    /// Releases the commands buffered for a tx id once a deposit or withdrawal
+    /// with that id was processed: in arrival order, applied if the id is now
+    /// recorded, or dropped as `unknown_tx` if the deposit or withdrawal was
+    /// ignored or failed. Released commands are not counted again towards the
+    /// dispute window.
+    ///
+    /// # Arguments
+    /// * `tx` - The tx id of the processed deposit or withdrawal.
+    /// * `released` - Where released commands and their results are gathered.
+    ///
+    /// # Returns
+    /// * `AppResult<()>` - `Ok(())` once released, or an `AppErrors` variant if
+    ///   loading a page for a released command fails.
+    fn release_orphans(&mut self, tx: TxId, released: &mut Vec<Released>) -> AppResult<()> {
+        let Some(waiting) = self.orphans.remove(&tx) else {
+            return Ok(());
+        };
+        self.buffered -= waiting.len();
+        let recorded = self.txs.contains_key(&tx);
+        for cmd in waiting {
+            let result = if recorded {
+                self.page_in(&cmd)?;
+                self.execute(&cmd)
+            } else {
+                Ok(Outcome::Ignored(IgnoreReason::UnknownTx))
+            };
+            released.push(Released {
+                transaction: cmd,
+                result,
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns the number of commands still buffered for tx ids not seen yet.
+    /// The buffer is not persisted: commands still buffered at the end of a
+    /// run are dropped.
+    pub fn buffered_count(&self) -> usize {
+        self.buffered
+    }
+
+    /// Retries a client's queued disputes after a deposit, in queue order:
+    /// each one the available funds now cover is applied. Disputes whose
+    /// transaction was disputed or compacted away since, or that the dispute
+    /// command ignores on retry, are dropped without being counted.
+    ///
+    /// # Arguments
+    /// * `client` - The client that deposited.
+    ///
+    /// # Returns
+    /// * `AppResult<()>` - `Ok(())` once retried, or an `AppErrors` variant if
+    ///   applying a dispute fails.
+    fn retry_pending_disputes(&mut self, client: ClientId) -> AppResult<()> {
+        let Some(queued) = self.pending_disputes.remove(&client) else {
+            return Ok(());
+        };
+        let mut waiting = Vec::new();
+        for tx in queued {
+            let Some(rec) = self.txs.get(&tx) else {
+                continue;
+            };
+            if !matches!(rec.state, DisputeState::Normal | DisputeState::Resolved) {
+                continue;
+            }
+            let amount = rec.amount;
+            if self
+                .acct(client)
+                .is_none_or(|acc| acc.available.0 < amount.0)
+            {
+                waiting.push(tx);
+                continue;
+            }
+            let outcome = process_dispute_command(self, &DisputeCommand { client, tx })?;
+            if outcome == Outcome::Applied {
+                self.activity.entry(client).or_default().disputes += 1;
+                self.track_dispute(client, tx);
+            }
+        }
+        if !waiting.is_empty() {
+            self.pending_disputes.insert(client, waiting);
+        }
+        Ok(())
+    }
+
+    /// Records that a dispute opened at the current command, dating it if the
+    /// engine has a dispute window.
+    fn track_dispute(&mut self, client: ClientId, tx: TxId) {
+        self.dispute_events
+            .push(DisputeEvent::Opened { client, tx });
+        if self.config.dispute_window.is_some() {
+            self.open_disputes.push_back((self.commands, tx));
+            self.dispute_opened.insert(tx, self.commands);
+        }
+    }
+
+    /// Resolves the disputes still open more than the dispute window's number
+    /// of commands after they opened, oldest first. Disputes resolved, charged
+    /// back or re-opened since are skipped.
+    ///
+    /// # Arguments
+    /// * `count` - The count of the command about to be applied.
+    ///
+    /// # Returns
+    /// * `AppResult<()>` - `Ok(())` once expired, or an `AppErrors` variant if
+    ///   resolving a dispute fails.
+    fn expire_disputes(&mut self, count: u64) -> AppResult<()> {
+        let Some(window) = self.config.dispute_window else {
+            return Ok(());
+        };
+        while let Some(&(opened, tx)) = self.open_disputes.front()
+            && opened.saturating_add(window) < count
+        {
+            self.open_disputes.pop_front();
+            if self.dispute_opened.get(&tx) != Some(&opened) {
+                continue;
+            }
+            self.dispute_opened.remove(&tx);
+            let Some(rec) = self.txs.get(&tx) else {
+                continue;
+            };
+            if rec.state != DisputeState::Disputed {
+                continue;
+            }
+            let client = rec.client;
+            process_resolve_command(self, &ResolveCommand { client, tx })?;
+            self.dispute_events.push(DisputeEvent::Expired(tx));
+            self.expired += 1;
+        }
+        Ok(())
+    }
+
+    /// Returns the number of disputes resolved automatically during this run
+    /// because they stayed open longer than the dispute window.
+    pub fn expired_disputes(&self) -> u64 {
+        self.expired
+    }
+
+    /// Returns a client's disputes waiting for funds under
+    /// [`UnfundedDisputePolicy::Queue`], in queue order. The queue is not
+    /// persisted: disputes still waiting at the end of a run are dropped.
+    pub fn pending_disputes(&self, client: ClientId) -> &[TxId] {
+        self.pending_disputes
+            .get(&client)
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns the number of disputes waiting for funds, across all clients.
+    pub fn pending_dispute_count(&self) -> usize {
+        self.pending_disputes.values().map(Vec::len).sum()
+    }
+
+    /// Queues a dispute until the client's available funds cover it. Returns
+    /// `false` if it is queued already.
+    pub(crate) fn queue_dispute(&mut self, client: ClientId, tx: TxId) -> bool {
+        let queued = self.pending_disputes.entry(client).or_default();
+        if queued.contains(&tx) {
+            return false;
+        }
+        queued.push(tx);
+        true
+    }
+
+    /// Removes a queued dispute. Returns `false` if it is not queued.
+    pub(crate) fn cancel_queued_dispute(&mut self, client: ClientId, tx: TxId) -> bool {
+        let Some(queued) = self.pending_disputes.get_mut(&client) else {
+            return false;
+        };
+        let before = queued.len();
+        queued.retain(|t| *t != tx);
+        let removed = queued.len() < before;
+        if queued.is_empty() {
+            self.pending_disputes.remove(&client);
+        }
+        removed
+    }
+
+    /// Returns what a client did during this run: the sums of its applied
+    /// deposits and withdrawals and the numbers of its disputes and
+    /// chargebacks. Activity is tallied from the commands this engine applied;
+    /// it is not persisted, so a loaded state starts with none.
+    pub fn activity(&self, client: ClientId) -> ClientActivity {
+        self.activity.get(&client).copied().unwrap_or_default()
+    }
+
+    /// Returns the money that moved into and out of the whole book during this
+    /// run: the sums of applied deposits and withdrawals and of the deposits
+    /// charged back. Like [`Engine::activity`], flows are not persisted, so a
+    /// loaded state starts with none.
+    pub fn flows(&self) -> FundFlows {
+        self.flows
+    }
+
+    /// Returns the engine's config: the error policy the row pipeline applies
+    /// to it and the policies its commands follow.
+    pub fn config(&self) -> EngineConfig {
+        self.config
+    }
+
+    /// Sets the engine's config. The config is not part of the persisted
+    /// state, so it is set anew for every run.
+    pub fn set_config(&mut self, config: EngineConfig) {
+        self.config = config;
+    }
+
+    /// Returns how the row pipeline handles row and command errors.
+    pub fn error_policy(&self) -> ErrorPolicy {
+        self.config.error_policy
+    }
+
+    /// Sets how the row pipeline handles row and command errors (see
+    /// [`Engine::set_config`]).
+    pub fn set_error_policy(&mut self, policy: ErrorPolicy) {
+        self.config.error_policy = policy;
+    }
+
+    /// Returns what happens to deposits for locked accounts.
+    pub fn locked_deposit_policy(&self) -> LockedDepositPolicy {
+        self.config.locked_deposits
+    }
+
+    /// Sets what happens to deposits for locked accounts (see
+    /// [`Engine::set_config`]).
+    pub fn set_locked_deposit_policy(&mut self, policy: LockedDepositPolicy) {
+        self.config.locked_deposits = policy;
+    }
+
+    /// Returns whether withdrawals can be disputed.
+    pub fn withdrawal_disputes(&self) -> bool {
+        self.config.withdrawal_disputes
+    }
+
+    /// Returns whether a reused tx id fails instead of being ignored.
+    pub fn strict_duplicates(&self) -> bool {
+        self.config.strict_duplicates
+    }
+
+    /// Returns what happens to disputes the available funds do not cover.
+    pub fn unfunded_dispute_policy(&self) -> UnfundedDisputePolicy {
+        self.config.unfunded_disputes
+    }
+
+    /// Returns whether resolved transactions can be disputed again.
+    pub fn redispute_policy(&self) -> RedisputePolicy {
+        self.config.redisputes
+    }
+
+    /// The result of a deposit or withdrawal reusing a recorded tx id: ignored
+    /// as a duplicate, or `AppErrors::DuplicateTx` under strict duplicates.
+    pub(crate) fn duplicate_tx(&self, tx: TxId) -> AppResult<Outcome> {
+        if self.config.strict_duplicates {
+            return Err(AppErrors::DuplicateTx(tx));
+        }
+        Ok(Outcome::Ignored(IgnoreReason::DuplicateTx))
+    }
+
+    /// Evaluates what applying a transaction would do, without changing the engine.
+    ///
+    /// The transaction is applied to a scratch engine holding copies of only the
+    /// state it can touch (the client's account, and the referenced tx record and
+    /// its owner's account), so a probe costs the same regardless of engine size.
+    ///
+    /// # Arguments
+    /// * `tx` - The transaction to evaluate.
+    ///
+    /// # Returns
+    /// * `AppResult<Probe>` - Whether the transaction would be applied or ignored and
+    ///   the client's resulting account, or the `AppErrors` variant applying it
+    ///   would fail with.
+    pub fn probe(&self, tx: &Transaction) -> AppResult<Probe> {
+        let client = tx.client();
+        let id = tx.tx();
+        let mut scratch = Engine {
+            config: self.config,
+            ..Engine::default()
+        };
+        if let Some(acc) = self.accounts.get(&client) {
+            scratch.accounts.insert(client, acc.clone());
+        }
+        if let Some(rec) = self.txs.get(&id) {
+            if let Some(owner) = self.accounts.get(&rec.client) {
+                scratch.accounts.insert(rec.client, owner.clone());
+            }
+            scratch.txs.insert(id, rec.clone());
+        } else if self.txs.contains_key(&id) {
+            scratch.txs.mark_seen(id);
+        }
+
+        let outcome = scratch.apply(tx.clone())?;
+        Ok(Probe {
+            outcome,
+            account: scratch.accounts.remove(&client).unwrap_or_default(),
+        })
+    }
+
+    /// Loads the pages a transaction can touch, if the engine was opened lazily
+    /// (see [`open_paged`](crate::services::paged_store::open_paged)): its
+    /// client's, and that of the client owning the referenced tx id. Also
+    /// installs any pages the background prefetch has read.
+    ///
+    /// [`Engine::apply`] pages in on its own; call this before inspecting the
+    /// state a transaction will touch, e.g. with [`Engine::probe`].
+    ///
+    /// # Arguments
+    /// * `tx` - The transaction about to be applied.
+    ///
+    /// # Returns
+    /// * `AppResult<()>` - `Ok(())` once loaded, or an `AppErrors` variant if a
+    ///   page cannot be read or fails its checksum.
+    pub fn page_in(&mut self, tx: &Transaction) -> AppResult<()> {
+        let Some(pager) = self.pager.as_mut() else {
+            return Ok(());
+        };
+        let mut pages = pager.take_prefetched()?;
+        pages.extend(pager.take(tx.client())?);
+        if let Some(owner) = pager.unloaded_owner(tx.tx()) {
+            pages.extend(pager.take(owner)?);
+        }
+        if pager.pending() == 0 {
+            self.pager = None;
+        }
+        self.install(pages);
+        Ok(())
+    }
+
+    /// Loads every page not loaded yet, so the whole state can be read (e.g.,
+    /// to emit, save or audit it). A no-op unless the engine was opened lazily.
+    ///
+    /// # Returns
+    /// * `AppResult<()>` - `Ok(())` once loaded, or an `AppErrors` variant if a
+    ///   page cannot be read or fails its checksum.
+    pub fn page_in_all(&mut self) -> AppResult<()> {
+        if let Some(mut pager) = self.pager.take() {
+            let pages = pager.take_all()?;
+            self.install(pages);
+        }
+        Ok(())
+    }
+
+    /// Returns the number of clients whose pages are not loaded yet.
+    pub fn pending_pages(&self) -> usize {
+        self.pager.as_ref().map_or(0, Pager::pending)
+    }
+
+    /// Makes the engine load its remaining state lazily from a pager.
+    pub(crate) fn set_pager(&mut self, pager: Pager) {
+        self.pager = (pager.pending() > 0).then_some(pager);
+    }
+
+    /// Installs loaded pages.
+    fn install(&mut self, pages: Vec<Page>) {
+        for page in pages {
+            if let Some(acc) = page.account {
+                self.accounts.insert(page.client, acc);
+            }
+            for (tx, rec) in page.records {
+                self.txs.insert(tx, rec);
+            }
+        }
+    }
+
     /// Returns a mutable reference to the account for the given client,
     /// creating a new empty account if it does not exist.
     pub fn acct_mut(&mut self, c: ClientId) -> &mut Account {
@@ -43,4 +844,696 @@ impl Engine {
     pub fn acct(&self, client: ClientId) -> Option<&Account> {
         self.accounts.get(&client)
     }
+
+    /// Returns the version of a client's account: the number of commands applied
+    /// to it, or `0` if the account does not exist.
+    ///
+    /// Versions only grow, so a consumer that last saw version `n` and now reads
+    /// `n + k` knows it missed `k - 1` updates.
+    pub fn version(&self, client: ClientId) -> u64 {
+        self.accounts.get(&client).map_or(0, |acc| acc.version)
+    }
+
+    /// Groups the transaction records by owning client, each client's records
+    /// sorted by tx id, for per-client statements.
+    pub fn tx_history(&self) -> HashMap<ClientId, Vec<(TxId, &TxRecord)>> {
+        let mut history: HashMap<ClientId, Vec<(TxId, &TxRecord)>> = HashMap::new();
+        for (tx, rec) in self.txs.iter() {
+            history.entry(rec.client).or_default().push((tx, rec));
+        }
+        for txs in history.values_mut() {
+            txs.sort_unstable_by_key(|(tx, _)| *tx);
+        }
+        history
+    }
+
+    /// Looks up a transaction record and its owner's account in a single probe
+    /// of the transaction store.
+    ///
+    /// The owning account is created if it does not exist yet. Returns `None`
+    /// without touching any account when the transaction is unknown.
+    pub fn tx_and_acct_mut(&mut self, tx: &TxId) -> Option<(&mut TxRecord, &mut Account)> {
+        let rec = self.txs.get_mut(tx)?;
+        let acc = self.accounts.entry(rec.client).or_default();
+        Some((rec, acc))
+    }
+
+    /// Attaches an operator annotation to an account or transaction record.
+    ///
+    /// # Arguments
+    /// * `note` - The annotation to attach.
+    ///
+    /// # Returns
+    /// * `AppResult<()>` - Returns `Ok(())` once attached, or
+    ///   `AppErrors::InvalidInput` if the author or text is blank, or the engine
+    ///   has no such account or has never seen the tx id.
+    pub fn annotate(&mut self, note: Annotation) -> AppResult<()> {
+        if note.author.trim().is_empty() || note.text.trim().is_empty() {
+            return Err(AppErrors::InvalidInput(
+                "an annotation needs an author and a note",
+            ));
+        }
+        let exists = match note.target {
+            NoteTarget::Account(client) => self.accounts.contains_key(&client),
+            NoteTarget::Tx(tx) => self.txs.contains_key(&tx),
+        };
+        if !exists {
+            return Err(AppErrors::InvalidInput(
+                "no such account or transaction record to annotate",
+            ));
+        }
+        self.notes.push(note);
+        Ok(())
+    }
+
+    /// Returns all operator annotations, oldest first.
+    pub fn annotations(&self) -> &[Annotation] {
+        &self.notes
+    }
+
+    /// Returns the operator annotations about a client, oldest first: those on
+    /// its account and on its live transaction records.
+    pub fn client_annotations(&self, client: ClientId) -> impl Iterator<Item = &Annotation> {
+        self.notes.iter().filter(move |note| match note.target {
+            NoteTarget::Account(c) => c == client,
+            NoteTarget::Tx(tx) => self.txs.get(&tx).is_some_and(|rec| rec.client == client),
+        })
+    }
+
+    /// Removes a client's account together with the given transaction records,
+    /// returning the account if it existed.
+    ///
+    /// Only records in `txs` that belong to `client` are removed, along with the
+    /// annotations on the account and those records. Their ids stay seen, so a
+    /// later row reusing one is still a duplicate. Used by streamed emission
+    /// to drop state for clients that will not be referenced again.
+    pub fn evict_client(&mut self, client: ClientId, txs: &[TxId]) -> Option<Account> {
+        self.notes.retain(|note| match note.target {
+            NoteTarget::Account(c) => c != client,
+            NoteTarget::Tx(tx) => {
+                !(txs.contains(&tx) && self.txs.get(&tx).is_some_and(|rec| rec.client == client))
+            }
+        });
+        for tx in txs {
+            if self.txs.get(tx).is_some_and(|rec| rec.client == client) {
+                self.txs.retire(*tx);
+            }
+        }
+        self.activity.remove(&client);
+        self.accounts.remove(&client)
+    }
+
+    /// Returns an estimate of the heap bytes reserved by the engine's collections.
+    pub fn heap_bytes(&self) -> usize {
+        self.accounts.capacity() * size_of::<(ClientId, Account)>() + self.txs.heap_bytes()
+    }
+
+    /// Compacts the engine: retires transaction records that can no longer change
+    /// state and shrinks overallocated collections.
+    ///
+    /// Retired ids are still detected as duplicates, so compaction does not change
+    /// the outcome of any later command.
+    ///
+    /// # Returns
+    /// * `CompactionStats` - The number of retired records and the estimated bytes freed.
+    pub fn compact(&mut self) -> CompactionStats {
+        let before = self.heap_bytes();
+        let retired_records = self.txs.retire_final(&self.config);
+        self.txs.shrink_to_fit();
+        self.accounts.shrink_to_fit();
+        CompactionStats {
+            retired_records,
+            freed_bytes: before.saturating_sub(self.heap_bytes()),
+        }
+    }
+
+    /// Audits the engine's internal consistency after bulk application.
+    ///
+    /// Checks that, for every account:
+    /// - `available` and `held` are non-negative (`available` may be negative
+    ///   under [`UnfundedDisputePolicy::Negative`]);
+    /// - `held` equals the sum of its currently disputed transactions;
+    /// - it is locked if any of its transactions was charged back.
+    ///
+    /// Also checks that every transaction record belongs to an existing account.
+    ///
+    /// # Returns
+    /// * `AppResult<()>` - Returns `Ok(())` if all invariants hold, or
+    ///   `AppErrors::Internal` listing every violation found.
+    pub fn verify(&self) -> AppResult<()> {
+        let mut held_by_disputes: HashMap<ClientId, Amount> = HashMap::new();
+        let mut charged_back: HashMap<ClientId, bool> = HashMap::new();
+        let mut violations = Vec::new();
+
+        for (tx, rec) in self.txs.iter() {
+            if !self.accounts.contains_key(&rec.client) {
+                violations.push(format!("tx {tx} references missing client {}", rec.client));
+            }
+            match rec.state {
+                DisputeState::Disputed => {
+                    let sum = held_by_disputes.entry(rec.client).or_default();
+                    *sum = sum.checked_add(rec.amount).ok_or(AppErrors::Overflow)?;
+                }
+                DisputeState::ChargedBack => {
+                    charged_back.insert(rec.client, true);
+                }
+                DisputeState::Normal | DisputeState::Resolved => {}
+            }
+        }
+
+        for (client, acc) in &self.accounts {
+            if acc.available.is_negative()
+                && self.config.unfunded_disputes != UnfundedDisputePolicy::Negative
+            {
+                violations.push(format!(
+                    "client {client}: negative available {}",
+                    acc.available
+                ));
+            }
+            if acc.held.is_negative() {
+                violations.push(format!("client {client}: negative held {}", acc.held));
+            }
+            let expected = held_by_disputes.get(client).copied().unwrap_or_default();
+            if acc.held != expected {
+                violations.push(format!(
+                    "client {client}: held {} != disputed total {expected}",
+                    acc.held
+                ));
+            }
+            if charged_back.contains_key(client) && !acc.locked {
+                violations.push(format!("client {client}: charged back but not locked"));
+            }
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(AppErrors::Internal(format!(
+                "{} invariant violation(s): {}",
+                violations.len(),
+                violations.join("; ")
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(client: ClientId, amount: i64, state: DisputeState) -> TxRecord {
+        TxRecord {
+            client,
+            kind: TxKind::Deposit,
+            amount: Amount(amount),
+            state,
+        }
+    }
+
+    #[test]
+    fn verify_accepts_consistent_state() {
+        let mut engine = Engine::default();
+        engine.txs.insert(1, record(1, 100, DisputeState::Disputed));
+        engine.txs.insert(2, record(1, 50, DisputeState::Normal));
+        let acc = engine.acct_mut(1);
+        acc.available = Amount(50);
+        acc.held = Amount(100);
+
+        assert!(engine.verify().is_ok());
+    }
+
+    #[test]
+    fn verify_reports_held_mismatch_and_unlocked_chargeback() {
+        let mut engine = Engine::default();
+        engine
+            .txs
+            .insert(1, record(1, 100, DisputeState::ChargedBack));
+        engine.acct_mut(1).held = Amount(5);
+
+        let err = engine.verify().unwrap_err().to_string();
+        assert!(err.contains("2 invariant violation(s)"), "{err}");
+        assert!(
+            err.contains("held 0.0005 != disputed total 0.0000"),
+            "{err}"
+        );
+        assert!(err.contains("charged back but not locked"), "{err}");
+    }
+
+    #[test]
+    fn queued_dispute_rejected_on_retry_is_dropped_uncounted() {
+        use crate::models::policy::RedisputePolicy;
+        use crate::models::tx_command::DepositCommand;
+
+        let mut engine = Engine::builder()
+            .unfunded_disputes(UnfundedDisputePolicy::Queue)
+            .redisputes(RedisputePolicy::Forbid)
+            .dispute_window(5)
+            .build();
+        let deposit = |tx| -> Transaction {
+            DepositCommand {
+                client: 1,
+                tx,
+                amount: Amount(10_000),
+            }
+            .into()
+        };
+        engine.apply(deposit(1)).unwrap();
+        // Queued while unfunded, then resolved by other means before the retry.
+        assert!(engine.queue_dispute(1, 1));
+        engine.txs.get_mut(&1).unwrap().state = DisputeState::Resolved;
+
+        engine.apply(deposit(2)).unwrap();
+
+        assert!(engine.pending_disputes(1).is_empty());
+        assert_eq!(engine.activity(1).disputes, 0);
+        assert!(engine.open_disputes.is_empty());
+        assert_eq!(engine.acct(1).unwrap().held, Amount::zero());
+    }
+
+    #[test]
+    fn verify_accepts_negative_available_only_under_the_negative_policy() {
+        let mut engine = Engine::default();
+        engine.txs.insert(1, record(1, 100, DisputeState::Disputed));
+        let acc = engine.acct_mut(1);
+        acc.available = Amount(-40);
+        acc.held = Amount(100);
+
+        let err = engine.verify().unwrap_err().to_string();
+        assert!(err.contains("negative available -0.0040"), "{err}");
+
+        engine.set_config(EngineConfig {
+            unfunded_disputes: UnfundedDisputePolicy::Negative,
+            ..EngineConfig::default()
+        });
+        assert!(engine.verify().is_ok());
+    }
+
+    #[test]
+    fn verify_reports_orphan_tx() {
+        let mut engine = Engine::default();
+        engine.txs.insert(9, record(4, 100, DisputeState::Normal));
+
+        let err = engine.verify().unwrap_err().to_string();
+        assert!(err.contains("tx 9 references missing client 4"), "{err}");
+    }
+
+    #[test]
+    fn apply_reports_applied_and_ignored() {
+        use crate::models::tx_command::{DepositCommand, DisputeCommand};
+
+        let mut engine = Engine::default();
+        let deposit = DepositCommand {
+            client: 1,
+            tx: 1,
+            amount: Amount(10_000),
+        };
+        assert_eq!(
+            engine.apply(deposit.clone().into()).unwrap(),
+            Outcome::Applied
+        );
+        assert_eq!(
+            engine.apply(deposit.into()).unwrap(),
+            Outcome::Ignored(IgnoreReason::DuplicateTx)
+        );
+
+        let dispute = DisputeCommand { client: 1, tx: 1 };
+        assert_eq!(engine.apply(dispute.into()).unwrap(), Outcome::Applied);
+        assert_eq!(engine.acct(1).unwrap().held, Amount(10_000));
+
+        let unknown = DisputeCommand { client: 1, tx: 99 };
+        assert_eq!(
+            engine.apply(unknown.into()).unwrap(),
+            Outcome::Ignored(IgnoreReason::UnknownTx)
+        );
+        assert_eq!(
+            engine.version(1),
+            2,
+            "only applied commands bump the version"
+        );
+        assert_eq!(engine.version(2), 0);
+    }
+
+    #[test]
+    fn compaction_keeps_withdrawals_disputable_when_withdrawal_disputes_are_on() {
+        use crate::models::tx_command::{DepositCommand, DisputeCommand, WithdrawalCommand};
+
+        for (withdrawal_disputes, retired) in [(false, 1), (true, 0)] {
+            let mut engine = Engine::builder()
+                .allow_withdrawal_disputes(withdrawal_disputes)
+                .build();
+            let deposit = DepositCommand {
+                client: 1,
+                tx: 1,
+                amount: Amount(10_000),
+            };
+            engine.apply(deposit.into()).unwrap();
+            // A far-off id lands in the overflow, where compaction retires records.
+            let withdrawal = WithdrawalCommand {
+                client: 1,
+                tx: 50_000,
+                amount: Amount(4_000),
+            };
+            engine.apply(withdrawal.into()).unwrap();
+
+            assert_eq!(engine.compact().retired_records, retired);
+            if withdrawal_disputes {
+                let cmd = DisputeCommand {
+                    client: 1,
+                    tx: 50_000,
+                };
+                assert_eq!(engine.apply(cmd.into()).unwrap(), Outcome::Applied);
+                assert_eq!(engine.acct(1).unwrap().held, Amount(4_000));
+            }
+        }
+    }
+
+    #[test]
+    fn builder_sets_withdrawal_disputes_and_strict_duplicates() {
+        use crate::models::tx_command::{
+            ChargebackCommand, DepositCommand, DisputeCommand, WithdrawalCommand,
+        };
+
+        let mut engine = Engine::builder()
+            .allow_withdrawal_disputes(true)
+            .strict_duplicates(true)
+            .build();
+        let deposit = DepositCommand {
+            client: 1,
+            tx: 1,
+            amount: Amount(10_000),
+        };
+        engine.apply(deposit.clone().into()).unwrap();
+        assert!(matches!(
+            engine.apply(deposit.into()),
+            Err(AppErrors::DuplicateTx(1))
+        ));
+        engine
+            .apply(
+                WithdrawalCommand {
+                    client: 1,
+                    tx: 2,
+                    amount: Amount(4_000),
+                }
+                .into(),
+            )
+            .unwrap();
+
+        let dispute = DisputeCommand { client: 1, tx: 2 };
+        assert_eq!(
+            engine.apply(dispute.clone().into()).unwrap(),
+            Outcome::Applied
+        );
+        let acc = engine.acct(1).unwrap();
+        assert_eq!((acc.available, acc.held), (Amount(6_000), Amount(4_000)));
+
+        let chargeback = ChargebackCommand { client: 1, tx: 2 };
+        assert_eq!(engine.apply(chargeback.into()).unwrap(), Outcome::Applied);
+        let acc = engine.acct(1).unwrap();
+        assert_eq!((acc.available, acc.held), (Amount(10_000), Amount::zero()));
+        assert!(acc.locked);
+        assert_eq!(engine.flows().withdrawn, Amount::zero());
+        assert!(engine.verify().is_ok());
+
+        let mut default = Engine::default();
+        default.txs.insert(
+            2,
+            TxRecord {
+                client: 1,
+                kind: TxKind::Withdrawal,
+                amount: Amount(4_000),
+                state: DisputeState::Normal,
+            },
+        );
+        assert_eq!(
+            default.apply(dispute.into()).unwrap(),
+            Outcome::Ignored(IgnoreReason::NotDisputable)
+        );
+    }
+
+    #[test]
+    fn buffers_commands_until_their_transaction_arrives() {
+        use crate::models::tx_command::{DepositCommand, DisputeCommand, ResolveCommand};
+
+        let mut engine = Engine::builder().orphan_buffer(2).build();
+        let dispute = |tx| -> Transaction { DisputeCommand { client: 1, tx }.into() };
+        let deposit = |tx| -> Transaction {
+            DepositCommand {
+                client: 1,
+                tx,
+                amount: Amount(10_000),
+            }
+            .into()
+        };
+        assert_eq!(
+            engine.apply(dispute(1)).unwrap(),
+            Outcome::Ignored(IgnoreReason::Buffered)
+        );
+        let resolve = ResolveCommand { client: 1, tx: 1 };
+        assert_eq!(
+            engine.apply(resolve.into()).unwrap(),
+            Outcome::Ignored(IgnoreReason::Buffered)
+        );
+        // The buffer is full.
+        assert_eq!(
+            engine.apply(dispute(2)).unwrap(),
+            Outcome::Ignored(IgnoreReason::UnknownTx)
+        );
+        assert_eq!(engine.buffered_count(), 2);
+
+        // The dispute and resolve are applied in order once the deposit arrives.
+        assert_eq!(engine.apply(deposit(1)).unwrap(), Outcome::Applied);
+        assert_eq!(engine.buffered_count(), 0);
+        let acc = engine.acct(1).unwrap();
+        assert_eq!((acc.available, acc.held), (Amount(10_000), Amount::zero()));
+        assert_eq!(engine.version(1), 3);
+        assert_eq!(engine.activity(1).disputes, 1);
+
+        // Room again: tx 2 is buffered and disputed on arrival.
+        engine.apply(dispute(2)).unwrap();
+        engine.apply(deposit(2)).unwrap();
+        assert_eq!(engine.acct(1).unwrap().held, Amount(10_000));
+    }
+
+    #[test]
+    fn released_commands_do_not_count_towards_the_dispute_window() {
+        use crate::models::tx_command::{DepositCommand, DisputeCommand};
+
+        let mut engine = Engine::builder().orphan_buffer(1).dispute_window(2).build();
+        let deposit = |tx| -> Transaction {
+            DepositCommand {
+                client: 1,
+                tx,
+                amount: Amount(10_000),
+            }
+            .into()
+        };
+        engine
+            .apply(DisputeCommand { client: 1, tx: 1 }.into())
+            .unwrap();
+        // The dispute is released, and opens, at command 2.
+        let mut released = Vec::new();
+        engine.apply_releasing(deposit(1), &mut released).unwrap();
+        assert_eq!(released.len(), 1);
+        assert_eq!(released[0].result.as_ref().unwrap(), &Outcome::Applied);
+        engine.apply(deposit(2)).unwrap();
+        engine.apply(deposit(3)).unwrap();
+        assert_eq!(engine.acct(1).unwrap().held, Amount(10_000));
+
+        engine.apply(deposit(4)).unwrap();
+        assert_eq!(engine.acct(1).unwrap().held, Amount::zero());
+        assert_eq!(engine.expired_disputes(), 1);
+    }
+
+    #[test]
+    fn failed_commands_do_not_count_towards_the_dispute_window() {
+        use crate::models::tx_command::{DepositCommand, DisputeCommand};
+
+        let mut engine = Engine::builder()
+            .dispute_window(0)
+            .strict_duplicates(true)
+            .build();
+        let deposit = |client, tx| -> Transaction {
+            DepositCommand {
+                client,
+                tx,
+                amount: Amount(10_000),
+            }
+            .into()
+        };
+        engine.apply(deposit(1, 1)).unwrap();
+        assert!(engine.apply(deposit(1, 1)).is_err());
+        assert_eq!(engine.commands, 1);
+
+        // An expiry that fails leaves the count alone too.
+        engine
+            .apply(DisputeCommand { client: 1, tx: 1 }.into())
+            .unwrap();
+        engine.acct_mut(1).available = Amount(i64::MAX);
+        assert!(matches!(
+            engine.apply(deposit(2, 2)),
+            Err(AppErrors::Overflow)
+        ));
+        assert_eq!(engine.commands, 2);
+    }
+
+    #[test]
+    fn reports_dispute_events_until_a_command_succeeds() {
+        use crate::models::tx_command::{DepositCommand, DisputeCommand};
+
+        let mut engine = Engine::builder()
+            .dispute_window(1)
+            .strict_duplicates(true)
+            .build();
+        let deposit = |tx| -> Transaction {
+            DepositCommand {
+                client: 1,
+                tx,
+                amount: Amount(10_000),
+            }
+            .into()
+        };
+        engine.apply(deposit(1)).unwrap();
+        assert!(engine.dispute_events().is_empty());
+        engine
+            .apply(DisputeCommand { client: 1, tx: 1 }.into())
+            .unwrap();
+        assert_eq!(
+            engine.dispute_events(),
+            [DisputeEvent::Opened { client: 1, tx: 1 }]
+        );
+        engine.apply(deposit(2)).unwrap();
+        assert!(engine.dispute_events().is_empty());
+
+        // The duplicate expires the dispute before failing; the expiry is
+        // reported with the next command.
+        assert!(engine.apply(deposit(1)).is_err());
+        engine.apply(deposit(3)).unwrap();
+        assert_eq!(engine.dispute_events(), [DisputeEvent::Expired(1)]);
+        engine.apply(deposit(4)).unwrap();
+        assert!(engine.dispute_events().is_empty());
+    }
+
+    #[test]
+    fn resolves_disputes_left_open_past_the_window() {
+        use crate::models::tx_command::{DepositCommand, DisputeCommand, ResolveCommand};
+
+        let mut engine = Engine::builder().dispute_window(2).build();
+        let deposit = |tx| -> Transaction {
+            DepositCommand {
+                client: 1,
+                tx,
+                amount: Amount(10_000),
+            }
+            .into()
+        };
+        let dispute: Transaction = DisputeCommand { client: 1, tx: 1 }.into();
+        engine.apply(deposit(1)).unwrap();
+        engine.apply(dispute.clone()).unwrap();
+        engine
+            .apply(ResolveCommand { client: 1, tx: 1 }.into())
+            .unwrap();
+        // Re-disputed at command 4: the stale entry of the first dispute
+        // (opened at command 2) does not expire it at command 5.
+        engine.apply(dispute).unwrap();
+        engine.apply(deposit(2)).unwrap();
+        engine.apply(deposit(3)).unwrap();
+        assert_eq!(engine.acct(1).unwrap().held, Amount(10_000));
+        assert_eq!(engine.expired_disputes(), 0);
+
+        // Two commands later, the dispute is resolved before the next applies.
+        engine.apply(deposit(4)).unwrap();
+        let acc = engine.acct(1).unwrap();
+        assert_eq!((acc.available, acc.held), (Amount(40_000), Amount::zero()));
+        assert_eq!(engine.txs.get(&1).unwrap().state, DisputeState::Resolved);
+        assert_eq!(engine.expired_disputes(), 1);
+    }
+
+    #[test]
+    fn probe_evaluates_without_mutating() {
+        use crate::models::tx_command::{DepositCommand, WithdrawalCommand};
+
+        let mut engine = Engine::default();
+        engine
+            .apply(
+                DepositCommand {
+                    client: 1,
+                    tx: 1,
+                    amount: Amount(10_000),
+                }
+                .into(),
+            )
+            .unwrap();
+        let withdrawal = |amount| -> Transaction {
+            WithdrawalCommand {
+                client: 1,
+                tx: 2,
+                amount: Amount(amount),
+            }
+            .into()
+        };
+
+        let ok = engine.probe(&withdrawal(4_000)).unwrap();
+        assert_eq!(ok.outcome, Outcome::Applied);
+        assert_eq!(ok.account.available, Amount(6_000));
+
+        let too_much = engine.probe(&withdrawal(20_000)).unwrap();
+        assert_eq!(
+            too_much.outcome,
+            Outcome::Ignored(IgnoreReason::InsufficientFunds)
+        );
+        assert_eq!(too_much.account.available, Amount(10_000));
+
+        assert_eq!(engine.acct(1).unwrap().available, Amount(10_000));
+        assert!(!engine.txs.contains_key(&2));
+    }
+
+    #[test]
+    fn tallies_the_activity_of_applied_commands() {
+        use crate::models::tx_command::{
+            ChargebackCommand, DepositCommand, DisputeCommand, WithdrawalCommand,
+        };
+
+        let mut engine = Engine::default();
+        let deposit = |tx, amount| -> Transaction {
+            DepositCommand {
+                client: 1,
+                tx,
+                amount: Amount(amount),
+            }
+            .into()
+        };
+        for tx in [
+            deposit(1, 10_000),
+            deposit(2, 5_000),
+            WithdrawalCommand {
+                client: 1,
+                tx: 3,
+                amount: Amount(4_000),
+            }
+            .into(),
+            // Ignored: overdraws, and the deposit is a duplicate.
+            WithdrawalCommand {
+                client: 1,
+                tx: 4,
+                amount: Amount(99_000),
+            }
+            .into(),
+            deposit(1, 10_000),
+            DisputeCommand { client: 1, tx: 2 }.into(),
+            ChargebackCommand { client: 1, tx: 2 }.into(),
+        ] {
+            engine.apply(tx).unwrap();
+        }
+
+        assert_eq!(
+            engine.activity(1),
+            ClientActivity {
+                deposited: Amount(15_000),
+                withdrawn: Amount(4_000),
+                disputes: 1,
+                chargebacks: 1,
+            }
+        );
+        assert_eq!(engine.activity(2), ClientActivity::default());
+    }
 }